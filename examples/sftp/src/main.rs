@@ -1,10 +1,9 @@
 use std::{
     env,
-    ffi::OsStr,
     fs::File,
     io::{self, Read, Seek, SeekFrom},
     net::TcpStream,
-    path::Path,
+    path::{Path, PathBuf},
     sync::mpsc,
 };
 
@@ -15,7 +14,7 @@ use cloud_filter::{
     placeholder::{ConvertOptions, Placeholder},
     placeholder_file::PlaceholderFile,
     root::{HydrationType, PopulationType, SecurityId, Session, SyncRootIdBuilder, SyncRootInfo},
-    utility::{FileTime, WriteAt},
+    utility::FileTime,
 };
 use rkyv::{Archive, Deserialize, Serialize};
 use ssh2::Sftp;
@@ -102,7 +101,7 @@ fn mark_in_sync(path: &Path, sftp: &Sftp) {
 
         let mut options = ConvertOptions::default()
             .mark_in_sync()
-            .blob(remote_path.clone().into_os_string().into_encoded_bytes());
+            .identity(&remote_path);
         let mut placeholder = match meta.is_dir() {
             true => {
                 options = options.has_children();
@@ -145,11 +144,11 @@ impl SyncFilter for Filter {
         ticket: ticket::FetchData,
         info: info::FetchData,
     ) -> CResult<()> {
-        let path = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(request.file_blob()) });
+        let path = request.file_identity::<PathBuf>()?;
 
         let range = info.required_file_range();
         let end = range.end;
-        let mut position = range.start;
+        let position = range.start;
 
         println!(
             "fetch_data {:?} {:?} {}",
@@ -159,37 +158,20 @@ impl SyncFilter for Filter {
         );
         let mut server_file = self
             .sftp
-            .open(path)
+            .open(&path)
             .map_err(|_| CloudErrorKind::InvalidRequest)?;
         server_file
             .seek(SeekFrom::Start(position))
             .map_err(|_| CloudErrorKind::InvalidRequest)?;
 
-        let mut buffer = [0; DOWNLOAD_CHUNK_SIZE_BYTES];
-
-        loop {
-            let mut bytes_read = server_file
-                .read(&mut buffer)
-                .map_err(|_| CloudErrorKind::InvalidRequest)?;
-
-            let unaligned = bytes_read % 4096;
-            if unaligned != 0 && position + (bytes_read as u64) < end {
-                bytes_read -= unaligned;
-                server_file
-                    .seek(SeekFrom::Current(-(unaligned as i64)))
-                    .unwrap();
-            }
-            ticket
-                .write_at(&buffer[0..bytes_read], position)
-                .map_err(|_| CloudErrorKind::InvalidRequest)?;
-            position += bytes_read as u64;
-
-            if position >= end {
-                break;
-            }
-
-            ticket.report_progress(end, position).unwrap();
-        }
+        let mut writer = ticket
+            .writer(position..end)
+            .chunk_size(DOWNLOAD_CHUNK_SIZE_BYTES);
+        io::copy(&mut server_file.take(end - position), &mut writer)
+            .map_err(|_| CloudErrorKind::InvalidRequest)?;
+        writer
+            .finish()
+            .map_err(|_| CloudErrorKind::InvalidRequest)?;
 
         Ok(())
     }
@@ -200,14 +182,14 @@ impl SyncFilter for Filter {
 
     fn delete(&self, request: Request, ticket: ticket::Delete, info: info::Delete) -> CResult<()> {
         println!("delete {:?}", request.path());
-        let path = Path::new(unsafe { OsStr::from_encoded_bytes_unchecked(request.file_blob()) });
+        let path = request.file_identity::<PathBuf>()?;
         match info.is_directory() {
             true => self
-                .remove_remote_dir_all(path)
+                .remove_remote_dir_all(&path)
                 .map_err(|_| CloudErrorKind::InvalidRequest)?,
             false => self
                 .sftp
-                .unlink(path)
+                .unlink(&path)
                 .map_err(|_| CloudErrorKind::InvalidRequest)?,
         }
         ticket.pass().unwrap();
@@ -288,7 +270,7 @@ impl SyncFilter for Filter {
                     )
                     .mark_in_sync()
                     .overwrite()
-                    .blob(path.into_os_string().into_encoded_bytes())
+                    .identity(&path)
             })
             .collect::<Vec<_>>();
 
@@ -312,7 +294,7 @@ impl SyncFilter for Filter {
         _info: info::ValidateData,
     ) -> CResult<()> {
         println!("validate_data");
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     fn cancel_fetch_placeholders(&self, _request: Request, _info: info::CancelFetchPlaceholders) {
@@ -330,7 +312,7 @@ impl SyncFilter for Filter {
         _info: info::Dehydrate,
     ) -> CResult<()> {
         println!("dehydrate");
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     fn dehydrated(&self, _request: Request, _info: info::Dehydrated) {
@@ -0,0 +1,143 @@
+//! Placeholder file blobs are opaque to the operating system, they are handed back to the
+//! [SyncFilter][crate::filter::SyncFilter] exactly as they were stored. Types implementing
+//! [FileIdentity] are framed as follows, so that a blob written by another version of the sync
+//! provider, or not written by it at all, is rejected instead of being misinterpreted:
+//!
+//! | Offset  | Size      | Content                                       |
+//! |---------|-----------|-----------------------------------------------|
+//! | `0`     | 1         | [FileIdentity::VERSION]                       |
+//! | `1`     | `n`       | The payload written by [FileIdentity::encode] |
+//! | `1 + n` | 4         | CRC-32 of the preceding bytes, little-endian  |
+
+use std::{ffi::OsString, path::PathBuf};
+
+use crate::{
+    error::{CResult, CloudErrorKind},
+    placeholder_file::MAX_BLOB_LENGTH,
+};
+
+/// The size of the version byte and the checksum surrounding the payload.
+const FRAME_LENGTH: usize = 1 + 4;
+
+/// The maximum size of a [FileIdentity] payload in bytes.
+pub const MAX_PAYLOAD_LENGTH: usize = MAX_BLOB_LENGTH - FRAME_LENGTH;
+
+/// A type stored in the blob of a placeholder.
+///
+/// See the [module][crate::blob] documentation for the encoding.
+pub trait FileIdentity: Sized {
+    /// The version of the encoding, written before the payload.
+    ///
+    /// Bump it whenever the payload layout changes, [FileIdentity::decode] receives the version
+    /// the blob was encoded with.
+    const VERSION: u8;
+
+    /// Appends the payload to the buffer.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Parses the payload encoded with the given version.
+    ///
+    /// Returns [None] if the payload is malformed or the version is not supported.
+    fn decode(version: u8, payload: &[u8]) -> Option<Self>;
+}
+
+/// Encodes the identity into a placeholder blob.
+///
+/// Returns [CloudErrorKind::MetadataTooLarge] if the blob exceeds [4KiB][MAX_BLOB_LENGTH].
+pub fn encode<T: FileIdentity>(identity: &T) -> CResult<Vec<u8>> {
+    let mut blob = vec![T::VERSION];
+    identity.encode(&mut blob);
+    if blob.len() > MAX_PAYLOAD_LENGTH + 1 {
+        return Err(CloudErrorKind::MetadataTooLarge.into());
+    }
+
+    let checksum = crc32(&blob);
+    blob.extend_from_slice(&checksum.to_le_bytes());
+    Ok(blob)
+}
+
+/// Decodes the identity from a placeholder blob.
+///
+/// Returns [CloudErrorKind::MetadataCorrupt] if the blob is truncated, its checksum does not
+/// match or [FileIdentity::decode] rejects it.
+pub fn decode<T: FileIdentity>(blob: &[u8]) -> CResult<T> {
+    if blob.len() < FRAME_LENGTH || blob.len() > MAX_BLOB_LENGTH {
+        return Err(CloudErrorKind::MetadataCorrupt.into());
+    }
+
+    let (data, checksum) = blob.split_at(blob.len() - 4);
+    if crc32(data).to_le_bytes() != checksum {
+        return Err(CloudErrorKind::MetadataCorrupt.into());
+    }
+
+    T::decode(data[0], &data[1..]).ok_or(CloudErrorKind::MetadataCorrupt.into())
+}
+
+/// The CRC-32 (IEEE 802.3) checksum of the buffer.
+fn crc32(buf: &[u8]) -> u32 {
+    !buf.iter().fold(!0, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+impl FileIdentity for String {
+    const VERSION: u8 = 1;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(version: u8, payload: &[u8]) -> Option<Self> {
+        match version {
+            1 => String::from_utf8(payload.to_vec()).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Paths are encoded losslessly in the platform representation, UTF-16 on Windows and raw bytes
+/// elsewhere.
+impl FileIdentity for PathBuf {
+    const VERSION: u8 = 1;
+
+    #[cfg(windows)]
+    fn encode(&self, buf: &mut Vec<u8>) {
+        use std::os::windows::ffi::OsStrExt;
+
+        buf.extend(self.as_os_str().encode_wide().flat_map(u16::to_le_bytes));
+    }
+
+    #[cfg(not(windows))]
+    fn encode(&self, buf: &mut Vec<u8>) {
+        use std::os::unix::ffi::OsStrExt;
+
+        buf.extend_from_slice(self.as_os_str().as_bytes());
+    }
+
+    #[cfg(windows)]
+    fn decode(version: u8, payload: &[u8]) -> Option<Self> {
+        use std::os::windows::ffi::OsStringExt;
+
+        if version != 1 || !payload.len().is_multiple_of(2) {
+            return None;
+        }
+
+        let wide = payload
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        Some(OsString::from_wide(&wide).into())
+    }
+
+    #[cfg(not(windows))]
+    fn decode(version: u8, payload: &[u8]) -> Option<Self> {
+        use std::os::unix::ffi::OsStringExt;
+
+        match version {
+            1 => Some(OsString::from_vec(payload.to_vec()).into()),
+            _ => None,
+        }
+    }
+}
@@ -5,10 +5,10 @@ use windows::{
     Win32::{
         Foundation,
         Storage::CloudFilters::{
-            self, CF_OPERATION_PARAMETERS_0, CF_OPERATION_PARAMETERS_0_0,
+            self, CF_FS_METADATA, CF_OPERATION_PARAMETERS_0, CF_OPERATION_PARAMETERS_0_0,
             CF_OPERATION_PARAMETERS_0_1, CF_OPERATION_PARAMETERS_0_2, CF_OPERATION_PARAMETERS_0_3,
             CF_OPERATION_PARAMETERS_0_4, CF_OPERATION_PARAMETERS_0_5, CF_OPERATION_PARAMETERS_0_6,
-            CF_OPERATION_PARAMETERS_0_7, CF_OPERATION_TYPE,
+            CF_OPERATION_PARAMETERS_0_7, CF_OPERATION_TYPE, CF_PLACEHOLDER_CREATE_INFO,
         },
     },
 };
@@ -17,8 +17,6 @@ use crate::{
     command::executor::{execute, Command, Fallible},
     error::CloudErrorKind,
     filter::{RawConnectionKey, RawTransferKey},
-    metadata::Metadata,
-    placeholder_file::PlaceholderFile,
 };
 
 /// Read data from a placeholder file.
@@ -109,12 +107,14 @@ impl Fallible for Write<'_> {
 }
 
 /// Update various properties on a placeholder.
+// TODO: respond to fetch_data/validate_data with this command
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Update<'a> {
     /// Whether or not to mark the placeholder as "synced."
     pub mark_in_sync: bool,
     /// Optional metadata to update.
-    pub metadata: Option<Metadata>,
+    pub metadata: Option<CF_FS_METADATA>,
     /// Optional file blob to update.
     pub blob: &'a [u8],
 }
@@ -138,7 +138,7 @@ impl Command for Update<'_> {
                 FsMetadata: self
                     .metadata
                     .as_ref()
-                    .map_or(ptr::null(), |metadata| &metadata.0 as *const _),
+                    .map_or(ptr::null(), |metadata| metadata as *const _),
                 FileIdentity: match self.blob.is_empty() {
                     true => ptr::null(),
                     false => self.blob.as_ptr() as *const _,
@@ -153,7 +153,7 @@ impl Command for Update<'_> {
 #[derive(Debug)]
 pub struct CreatePlaceholders<'a> {
     /// The placeholders to create.
-    pub placeholders: &'a mut [PlaceholderFile],
+    pub placeholders: &'a mut [CF_PLACEHOLDER_CREATE_INFO],
     /// The total amount of placeholders that are a child of the current directory.
     pub total: u64,
 }
@@ -58,7 +58,7 @@ pub fn execute<C: Command>(
     connection_key: RawConnectionKey,
     transfer_key: RawTransferKey,
 ) -> core::Result<C::Result> {
-    unsafe {
+    let result = unsafe {
         CfExecute(
             &CF_OPERATION_INFO {
                 StructSize: mem::size_of::<CF_OPERATION_INFO>() as u32,
@@ -77,6 +77,98 @@ pub fn execute<C: Command>(
                 Anonymous: info,
             } as *mut _,
         )
-        .and(Ok(C::result(info)))
+    };
+
+    #[cfg(feature = "tracing")]
+    trace(C::OPERATION, &info, transfer_key, &result);
+
+    result.map(|_| unsafe { C::result(info) })
+}
+
+/// Records the operation executed through `CfExecute` and its result.
+#[cfg(feature = "tracing")]
+fn trace(
+    operation: CF_OPERATION_TYPE,
+    info: &CF_OPERATION_PARAMETERS_0,
+    transfer_key: RawTransferKey,
+    result: &core::Result<()>,
+) {
+    // the field of the union is determined by the operation type
+    let (name, offset, length, status) = unsafe {
+        match operation {
+            CloudFilters::CF_OPERATION_TYPE_TRANSFER_DATA => (
+                "transfer_data",
+                Some(info.TransferData.Offset),
+                Some(info.TransferData.Length),
+                Some(info.TransferData.CompletionStatus),
+            ),
+            CloudFilters::CF_OPERATION_TYPE_RETRIEVE_DATA => (
+                "retrieve_data",
+                Some(info.RetrieveData.Offset),
+                Some(info.RetrieveData.Length),
+                None,
+            ),
+            CloudFilters::CF_OPERATION_TYPE_ACK_DATA => (
+                "ack_data",
+                Some(info.AckData.Offset),
+                Some(info.AckData.Length),
+                Some(info.AckData.CompletionStatus),
+            ),
+            CloudFilters::CF_OPERATION_TYPE_RESTART_HYDRATION => {
+                ("restart_hydration", None, None, None)
+            }
+            CloudFilters::CF_OPERATION_TYPE_TRANSFER_PLACEHOLDERS => (
+                "transfer_placeholders",
+                None,
+                Some(info.TransferPlaceholders.PlaceholderCount as i64),
+                Some(info.TransferPlaceholders.CompletionStatus),
+            ),
+            CloudFilters::CF_OPERATION_TYPE_ACK_DEHYDRATE => (
+                "ack_dehydrate",
+                None,
+                None,
+                Some(info.AckDehydrate.CompletionStatus),
+            ),
+            CloudFilters::CF_OPERATION_TYPE_ACK_DELETE => (
+                "ack_delete",
+                None,
+                None,
+                Some(info.AckDelete.CompletionStatus),
+            ),
+            CloudFilters::CF_OPERATION_TYPE_ACK_RENAME => (
+                "ack_rename",
+                None,
+                None,
+                Some(info.AckRename.CompletionStatus),
+            ),
+            _ => ("unknown", None, None, None),
+        }
+    };
+    let status = status.map(|status| format!("{:#010x}", status.0 as u32));
+    let hresult = format!(
+        "{:#010x}",
+        result.as_ref().err().map_or(0, |err| err.code().0) as u32
+    );
+
+    match result {
+        Ok(()) => tracing::debug!(
+            operation = name,
+            transfer_key,
+            offset,
+            length,
+            completion_status = status,
+            hresult,
+            "CfExecute"
+        ),
+        Err(err) => tracing::warn!(
+            operation = name,
+            transfer_key,
+            offset,
+            length,
+            completion_status = status,
+            hresult,
+            error = %err,
+            "CfExecute failed"
+        ),
     }
 }
@@ -1,10 +1,19 @@
-use windows::Win32::Foundation::{self, NTSTATUS};
+use std::{
+    error::Error,
+    fmt, io,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+#[cfg(windows)]
+use windows::Win32::Foundation::NTSTATUS;
+use windows_core::HRESULT;
 
 /// [SyncFilter][crate::filter::SyncFilter] trait callback result type.
-pub type CResult<T> = std::result::Result<T, CloudErrorKind>;
+pub type CResult<T> = std::result::Result<T, CloudError>;
 
 /// Predefined error types provided by the operating system.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "record", derive(serde::Serialize, serde::Deserialize))]
 pub enum CloudErrorKind {
     /// Access to the cloud file is denied.
     AccessDenied,
@@ -71,64 +80,461 @@ pub enum CloudErrorKind {
     ValidationFailed,
 }
 
+/// The codes of each [CloudErrorKind], in the order of declaration, as
+/// `(kind, NTSTATUS, Win32 error code)`.
+///
+/// The operating system reports the NTSTATUS failing an operation to the filter, while the Win32
+/// APIs of the library fail with the matching Win32 error code wrapped in an [HRESULT].
+const CODES: [(CloudErrorKind, u32, u32); 31] = [
+    // STATUS_CLOUD_FILE_ACCESS_DENIED, ERROR_CLOUD_FILE_ACCESS_DENIED
+    (CloudErrorKind::AccessDenied, 0xC000CF18, 395),
+    // STATUS_CLOUD_FILE_ALREADY_CONNECTED, ERROR_CLOUD_FILE_ALREADY_CONNECTED
+    (CloudErrorKind::AlreadyConnected, 0xC000CF09, 378),
+    // STATUS_CLOUD_FILE_AUTHENTICATION_FAILED, ERROR_CLOUD_FILE_AUTHENTICATION_FAILED
+    (CloudErrorKind::AuthenticationFailed, 0xC000CF0F, 386),
+    // STATUS_CLOUD_FILE_CONNECTED_PROVIDER_ONLY, ERROR_CLOUD_FILE_CONNECTED_PROVIDER_ONLY
+    (CloudErrorKind::ConnectedProviderOnly, 0xC000CF0D, 382),
+    // STATUS_CLOUD_FILE_DEHYDRATION_DISALLOWED, ERROR_CLOUD_FILE_DEHYDRATION_DISALLOWED
+    (CloudErrorKind::DehydrationDisallowed, 0xC000CF20, 434),
+    // STATUS_CLOUD_FILE_INCOMPATIBLE_HARDLINKS, ERROR_CLOUD_FILE_INCOMPATIBLE_HARDLINKS
+    (CloudErrorKind::IncompatibleHardlinks, 0xC000CF19, 396),
+    // STATUS_CLOUD_FILE_INSUFFICIENT_RESOURCES, ERROR_CLOUD_FILE_INSUFFICIENT_RESOURCES
+    (CloudErrorKind::InsufficientResources, 0xC000CF10, 387),
+    // STATUS_CLOUD_FILE_INVALID_REQUEST, ERROR_CLOUD_FILE_INVALID_REQUEST
+    (CloudErrorKind::InvalidRequest, 0xC000CF0B, 380),
+    // STATUS_CLOUD_FILE_IN_USE, ERROR_CLOUD_FILE_IN_USE
+    (CloudErrorKind::InUse, 0xC000CF14, 391),
+    // STATUS_CLOUD_FILE_METADATA_CORRUPT, ERROR_CLOUD_FILE_METADATA_CORRUPT
+    (CloudErrorKind::MetadataCorrupt, 0xC000CF02, 363),
+    // STATUS_CLOUD_FILE_METADATA_TOO_LARGE, ERROR_CLOUD_FILE_METADATA_TOO_LARGE
+    (CloudErrorKind::MetadataTooLarge, 0xC000CF03, 364),
+    // STATUS_CLOUD_FILE_NETWORK_UNAVAILABLE, ERROR_CLOUD_FILE_NETWORK_UNAVAILABLE
+    (CloudErrorKind::NetworkUnavailable, 0xC000CF11, 388),
+    // STATUS_CLOUD_FILE_NOT_IN_SYNC, ERROR_CLOUD_FILE_NOT_IN_SYNC
+    (CloudErrorKind::NotInSync, 0xC000CF08, 377),
+    // STATUS_CLOUD_FILE_NOT_SUPPORTED, ERROR_CLOUD_FILE_NOT_SUPPORTED
+    (CloudErrorKind::NotSupported, 0xC000CF0A, 379),
+    // STATUS_CLOUD_FILE_NOT_UNDER_SYNC_ROOT, ERROR_CLOUD_FILE_NOT_UNDER_SYNC_ROOT
+    (CloudErrorKind::NotUnderSyncRoot, 0xC000CF13, 390),
+    // STATUS_CLOUD_FILE_PINNED, ERROR_CLOUD_FILE_PINNED
+    (CloudErrorKind::Pinned, 0xC000CF15, 392),
+    // STATUS_CLOUD_FILE_PROPERTY_BLOB_CHECKSUM_MISMATCH,
+    // ERROR_CLOUD_FILE_PROPERTY_BLOB_CHECKSUM_MISMATCH
+    (
+        CloudErrorKind::PropertyBlobChecksumMismatch,
+        0x8000CF00,
+        366,
+    ),
+    // STATUS_CLOUD_FILE_PROPERTY_BLOB_TOO_LARGE, ERROR_CLOUD_FILE_PROPERTY_BLOB_TOO_LARGE
+    (CloudErrorKind::PropertyBlobTooLarge, 0x8000CF04, 365),
+    // STATUS_CLOUD_FILE_PROPERTY_CORRUPT, ERROR_CLOUD_FILE_PROPERTY_CORRUPT
+    (CloudErrorKind::PropertyCorrupt, 0xC000CF17, 394),
+    // STATUS_CLOUD_FILE_PROPERTY_LOCK_CONFLICT, ERROR_CLOUD_FILE_PROPERTY_LOCK_CONFLICT
+    (CloudErrorKind::PropertyLockConflict, 0xC000CF1A, 397),
+    // STATUS_CLOUD_FILE_PROPERTY_VERSION_NOT_SUPPORTED,
+    // ERROR_CLOUD_FILE_PROPERTY_VERSION_NOT_SUPPORTED
+    (CloudErrorKind::PropertyVersionNotSupported, 0xC000CF06, 375),
+    // STATUS_CLOUD_FILE_PROVIDER_NOT_RUNNING, ERROR_CLOUD_FILE_PROVIDER_NOT_RUNNING
+    (CloudErrorKind::ProviderNotRunning, 0xC000CF01, 362),
+    // STATUS_CLOUD_FILE_PROVIDER_TERMINATED, ERROR_CLOUD_FILE_PROVIDER_TERMINATED
+    (CloudErrorKind::ProviderTerminated, 0xC000CF1D, 404),
+    // STATUS_CLOUD_FILE_READ_ONLY_VOLUME, ERROR_CLOUD_FILE_READ_ONLY_VOLUME
+    (CloudErrorKind::ReadOnlyVolume, 0xC000CF0C, 381),
+    // STATUS_CLOUD_FILE_REQUEST_ABORTED, ERROR_CLOUD_FILE_REQUEST_ABORTED
+    (CloudErrorKind::RequestAborted, 0xC000CF16, 393),
+    // STATUS_CLOUD_FILE_REQUEST_CANCELED, ERROR_CLOUD_FILE_REQUEST_CANCELED
+    (CloudErrorKind::RequestCancelled, 0xC000CF1B, 398),
+    // STATUS_CLOUD_FILE_REQUEST_TIMEOUT, ERROR_CLOUD_FILE_REQUEST_TIMEOUT
+    (CloudErrorKind::RequestTimeout, 0xC000CF1F, 426),
+    // STATUS_CLOUD_FILE_SYNC_ROOT_METADATA_CORRUPT, ERROR_CLOUD_FILE_SYNC_ROOT_METADATA_CORRUPT
+    (CloudErrorKind::SyncRootMetadataCorrupt, 0xC000CF00, 358),
+    // STATUS_CLOUD_FILE_TOO_MANY_PROPERTY_BLOBS, ERROR_CLOUD_FILE_TOO_MANY_PROPERTY_BLOBS
+    (CloudErrorKind::TooManyPropertyBlobs, 0x8000CF05, 374),
+    // STATUS_CLOUD_FILE_UNSUCCESSFUL, ERROR_CLOUD_FILE_UNSUCCESSFUL
+    (CloudErrorKind::Unsuccessful, 0xC000CF12, 389),
+    // STATUS_CLOUD_FILE_VALIDATION_FAILED, ERROR_CLOUD_FILE_VALIDATION_FAILED
+    (CloudErrorKind::ValidationFailed, 0xC000CF0E, 383),
+];
+
+// indexing the table by the discriminant relies on the order of the entries
+const _: () = {
+    let mut i = 0;
+    while i < CODES.len() {
+        assert!(
+            CODES[i].0 as usize == i,
+            "the codes are not in declaration order"
+        );
+        i += 1;
+    }
+};
+
+impl CloudErrorKind {
+    /// Iterates over every [CloudErrorKind].
+    pub fn all() -> impl Iterator<Item = Self> {
+        CODES.iter().map(|&(kind, ..)| kind)
+    }
+
+    /// The raw NTSTATUS reported to the operating system when an operation fails with this kind.
+    pub const fn ntstatus(self) -> i32 {
+        CODES[self as usize].1 as i32
+    }
+
+    /// The [HRESULT] wrapping the Win32 error code the APIs of the library fail with.
+    pub const fn hresult(self) -> HRESULT {
+        HRESULT::from_win32(CODES[self as usize].2)
+    }
+
+    /// Whether the operation may succeed if retried later, e.g. once the network is back or the
+    /// file is no longer in use.
+    ///
+    /// Cancellations are not transient, the user or the operating system asked for the operation
+    /// to stop.
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            CloudErrorKind::InsufficientResources
+                | CloudErrorKind::InUse
+                | CloudErrorKind::NetworkUnavailable
+                | CloudErrorKind::PropertyLockConflict
+                | CloudErrorKind::RequestAborted
+                | CloudErrorKind::RequestTimeout
+        )
+    }
+}
+
+#[cfg(windows)]
 impl From<CloudErrorKind> for NTSTATUS {
     fn from(error: CloudErrorKind) -> Self {
-        match error {
-            CloudErrorKind::AccessDenied => Foundation::STATUS_CLOUD_FILE_ACCESS_DENIED,
-            CloudErrorKind::AlreadyConnected => Foundation::STATUS_CLOUD_FILE_ALREADY_CONNECTED,
+        NTSTATUS(error.ntstatus())
+    }
+}
+
+#[cfg(windows)]
+impl TryFrom<NTSTATUS> for CloudErrorKind {
+    type Error = NTSTATUS;
+
+    /// Maps the `STATUS_CLOUD_FILE_*` codes back to their [CloudErrorKind], returning any other
+    /// status as is.
+    fn try_from(status: NTSTATUS) -> Result<Self, Self::Error> {
+        CODES
+            .iter()
+            .find(|&&(_, code, _)| code as i32 == status.0)
+            .map(|&(kind, ..)| kind)
+            .ok_or(status)
+    }
+}
+
+impl TryFrom<HRESULT> for CloudErrorKind {
+    type Error = HRESULT;
+
+    /// Maps the [HRESULT]s wrapping a `STATUS_CLOUD_FILE_*` code or an `ERROR_CLOUD_FILE_*` Win32
+    /// error code back to their [CloudErrorKind], returning any other [HRESULT] as is.
+    fn try_from(code: HRESULT) -> Result<Self, Self::Error> {
+        CODES
+            .iter()
+            .find(|&&(_, status, win32)| {
+                code == HRESULT::from_nt(status as i32) || code == HRESULT::from_win32(win32)
+            })
+            .map(|&(kind, ..)| kind)
+            .ok_or(code)
+    }
+}
+
+impl CloudErrorKind {
+    fn description(&self) -> &'static str {
+        match self {
+            CloudErrorKind::AccessDenied => "access to the cloud file is denied",
+            CloudErrorKind::AlreadyConnected => {
+                "the cloud sync root is already connected with another cloud sync provider"
+            }
             CloudErrorKind::AuthenticationFailed => {
-                Foundation::STATUS_CLOUD_FILE_AUTHENTICATION_FAILED
+                "the cloud sync provider failed user authentication"
             }
             CloudErrorKind::ConnectedProviderOnly => {
-                Foundation::STATUS_CLOUD_FILE_CONNECTED_PROVIDER_ONLY
+                "the operation is reserved for a connected cloud sync provider"
             }
             CloudErrorKind::DehydrationDisallowed => {
-                Foundation::STATUS_CLOUD_FILE_DEHYDRATION_DISALLOWED
+                "dehydration of the cloud file is disallowed by the cloud sync provider"
             }
             CloudErrorKind::IncompatibleHardlinks => {
-                Foundation::STATUS_CLOUD_FILE_INCOMPATIBLE_HARDLINKS
+                "the cloud operation cannot be performed on a file with incompatible hardlinks"
             }
             CloudErrorKind::InsufficientResources => {
-                Foundation::STATUS_CLOUD_FILE_INSUFFICIENT_RESOURCES
-            }
-            CloudErrorKind::InvalidRequest => Foundation::STATUS_CLOUD_FILE_INVALID_REQUEST,
-            CloudErrorKind::InUse => Foundation::STATUS_CLOUD_FILE_IN_USE,
-            CloudErrorKind::MetadataCorrupt => Foundation::STATUS_CLOUD_FILE_METADATA_CORRUPT,
-            CloudErrorKind::MetadataTooLarge => Foundation::STATUS_CLOUD_FILE_METADATA_TOO_LARGE,
-            CloudErrorKind::NetworkUnavailable => Foundation::STATUS_CLOUD_FILE_NETWORK_UNAVAILABLE,
-            CloudErrorKind::NotInSync => Foundation::STATUS_CLOUD_FILE_NOT_IN_SYNC,
-            CloudErrorKind::NotSupported => Foundation::STATUS_CLOUD_FILE_NOT_SUPPORTED,
-            CloudErrorKind::NotUnderSyncRoot => Foundation::STATUS_CLOUD_FILE_NOT_UNDER_SYNC_ROOT,
-            CloudErrorKind::Pinned => Foundation::STATUS_CLOUD_FILE_PINNED,
-            CloudErrorKind::PropertyBlobChecksumMismatch => {
-                Foundation::STATUS_CLOUD_FILE_PROPERTY_BLOB_CHECKSUM_MISMATCH
+                "the cloud sync provider failed to perform the operation due to low system resources"
+            }
+            CloudErrorKind::InvalidRequest => "the cloud operation is invalid",
+            CloudErrorKind::InUse => "the operation cannot be performed on cloud files in use",
+            CloudErrorKind::MetadataCorrupt => "the cloud file metadata is corrupt and unreadable",
+            CloudErrorKind::MetadataTooLarge => "the cloud file metadata is too large",
+            CloudErrorKind::NetworkUnavailable => {
+                "the cloud sync provider failed to perform the operation due to network being unavailable"
             }
-            CloudErrorKind::PropertyBlobTooLarge => {
-                Foundation::STATUS_CLOUD_FILE_PROPERTY_BLOB_TOO_LARGE
+            CloudErrorKind::NotInSync => "the file is not in sync with the cloud",
+            CloudErrorKind::NotSupported => {
+                "the operation is not supported by the cloud sync provider"
+            }
+            CloudErrorKind::NotUnderSyncRoot => {
+                "the operation is only supported on files under a cloud sync root"
+            }
+            CloudErrorKind::Pinned => "the operation cannot be performed on pinned cloud files",
+            CloudErrorKind::PropertyBlobChecksumMismatch => {
+                "the cloud file property is possibly corrupt"
             }
-            CloudErrorKind::PropertyCorrupt => Foundation::STATUS_CLOUD_FILE_PROPERTY_CORRUPT,
+            CloudErrorKind::PropertyBlobTooLarge => "the cloud file property is too large",
+            CloudErrorKind::PropertyCorrupt => "the cloud file's property store is corrupt",
             CloudErrorKind::PropertyLockConflict => {
-                Foundation::STATUS_CLOUD_FILE_PROPERTY_LOCK_CONFLICT
+                "the operation failed due to a conflicting cloud file property lock"
             }
             CloudErrorKind::PropertyVersionNotSupported => {
-                Foundation::STATUS_CLOUD_FILE_PROPERTY_VERSION_NOT_SUPPORTED
+                "the version of the cloud file property store is not supported"
             }
-            CloudErrorKind::ProviderNotRunning => {
-                Foundation::STATUS_CLOUD_FILE_PROVIDER_NOT_RUNNING
+            CloudErrorKind::ProviderNotRunning => "the cloud file provider is not running",
+            CloudErrorKind::ProviderTerminated => "the cloud file provider exited unexpectedly",
+            CloudErrorKind::ReadOnlyVolume => {
+                "the cloud operation is not supported on a read-only volume"
             }
-            CloudErrorKind::ProviderTerminated => Foundation::STATUS_CLOUD_FILE_PROVIDER_TERMINATED,
-            CloudErrorKind::ReadOnlyVolume => Foundation::STATUS_CLOUD_FILE_READ_ONLY_VOLUME,
-            CloudErrorKind::RequestAborted => Foundation::STATUS_CLOUD_FILE_REQUEST_ABORTED,
-            CloudErrorKind::RequestCancelled => Foundation::STATUS_CLOUD_FILE_REQUEST_CANCELED,
-            CloudErrorKind::RequestTimeout => Foundation::STATUS_CLOUD_FILE_REQUEST_TIMEOUT,
-            CloudErrorKind::SyncRootMetadataCorrupt => {
-                Foundation::STATUS_CLOUD_FILE_SYNC_ROOT_METADATA_CORRUPT
+            CloudErrorKind::RequestAborted => "the cloud operation was aborted",
+            CloudErrorKind::RequestCancelled => "the cloud operation was canceled by user",
+            CloudErrorKind::RequestTimeout => {
+                "the cloud operation was not completed before the time-out period expired"
             }
+            CloudErrorKind::SyncRootMetadataCorrupt => "the cloud sync root metadata is corrupted",
             CloudErrorKind::TooManyPropertyBlobs => {
-                Foundation::STATUS_CLOUD_FILE_TOO_MANY_PROPERTY_BLOBS
+                "the maximum number of cloud file properties has been reached"
+            }
+            CloudErrorKind::Unsuccessful => "the cloud operation was unsuccessful",
+            CloudErrorKind::ValidationFailed => {
+                "the cloud sync provider failed to validate the downloaded data"
             }
-            CloudErrorKind::Unsuccessful => Foundation::STATUS_CLOUD_FILE_UNSUCCESSFUL,
-            CloudErrorKind::ValidationFailed => Foundation::STATUS_CLOUD_FILE_VALIDATION_FAILED,
         }
     }
 }
+
+impl fmt::Display for CloudErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl From<io::ErrorKind> for CloudErrorKind {
+    /// Maps the [io::ErrorKind] to the closest [CloudErrorKind], e.g.
+    /// [io::ErrorKind::ConnectionRefused] to [CloudErrorKind::NetworkUnavailable] or
+    /// [io::ErrorKind::PermissionDenied] to [CloudErrorKind::AccessDenied].
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            // the placeholder no longer matches the remote
+            io::ErrorKind::NotFound | io::ErrorKind::UnexpectedEof => CloudErrorKind::NotInSync,
+            io::ErrorKind::PermissionDenied => CloudErrorKind::AccessDenied,
+            io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::NetworkDown
+            | io::ErrorKind::TimedOut => CloudErrorKind::NetworkUnavailable,
+            io::ErrorKind::InvalidInput => CloudErrorKind::InvalidRequest,
+            io::ErrorKind::Unsupported => CloudErrorKind::NotSupported,
+            io::ErrorKind::OutOfMemory => CloudErrorKind::InsufficientResources,
+            io::ErrorKind::ReadOnlyFilesystem => CloudErrorKind::ReadOnlyVolume,
+            io::ErrorKind::ResourceBusy => CloudErrorKind::InUse,
+            _ => CloudErrorKind::Unsuccessful,
+        }
+    }
+}
+
+impl CloudErrorKind {
+    /// Maps the [HRESULT] to its [CloudErrorKind], or the common Win32 errors to the closest
+    /// [CloudErrorKind], any other error maps to [CloudErrorKind::Unsuccessful].
+    fn classify(code: HRESULT) -> Self {
+        if let Ok(kind) = Self::try_from(code) {
+            return kind;
+        }
+
+        /// `FACILITY_WIN32`, the facility of the HRESULTs wrapping a Win32 error code.
+        const FACILITY_WIN32: u32 = 7;
+        /// `E_NOTIMPL`
+        const NOT_IMPLEMENTED: HRESULT = HRESULT(0x80004001_u32 as i32);
+
+        let code_bits = code.0 as u32;
+        if code == NOT_IMPLEMENTED {
+            return CloudErrorKind::NotSupported;
+        }
+        if !code.is_err() || (code_bits >> 16) & 0x1fff != FACILITY_WIN32 {
+            return CloudErrorKind::Unsuccessful;
+        }
+
+        match code_bits & 0xffff {
+            // ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND
+            2 | 3 => CloudErrorKind::NotInSync,
+            // ERROR_ACCESS_DENIED
+            5 => CloudErrorKind::AccessDenied,
+            // ERROR_NOT_ENOUGH_MEMORY, ERROR_OUTOFMEMORY
+            8 | 14 => CloudErrorKind::InsufficientResources,
+            // ERROR_WRITE_PROTECT
+            19 => CloudErrorKind::ReadOnlyVolume,
+            // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+            32 | 33 => CloudErrorKind::InUse,
+            // ERROR_NOT_SUPPORTED
+            50 => CloudErrorKind::NotSupported,
+            // ERROR_INVALID_PARAMETER
+            87 => CloudErrorKind::InvalidRequest,
+            // ERROR_OPERATION_ABORTED
+            995 => CloudErrorKind::RequestAborted,
+            // ERROR_CANCELLED
+            1223 => CloudErrorKind::RequestCancelled,
+            // ERROR_CONNECTION_REFUSED, ERROR_NETWORK_UNREACHABLE, ERROR_HOST_UNREACHABLE,
+            // ERROR_CONNECTION_ABORTED
+            1225 | 1231 | 1232 | 1236 => CloudErrorKind::NetworkUnavailable,
+            // ERROR_TIMEOUT
+            1460 => CloudErrorKind::RequestTimeout,
+            _ => CloudErrorKind::Unsuccessful,
+        }
+    }
+}
+
+/// The error returned by the callbacks of a [SyncFilter][crate::filter::SyncFilter], carrying
+/// the [CloudErrorKind] reported to the operating system alongside the context of the failure.
+///
+/// Any [CloudErrorKind], [io::Error] or [windows_core::Error] converts into a [CloudError] with
+/// the `?` operator, the latter two being classified into the closest [CloudErrorKind] and kept
+/// as the [source][Error::source] of the error. Before an operation is failed, the framework
+/// passes the error to the hook installed with [set_hook].
+///
+/// The alternate format (`{:#}`) displays the whole chain of sources.
+#[derive(Debug)]
+pub struct CloudError {
+    kind: CloudErrorKind,
+    message: Option<String>,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl CloudError {
+    /// Creates a new [CloudError] with a message describing the failure.
+    pub fn new(kind: CloudErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: Some(message.into()),
+            source: None,
+        }
+    }
+
+    /// Creates a new [CloudError] caused by the source error.
+    pub fn with_source(
+        kind: CloudErrorKind,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            message: None,
+            source: Some(source.into()),
+        }
+    }
+
+    /// Replaces the message describing the failure, keeping the kind and the source.
+    pub fn context(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// The [CloudErrorKind] reported to the operating system.
+    pub fn kind(&self) -> CloudErrorKind {
+        self.kind
+    }
+
+    /// The message describing the failure, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl fmt::Display for CloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => f.write_str(message)?,
+            None => write!(f, "{}", self.kind)?,
+        }
+
+        if f.alternate() {
+            let mut source = self.source();
+            while let Some(err) = source {
+                write!(f, ": {err}")?;
+                source = err.source();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for CloudError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+impl From<CloudErrorKind> for CloudError {
+    fn from(kind: CloudErrorKind) -> Self {
+        Self {
+            kind,
+            message: None,
+            source: None,
+        }
+    }
+}
+
+impl From<io::Error> for CloudError {
+    /// Classifies the [io::Error] by its [kind][io::Error::kind], or by the [CloudError] or
+    /// [windows_core::Error] it wraps.
+    fn from(err: io::Error) -> Self {
+        let kind = match err.get_ref() {
+            Some(inner) if inner.is::<CloudError>() => {
+                return *err.into_inner().unwrap().downcast::<CloudError>().unwrap();
+            }
+            Some(inner) => match inner.downcast_ref::<windows_core::Error>() {
+                Some(inner) => CloudErrorKind::classify(inner.code()),
+                None => err.kind().into(),
+            },
+            None => err.kind().into(),
+        };
+
+        Self::with_source(kind, err)
+    }
+}
+
+impl From<windows_core::Error> for CloudError {
+    /// Classifies the [windows_core::Error] by its [HRESULT].
+    fn from(err: windows_core::Error) -> Self {
+        Self::with_source(CloudErrorKind::classify(err.code()), err)
+    }
+}
+
+/// The hook called with the errors failing an operation.
+type Hook = Arc<dyn Fn(&CloudError) + Send + Sync>;
+
+static HOOK: LazyLock<RwLock<Option<Hook>>> = LazyLock::new(Default::default);
+
+/// Registers a hook called with each [CloudError] returned by a callback, right before the
+/// framework fails the operation with its [kind][CloudError::kind], replacing the previous hook.
+///
+/// By default, the error is emitted as an error event when the `tracing` feature is enabled, and
+/// ignored otherwise. The hook may itself call [set_hook].
+pub fn set_hook(hook: impl Fn(&CloudError) + Send + Sync + 'static) {
+    *HOOK.write().unwrap() = Some(Arc::new(hook));
+}
+
+/// Passes the error failing an operation to the hook.
+pub(crate) fn report(err: &CloudError) {
+    // the lock is released before calling the hook
+    let hook = HOOK.read().unwrap().clone();
+    match hook {
+        Some(hook) => hook(err),
+        #[cfg(feature = "tracing")]
+        None => tracing::error!(kind = ?err.kind(), "operation failed: {err:#}"),
+        #[cfg(not(feature = "tracing"))]
+        None => {}
+    }
+}
@@ -45,7 +45,7 @@ pub trait Filter: Send + Sync {
         _ticket: ticket::ValidateData,
         _info: info::ValidateData,
     ) -> impl Future<Output = CResult<()>> {
-        async { Err(CloudErrorKind::NotSupported) }
+        async { Err(CloudErrorKind::NotSupported.into()) }
     }
 
     /// A directory population has been requested. The behavior of this callback is dependent on
@@ -56,7 +56,7 @@ pub trait Filter: Send + Sync {
         _ticket: ticket::FetchPlaceholders,
         _info: info::FetchPlaceholders,
     ) -> impl Future<Output = CResult<()>> {
-        async { Err(CloudErrorKind::NotSupported) }
+        async { Err(CloudErrorKind::NotSupported.into()) }
     }
 
     /// A directory population request has been cancelled.
@@ -91,7 +91,7 @@ pub trait Filter: Send + Sync {
         _ticket: ticket::Dehydrate,
         _info: info::Dehydrate,
     ) -> impl Future<Output = CResult<()>> {
-        async { Err(CloudErrorKind::NotSupported) }
+        async { Err(CloudErrorKind::NotSupported.into()) }
     }
 
     /// A placeholder dehydration request has been cancelled.
@@ -109,7 +109,7 @@ pub trait Filter: Send + Sync {
         _ticket: ticket::Delete,
         _info: info::Delete,
     ) -> impl Future<Output = CResult<()>> {
-        async { Err(CloudErrorKind::NotSupported) }
+        async { Err(CloudErrorKind::NotSupported.into()) }
     }
 
     /// A placeholder file has been deleted.
@@ -130,7 +130,7 @@ pub trait Filter: Send + Sync {
         _ticket: ticket::Rename,
         _info: info::Rename,
     ) -> impl Future<Output = CResult<()>> {
-        async { Err(CloudErrorKind::NotSupported) }
+        async { Err(CloudErrorKind::NotSupported.into()) }
     }
 
     /// A placeholder file has been renamed or moved.
@@ -162,7 +162,9 @@ where
     F: Filter,
     B: Fn(LocalBoxFuture<'_, ()>) + Send + Sync,
 {
-    pub(crate) fn new(filter: F, block_on: B) -> Self {
+    /// Creates a new [AsyncBridge], `block_on` is used to drive each callback future to
+    /// completion.
+    pub fn new(filter: F, block_on: B) -> Self {
         Self { filter, block_on }
     }
 }
@@ -0,0 +1,260 @@
+use std::{fmt::Debug, ops::Range, sync::Mutex};
+
+#[cfg(windows)]
+use windows::Win32::Storage::CloudFilters::{CfReportProviderProgress, CF_CONNECTION_KEY};
+use windows_core::{self as core, HRESULT};
+
+use crate::placeholder_file::PlaceholderFile;
+#[cfg(windows)]
+use crate::{
+    command::{self, Command},
+    filter::{RawConnectionKey, RawTransferKey},
+    placeholder_file::RawPlaceholders,
+};
+
+/// The target of the operations issued through the tickets.
+///
+/// During a Cloud Filter callback, the tickets execute their operations through `CfExecute`. To
+/// call a [SyncFilter][crate::filter::SyncFilter] without the operating system, e.g. from a unit
+/// test, construct the tickets with a different [Backend], such as a [RecordingBackend].
+pub trait Backend: Debug + Send + Sync {
+    /// Reads data at an offset from the placeholder file.
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64>;
+
+    /// Writes data at an offset to the placeholder file.
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()>;
+
+    /// Reports the progress of the current operation.
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()>;
+
+    /// Validates the data range in the placeholder file.
+    fn validate(&self, range: Range<u64>) -> core::Result<()>;
+
+    /// Creates the placeholders under the placeholder directory.
+    ///
+    /// The result of each placeholder must be written back to the [PlaceholderFile].
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()>;
+
+    /// Confirms the dehydration of the placeholder file, updating its blob if it is not empty.
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()>;
+
+    /// Confirms the deletion of the placeholder.
+    fn delete(&self) -> core::Result<()>;
+
+    /// Confirms the rename/move of the placeholder.
+    fn rename(&self) -> core::Result<()>;
+}
+
+/// Executes the operations through `CfExecute`.
+#[cfg(windows)]
+#[derive(Debug)]
+pub(crate) struct CfBackend {
+    connection_key: RawConnectionKey,
+    transfer_key: RawTransferKey,
+}
+
+#[cfg(windows)]
+impl CfBackend {
+    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
+        Self {
+            connection_key,
+            transfer_key,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Backend for CfBackend {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        command::Read {
+            buffer: buf,
+            position: offset,
+        }
+        .execute(self.connection_key, self.transfer_key)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        command::Write {
+            buffer: buf,
+            position: offset,
+        }
+        .execute(self.connection_key, self.transfer_key)
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        unsafe {
+            CfReportProviderProgress(
+                CF_CONNECTION_KEY(self.connection_key),
+                self.transfer_key,
+                total as i64,
+                completed as i64,
+            )
+        }
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        command::Validate { range }.execute(self.connection_key, self.transfer_key)
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        let mut raw = RawPlaceholders::new(placeholders);
+        let result = command::CreatePlaceholders {
+            total: raw.infos.len() as _,
+            placeholders: &mut raw.infos,
+        }
+        .execute(self.connection_key, self.transfer_key);
+        raw.write_back(placeholders);
+
+        result
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        command::Dehydrate { blob }.execute(self.connection_key, self.transfer_key)
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        command::Delete.execute(self.connection_key, self.transfer_key)
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        command::Rename.execute(self.connection_key, self.transfer_key)
+    }
+}
+
+/// An operation issued through a ticket, as recorded by the [RecordingBackend].
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Data was read from the placeholder file.
+    Read {
+        /// The offset of the read.
+        offset: u64,
+        /// The amount of bytes requested.
+        length: u64,
+    },
+    /// Data was written to the placeholder file.
+    Write {
+        /// The offset of the write.
+        offset: u64,
+        /// The bytes written.
+        data: Vec<u8>,
+    },
+    /// The progress of the operation was reported.
+    ReportProgress {
+        /// The total amount of work.
+        total: u64,
+        /// The amount of work completed.
+        completed: u64,
+    },
+    /// The data range was validated.
+    Validate(Range<u64>),
+    /// The placeholders were created.
+    CreatePlaceholders(Vec<PlaceholderFile>),
+    /// The dehydration was confirmed with the blob.
+    Dehydrate(Vec<u8>),
+    /// The deletion was confirmed.
+    Delete,
+    /// The rename/move was confirmed.
+    Rename,
+}
+
+/// A [Backend] that keeps the file data in memory and records every operation issued through the
+/// tickets.
+#[derive(Debug, Default)]
+pub struct RecordingBackend {
+    data: Mutex<Vec<u8>>,
+    operations: Mutex<Vec<Operation>>,
+}
+
+impl RecordingBackend {
+    /// Creates a new [RecordingBackend] with an empty placeholder file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [RecordingBackend] whose placeholder file contains the data.
+    pub fn with_data(data: Vec<u8>) -> Self {
+        Self {
+            data: Mutex::new(data),
+            ..Default::default()
+        }
+    }
+
+    /// The operations recorded so far, in the order they were issued.
+    pub fn operations(&self) -> Vec<Operation> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    /// The content of the placeholder file, including the data written so far.
+    pub fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn record(&self, operation: Operation) {
+        self.operations.lock().unwrap().push(operation);
+    }
+}
+
+impl Backend for RecordingBackend {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        self.record(Operation::Read {
+            offset,
+            length: buf.len() as u64,
+        });
+        let data = self.data.lock().unwrap();
+
+        let start = (offset as usize).min(data.len());
+        let end = (start + buf.len()).min(data.len());
+        buf[..end - start].copy_from_slice(&data[start..end]);
+
+        Ok((end - start) as u64)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        self.record(Operation::Write {
+            offset,
+            data: buf.to_vec(),
+        });
+        let mut data = self.data.lock().unwrap();
+
+        let end = offset as usize + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset as usize..end].copy_from_slice(buf);
+
+        Ok(())
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        self.record(Operation::ReportProgress { total, completed });
+        Ok(())
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        self.record(Operation::Validate(range));
+        Ok(())
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        for placeholder in placeholders.iter_mut() {
+            placeholder.result = HRESULT(0);
+        }
+        self.record(Operation::CreatePlaceholders(placeholders.to_vec()));
+        Ok(())
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        self.record(Operation::Dehydrate(blob.to_vec()));
+        Ok(())
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.record(Operation::Delete);
+        Ok(())
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.record(Operation::Rename);
+        Ok(())
+    }
+}
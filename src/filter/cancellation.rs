@@ -0,0 +1,133 @@
+use std::{
+    future::Future,
+    mem,
+    ops::Range,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// A token tripped when the operation it was handed to is cancelled.
+///
+/// The tokens of [FetchData][crate::filter::ticket::FetchData] and
+/// [FetchPlaceholders][crate::filter::ticket::FetchPlaceholders] tickets are tracked by the
+/// transfer key of the request, and tripped right before
+/// [SyncFilter::cancel_fetch_data][crate::filter::SyncFilter::cancel_fetch_data] or
+/// [SyncFilter::cancel_fetch_placeholders][crate::filter::SyncFilter::cancel_fetch_placeholders]
+/// is called for the same transfer key, e.g. when the user cancels the hydration or the
+/// operation times out.
+///
+/// Synchronous callbacks can poll [CancellationToken::is_cancelled] between chunks, while
+/// asynchronous callbacks can race their work against [CancellationToken::cancelled].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    range: Option<Range<u64>>,
+    wakers: Vec<Waker>,
+}
+
+impl CancellationToken {
+    /// Creates a new [CancellationToken] that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operation, waking up the pending [CancellationToken::cancelled] futures.
+    pub fn cancel(&self) {
+        self.trip(None);
+    }
+
+    /// Cancels the operation, recording the range of the file that was cancelled.
+    pub(crate) fn trip(&self, range: Option<Range<u64>>) {
+        let wakers = {
+            let mut state = self.0.state.lock().unwrap();
+            if range.is_some() {
+                state.range = range;
+            }
+            self.0.cancelled.store(true, Ordering::Release);
+            mem::take(&mut state.wakers)
+        };
+
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Whether or not the operation has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// The range of the file that was cancelled, as reported by
+    /// [CancelFetchData::file_range][crate::filter::info::CancelFetchData::file_range].
+    ///
+    /// Returns [None] if the operation is not cancelled or the cancellation did not target a
+    /// range.
+    pub fn cancelled_range(&self) -> Option<Range<u64>> {
+        self.0.state.lock().unwrap().range.clone()
+    }
+
+    /// A future that completes once the operation is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled(self)
+    }
+
+    /// A reference to the token that does not keep it alive.
+    #[cfg(windows)]
+    pub(crate) fn downgrade(&self) -> WeakCancellationToken {
+        WeakCancellationToken(Arc::downgrade(&self.0))
+    }
+}
+
+/// A [CancellationToken] that is dropped along with the ticket owning it.
+#[cfg(windows)]
+#[derive(Debug, Clone)]
+pub(crate) struct WeakCancellationToken(std::sync::Weak<Inner>);
+
+#[cfg(windows)]
+impl WeakCancellationToken {
+    pub(crate) fn upgrade(&self) -> Option<CancellationToken> {
+        self.0.upgrade().map(CancellationToken)
+    }
+
+    /// Whether or not the ticket owning the token is still alive.
+    pub(crate) fn is_alive(&self) -> bool {
+        self.0.strong_count() != 0
+    }
+}
+
+/// A future that completes once the [CancellationToken] is cancelled, created with
+/// [CancellationToken::cancelled].
+#[derive(Debug)]
+pub struct Cancelled<'a>(&'a CancellationToken);
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let token = &self.0 .0;
+        if token.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+
+        let mut state = token.state.lock().unwrap();
+        // checked again under the lock so that the wake up cannot be missed
+        if token.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
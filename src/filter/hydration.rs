@@ -0,0 +1,204 @@
+use std::{
+    io,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    filter::{info, ticket},
+    utility::WriteAt,
+};
+
+/// The alignment required by `CF_OPERATION_TYPE_TRANSFER_DATA`.
+const TRANSFER_ALIGNMENT: u64 = 4096;
+/// The default size of the segments downloaded by each worker.
+const DEFAULT_SEGMENT_SIZE: usize = 1024 * 1024;
+/// The default number of workers.
+const DEFAULT_WORKERS: usize = 4;
+
+/// Hydrates a placeholder by downloading aligned segments of the requested ranges concurrently.
+///
+/// The [required range][info::FetchData::required_file_range], and optionally the
+/// [optional range][info::FetchData::optional_file_range], are aligned to 4KiB and split into
+/// segments. A bounded number of workers read the segments from a user-supplied range reader and
+/// write them to the placeholder in any order, while the aggregate progress is reported through
+/// [FetchData::report_progress][ticket::FetchData::report_progress] after each segment.
+///
+/// ```no_run
+/// # use cloud_filter::filter::{info, ticket, ParallelHydration, Request};
+/// # fn read_remote(range: std::ops::Range<u64>, buf: &mut [u8]) -> std::io::Result<()> { Ok(()) }
+/// # fn fetch_data(request: Request, ticket: ticket::FetchData, info: info::FetchData) -> std::io::Result<()> {
+/// ParallelHydration::new()
+///     .workers(8)
+///     .optional_range(true)
+///     .run(&ticket, &info, request.file_size(), read_remote)
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParallelHydration {
+    segment_size: usize,
+    workers: usize,
+    optional_range: bool,
+}
+
+impl ParallelHydration {
+    /// Creates a new [ParallelHydration].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The size of the segments, defaults to 1MiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is not a non-zero multiple of 4KiB.
+    pub fn segment_size(mut self, size: usize) -> Self {
+        assert!(
+            size != 0 && (size as u64).is_multiple_of(TRANSFER_ALIGNMENT),
+            "segment size must be a non-zero multiple of {TRANSFER_ALIGNMENT} bytes, got {size} bytes"
+        );
+        self.segment_size = size;
+        self
+    }
+
+    /// The maximum number of segments downloaded concurrently, defaults to 4.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of workers is `0`.
+    pub fn workers(mut self, workers: usize) -> Self {
+        assert!(workers != 0, "the number of workers must not be 0");
+        self.workers = workers;
+        self
+    }
+
+    /// Whether or not to hydrate the optional range along with the required range, defaults to
+    /// `false`.
+    pub fn optional_range(mut self, yes: bool) -> Self {
+        self.optional_range = yes;
+        self
+    }
+
+    /// The aligned, non-overlapping ranges to hydrate in a file of the given size.
+    fn ranges(&self, info: &info::FetchData, file_size: u64) -> Vec<Range<u64>> {
+        let align = |range: Range<u64>| {
+            let start = range.start - range.start % TRANSFER_ALIGNMENT;
+            let end = range
+                .end
+                .min(file_size)
+                .next_multiple_of(TRANSFER_ALIGNMENT)
+                .min(file_size);
+            start..end.max(start)
+        };
+
+        let mut ranges = vec![align(info.required_file_range())];
+        if self.optional_range {
+            ranges.push(align(info.optional_file_range()));
+            ranges.sort_by_key(|range| range.start);
+            if ranges[1].start <= ranges[0].end {
+                ranges[0].end = ranges[0].end.max(ranges[1].end);
+                ranges.pop();
+            }
+        }
+
+        ranges.retain(|range| !range.is_empty());
+        ranges
+    }
+
+    /// Hydrates the ranges of the placeholder, reading each segment with the closure.
+    ///
+    /// The closure must fill the whole buffer with the data of the range. It is called from
+    /// multiple threads, in no particular order.
+    ///
+    /// Returns the first error encountered, the segments not yet started are then skipped. The
+    /// segments are also skipped once the operation is
+    /// [cancelled][ticket::FetchData::cancellation_token], returning an error of kind
+    /// [io::ErrorKind::Other].
+    pub fn run<R>(
+        &self,
+        ticket: &ticket::FetchData,
+        info: &info::FetchData,
+        file_size: u64,
+        read: R,
+    ) -> io::Result<()>
+    where
+        R: Fn(Range<u64>, &mut [u8]) -> io::Result<()> + Sync,
+    {
+        let segments = self
+            .ranges(info, file_size)
+            .into_iter()
+            .flat_map(|range| {
+                range
+                    .clone()
+                    .step_by(self.segment_size)
+                    .map(move |start| start..(start + self.segment_size as u64).min(range.end))
+            })
+            .collect::<Vec<_>>();
+        let total = segments
+            .iter()
+            .map(|segment| segment.end - segment.start)
+            .sum::<u64>();
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let completed = Mutex::new(0);
+        let error = Mutex::new(None);
+        let worker = || {
+            let mut buf = vec![0; self.segment_size];
+            while !failed.load(Ordering::Acquire) {
+                let Some(segment) = segments.get(next.fetch_add(1, Ordering::AcqRel)) else {
+                    break;
+                };
+
+                let buf = &mut buf[..(segment.end - segment.start) as usize];
+                let result = match ticket.cancellation_token().is_cancelled() {
+                    true => Err(io::Error::other("the operation was cancelled")),
+                    false => read(segment.clone(), buf),
+                };
+                let result = result.and_then(|_| {
+                    ticket
+                        .write_at(buf, segment.start)
+                        .map_err(io::Error::other)?;
+
+                    // reported under the lock so that the progress never goes backwards
+                    let mut completed = completed.lock().unwrap();
+                    *completed += segment.end - segment.start;
+                    ticket
+                        .report_progress(total, *completed)
+                        .map_err(io::Error::other)
+                });
+
+                if let Err(err) = result {
+                    failed.store(true, Ordering::Release);
+                    error.lock().unwrap().get_or_insert(err);
+                }
+            }
+        };
+
+        thread::scope(|scope| {
+            for _ in 1..self.workers.min(segments.len()) {
+                scope.spawn(worker);
+            }
+            worker();
+        });
+
+        match error.into_inner().unwrap() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for ParallelHydration {
+    fn default() -> Self {
+        Self {
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            workers: DEFAULT_WORKERS,
+            optional_range: false,
+        }
+    }
+}
@@ -1,34 +1,70 @@
-use std::{ffi::OsString, fmt::Debug, ops::Range, path::PathBuf};
+use std::{fmt::Debug, ops::Range, path::PathBuf};
+
+#[cfg(windows)]
+use std::ffi::OsString;
 
 use nt_time::FileTime;
+#[cfg(windows)]
 use widestring::U16CStr;
+#[cfg(windows)]
 use windows::Win32::Storage::CloudFilters::{
     self, CF_CALLBACK_DEHYDRATION_REASON, CF_CALLBACK_PARAMETERS_0_0, CF_CALLBACK_PARAMETERS_0_1,
     CF_CALLBACK_PARAMETERS_0_10, CF_CALLBACK_PARAMETERS_0_11, CF_CALLBACK_PARAMETERS_0_2,
-    CF_CALLBACK_PARAMETERS_0_3, CF_CALLBACK_PARAMETERS_0_4, CF_CALLBACK_PARAMETERS_0_5,
-    CF_CALLBACK_PARAMETERS_0_6, CF_CALLBACK_PARAMETERS_0_7, CF_CALLBACK_PARAMETERS_0_8,
-    CF_CALLBACK_PARAMETERS_0_9,
+    CF_CALLBACK_PARAMETERS_0_3, CF_CALLBACK_PARAMETERS_0_5, CF_CALLBACK_PARAMETERS_0_6,
+    CF_CALLBACK_PARAMETERS_0_7, CF_CALLBACK_PARAMETERS_0_8, CF_CALLBACK_PARAMETERS_0_9,
 };
 
 /// Information for the [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] callback.
-pub struct FetchData(pub(crate) CF_CALLBACK_PARAMETERS_0_6);
+#[derive(Clone)]
+pub struct FetchData {
+    pub(crate) interrupted_hydration: bool,
+    pub(crate) explicit_hydration: bool,
+    pub(crate) required_file_range: Range<u64>,
+    pub(crate) optional_file_range: Range<u64>,
+    pub(crate) last_dehydration_time: FileTime,
+    pub(crate) last_dehydration_reason: Option<DehydrationReason>,
+}
 
 impl FetchData {
+    /// Creates a builder to construct a [FetchData] without the operating system.
+    pub fn builder() -> FetchDataBuilder {
+        FetchDataBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_6) -> Self {
+        Self {
+            interrupted_hydration: (params.Flags
+                & CloudFilters::CF_CALLBACK_FETCH_DATA_FLAG_RECOVERY)
+                .0
+                != 0,
+            explicit_hydration: (params.Flags
+                & CloudFilters::CF_CALLBACK_FETCH_DATA_FLAG_EXPLICIT_HYDRATION)
+                .0
+                != 0,
+            required_file_range: (params.RequiredFileOffset as u64)
+                ..(params.RequiredFileOffset + params.RequiredLength) as u64,
+            optional_file_range: (params.OptionalFileOffset as u64)
+                ..(params.OptionalFileOffset + params.OptionalLength) as u64,
+            last_dehydration_time: params.LastDehydrationTime.try_into().unwrap(),
+            last_dehydration_reason: DehydrationReason::from_win32(params.LastDehydrationReason),
+        }
+    }
+
     /// Whether or not the callback was called from an interrupted hydration.
     pub fn interrupted_hydration(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_FETCH_DATA_FLAG_RECOVERY).0 != 0
+        self.interrupted_hydration
     }
 
     /// Whether or not the callback was called from an explicit hydration via
     /// [Placeholder::hydrate][crate::placeholder::Placeholder::hydrate].
     pub fn explicit_hydration(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_FETCH_DATA_FLAG_EXPLICIT_HYDRATION).0 != 0
+        self.explicit_hydration
     }
 
     /// The amount of bytes that must be written to the placeholder.
     pub fn required_file_range(&self) -> Range<u64> {
-        (self.0.RequiredFileOffset as u64)
-            ..(self.0.RequiredFileOffset + self.0.RequiredLength) as u64
+        self.required_file_range.clone()
     }
 
     /// The amount of bytes that must be written to the placeholder.
@@ -37,18 +73,17 @@ impl FetchData {
     ///
     /// [Discussion](https://docs.microsoft.com/en-us/answers/questions/748214/what-is-fetchdataoptionalfileoffset-cfapi.html).
     pub fn optional_file_range(&self) -> Range<u64> {
-        (self.0.OptionalFileOffset as u64)
-            ..(self.0.OptionalFileOffset + self.0.OptionalLength) as u64
+        self.optional_file_range.clone()
     }
 
     /// The last time the file was dehydrated.
     pub fn last_dehydration_time(&self) -> FileTime {
-        self.0.LastDehydrationTime.try_into().unwrap()
+        self.last_dehydration_time
     }
 
     /// The reason the file was last dehydrated.
     pub fn last_dehydration_reason(&self) -> Option<DehydrationReason> {
-        DehydrationReason::from_win32(self.0.LastDehydrationReason)
+        self.last_dehydration_reason
     }
 }
 
@@ -64,26 +99,105 @@ impl Debug for FetchData {
     }
 }
 
+/// A builder to construct a [FetchData].
+#[derive(Debug)]
+pub struct FetchDataBuilder(FetchData);
+
+impl FetchDataBuilder {
+    /// Whether or not the callback was called from an interrupted hydration.
+    pub fn interrupted_hydration(mut self, yes: bool) -> Self {
+        self.0.interrupted_hydration = yes;
+        self
+    }
+
+    /// Whether or not the callback was called from an explicit hydration.
+    pub fn explicit_hydration(mut self, yes: bool) -> Self {
+        self.0.explicit_hydration = yes;
+        self
+    }
+
+    /// The range of bytes that must be written to the placeholder.
+    pub fn required_range(mut self, range: Range<u64>) -> Self {
+        self.0.required_file_range = range;
+        self
+    }
+
+    /// The range of bytes that may be written to the placeholder.
+    pub fn optional_range(mut self, range: Range<u64>) -> Self {
+        self.0.optional_file_range = range;
+        self
+    }
+
+    /// The last time the file was dehydrated.
+    pub fn last_dehydration_time(mut self, time: FileTime) -> Self {
+        self.0.last_dehydration_time = time;
+        self
+    }
+
+    /// The reason the file was last dehydrated.
+    pub fn last_dehydration_reason(mut self, reason: DehydrationReason) -> Self {
+        self.0.last_dehydration_reason = Some(reason);
+        self
+    }
+
+    /// Constructs the [FetchData].
+    pub fn build(self) -> FetchData {
+        self.0
+    }
+}
+
+impl Default for FetchDataBuilder {
+    fn default() -> Self {
+        Self(FetchData {
+            interrupted_hydration: false,
+            explicit_hydration: false,
+            required_file_range: 0..0,
+            optional_file_range: 0..0,
+            last_dehydration_time: FileTime::default(),
+            last_dehydration_reason: None,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::cancel_fetch_data][crate::filter::SyncFilter::cancel_fetch_data] callback.
-pub struct CancelFetchData(pub(crate) CF_CALLBACK_PARAMETERS_0_0);
+pub struct CancelFetchData {
+    pub(crate) timeout: bool,
+    pub(crate) user_cancelled: bool,
+    pub(crate) file_range: Range<u64>,
+}
 
 impl CancelFetchData {
+    /// Creates a builder to construct a [CancelFetchData] without the operating system.
+    pub fn builder() -> CancelFetchDataBuilder {
+        CancelFetchDataBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_0) -> Self {
+        let range = unsafe { params.Anonymous.FetchData };
+        Self {
+            timeout: (params.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_TIMEOUT).0 != 0,
+            user_cancelled: (params.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_ABORTED).0
+                != 0,
+            file_range: (range.FileOffset as u64)..(range.FileOffset + range.Length) as u64,
+        }
+    }
+
     /// Whether or not the callback failed as a result of the 60 second timeout.
     pub fn timeout(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_TIMEOUT).0 != 0
+        self.timeout
     }
 
     /// The user has cancelled the request manually.
     ///
     /// A user could cancel a request through a download toast?
     pub fn user_cancelled(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_ABORTED).0 != 0
+        self.user_cancelled
     }
 
     /// The range of the file data that is no longer required.
     pub fn file_range(&self) -> Range<u64> {
-        let range = unsafe { self.0.Anonymous.FetchData };
-        (range.FileOffset as u64)..(range.FileOffset + range.Length) as u64
+        self.file_range.clone()
     }
 }
 
@@ -97,19 +211,77 @@ impl Debug for CancelFetchData {
     }
 }
 
+/// A builder to construct a [CancelFetchData].
+#[derive(Debug)]
+pub struct CancelFetchDataBuilder(CancelFetchData);
+
+impl CancelFetchDataBuilder {
+    /// Whether or not the request was cancelled as a result of the 60 second timeout.
+    pub fn timeout(mut self, yes: bool) -> Self {
+        self.0.timeout = yes;
+        self
+    }
+
+    /// Whether or not the user cancelled the request manually.
+    pub fn user_cancelled(mut self, yes: bool) -> Self {
+        self.0.user_cancelled = yes;
+        self
+    }
+
+    /// The range of the file data that is no longer required.
+    pub fn file_range(mut self, range: Range<u64>) -> Self {
+        self.0.file_range = range;
+        self
+    }
+
+    /// Constructs the [CancelFetchData].
+    pub fn build(self) -> CancelFetchData {
+        self.0
+    }
+}
+
+impl Default for CancelFetchDataBuilder {
+    fn default() -> Self {
+        Self(CancelFetchData {
+            timeout: false,
+            user_cancelled: false,
+            file_range: 0..0,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::validate_data][crate::filter::SyncFilter::validate_data] callback.
-pub struct ValidateData(pub(crate) CF_CALLBACK_PARAMETERS_0_11);
+pub struct ValidateData {
+    pub(crate) explicit_hydration: bool,
+    pub(crate) file_range: Range<u64>,
+}
 
 impl ValidateData {
+    /// Creates a builder to construct a [ValidateData] without the operating system.
+    pub fn builder() -> ValidateDataBuilder {
+        ValidateDataBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_11) -> Self {
+        Self {
+            explicit_hydration: (params.Flags
+                & CloudFilters::CF_CALLBACK_VALIDATE_DATA_FLAG_EXPLICIT_HYDRATION)
+                .0
+                != 0,
+            file_range: (params.RequiredFileOffset as u64)
+                ..(params.RequiredFileOffset + params.RequiredLength) as u64,
+        }
+    }
+
     /// Whether or not the callback failed as a result of the 60 second timeout.
     pub fn explicit_hydration(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_VALIDATE_DATA_FLAG_EXPLICIT_HYDRATION).0 != 0
+        self.explicit_hydration
     }
 
     /// The range of data to validate.
     pub fn file_range(&self) -> Range<u64> {
-        (self.0.RequiredFileOffset as u64)
-            ..(self.0.RequiredFileOffset + self.0.RequiredLength) as u64
+        self.file_range.clone()
     }
 }
 
@@ -122,18 +294,64 @@ impl Debug for ValidateData {
     }
 }
 
+/// A builder to construct a [ValidateData].
+#[derive(Debug)]
+pub struct ValidateDataBuilder(ValidateData);
+
+impl ValidateDataBuilder {
+    /// Whether or not the callback was called from an explicit hydration.
+    pub fn explicit_hydration(mut self, yes: bool) -> Self {
+        self.0.explicit_hydration = yes;
+        self
+    }
+
+    /// The range of data to validate.
+    pub fn file_range(mut self, range: Range<u64>) -> Self {
+        self.0.file_range = range;
+        self
+    }
+
+    /// Constructs the [ValidateData].
+    pub fn build(self) -> ValidateData {
+        self.0
+    }
+}
+
+impl Default for ValidateDataBuilder {
+    fn default() -> Self {
+        Self(ValidateData {
+            explicit_hydration: false,
+            file_range: 0..0,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders]
 /// callback.
-pub struct FetchPlaceholders(pub(crate) CF_CALLBACK_PARAMETERS_0_7);
+#[derive(Clone)]
+pub struct FetchPlaceholders {
+    pub(crate) pattern: String,
+}
 
 impl FetchPlaceholders {
+    /// Creates a builder to construct a [FetchPlaceholders] without the operating system.
+    pub fn builder() -> FetchPlaceholdersBuilder {
+        FetchPlaceholdersBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_7) -> Self {
+        Self {
+            pattern: unsafe { U16CStr::from_ptr_str(params.Pattern.0) }.to_string_lossy(),
+        }
+    }
+
     /// A glob pattern specifying the files that should be fetched.
     ///
     /// This field is completely optional and does not have to be respected.
     #[cfg(feature = "globs")]
     pub fn pattern(&self) -> Result<globset::Glob, globset::Error> {
-        let pattern = unsafe { U16CStr::from_ptr_str(self.0.Pattern.0) }.to_string_lossy();
-        globset::Glob::new(&pattern)
+        globset::Glob::new(&self.pattern)
     }
 
     /// A glob pattern specifying the files that should be fetched.
@@ -141,7 +359,7 @@ impl FetchPlaceholders {
     /// This field is completely optional and does not have to be respected.
     #[cfg(not(feature = "globs"))]
     pub fn pattern(&self) -> String {
-        unsafe { U16CStr::from_ptr_str(self.0.Pattern.0) }.to_string_lossy()
+        self.pattern.clone()
     }
 }
 
@@ -153,23 +371,65 @@ impl Debug for FetchPlaceholders {
     }
 }
 
+/// A builder to construct a [FetchPlaceholders].
+#[derive(Debug)]
+pub struct FetchPlaceholdersBuilder(FetchPlaceholders);
+
+impl FetchPlaceholdersBuilder {
+    /// A glob pattern specifying the files that should be fetched, defaults to `*`.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.0.pattern = pattern.into();
+        self
+    }
+
+    /// Constructs the [FetchPlaceholders].
+    pub fn build(self) -> FetchPlaceholders {
+        self.0
+    }
+}
+
+impl Default for FetchPlaceholdersBuilder {
+    fn default() -> Self {
+        Self(FetchPlaceholders {
+            pattern: "*".to_owned(),
+        })
+    }
+}
+
 /// Information for the
 /// [SyncFilter::cancel_fetch_placeholders][super::SyncFilter::cancel_fetch_placeholders] callback.
-pub struct CancelFetchPlaceholders(pub(crate) CF_CALLBACK_PARAMETERS_0_0);
+pub struct CancelFetchPlaceholders {
+    pub(crate) timeout: bool,
+    pub(crate) user_cancelled: bool,
+}
 
 impl CancelFetchPlaceholders {
+    /// Creates a builder to construct a [CancelFetchPlaceholders] without the operating system.
+    pub fn builder() -> CancelFetchPlaceholdersBuilder {
+        CancelFetchPlaceholdersBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_0) -> Self {
+        Self {
+            timeout: (params.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_TIMEOUT).0 != 0,
+            user_cancelled: (params.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_ABORTED).0
+                != 0,
+        }
+    }
+
     /// Whether or not the callback failed as a result of the 60 second timeout.
     ///
     // Read more [here][crate::filter::Request::reset_timeout].
     pub fn timeout(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_TIMEOUT).0 != 0
+        self.timeout
     }
 
     /// The user has cancelled the request manually.
     ///
     /// A user could cancel a request through a download toast?
     pub fn user_cancelled(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_CANCEL_FLAG_IO_ABORTED).0 != 0
+        self.user_cancelled
     }
 }
 
@@ -182,19 +442,72 @@ impl Debug for CancelFetchPlaceholders {
     }
 }
 
+/// A builder to construct a [CancelFetchPlaceholders].
+#[derive(Debug)]
+pub struct CancelFetchPlaceholdersBuilder(CancelFetchPlaceholders);
+
+impl CancelFetchPlaceholdersBuilder {
+    /// Whether or not the request was cancelled as a result of the 60 second timeout.
+    pub fn timeout(mut self, yes: bool) -> Self {
+        self.0.timeout = yes;
+        self
+    }
+
+    /// Whether or not the user cancelled the request manually.
+    pub fn user_cancelled(mut self, yes: bool) -> Self {
+        self.0.user_cancelled = yes;
+        self
+    }
+
+    /// Constructs the [CancelFetchPlaceholders].
+    pub fn build(self) -> CancelFetchPlaceholders {
+        self.0
+    }
+}
+
+impl Default for CancelFetchPlaceholdersBuilder {
+    fn default() -> Self {
+        Self(CancelFetchPlaceholders {
+            timeout: false,
+            user_cancelled: false,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::opened][super::SyncFilter::opened] callback.
-pub struct Opened(pub(crate) CF_CALLBACK_PARAMETERS_0_8);
+pub struct Opened {
+    pub(crate) metadata_corrupt: bool,
+    pub(crate) metadata_unsupported: bool,
+}
 
 impl Opened {
+    /// Creates a builder to construct a [Opened] without the operating system.
+    pub fn builder() -> OpenedBuilder {
+        OpenedBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_8) -> Self {
+        Self {
+            metadata_corrupt: (params.Flags
+                & CloudFilters::CF_CALLBACK_OPEN_COMPLETION_FLAG_PLACEHOLDER_UNKNOWN)
+                .0
+                != 0,
+            metadata_unsupported: (params.Flags
+                & CloudFilters::CF_CALLBACK_OPEN_COMPLETION_FLAG_PLACEHOLDER_UNSUPPORTED)
+                .0
+                != 0,
+        }
+    }
+
     /// The placeholder metadata is corrupt.
     pub fn metadata_corrupt(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_OPEN_COMPLETION_FLAG_PLACEHOLDER_UNKNOWN).0 != 0
+        self.metadata_corrupt
     }
 
     /// The placeholder metadata is not supported.
     pub fn metadata_unsupported(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_OPEN_COMPLETION_FLAG_PLACEHOLDER_UNSUPPORTED).0
-            != 0
+        self.metadata_unsupported
     }
 }
 
@@ -207,13 +520,60 @@ impl Debug for Opened {
     }
 }
 
+/// A builder to construct an [Opened].
+#[derive(Debug)]
+pub struct OpenedBuilder(Opened);
+
+impl OpenedBuilder {
+    /// Whether or not the placeholder metadata is corrupt.
+    pub fn metadata_corrupt(mut self, yes: bool) -> Self {
+        self.0.metadata_corrupt = yes;
+        self
+    }
+
+    /// Whether or not the placeholder metadata is not supported.
+    pub fn metadata_unsupported(mut self, yes: bool) -> Self {
+        self.0.metadata_unsupported = yes;
+        self
+    }
+
+    /// Constructs the [Opened].
+    pub fn build(self) -> Opened {
+        self.0
+    }
+}
+
+impl Default for OpenedBuilder {
+    fn default() -> Self {
+        Self(Opened {
+            metadata_corrupt: false,
+            metadata_unsupported: false,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::closed][super::SyncFilter::closed] callback.
-pub struct Closed(pub(crate) CF_CALLBACK_PARAMETERS_0_1);
+pub struct Closed {
+    pub(crate) deleted: bool,
+}
 
 impl Closed {
+    /// Creates a builder to construct a [Closed] without the operating system.
+    pub fn builder() -> ClosedBuilder {
+        ClosedBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_1) -> Self {
+        Self {
+            deleted: (params.Flags & CloudFilters::CF_CALLBACK_CLOSE_COMPLETION_FLAG_DELETED).0
+                != 0,
+        }
+    }
+
     /// Whether or not the placeholder was deleted as a result of the close.
     pub fn deleted(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_CLOSE_COMPLETION_FLAG_DELETED).0 != 0
+        self.deleted
     }
 }
 
@@ -225,18 +585,57 @@ impl Debug for Closed {
     }
 }
 
+/// A builder to construct a [Closed].
+#[derive(Debug)]
+pub struct ClosedBuilder(Closed);
+
+impl ClosedBuilder {
+    /// Whether or not the placeholder was deleted as a result of the close.
+    pub fn deleted(mut self, yes: bool) -> Self {
+        self.0.deleted = yes;
+        self
+    }
+
+    /// Constructs the [Closed].
+    pub fn build(self) -> Closed {
+        self.0
+    }
+}
+
+impl Default for ClosedBuilder {
+    fn default() -> Self {
+        Self(Closed { deleted: false })
+    }
+}
+
 /// Information for the [SyncFilter::dehydrate][super::SyncFilter::dehydrate] callback.
-pub struct Dehydrate(pub(crate) CF_CALLBACK_PARAMETERS_0_3);
+pub struct Dehydrate {
+    pub(crate) background: bool,
+    pub(crate) reason: Option<DehydrationReason>,
+}
 
 impl Dehydrate {
+    /// Creates a builder to construct a [Dehydrate] without the operating system.
+    pub fn builder() -> DehydrateBuilder {
+        DehydrateBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_3) -> Self {
+        Self {
+            background: (params.Flags & CloudFilters::CF_CALLBACK_DEHYDRATE_FLAG_BACKGROUND).0 != 0,
+            reason: DehydrationReason::from_win32(params.Reason),
+        }
+    }
+
     /// Whether or not the callback was called from a system background service.
     pub fn background(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_DEHYDRATE_FLAG_BACKGROUND).0 != 0
+        self.background
     }
 
     /// The reason the file is being dehydrated.
     pub fn reason(&self) -> Option<DehydrationReason> {
-        DehydrationReason::from_win32(self.0.Reason)
+        self.reason
     }
 }
 
@@ -249,23 +648,79 @@ impl Debug for Dehydrate {
     }
 }
 
+/// A builder to construct a [Dehydrate].
+#[derive(Debug)]
+pub struct DehydrateBuilder(Dehydrate);
+
+impl DehydrateBuilder {
+    /// Whether or not the callback was called from a system background service.
+    pub fn background(mut self, yes: bool) -> Self {
+        self.0.background = yes;
+        self
+    }
+
+    /// The reason the file is being dehydrated.
+    pub fn reason(mut self, reason: DehydrationReason) -> Self {
+        self.0.reason = Some(reason);
+        self
+    }
+
+    /// Constructs the [Dehydrate].
+    pub fn build(self) -> Dehydrate {
+        self.0
+    }
+}
+
+impl Default for DehydrateBuilder {
+    fn default() -> Self {
+        Self(Dehydrate {
+            background: false,
+            reason: None,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::dehydrated][super::SyncFilter::dehydrated] callback.
-pub struct Dehydrated(pub(crate) CF_CALLBACK_PARAMETERS_0_2);
+pub struct Dehydrated {
+    pub(crate) background: bool,
+    pub(crate) already_hydrated: bool,
+    pub(crate) reason: Option<DehydrationReason>,
+}
 
 impl Dehydrated {
+    /// Creates a builder to construct a [Dehydrated] without the operating system.
+    pub fn builder() -> DehydratedBuilder {
+        DehydratedBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_2) -> Self {
+        Self {
+            background: (params.Flags
+                & CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_BACKGROUND)
+                .0
+                != 0,
+            already_hydrated: (params.Flags
+                & CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_DEHYDRATED)
+                .0
+                != 0,
+            reason: DehydrationReason::from_win32(params.Reason),
+        }
+    }
+
     /// Whether or not the callback was called from a system background service.
     pub fn background(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_BACKGROUND).0 != 0
+        self.background
     }
 
     /// Whether or not the placeholder was already hydrated.
     pub fn already_hydrated(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_DEHYDRATE_COMPLETION_FLAG_DEHYDRATED).0 != 0
+        self.already_hydrated
     }
 
     /// The reason the file is being dehydrated.
     pub fn reason(&self) -> Option<DehydrationReason> {
-        DehydrationReason::from_win32(self.0.Reason)
+        self.reason
     }
 }
 
@@ -279,19 +734,75 @@ impl Debug for Dehydrated {
     }
 }
 
+/// A builder to construct a [Dehydrated].
+#[derive(Debug)]
+pub struct DehydratedBuilder(Dehydrated);
+
+impl DehydratedBuilder {
+    /// Whether or not the callback was called from a system background service.
+    pub fn background(mut self, yes: bool) -> Self {
+        self.0.background = yes;
+        self
+    }
+
+    /// Whether or not the placeholder was already hydrated.
+    pub fn already_hydrated(mut self, yes: bool) -> Self {
+        self.0.already_hydrated = yes;
+        self
+    }
+
+    /// The reason the file is being dehydrated.
+    pub fn reason(mut self, reason: DehydrationReason) -> Self {
+        self.0.reason = Some(reason);
+        self
+    }
+
+    /// Constructs the [Dehydrated].
+    pub fn build(self) -> Dehydrated {
+        self.0
+    }
+}
+
+impl Default for DehydratedBuilder {
+    fn default() -> Self {
+        Self(Dehydrated {
+            background: false,
+            already_hydrated: false,
+            reason: None,
+        })
+    }
+}
+
 /// Information for the [SyncFilter::delete][super::SyncFilter::delete] callback.
-pub struct Delete(pub(crate) CF_CALLBACK_PARAMETERS_0_5);
+pub struct Delete {
+    pub(crate) is_directory: bool,
+    pub(crate) is_undelete: bool,
+}
 
 impl Delete {
+    /// Creates a builder to construct a [Delete] without the operating system.
+    pub fn builder() -> DeleteBuilder {
+        DeleteBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_5) -> Self {
+        Self {
+            is_directory: (params.Flags & CloudFilters::CF_CALLBACK_DELETE_FLAG_IS_DIRECTORY).0
+                != 0,
+            is_undelete: (params.Flags & CloudFilters::CF_CALLBACK_DELETE_FLAG_IS_UNDELETE).0 != 0,
+        }
+    }
+
     /// Whether or not the placeholder being deleted is a directory.
     pub fn is_directory(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_DELETE_FLAG_IS_DIRECTORY).0 != 0
+        self.is_directory
     }
 
     // TODO: missing docs
     /// The placeholder is being undeleted.
     pub fn is_undelete(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_DELETE_FLAG_IS_UNDELETE).0 != 0
+        self.is_undelete
     }
 }
 
@@ -304,35 +815,102 @@ impl Debug for Delete {
     }
 }
 
-/// Information for the [SyncFilter::deleted][crate::filter::SyncFilter::deleted] callback.
+/// A builder to construct a [Delete].
 #[derive(Debug)]
-#[allow(dead_code)]
-pub struct Deleted(pub(crate) CF_CALLBACK_PARAMETERS_0_4);
+pub struct DeleteBuilder(Delete);
+
+impl DeleteBuilder {
+    /// Whether or not the placeholder being deleted is a directory.
+    pub fn is_directory(mut self, yes: bool) -> Self {
+        self.0.is_directory = yes;
+        self
+    }
+
+    /// Whether or not the placeholder is being undeleted.
+    pub fn is_undelete(mut self, yes: bool) -> Self {
+        self.0.is_undelete = yes;
+        self
+    }
+
+    /// Constructs the [Delete].
+    pub fn build(self) -> Delete {
+        self.0
+    }
+}
+
+impl Default for DeleteBuilder {
+    fn default() -> Self {
+        Self(Delete {
+            is_directory: false,
+            is_undelete: false,
+        })
+    }
+}
+
+/// Information for the [SyncFilter::deleted][crate::filter::SyncFilter::deleted] callback.
+#[derive(Debug, Default)]
+pub struct Deleted {
+    pub(crate) _private: (),
+}
+
+impl Deleted {
+    /// Creates a [Deleted] outside of the
+    /// [SyncFilter::deleted][crate::filter::SyncFilter::deleted] callback.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 
 /// Information for the [SyncFilter::rename][crate::filter::SyncFilter::rename] callback.
-pub struct Rename(pub(crate) CF_CALLBACK_PARAMETERS_0_10, pub(crate) OsString);
+pub struct Rename {
+    pub(crate) is_directory: bool,
+    pub(crate) source_in_scope: bool,
+    pub(crate) target_in_scope: bool,
+    pub(crate) target_path: PathBuf,
+}
 
 impl Rename {
+    /// Creates a builder to construct a [Rename] without the operating system.
+    pub fn builder() -> RenameBuilder {
+        RenameBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_10, volume_letter: OsString) -> Self {
+        let mut target_path = PathBuf::from(volume_letter);
+        target_path.push(unsafe { U16CStr::from_ptr_str(params.TargetPath.0) }.to_os_string());
+
+        Self {
+            is_directory: (params.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_IS_DIRECTORY).0
+                != 0,
+            source_in_scope: (params.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_SOURCE_IN_SCOPE)
+                .0
+                != 0,
+            target_in_scope: (params.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_TARGET_IN_SCOPE)
+                .0
+                != 0,
+            target_path,
+        }
+    }
+
     /// Whether or not the placeholder being renamed is a directory.
     pub fn is_directory(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_IS_DIRECTORY).0 != 0
+        self.is_directory
     }
 
     /// Whether or not the placeholder was originally in the sync root.
     pub fn source_in_scope(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_SOURCE_IN_SCOPE).0 != 0
+        self.source_in_scope
     }
 
     /// Whether or not the placeholder is being moved inside the sync root.
     pub fn target_in_scope(&self) -> bool {
-        (self.0.Flags & CloudFilters::CF_CALLBACK_RENAME_FLAG_TARGET_IN_SCOPE).0 != 0
+        self.target_in_scope
     }
 
     /// The full path the placeholder is being moved to.
     pub fn target_path(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.1);
-        path.push(unsafe { U16CStr::from_ptr_str(self.0.TargetPath.0) }.to_os_string());
-        path
+        self.target_path.clone()
     }
 }
 
@@ -347,15 +925,74 @@ impl Debug for Rename {
     }
 }
 
+/// A builder to construct a [Rename].
+#[derive(Debug)]
+pub struct RenameBuilder(Rename);
+
+impl RenameBuilder {
+    /// Whether or not the placeholder being renamed is a directory.
+    pub fn is_directory(mut self, yes: bool) -> Self {
+        self.0.is_directory = yes;
+        self
+    }
+
+    /// Whether or not the placeholder was originally in the sync root, defaults to `true`.
+    pub fn source_in_scope(mut self, yes: bool) -> Self {
+        self.0.source_in_scope = yes;
+        self
+    }
+
+    /// Whether or not the placeholder is being moved inside the sync root, defaults to `true`.
+    pub fn target_in_scope(mut self, yes: bool) -> Self {
+        self.0.target_in_scope = yes;
+        self
+    }
+
+    /// The full path the placeholder is being moved to.
+    pub fn target_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.0.target_path = path.into();
+        self
+    }
+
+    /// Constructs the [Rename].
+    pub fn build(self) -> Rename {
+        self.0
+    }
+}
+
+impl Default for RenameBuilder {
+    fn default() -> Self {
+        Self(Rename {
+            is_directory: false,
+            source_in_scope: true,
+            target_in_scope: true,
+            target_path: PathBuf::new(),
+        })
+    }
+}
+
 /// Information for the [SyncFilter::renamed][crate::filter::SyncFilter::renamed] callback.
-pub struct Renamed(pub(crate) CF_CALLBACK_PARAMETERS_0_9, pub(crate) OsString);
+pub struct Renamed {
+    pub(crate) source_path: PathBuf,
+}
 
 impl Renamed {
+    /// Creates a builder to construct a [Renamed] without the operating system.
+    pub fn builder() -> RenamedBuilder {
+        RenamedBuilder::default()
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn from_raw(params: &CF_CALLBACK_PARAMETERS_0_9, volume_letter: OsString) -> Self {
+        let mut source_path = PathBuf::from(volume_letter);
+        source_path.push(unsafe { U16CStr::from_ptr_str(params.SourcePath.0) }.to_os_string());
+
+        Self { source_path }
+    }
+
     /// The full path the placeholder has been moved from.
     pub fn source_path(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.1);
-        path.push(unsafe { U16CStr::from_ptr_str(self.0.SourcePath.0) }.to_os_string());
-        path
+        self.source_path.clone()
     }
 }
 
@@ -367,8 +1004,34 @@ impl Debug for Renamed {
     }
 }
 
+/// A builder to construct a [Renamed].
+#[derive(Debug)]
+pub struct RenamedBuilder(Renamed);
+
+impl RenamedBuilder {
+    /// The full path the placeholder has been moved from.
+    pub fn source_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.0.source_path = path.into();
+        self
+    }
+
+    /// Constructs the [Renamed].
+    pub fn build(self) -> Renamed {
+        self.0
+    }
+}
+
+impl Default for RenamedBuilder {
+    fn default() -> Self {
+        Self(Renamed {
+            source_path: PathBuf::new(),
+        })
+    }
+}
+
 /// The reason a placeholder has been dehydrated.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "record", derive(serde::Serialize, serde::Deserialize))]
 pub enum DehydrationReason {
     /// The user manually dehydrated the placeholder.
     UserManually,
@@ -384,6 +1047,7 @@ pub enum DehydrationReason {
     OsUpgrade,
 }
 
+#[cfg(windows)]
 impl DehydrationReason {
     fn from_win32(reason: CF_CALLBACK_DEHYDRATION_REASON) -> Option<DehydrationReason> {
         match reason {
@@ -0,0 +1,82 @@
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::filter::{Backend, CancellationToken};
+
+/// Keeps a long-running operation alive until dropped, created with
+/// [FetchData::keep_alive][crate::filter::ticket::FetchData::keep_alive] or
+/// [FetchPlaceholders::keep_alive][crate::filter::ticket::FetchPlaceholders::keep_alive].
+///
+/// The operating system cancels a callback after 60 seconds without activity, failing the
+/// operation with [CloudErrorKind::RequestTimeout][crate::error::CloudErrorKind::RequestTimeout].
+/// While the guard is alive, a background timer re-reports the last progress of the operation at
+/// the given interval, which resets the timeout without moving the progress bar. The timer stops
+/// once the guard is dropped or the operation is cancelled.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use cloud_filter::filter::ticket;
+/// # fn restore_from_tape() {}
+/// # fn fetch_data(ticket: ticket::FetchData) {
+/// let keep_alive = ticket.keep_alive(Duration::from_secs(20));
+/// restore_from_tape();
+/// drop(keep_alive);
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use = "the operation is only kept alive until the guard is dropped"]
+pub struct KeepAlive {
+    stop: Option<Sender<()>>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    /// Starts the background timer reporting the progress through the [Backend].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub(crate) fn spawn(
+        backend: Arc<dyn Backend>,
+        progress: Arc<Mutex<(u64, u64)>>,
+        token: CancellationToken,
+        interval: Duration,
+    ) -> Self {
+        assert!(!interval.is_zero(), "the keep-alive interval must not be 0");
+
+        let (stop, receiver) = mpsc::channel::<()>();
+        let timer = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(interval) {
+                if token.is_cancelled() {
+                    break;
+                }
+
+                // reported under the lock so that the progress never goes backwards
+                let progress = progress.lock().unwrap();
+                if backend.report_progress(progress.0, progress.1).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            timer: Some(timer),
+        }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(timer) = self.timer.take() {
+            _ = timer.join();
+        }
+    }
+}
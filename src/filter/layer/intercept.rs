@@ -0,0 +1,163 @@
+use std::{future::Future, path::PathBuf, sync::Arc};
+
+use crate::{
+    error::CResult,
+    filter::{info, ticket, Filter, Request, SyncFilter},
+};
+
+/// A filter made of a [SyncLayer] or an [AsyncLayer] intercepting the callbacks of the inner
+/// filter, created by [Intercept][super::Intercept] or [Layered::new].
+///
+/// [Layered] implements [SyncFilter] when the layer is a [SyncLayer], and [Filter] when the layer
+/// is an [AsyncLayer].
+#[derive(Debug)]
+pub struct Layered<L, F> {
+    layer: L,
+    inner: F,
+}
+
+impl<L, F> Layered<L, F> {
+    /// Wraps the inner filter with the layer.
+    pub fn new(layer: L, inner: F) -> Self {
+        Self { layer, inner }
+    }
+
+    /// The layer intercepting the callbacks.
+    pub fn layer(&self) -> &L {
+        &self.layer
+    }
+
+    /// The inner filter.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+}
+
+/// Generates the layer traits, forwarding each callback to the next filter by default, and the
+/// filter implementations of [Layered] handing each callback to its layer.
+macro_rules! layers {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+        /// Intercepts the callbacks of a [SyncFilter].
+        ///
+        /// Each method receives the next filter of the stack alongside the arguments of the
+        /// callback. A layer can inspect the [Request] and the info, delegate to the next filter,
+        /// or short-circuit the callback with an error, e.g.
+        /// `Err(CloudErrorKind::AccessDenied.into())`. Every callback is forwarded as is by
+        /// default, so a layer only overrides the callbacks it needs.
+        ///
+        /// Add a layer to a [FilterStack][super::FilterStack] with
+        /// [FilterStack::intercept][super::FilterStack::intercept].
+        pub trait SyncLayer<F: SyncFilter>: Send + Sync {
+            $(
+                #[doc = concat!(
+                    "Intercepts [SyncFilter::", stringify!($name), "], forwarded to the next ",
+                    "filter by default."
+                )]
+                fn $name(&self, next: &F, $($arg: $ty),*) $(-> $ret)? {
+                    next.$name($($arg),*)
+                }
+            )*
+        }
+
+        /// Intercepts the callbacks of a [Filter], the async counterpart of [SyncLayer].
+        pub trait AsyncLayer<F: Filter>: Send + Sync {
+            $(
+                #[doc = concat!(
+                    "Intercepts [Filter::", stringify!($name), "], forwarded to the next ",
+                    "filter by default."
+                )]
+                fn $name(
+                    &self,
+                    next: &F,
+                    $($arg: $ty),*
+                ) -> impl Future<Output = layers!(@output $($ret)?)> {
+                    next.$name($($arg),*)
+                }
+            )*
+        }
+
+        /// A layer shared with the rest of the application.
+        impl<L, F> SyncLayer<F> for Arc<L>
+        where
+            L: SyncLayer<F> + ?Sized,
+            F: SyncFilter,
+        {
+            $(
+                fn $name(&self, next: &F, $($arg: $ty),*) $(-> $ret)? {
+                    (**self).$name(next, $($arg),*)
+                }
+            )*
+        }
+
+        /// A layer shared with the rest of the application.
+        impl<L, F> AsyncLayer<F> for Arc<L>
+        where
+            L: AsyncLayer<F> + ?Sized,
+            F: Filter,
+        {
+            $(
+                fn $name(
+                    &self,
+                    next: &F,
+                    $($arg: $ty),*
+                ) -> impl Future<Output = layers!(@output $($ret)?)> {
+                    (**self).$name(next, $($arg),*)
+                }
+            )*
+        }
+
+        impl<L, F> SyncFilter for Layered<L, F>
+        where
+            L: SyncLayer<F>,
+            F: SyncFilter,
+        {
+            $(
+                fn $name(&self, $($arg: $ty),*) $(-> $ret)? {
+                    SyncLayer::$name(&self.layer, &self.inner, $($arg),*)
+                }
+            )*
+        }
+
+        impl<L, F> Filter for Layered<L, F>
+        where
+            L: AsyncLayer<F>,
+            F: Filter,
+        {
+            $(
+                fn $name(
+                    &self,
+                    $($arg: $ty),*
+                ) -> impl Future<Output = layers!(@output $($ret)?)> {
+                    AsyncLayer::$name(&self.layer, &self.inner, $($arg),*)
+                }
+            )*
+        }
+    };
+    (@output) => { () };
+    (@output $ret:ty) => { $ret };
+}
+
+layers! {
+    fn fetch_data(request: Request, ticket: ticket::FetchData, info: info::FetchData) -> CResult<()>;
+    fn cancel_fetch_data(request: Request, info: info::CancelFetchData);
+    fn validate_data(
+        request: Request,
+        ticket: ticket::ValidateData,
+        info: info::ValidateData
+    ) -> CResult<()>;
+    fn fetch_placeholders(
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders
+    ) -> CResult<()>;
+    fn cancel_fetch_placeholders(request: Request, info: info::CancelFetchPlaceholders);
+    fn opened(request: Request, info: info::Opened);
+    fn closed(request: Request, info: info::Closed);
+    fn dehydrate(request: Request, ticket: ticket::Dehydrate, info: info::Dehydrate) -> CResult<()>;
+    fn dehydrated(request: Request, info: info::Dehydrated);
+    fn delete(request: Request, ticket: ticket::Delete, info: info::Delete) -> CResult<()>;
+    fn deleted(request: Request, info: info::Deleted);
+    fn rename(request: Request, ticket: ticket::Rename, info: info::Rename) -> CResult<()>;
+    fn renamed(request: Request, info: info::Renamed);
+    fn state_changed(changes: Vec<PathBuf>);
+}
@@ -0,0 +1,526 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use windows_core as core;
+
+use crate::{
+    error::CResult,
+    filter::{info, layer::SyncLayer, ticket, Backend, Request, SyncFilter},
+    placeholder_file::PlaceholderFile,
+};
+
+/// The bytes transferred to the placeholders.
+pub static TRANSFERRED_BYTES: Metric = Metric {
+    name: "cloud_filter_transferred_bytes_total",
+    help: "Bytes transferred to the placeholders.",
+    kind: MetricKind::Counter,
+};
+
+/// The size of each write to a placeholder.
+pub static WRITE_SIZE: Metric = Metric {
+    name: "cloud_filter_write_size_bytes",
+    help: "Size of each write to a placeholder.",
+    kind: MetricKind::Histogram(&[
+        4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+    ]),
+};
+
+/// The latency of each callback, labeled by `callback`.
+pub static CALLBACK_DURATION: Metric = Metric {
+    name: "cloud_filter_callback_duration_seconds",
+    help: "Latency of the callbacks.",
+    kind: MetricKind::Histogram(&[
+        0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0,
+    ]),
+};
+
+/// The cancelled hydrations, labeled by `reason`: `timeout`, `user` or `other`.
+pub static CANCELLATIONS: Metric = Metric {
+    name: "cloud_filter_fetch_data_cancellations_total",
+    help: "Hydrations cancelled by a timeout or by the user.",
+    kind: MetricKind::Counter,
+};
+
+/// The failed callbacks, labeled by `callback` and by `kind`, the [Debug] representation of the
+/// [CloudErrorKind][crate::error::CloudErrorKind].
+pub static FAILURES: Metric = Metric {
+    name: "cloud_filter_callback_failures_total",
+    help: "Callbacks failed with an error.",
+    kind: MetricKind::Counter,
+};
+
+/// The description of a metric recorded by [Metrics].
+#[derive(Debug)]
+pub struct Metric {
+    /// The name of the metric, following the Prometheus naming conventions.
+    pub name: &'static str,
+    /// A description of the metric.
+    pub help: &'static str,
+    /// The type of the metric.
+    pub kind: MetricKind,
+}
+
+/// The type of a [Metric].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricKind {
+    /// A monotonically increasing count.
+    Counter,
+    /// A distribution of observations, with the upper bounds of its buckets in increasing order.
+    Histogram(&'static [f64]),
+}
+
+/// The labels of a series, as `(name, value)`.
+pub type Labels<'a> = &'a [(&'static str, &'a str)];
+
+/// The destination of the metrics recorded by [Metrics], e.g. [InMemorySink] or an adapter to a
+/// metrics library.
+pub trait MetricsSink: Send + Sync {
+    /// Increments the [MetricKind::Counter] with the labels.
+    fn increment(&self, metric: &'static Metric, labels: Labels<'_>, value: u64);
+
+    /// Records an observation of the [MetricKind::Histogram] with the labels.
+    fn observe(&self, metric: &'static Metric, labels: Labels<'_>, value: f64);
+}
+
+/// A [SyncLayer] recording metrics about the callbacks of the filter into a [MetricsSink].
+///
+/// The layer records:
+/// - [TRANSFERRED_BYTES] and [WRITE_SIZE] for each write of
+///   [ticket::FetchData][crate::filter::ticket::FetchData],
+/// - [CALLBACK_DURATION] for each callback,
+/// - [CANCELLATIONS] for each [cancelled hydration][SyncFilter::cancel_fetch_data],
+/// - [FAILURES] for each callback returning an error.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use cloud_filter::{
+/// #     error::CResult,
+/// #     filter::{info, layer::{metrics::{InMemorySink, Metrics}, FilterStack}, ticket, Request, SyncFilter},
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// let sink = Arc::new(InMemorySink::new());
+/// let filter = FilterStack::new()
+///     .intercept(Metrics::new(sink.clone()))
+///     .filter(MyFilter);
+///
+/// // served on the metrics endpoint
+/// let body = sink.snapshot().to_prometheus();
+/// ```
+pub struct Metrics<S: ?Sized> {
+    sink: Arc<S>,
+}
+
+impl<S: MetricsSink + ?Sized + 'static> Metrics<S> {
+    /// Creates a new [Metrics] layer recording into the sink.
+    pub fn new(sink: Arc<S>) -> Self {
+        Self { sink }
+    }
+
+    /// The sink the metrics are recorded into.
+    pub fn sink(&self) -> &Arc<S> {
+        &self.sink
+    }
+
+    /// Runs the callback, recording its latency and its failure.
+    fn call(&self, callback: &'static str, f: impl FnOnce() -> CResult<()>) -> CResult<()> {
+        let started = Instant::now();
+        let result = f();
+        self.sink.observe(
+            &CALLBACK_DURATION,
+            &[("callback", callback)],
+            started.elapsed().as_secs_f64(),
+        );
+
+        if let Err(err) = &result {
+            let kind = format!("{:?}", err.kind());
+            self.sink
+                .increment(&FAILURES, &[("callback", callback), ("kind", &kind)], 1);
+        }
+        result
+    }
+
+    /// Runs the notification callback, recording its latency.
+    fn notify(&self, callback: &'static str, f: impl FnOnce()) {
+        _ = self.call(callback, || {
+            f();
+            Ok(())
+        });
+    }
+}
+
+impl<F, S> SyncLayer<F> for Metrics<S>
+where
+    F: SyncFilter,
+    S: MetricsSink + ?Sized + 'static,
+{
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let backend = Arc::new(Metered {
+            backend: ticket.backend().clone(),
+            sink: self.sink.clone(),
+        });
+        self.call("fetch_data", || {
+            next.fetch_data(request, ticket.reissue(backend), info)
+        })
+    }
+
+    fn cancel_fetch_data(&self, next: &F, request: Request, info: info::CancelFetchData) {
+        let reason = match (info.timeout(), info.user_cancelled()) {
+            (true, _) => "timeout",
+            (false, true) => "user",
+            (false, false) => "other",
+        };
+        self.sink
+            .increment(&CANCELLATIONS, &[("reason", reason)], 1);
+        self.notify("cancel_fetch_data", || {
+            next.cancel_fetch_data(request, info)
+        })
+    }
+
+    fn validate_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::ValidateData,
+        info: info::ValidateData,
+    ) -> CResult<()> {
+        self.call("validate_data", || {
+            next.validate_data(request, ticket, info)
+        })
+    }
+
+    fn fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        self.call("fetch_placeholders", || {
+            next.fetch_placeholders(request, ticket, info)
+        })
+    }
+
+    fn cancel_fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        info: info::CancelFetchPlaceholders,
+    ) {
+        self.notify("cancel_fetch_placeholders", || {
+            next.cancel_fetch_placeholders(request, info)
+        })
+    }
+
+    fn opened(&self, next: &F, request: Request, info: info::Opened) {
+        self.notify("opened", || next.opened(request, info))
+    }
+
+    fn closed(&self, next: &F, request: Request, info: info::Closed) {
+        self.notify("closed", || next.closed(request, info))
+    }
+
+    fn dehydrate(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Dehydrate,
+        info: info::Dehydrate,
+    ) -> CResult<()> {
+        self.call("dehydrate", || next.dehydrate(request, ticket, info))
+    }
+
+    fn dehydrated(&self, next: &F, request: Request, info: info::Dehydrated) {
+        self.notify("dehydrated", || next.dehydrated(request, info))
+    }
+
+    fn delete(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Delete,
+        info: info::Delete,
+    ) -> CResult<()> {
+        self.call("delete", || next.delete(request, ticket, info))
+    }
+
+    fn deleted(&self, next: &F, request: Request, info: info::Deleted) {
+        self.notify("deleted", || next.deleted(request, info))
+    }
+
+    fn rename(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Rename,
+        info: info::Rename,
+    ) -> CResult<()> {
+        self.call("rename", || next.rename(request, ticket, info))
+    }
+
+    fn renamed(&self, next: &F, request: Request, info: info::Renamed) {
+        self.notify("renamed", || next.renamed(request, info))
+    }
+
+    fn state_changed(&self, next: &F, changes: Vec<PathBuf>) {
+        self.notify("state_changed", || next.state_changed(changes))
+    }
+}
+
+/// Records the size of the writes executed through the [Backend].
+struct Metered<S: ?Sized> {
+    backend: Arc<dyn Backend>,
+    sink: Arc<S>,
+}
+
+impl<S: ?Sized> std::fmt::Debug for Metered<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metered")
+            .field("backend", &self.backend)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: MetricsSink + ?Sized> Backend for Metered<S> {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        self.backend.read(buf, offset)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        self.backend.write(buf, offset)?;
+        self.sink
+            .increment(&TRANSFERRED_BYTES, &[], buf.len() as u64);
+        self.sink.observe(&WRITE_SIZE, &[], buf.len() as f64);
+        Ok(())
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        self.backend.report_progress(total, completed)
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        self.backend.validate(range)
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        self.backend.create_placeholders(placeholders)
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        self.backend.dehydrate(blob)
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.backend.delete()
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.backend.rename()
+    }
+}
+
+/// The identity of a series: the name of its metric and its labels.
+type SeriesKey = (&'static str, Vec<(&'static str, String)>);
+
+/// The value of a series.
+#[derive(Debug, Clone)]
+enum Series {
+    Counter(&'static Metric, u64),
+    Histogram(&'static Metric, Histogram),
+}
+
+/// A [MetricsSink] keeping the metrics in memory, to be inspected through a [Snapshot].
+#[derive(Debug, Default)]
+pub struct InMemorySink {
+    series: Mutex<BTreeMap<SeriesKey, Series>>,
+}
+
+impl InMemorySink {
+    /// Creates an empty [InMemorySink].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies the current value of every series.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            series: self.series.lock().unwrap().clone(),
+        }
+    }
+
+    fn key(metric: &'static Metric, labels: Labels<'_>) -> SeriesKey {
+        (
+            metric.name,
+            labels
+                .iter()
+                .map(|&(name, value)| (name, value.to_owned()))
+                .collect(),
+        )
+    }
+}
+
+impl MetricsSink for InMemorySink {
+    fn increment(&self, metric: &'static Metric, labels: Labels<'_>, value: u64) {
+        let mut series = self.series.lock().unwrap();
+        match series
+            .entry(Self::key(metric, labels))
+            .or_insert(Series::Counter(metric, 0))
+        {
+            Series::Counter(_, count) => *count += value,
+            Series::Histogram(..) => panic!("{} is not a counter", metric.name),
+        }
+    }
+
+    fn observe(&self, metric: &'static Metric, labels: Labels<'_>, value: f64) {
+        let MetricKind::Histogram(bounds) = metric.kind else {
+            panic!("{} is not a histogram", metric.name);
+        };
+
+        let mut series = self.series.lock().unwrap();
+        let histogram = series.entry(Self::key(metric, labels)).or_insert_with(|| {
+            Series::Histogram(
+                metric,
+                Histogram {
+                    buckets: bounds.iter().map(|&bound| (bound, 0)).collect(),
+                    sum: 0.0,
+                    count: 0,
+                },
+            )
+        });
+        if let Series::Histogram(_, histogram) = histogram {
+            for (bound, count) in &mut histogram.buckets {
+                if value <= *bound {
+                    *count += 1;
+                }
+            }
+            histogram.sum += value;
+            histogram.count += 1;
+        }
+    }
+}
+
+/// The observations of a histogram series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// The upper bound of each bucket, with the cumulative number of observations less than or
+    /// equal to it.
+    pub buckets: Vec<(f64, u64)>,
+    /// The sum of the observations.
+    pub sum: f64,
+    /// The number of observations.
+    pub count: u64,
+}
+
+/// The metrics recorded by an [InMemorySink] at a point in time.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    series: BTreeMap<SeriesKey, Series>,
+}
+
+impl Snapshot {
+    /// The value of the counter series, `0` if it was never incremented.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        match self.find(name, labels) {
+            Some(Series::Counter(_, count)) => *count,
+            _ => 0,
+        }
+    }
+
+    /// The observations of the histogram series, if any.
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> Option<&Histogram> {
+        match self.find(name, labels) {
+            Some(Series::Histogram(_, histogram)) => Some(histogram),
+            _ => None,
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut previous = None;
+        for ((name, labels), series) in &self.series {
+            let metric = match series {
+                Series::Counter(metric, _) | Series::Histogram(metric, _) => metric,
+            };
+            if previous != Some(name) {
+                let kind = match metric.kind {
+                    MetricKind::Counter => "counter",
+                    MetricKind::Histogram(_) => "histogram",
+                };
+                _ = writeln!(out, "# HELP {name} {}", metric.help);
+                _ = writeln!(out, "# TYPE {name} {kind}");
+                previous = Some(name);
+            }
+
+            match series {
+                Series::Counter(_, count) => {
+                    _ = writeln!(out, "{name}{} {count}", render_labels(labels, None));
+                }
+                Series::Histogram(_, histogram) => {
+                    for (bound, count) in &histogram.buckets {
+                        let bound = bound.to_string();
+                        let labels = render_labels(labels, Some(&bound));
+                        _ = writeln!(out, "{name}_bucket{labels} {count}");
+                    }
+                    let labels_inf = render_labels(labels, Some("+Inf"));
+                    _ = writeln!(out, "{name}_bucket{labels_inf} {}", histogram.count);
+
+                    let labels = render_labels(labels, None);
+                    _ = writeln!(out, "{name}_sum{labels} {}", histogram.sum);
+                    _ = writeln!(out, "{name}_count{labels} {}", histogram.count);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn find(&self, name: &str, labels: &[(&str, &str)]) -> Option<&Series> {
+        self.series.iter().find_map(|((key, key_labels), series)| {
+            let matches = *key == name
+                && key_labels.len() == labels.len()
+                && key_labels
+                    .iter()
+                    .zip(labels)
+                    .all(|((a, b), (c, d))| a == c && b == d);
+            matches.then_some(series)
+        })
+    }
+}
+
+/// Renders the labels of a series, with the `le` label of a histogram bucket.
+fn render_labels(labels: &[(&'static str, String)], le: Option<&str>) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .chain(le.map(|le| ("le", le)))
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', r"\\")
+                .replace('"', r#"\""#)
+                .replace('\n', r"\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect::<Vec<_>>();
+
+    match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", labels.join(",")),
+    }
+}
@@ -0,0 +1,12 @@
+pub use intercept::{AsyncLayer, Layered, SyncLayer};
+pub use metrics::Metrics;
+pub use policy::{Decision, Matcher, Policy};
+pub use retry::Retry;
+pub use stack::{FilterStack, Identity, Intercept, Layer, Stack};
+
+mod intercept;
+/// This module contains the [Metrics] layer and the sinks recording its metrics.
+pub mod metrics;
+mod policy;
+mod retry;
+mod stack;
@@ -0,0 +1,304 @@
+use std::ffi::OsString;
+
+use crate::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info,
+        layer::{AsyncLayer, SyncLayer},
+        request::Process,
+        ticket, Filter, Request, SyncFilter,
+    },
+    utility::WriteAt,
+};
+
+/// The size of the chunks of zeros written by [Decision::Zeros].
+const ZEROS_CHUNK: u64 = 1024 * 1024;
+/// The alignment required by `CF_OPERATION_TYPE_TRANSFER_DATA`.
+const TRANSFER_ALIGNMENT: u64 = 4096;
+
+/// The hook called with each decision.
+type OnDecision = Box<dyn Fn(&Request, Decision) + Send + Sync>;
+
+/// The outcome of the [Policy] for a hydration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// The hydration is forwarded to the filter.
+    Allow,
+    /// The hydration fails with [CloudErrorKind::AccessDenied].
+    Deny,
+    /// The required range is filled with zeros without calling the filter.
+    ///
+    /// The zeros stay in the placeholder until it is dehydrated, so only serve them to processes
+    /// that never write the file back, e.g. a content indexer.
+    Zeros,
+    /// Only the first bytes of the file are forwarded to the filter, the rest of the hydration
+    /// fails with [CloudErrorKind::AccessDenied].
+    ///
+    /// The length is rounded up to a multiple of 4KiB, the transferred ranges having to end on a
+    /// 4KiB boundary or on the file size.
+    ///
+    /// Most formats keep their thumbnail and metadata at the start of the file, which is enough
+    /// for the shell to render a preview without downloading the whole file.
+    Thumbnail(u64),
+}
+
+/// Matches the [Process] that triggered a callback.
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Matches the [package name][Process::name], ignoring the ASCII case.
+    PackageName(OsString),
+    /// Matches the [application id][Process::application_id], ignoring the ASCII case.
+    ApplicationId(OsString),
+    /// Matches the [path of the executable][Process::path].
+    #[cfg(feature = "globs")]
+    ImagePath(globset::GlobMatcher),
+}
+
+impl Matcher {
+    /// Matches the [package name][Process::name], ignoring the ASCII case.
+    pub fn package_name(name: impl Into<OsString>) -> Self {
+        Self::PackageName(name.into())
+    }
+
+    /// Matches the [application id][Process::application_id], ignoring the ASCII case.
+    pub fn application_id(id: impl Into<OsString>) -> Self {
+        Self::ApplicationId(id.into())
+    }
+
+    /// Matches the [path of the executable][Process::path] against a case insensitive glob,
+    /// where `\` is a path separator and `*` matches across separators, e.g.
+    /// `*\SearchProtocolHost.exe`.
+    #[cfg(feature = "globs")]
+    pub fn image_path(pattern: &str) -> Result<Self, globset::Error> {
+        Ok(Self::ImagePath(
+            globset::GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .backslash_escape(false)
+                .build()?
+                .compile_matcher(),
+        ))
+    }
+
+    /// Whether or not the process matches.
+    pub fn matches(&self, process: &Process) -> bool {
+        match self {
+            Self::PackageName(name) => process.name.eq_ignore_ascii_case(name),
+            Self::ApplicationId(id) => process.application_id.eq_ignore_ascii_case(id),
+            #[cfg(feature = "globs")]
+            Self::ImagePath(glob) => process
+                .path
+                .as_ref()
+                .is_some_and(|path| glob.is_match(path)),
+        }
+    }
+}
+
+/// A [SyncLayer] and [AsyncLayer] deciding which processes may hydrate the placeholders, e.g. to
+/// keep content indexers and antivirus scanners from downloading the whole sync root.
+///
+/// The rules are evaluated in the order they were added, the first rule matching the process of
+/// the [fetch_data][SyncFilter::fetch_data] callback decides its outcome. Hydrations matching no
+/// rule are allowed, unless [Policy::otherwise] says otherwise. The other callbacks are forwarded
+/// as is.
+///
+/// Each decision is logged as a `tracing` event with the `tracing` feature, and handed to
+/// [Policy::on_decision].
+///
+/// ```
+/// # use cloud_filter::{
+/// #     error::CResult,
+/// #     filter::{info, layer::{Decision, FilterStack, Matcher, Policy}, ticket, Request, SyncFilter},
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// let policy = Policy::new()
+///     .rule(Matcher::package_name("Microsoft.Windows.Search"), Decision::Zeros)
+///     .rule(Matcher::application_id("Microsoft.Windows.Explorer"), Decision::Thumbnail(64 * 1024))
+///     .deny(Matcher::package_name("Contoso.Antivirus"));
+/// let filter = FilterStack::new().intercept(policy).filter(MyFilter);
+/// ```
+pub struct Policy {
+    rules: Vec<(Matcher, Decision)>,
+    otherwise: Decision,
+    on_decision: Option<OnDecision>,
+}
+
+impl Policy {
+    /// Creates a [Policy] without rules, allowing every hydration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule deciding the outcome of the hydrations triggered by the matching processes.
+    pub fn rule(mut self, matcher: Matcher, decision: Decision) -> Self {
+        self.rules.push((matcher, decision));
+        self
+    }
+
+    /// Adds a rule allowing the matching processes, see [Policy::rule].
+    pub fn allow(self, matcher: Matcher) -> Self {
+        self.rule(matcher, Decision::Allow)
+    }
+
+    /// Adds a rule denying the matching processes, see [Policy::rule].
+    pub fn deny(self, matcher: Matcher) -> Self {
+        self.rule(matcher, Decision::Deny)
+    }
+
+    /// The outcome of the hydrations matching no rule, defaults to [Decision::Allow].
+    pub fn otherwise(mut self, decision: Decision) -> Self {
+        self.otherwise = decision;
+        self
+    }
+
+    /// Calls the hook with each decision, e.g. to audit the denied processes.
+    pub fn on_decision(
+        mut self,
+        hook: impl Fn(&Request, Decision) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_decision = Some(Box::new(hook));
+        self
+    }
+
+    /// The outcome of a hydration triggered by the process.
+    pub fn decide(&self, process: &Process) -> Decision {
+        self.rules
+            .iter()
+            .find(|(matcher, _)| matcher.matches(process))
+            .map_or(self.otherwise, |&(_, decision)| decision)
+    }
+
+    /// Decides the outcome of the hydration, responding to it unless it is forwarded to the
+    /// filter.
+    fn admit(
+        &self,
+        request: &Request,
+        ticket: &ticket::FetchData,
+        mut info: info::FetchData,
+    ) -> Admission {
+        let decision = self.decide(&request.process());
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            process = ?request.process(),
+            path = %request.path().display(),
+            ?decision,
+            "hydration policy"
+        );
+        if let Some(hook) = &self.on_decision {
+            hook(request, decision);
+        }
+
+        match decision {
+            Decision::Allow => Admission::Forward {
+                info,
+                truncated: false,
+            },
+            Decision::Deny => Admission::Respond(Err(CloudErrorKind::AccessDenied.into())),
+            Decision::Zeros => Admission::Respond(write_zeros(ticket, info.required_file_range)),
+            Decision::Thumbnail(len) => {
+                let len = len
+                    .checked_next_multiple_of(TRANSFER_ALIGNMENT)
+                    .unwrap_or(u64::MAX);
+                let range = info.required_file_range.clone();
+                if range.start >= len {
+                    return Admission::Respond(Err(CloudErrorKind::AccessDenied.into()));
+                }
+
+                info.required_file_range = range.start..range.end.min(len);
+                info.optional_file_range = info.required_file_range.clone();
+                Admission::Forward {
+                    info,
+                    truncated: range.end > len,
+                }
+            }
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            otherwise: Decision::Allow,
+            on_decision: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Policy")
+            .field("rules", &self.rules)
+            .field("otherwise", &self.otherwise)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: SyncFilter> SyncLayer<F> for Policy {
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        match self.admit(&request, &ticket, info) {
+            Admission::Respond(result) => result,
+            Admission::Forward { info, truncated } => {
+                next.fetch_data(request, ticket, info)?;
+                match truncated {
+                    true => Err(CloudErrorKind::AccessDenied.into()),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl<F: Filter> AsyncLayer<F> for Policy {
+    async fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        match self.admit(&request, &ticket, info) {
+            Admission::Respond(result) => result,
+            Admission::Forward { info, truncated } => {
+                next.fetch_data(request, ticket, info).await?;
+                match truncated {
+                    true => Err(CloudErrorKind::AccessDenied.into()),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// What [Policy] does with a hydration.
+enum Admission {
+    /// Forwards the hydration to the filter, failing the rest of it if the range was truncated.
+    Forward {
+        info: info::FetchData,
+        truncated: bool,
+    },
+    /// Responds to the hydration without calling the filter.
+    Respond(CResult<()>),
+}
+
+/// Fills the range of the placeholder with zeros.
+fn write_zeros(ticket: &ticket::FetchData, range: std::ops::Range<u64>) -> CResult<()> {
+    let zeros = vec![0; ZEROS_CHUNK.min(range.end - range.start) as usize];
+    let mut offset = range.start;
+    while offset < range.end {
+        let len = ZEROS_CHUNK.min(range.end - offset) as usize;
+        ticket.write_at(&zeros[..len], offset)?;
+        offset += len as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,442 @@
+use std::{
+    future::{self, Future},
+    hash::{BuildHasher, RandomState},
+    ops::Range,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::Poll,
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows_core as core;
+
+use crate::{
+    error::{CResult, CloudError, CloudErrorKind},
+    filter::{
+        info,
+        layer::{AsyncLayer, SyncLayer},
+        ticket, Backend, CancellationToken, Filter, Request, SyncFilter,
+    },
+    placeholder_file::PlaceholderFile,
+    utility::LocalBoxFuture,
+};
+
+/// The interval at which a blocking backoff checks for cancellation.
+const CANCELLATION_POLL: Duration = Duration::from_millis(50);
+
+/// The predicate deciding which errors are retried.
+type RetryIf = Box<dyn Fn(CloudErrorKind) -> bool + Send + Sync>;
+/// The hook waiting for the backoffs of the async callbacks.
+type Sleep = Box<dyn Fn(Duration) -> LocalBoxFuture<'static, ()> + Send + Sync>;
+
+/// A [SyncLayer] and [AsyncLayer] retrying the [fetch_data][SyncFilter::fetch_data] and
+/// [fetch_placeholders][SyncFilter::fetch_placeholders] callbacks of the next filter when they
+/// fail with a [transient][CloudErrorKind::is_transient] error, such as
+/// [CloudErrorKind::NetworkUnavailable] or [CloudErrorKind::InsufficientResources].
+///
+/// Each retry waits for an exponential backoff with jitter. A retried `fetch_data` resumes from
+/// the end of the data already written to the placeholder: the required range of
+/// [info::FetchData] given to the filter starts at that offset, and the operation succeeds as soon
+/// as the whole required range is written.
+///
+/// The operating system cancels a callback after 60 seconds without activity. The retries give
+/// up, failing the operation with the last error, once the next attempt would start after the
+/// [deadline][Retry::deadline] since the last write or progress report, or as soon as the
+/// operation is cancelled. The other callbacks are forwarded as is.
+///
+/// The async callbacks wait for their backoff with the [Retry::sleep] hook, e.g. the timer of
+/// the runtime, and block the thread polling them like the sync callbacks without it.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use cloud_filter::{
+/// #     error::CResult,
+/// #     filter::{info, layer::{FilterStack, Retry}, ticket, Request, SyncFilter},
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// let retry = Retry::new()
+///     .max_attempts(3)
+///     .initial_backoff(Duration::from_millis(500));
+/// let filter = FilterStack::new().intercept(retry).filter(MyFilter);
+/// ```
+pub struct Retry {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    deadline: Duration,
+    retry_if: RetryIf,
+    sleep: Option<Sleep>,
+}
+
+impl Retry {
+    /// Creates a [Retry] layer retrying at most 5 times with a backoff starting at 200ms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The maximum number of attempts, including the first one, defaults to `5`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of attempts is zero.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        assert!(attempts != 0, "at least one attempt is required");
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// The backoff before the first retry, doubled for each following retry, defaults to 200ms.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The maximum backoff between two attempts, defaults to 5 seconds.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// The time after the last activity of the operation past which no attempt is started,
+    /// defaults to 30 seconds.
+    ///
+    /// It must leave enough time for an attempt to complete or make progress before the
+    /// operating system times out the operation after 60 seconds without activity.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Decides which errors are retried, defaults to [CloudErrorKind::is_transient].
+    pub fn retry_if(
+        mut self,
+        retry_if: impl Fn(CloudErrorKind) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Box::new(retry_if);
+        self
+    }
+
+    /// Waits for the backoffs of the async callbacks with the hook, e.g. `tokio::time::sleep`,
+    /// instead of blocking the thread polling them.
+    pub fn sleep<S>(mut self, sleep: impl Fn(Duration) -> S + Send + Sync + 'static) -> Self
+    where
+        S: Future<Output = ()> + 'static,
+    {
+        self.sleep = Some(Box::new(move |duration| Box::pin(sleep(duration))));
+        self
+    }
+
+    /// The backoff before the next attempt, or [None] if the error must be returned.
+    fn backoff(
+        &self,
+        attempt: u32,
+        err: &CloudError,
+        token: &CancellationToken,
+        active: Instant,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || token.is_cancelled() || !(self.retry_if)(err.kind()) {
+            return None;
+        }
+
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_backoff);
+        let backoff = jitter(backoff);
+        (active.elapsed() + backoff < self.deadline).then_some(backoff)
+    }
+
+    /// Waits for the backoff of an async callback with the [Retry::sleep] hook, completing with
+    /// `false` if the operation is cancelled in the meantime.
+    async fn wait(&self, backoff: Duration, token: &CancellationToken) -> bool {
+        let Some(hook) = &self.sleep else {
+            return sleep(backoff, token);
+        };
+
+        let mut sleep = hook(backoff);
+        let mut cancelled = token.cancelled();
+        future::poll_fn(|cx| {
+            if Pin::new(&mut cancelled).poll(cx).is_ready() {
+                return Poll::Ready(false);
+            }
+            sleep.as_mut().poll(cx).map(|()| true)
+        })
+        .await
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            deadline: Duration::from_secs(30),
+            retry_if: Box::new(CloudErrorKind::is_transient),
+            sleep: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Retry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Retry")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: SyncFilter> SyncLayer<F> for Retry {
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        mut info: info::FetchData,
+    ) -> CResult<()> {
+        let resume = Arc::new(Resume::new(
+            ticket.backend().clone(),
+            info.required_file_range.start,
+        ));
+        let token = ticket.cancellation_token();
+
+        let mut attempt = 1;
+        loop {
+            let err = match next.fetch_data(
+                request.clone(),
+                ticket.reissue(resume.clone()),
+                info.clone(),
+            ) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let Some(backoff) = self.backoff(attempt, &err, token, resume.active()) else {
+                return Err(err);
+            };
+            if !sleep(backoff, token) {
+                return Err(err);
+            }
+
+            if !resume.update(&mut info) {
+                return Ok(());
+            }
+            attempt += 1;
+        }
+    }
+
+    fn fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let started = Instant::now();
+        let token = ticket.cancellation_token();
+
+        let mut attempt = 1;
+        loop {
+            let err = match next.fetch_placeholders(
+                request.clone(),
+                ticket.reissue(ticket.backend().clone()),
+                info.clone(),
+            ) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let Some(backoff) = self.backoff(attempt, &err, token, started) else {
+                return Err(err);
+            };
+            if !sleep(backoff, token) {
+                return Err(err);
+            }
+            attempt += 1;
+        }
+    }
+}
+
+impl<F: Filter> AsyncLayer<F> for Retry {
+    async fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        mut info: info::FetchData,
+    ) -> CResult<()> {
+        let resume = Arc::new(Resume::new(
+            ticket.backend().clone(),
+            info.required_file_range.start,
+        ));
+        let token = ticket.cancellation_token();
+
+        let mut attempt = 1;
+        loop {
+            let err = match next
+                .fetch_data(
+                    request.clone(),
+                    ticket.reissue(resume.clone()),
+                    info.clone(),
+                )
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let Some(backoff) = self.backoff(attempt, &err, token, resume.active()) else {
+                return Err(err);
+            };
+            if !self.wait(backoff, token).await {
+                return Err(err);
+            }
+
+            if !resume.update(&mut info) {
+                return Ok(());
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let started = Instant::now();
+        let token = ticket.cancellation_token();
+
+        let mut attempt = 1;
+        loop {
+            let err = match next
+                .fetch_placeholders(
+                    request.clone(),
+                    ticket.reissue(ticket.backend().clone()),
+                    info.clone(),
+                )
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let Some(backoff) = self.backoff(attempt, &err, token, started) else {
+                return Err(err);
+            };
+            if !self.wait(backoff, token).await {
+                return Err(err);
+            }
+            attempt += 1;
+        }
+    }
+}
+
+/// Tracks the contiguous data written from the start of the required range, and the last
+/// activity of the operation.
+#[derive(Debug)]
+struct Resume {
+    backend: Arc<dyn Backend>,
+    state: Mutex<(u64, Instant)>,
+}
+
+impl Resume {
+    fn new(backend: Arc<dyn Backend>, offset: u64) -> Self {
+        Self {
+            backend,
+            state: Mutex::new((offset, Instant::now())),
+        }
+    }
+
+    /// The last write or progress report of the operation.
+    fn active(&self) -> Instant {
+        self.state.lock().unwrap().1
+    }
+
+    /// Moves the start of the ranges past the written data, returns `false` if the required range
+    /// is entirely written.
+    fn update(&self, info: &mut info::FetchData) -> bool {
+        let offset = self.state.lock().unwrap().0;
+        let skip = |range: &mut Range<u64>| range.start = range.start.max(offset).min(range.end);
+        skip(&mut info.required_file_range);
+        skip(&mut info.optional_file_range);
+
+        !info.required_file_range.is_empty()
+    }
+}
+
+impl Backend for Resume {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        self.backend.read(buf, offset)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        self.backend.write(buf, offset)?;
+
+        let mut state = self.state.lock().unwrap();
+        let end = offset + buf.len() as u64;
+        if (offset..end).contains(&state.0) {
+            state.0 = end;
+        }
+        state.1 = Instant::now();
+        Ok(())
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        self.backend.report_progress(total, completed)?;
+        self.state.lock().unwrap().1 = Instant::now();
+        Ok(())
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        self.backend.validate(range)
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        self.backend.create_placeholders(placeholders)
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        self.backend.dehydrate(blob)
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.backend.delete()
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.backend.rename()
+    }
+}
+
+/// Picks a random duration between half and all of the backoff, so that the operations failed
+/// by the same outage do not retry in lockstep.
+fn jitter(backoff: Duration) -> Duration {
+    let random = RandomState::new().hash_one(Instant::now());
+    backoff / 2 + backoff.mul_f64((random % 1024) as f64 / 2048.0)
+}
+
+/// Blocks for the duration, returns `false` if the operation is cancelled in the meantime.
+fn sleep(duration: Duration, token: &CancellationToken) -> bool {
+    let deadline = Instant::now() + duration;
+    while !token.is_cancelled() {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::sleep((deadline - now).min(CANCELLATION_POLL));
+    }
+
+    false
+}
@@ -0,0 +1,135 @@
+use crate::filter::layer::Layered;
+
+/// Wraps a filter into another filter, e.g. to add logging, authorization or retries to its
+/// callbacks.
+///
+/// A [SyncLayer][super::SyncLayer] or an [AsyncLayer][super::AsyncLayer] only overriding some of
+/// the callbacks is turned into a [Layer] by [Intercept].
+pub trait Layer<F> {
+    /// The filter wrapping the inner filter.
+    type Filter;
+
+    /// Wraps the inner filter.
+    fn layer(self, inner: F) -> Self::Filter;
+}
+
+/// A [Layer] returning the filter as is.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl<F> Layer<F> for Identity {
+    type Filter = F;
+
+    fn layer(self, inner: F) -> Self::Filter {
+        inner
+    }
+}
+
+/// Two [Layer]s applied one after the other, the outer layer wrapping the filter produced by the
+/// inner layer.
+#[derive(Debug, Clone, Copy)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<F, Inner, Outer> Layer<F> for Stack<Inner, Outer>
+where
+    Inner: Layer<F>,
+    Outer: Layer<Inner::Filter>,
+{
+    type Filter = Outer::Filter;
+
+    fn layer(self, inner: F) -> Self::Filter {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+/// A [Layer] wrapping the filter into a [Layered] filter, handing its callbacks to a
+/// [SyncLayer][super::SyncLayer] or an [AsyncLayer][super::AsyncLayer].
+#[derive(Debug, Clone, Copy)]
+pub struct Intercept<L>(pub L);
+
+impl<L, F> Layer<F> for Intercept<L> {
+    type Filter = Layered<L, F>;
+
+    fn layer(self, inner: F) -> Self::Filter {
+        Layered::new(self.0, inner)
+    }
+}
+
+/// A builder stacking [Layer]s around a filter.
+///
+/// The first layer added is the outermost one: it sees each callback first and decides whether
+/// to hand it to the following layers.
+///
+/// ```
+/// # use cloud_filter::{
+/// #     error::{CResult, CloudErrorKind},
+/// #     filter::{info, layer::{FilterStack, SyncLayer}, ticket, Request, SyncFilter},
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// struct ReadOnly;
+///
+/// impl<F: SyncFilter> SyncLayer<F> for ReadOnly {
+///     fn delete(
+///         &self,
+///         _next: &F,
+///         _request: Request,
+///         _ticket: ticket::Delete,
+///         _info: info::Delete,
+///     ) -> CResult<()> {
+///         Err(CloudErrorKind::AccessDenied.into())
+///     }
+/// }
+///
+/// let filter = FilterStack::new().intercept(ReadOnly).filter(MyFilter);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FilterStack<L> {
+    layer: L,
+}
+
+impl FilterStack<Identity> {
+    /// Creates an empty [FilterStack].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for FilterStack<Identity> {
+    fn default() -> Self {
+        Self { layer: Identity }
+    }
+}
+
+impl<L> FilterStack<L> {
+    /// Adds a [Layer] inside the previously added layers.
+    pub fn layer<T>(self, layer: T) -> FilterStack<Stack<T, L>> {
+        FilterStack {
+            layer: Stack {
+                inner: layer,
+                outer: self.layer,
+            },
+        }
+    }
+
+    /// Adds a [SyncLayer][super::SyncLayer] or an [AsyncLayer][super::AsyncLayer] inside the
+    /// previously added layers, see [Intercept].
+    pub fn intercept<T>(self, layer: T) -> FilterStack<Stack<Intercept<T>, L>> {
+        self.layer(Intercept(layer))
+    }
+
+    /// Wraps the filter with the layers.
+    pub fn filter<F>(self, filter: F) -> L::Filter
+    where
+        L: Layer<F>,
+    {
+        self.layer.layer(filter)
+    }
+}
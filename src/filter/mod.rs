@@ -2,18 +2,40 @@
 /// [SyncFilter][crate::filter::SyncFilter] trait and the [Filter][crate::filter::Filter] trait.
 pub mod info;
 
+/// This module contains wrappers around the [SyncFilter][crate::filter::SyncFilter] trait and the
+/// [Filter][crate::filter::Filter] trait adding behavior to the callbacks of a filter.
+pub mod layer;
+
 /// This module contains the structs that are used to represent the operation that the engine
 /// needs to perform. They are used as parameters to the methods of the [SyncFilter][crate::filter::SyncFilter]
 /// trait and the [Filter][crate::filter::Filter] trait.
 pub mod ticket;
 
 pub use async_filter::{AsyncBridge, Filter};
+#[cfg(windows)]
+pub(crate) use backend::CfBackend;
+pub use backend::{Backend, Operation, RecordingBackend};
+pub use cancellation::{CancellationToken, Cancelled};
+pub use hydration::ParallelHydration;
+pub use keep_alive::KeepAlive;
+#[cfg(windows)]
 pub(crate) use proxy::{callbacks, Callbacks};
-pub use request::{Process, Request};
-pub(crate) use request::{RawConnectionKey, RawTransferKey};
+#[cfg(windows)]
+pub(crate) use request::RawConnectionKey;
+pub(crate) use request::RawTransferKey;
+pub use request::{Process, ProcessBuilder, Request, RequestBuilder};
 pub use sync_filter::SyncFilter;
+#[cfg(all(windows, feature = "tokio"))]
+pub use tokio_bridge::TokioBridge;
 
 mod async_filter;
+mod backend;
+mod cancellation;
+mod hydration;
+mod keep_alive;
+#[cfg(windows)]
 mod proxy;
 mod request;
 mod sync_filter;
+#[cfg(all(windows, feature = "tokio"))]
+mod tokio_bridge;
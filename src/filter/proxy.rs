@@ -1,6 +1,10 @@
 #![allow(clippy::missing_safety_doc)]
 
-use std::sync::{Arc, Weak};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{Arc, LazyLock, Mutex, Weak},
+};
 
 use windows::Win32::Storage::CloudFilters::{
     self, CF_CALLBACK_INFO, CF_CALLBACK_PARAMETERS, CF_CALLBACK_REGISTRATION,
@@ -8,11 +12,44 @@ use windows::Win32::Storage::CloudFilters::{
 
 use crate::{
     command::{self, Fallible},
-    filter::{info, ticket, Request, SyncFilter},
+    error,
+    filter::{
+        cancellation::WeakCancellationToken, info, ticket, Backend, CancellationToken, CfBackend,
+        RawConnectionKey, RawTransferKey, Request, SyncFilter,
+    },
 };
 
+/// The cancellation tokens of the in-flight fetch operations, keyed by their connection and
+/// transfer keys.
+///
+/// The tokens are held weakly so that they stay registered for as long as their ticket is alive,
+/// even if the operation completes asynchronously after the callback returned.
+static TOKENS: LazyLock<Mutex<HashMap<(RawConnectionKey, RawTransferKey), WeakCancellationToken>>> =
+    LazyLock::new(Default::default);
+
 pub type Callbacks = [CF_CALLBACK_REGISTRATION; 14];
 
+/// Enters a span covering the rest of the callback, with the fields of its [Request], when the
+/// `tracing` feature is enabled.
+macro_rules! enter_span {
+    ($callback:literal, $request:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = {
+            let request: &Request = $request;
+            tracing::info_span!(
+                $callback,
+                path = %request.path().display(),
+                file_id = request.file_id(),
+                process_id = request.process().id(),
+                process_name = %request.process().name().to_string_lossy(),
+                priority_hint = request.priority_hint(),
+                transfer_key = request.transfer_key(),
+            )
+            .entered()
+        };
+    };
+}
+
 macro_rules! callbacks {
     ($([$type:path, $name:ident]),*) => {
         [
@@ -81,20 +118,26 @@ pub unsafe extern "system" fn fetch_data<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("fetch_data", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::FetchData::new(connection_key, transfer_key);
+        let ticket = ticket::FetchData::with_token(
+            backend(connection_key, transfer_key),
+            register_token(connection_key, transfer_key),
+        );
 
         let Err(e) = filter.fetch_data(
             request,
             ticket,
-            info::FetchData((*params).Anonymous.FetchData),
+            info::FetchData::from_raw(&(*params).Anonymous.FetchData),
         ) else {
             return;
         };
 
-        command::Write::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::Write::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
@@ -103,20 +146,23 @@ pub unsafe extern "system" fn validate_data<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("validate_data", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::ValidateData::new(connection_key, transfer_key);
+        let ticket = ticket::ValidateData::new(backend(connection_key, transfer_key));
 
         let Err(e) = filter.validate_data(
             request,
             ticket,
-            info::ValidateData((*params).Anonymous.ValidateData),
+            info::ValidateData::from_raw(&(*params).Anonymous.ValidateData),
         ) else {
             return;
         };
 
-        command::Validate::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::Validate::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
@@ -125,10 +171,16 @@ pub unsafe extern "system" fn cancel_fetch_data<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        filter.cancel_fetch_data(
-            Request::new(*info),
-            info::CancelFetchData((*params).Anonymous.Cancel),
+        let request = Request::from_raw(&*info);
+        enter_span!("cancel_fetch_data", &request);
+        let info = info::CancelFetchData::from_raw(&(*params).Anonymous.Cancel);
+        cancel_token(
+            request.connection_key(),
+            request.transfer_key(),
+            Some(info.file_range()),
         );
+
+        filter.cancel_fetch_data(request, info);
     }
 }
 
@@ -137,20 +189,26 @@ pub unsafe extern "system" fn fetch_placeholders<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("fetch_placeholders", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::FetchPlaceholders::new(connection_key, transfer_key);
+        let ticket = ticket::FetchPlaceholders::with_token(
+            backend(connection_key, transfer_key),
+            register_token(connection_key, transfer_key),
+        );
 
         let Err(e) = filter.fetch_placeholders(
             request,
             ticket,
-            info::FetchPlaceholders((*params).Anonymous.FetchPlaceholders),
+            info::FetchPlaceholders::from_raw(&(*params).Anonymous.FetchPlaceholders),
         ) else {
             return;
         };
 
-        command::CreatePlaceholders::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::CreatePlaceholders::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
@@ -159,9 +217,13 @@ pub unsafe extern "system" fn cancel_fetch_placeholders<T: SyncFilter + 'static>
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
+        let request = Request::from_raw(&*info);
+        enter_span!("cancel_fetch_placeholders", &request);
+        cancel_token(request.connection_key(), request.transfer_key(), None);
+
         filter.cancel_fetch_placeholders(
-            Request::new(*info),
-            info::CancelFetchPlaceholders((*params).Anonymous.Cancel),
+            request,
+            info::CancelFetchPlaceholders::from_raw(&(*params).Anonymous.Cancel),
         );
     }
 }
@@ -171,9 +233,11 @@ pub unsafe extern "system" fn notify_file_open_completion<T: SyncFilter + 'stati
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_file_open_completion", &request);
         filter.opened(
-            Request::new(*info),
-            info::Opened((*params).Anonymous.OpenCompletion),
+            request,
+            info::Opened::from_raw(&(*params).Anonymous.OpenCompletion),
         );
     }
 }
@@ -183,9 +247,11 @@ pub unsafe extern "system" fn notify_file_close_completion<T: SyncFilter + 'stat
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_file_close_completion", &request);
         filter.closed(
-            Request::new(*info),
-            info::Closed((*params).Anonymous.CloseCompletion),
+            request,
+            info::Closed::from_raw(&(*params).Anonymous.CloseCompletion),
         );
     }
 }
@@ -195,20 +261,23 @@ pub unsafe extern "system" fn notify_dehydrate<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_dehydrate", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::Dehydrate::new(connection_key, transfer_key);
+        let ticket = ticket::Dehydrate::new(backend(connection_key, transfer_key));
 
         let Err(e) = filter.dehydrate(
             request,
             ticket,
-            info::Dehydrate((*params).Anonymous.Dehydrate),
+            info::Dehydrate::from_raw(&(*params).Anonymous.Dehydrate),
         ) else {
             return;
         };
 
-        command::Dehydrate::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::Dehydrate::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
@@ -217,9 +286,11 @@ pub unsafe extern "system" fn notify_dehydrate_completion<T: SyncFilter + 'stati
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_dehydrate_completion", &request);
         filter.dehydrated(
-            Request::new(*info),
-            info::Dehydrated((*params).Anonymous.DehydrateCompletion),
+            request,
+            info::Dehydrated::from_raw(&(*params).Anonymous.DehydrateCompletion),
         );
     }
 }
@@ -229,29 +300,34 @@ pub unsafe extern "system" fn notify_delete<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_delete", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::Delete::new(connection_key, transfer_key);
+        let ticket = ticket::Delete::new(backend(connection_key, transfer_key));
 
-        let Err(e) = filter.delete(request, ticket, info::Delete((*params).Anonymous.Delete))
-        else {
+        let Err(e) = filter.delete(
+            request,
+            ticket,
+            info::Delete::from_raw(&(*params).Anonymous.Delete),
+        ) else {
             return;
         };
 
-        command::Delete::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::Delete::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
 pub unsafe extern "system" fn notify_delete_completion<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
-    params: *const CF_CALLBACK_PARAMETERS,
+    _params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        filter.deleted(
-            Request::new(*info),
-            info::Deleted((*params).Anonymous.DeleteCompletion),
-        );
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_delete_completion", &request);
+        filter.deleted(request, info::Deleted { _private: () });
     }
 }
 
@@ -260,17 +336,20 @@ pub unsafe extern "system" fn notify_rename<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_rename", &request);
         let connection_key = request.connection_key();
         let transfer_key = request.transfer_key();
-        let ticket = ticket::Rename::new(connection_key, transfer_key);
-        let info = info::Rename((*params).Anonymous.Rename, request.volume_letter());
+        let ticket = ticket::Rename::new(backend(connection_key, transfer_key));
+        let info = info::Rename::from_raw(&(*params).Anonymous.Rename, request.volume_letter());
 
         let Err(e) = filter.rename(request, ticket, info) else {
             return;
         };
 
-        command::Rename::fail(connection_key, transfer_key, e).unwrap();
+        error::report(&e);
+
+        command::Rename::fail(connection_key, transfer_key, e.kind()).unwrap();
     }
 }
 
@@ -279,15 +358,49 @@ pub unsafe extern "system" fn notify_rename_completion<T: SyncFilter + 'static>(
     params: *const CF_CALLBACK_PARAMETERS,
 ) {
     if let Some(filter) = filter_from_info::<T>(info) {
-        let request = Request::new(*info);
-        let info = info::Renamed(
-            (*params).Anonymous.RenameCompletion,
+        let request = Request::from_raw(&*info);
+        enter_span!("notify_rename_completion", &request);
+        let info = info::Renamed::from_raw(
+            &(*params).Anonymous.RenameCompletion,
             request.volume_letter(),
         );
         filter.renamed(request, info);
     }
 }
 
+fn backend(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Arc<dyn Backend> {
+    Arc::new(CfBackend::new(connection_key, transfer_key))
+}
+
+/// Creates the cancellation token of an in-flight fetch operation, forgetting the tokens whose
+/// tickets have been dropped.
+fn register_token(
+    connection_key: RawConnectionKey,
+    transfer_key: RawTransferKey,
+) -> CancellationToken {
+    let token = CancellationToken::new();
+    let mut tokens = TOKENS.lock().unwrap();
+    tokens.retain(|_, token| token.is_alive());
+    tokens.insert((connection_key, transfer_key), token.downgrade());
+    token
+}
+
+/// Trips the cancellation token of the fetch operation, if it is still in flight.
+fn cancel_token(
+    connection_key: RawConnectionKey,
+    transfer_key: RawTransferKey,
+    range: Option<Range<u64>>,
+) {
+    let token = TOKENS
+        .lock()
+        .unwrap()
+        .get(&(connection_key, transfer_key))
+        .and_then(WeakCancellationToken::upgrade);
+    if let Some(token) = token {
+        token.trip(range);
+    }
+}
+
 unsafe fn filter_from_info<T: SyncFilter + 'static>(
     info: *const CF_CALLBACK_INFO,
 ) -> Option<Arc<T>> {
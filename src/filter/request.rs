@@ -1,73 +1,123 @@
-use std::{ffi::OsString, path::PathBuf, slice};
+use std::{ffi::OsString, path::PathBuf};
 
+#[cfg(windows)]
+use std::slice;
+
+#[cfg(windows)]
 use widestring::{u16cstr, U16CStr};
+#[cfg(windows)]
 use windows::Win32::Storage::CloudFilters::{CF_CALLBACK_INFO, CF_PROCESS_INFO};
 
+use crate::{
+    blob::{self, FileIdentity},
+    error::CResult,
+};
+
 pub type RawConnectionKey = i64;
 pub type RawTransferKey = i64;
 
 /// A struct containing standard information for the current file operation.
-pub struct Request(CF_CALLBACK_INFO);
+#[derive(Clone)]
+pub struct Request {
+    pub(crate) volume_guid_path: OsString,
+    pub(crate) volume_letter: OsString,
+    pub(crate) volume_serial_number: u32,
+    pub(crate) process: Process,
+    pub(crate) sync_root_file_id: i64,
+    pub(crate) file_id: i64,
+    pub(crate) file_size: u64,
+    pub(crate) path: PathBuf,
+    pub(crate) priority_hint: u8,
+    pub(crate) file_blob: Vec<u8>,
+    pub(crate) register_blob: Vec<u8>,
+    pub(crate) connection_key: RawConnectionKey,
+    pub(crate) transfer_key: RawTransferKey,
+}
 
 impl Request {
-    pub(crate) fn new(info: CF_CALLBACK_INFO) -> Self {
-        Self(info)
+    /// Creates a builder to construct a [Request] without the operating system.
+    pub fn builder() -> RequestBuilder {
+        RequestBuilder::default()
+    }
+
+    /// Copies the information out of the raw callback info.
+    ///
+    /// # Safety
+    ///
+    /// The pointers in `info` must be valid, which is the case for the duration of a Cloud Filter
+    /// callback.
+    #[cfg(windows)]
+    pub(crate) unsafe fn from_raw(info: &CF_CALLBACK_INFO) -> Self {
+        let volume_letter = U16CStr::from_ptr_str(info.VolumeDosName.0).to_os_string();
+        let mut path = PathBuf::from(&volume_letter);
+        path.push(U16CStr::from_ptr_str(info.NormalizedPath.0).to_os_string());
+
+        Self {
+            volume_guid_path: U16CStr::from_ptr_str(info.VolumeGuidName.0).to_os_string(),
+            volume_letter,
+            volume_serial_number: info.VolumeSerialNumber,
+            process: Process::from_raw(&*info.ProcessInfo),
+            sync_root_file_id: info.SyncRootFileId,
+            file_id: info.FileId,
+            file_size: info.FileSize as u64,
+            path,
+            priority_hint: info.PriorityHint,
+            file_blob: raw_blob(info.FileIdentity, info.FileIdentityLength),
+            register_blob: raw_blob(info.SyncRootIdentity, info.SyncRootIdentityLength),
+            connection_key: info.ConnectionKey.0,
+            transfer_key: info.TransferKey,
+        }
     }
 
     /// The GUID path of the current volume.
     ///
     /// The returned value comes in the form `\?\Volume{GUID}`.
     pub fn volume_guid_path(&self) -> OsString {
-        unsafe { U16CStr::from_ptr_str(self.0.VolumeGuidName.0) }.to_os_string()
+        self.volume_guid_path.clone()
     }
 
     /// The letter of the current volume.
     ///
     /// The returned value comes in the form `X:`, where `X` is the drive letter.
     pub fn volume_letter(&self) -> OsString {
-        unsafe { U16CStr::from_ptr_str(self.0.VolumeDosName.0) }.to_os_string()
+        self.volume_letter.clone()
     }
 
     /// The serial number of the current volume.
     pub fn volume_serial_number(&self) -> u32 {
-        self.0.VolumeSerialNumber
+        self.volume_serial_number
     }
 
     /// Information of the user process that triggered the callback.
     pub fn process(&self) -> Process {
-        Process(unsafe { *self.0.ProcessInfo })
+        self.process.clone()
     }
 
     /// The NTFS file ID of the sync root folder under which the placeholder being operated on
     /// resides.
     pub fn sync_root_file_id(&self) -> i64 {
-        self.0.SyncRootFileId
+        self.sync_root_file_id
     }
 
     /// The NTFS file ID of the placeholder file/directory.
     pub fn file_id(&self) -> i64 {
-        self.0.FileId
+        self.file_id
     }
 
     /// The logical size of the placeholder file.
     ///
     /// If the placeholder is a directory, this value will always equal 0.
     pub fn file_size(&self) -> u64 {
-        self.0.FileSize as u64
+        self.file_size
     }
 
-    // For now this should be cached on creation
     /// The absolute path of the placeholder file/directory starting from the root directory of the
     /// volume.
     ///
     /// [Read here for more information on this
     /// function.](https://docs.microsoft.com/en-us/windows/win32/api/cfapi/ns-cfapi-cf_callback_info#remarks)
     pub fn path(&self) -> PathBuf {
-        let mut path =
-            PathBuf::from(unsafe { U16CStr::from_ptr_str(self.0.VolumeDosName.0) }.to_os_string());
-        path.push(unsafe { U16CStr::from_ptr_str(self.0.NormalizedPath.0) }.to_os_string());
-
-        path
+        self.path.clone()
     }
 
     /// A numeric scale ranging from
@@ -77,7 +127,7 @@ impl Request {
     /// [Currently, this value does not
     /// change.](https://docs.microsoft.com/en-us/answers/questions/798674/priority-in-cf-callback-info.html)
     pub fn priority_hint(&self) -> u8 {
-        self.0.PriorityHint
+        self.priority_hint
     }
 
     // https://docs.microsoft.com/en-us/answers/questions/749979/what-is-a-requestkey-cfapi.html
@@ -87,42 +137,32 @@ impl Request {
 
     /// The byte slice assigned to the current placeholder file/directory.
     pub fn file_blob(&self) -> &[u8] {
-        unsafe {
-            slice::from_raw_parts(
-                self.0.FileIdentity as *mut u8,
-                self.0.FileIdentityLength as usize,
-            )
-        }
+        &self.file_blob
+    }
+
+    /// Decodes the [FileIdentity] stored in the [file blob][Request::file_blob].
+    ///
+    /// Returns [CloudErrorKind::MetadataCorrupt][crate::error::CloudErrorKind::MetadataCorrupt] if
+    /// the blob is malformed, see [blob::decode].
+    pub fn file_identity<T: FileIdentity>(&self) -> CResult<T> {
+        blob::decode(&self.file_blob)
     }
 
     /// The byte slice assigned to the current sync root on registration.
     pub fn register_blob(&self) -> &[u8] {
-        unsafe {
-            slice::from_raw_parts(
-                self.0.SyncRootIdentity as *mut u8,
-                self.0.SyncRootIdentityLength as usize,
-            )
-        }
+        &self.register_blob
     }
 
-    // // https://docs.microsoft.com/en-us/windows/win32/api/cfapi/ne-cfapi-cf_callback_type#remarks
-    // // after 60 seconds of no report, windows will cancel the request with an error,
-    // // this function is a "hack" to avoid the timeout
-    // // https://docs.microsoft.com/en-us/windows/win32/api/cfapi/nf-cfapi-cfexecute#remarks
-    // // CfExecute will reset any timers as stated
-    // /// By default, the operating system will invalidate the callback after 60 seconds of no
-    // /// activity (meaning, no placeholder methods are invoked). If you are prone to this issue,
-    // /// consider calling this method or call placeholder methods more frequently.
-    // pub fn reset_timeout() {}
-
     /// A raw connection key used to identify the connection.
+    #[cfg_attr(not(windows), allow(dead_code))]
     pub(crate) fn connection_key(&self) -> RawConnectionKey {
-        self.0.ConnectionKey.0
+        self.connection_key
     }
 
     /// A raw transfer key used to identify the current file operation.
+    #[cfg_attr(not(windows), allow(dead_code))]
     pub(crate) fn transfer_key(&self) -> RawTransferKey {
-        self.0.TransferKey
+        self.transfer_key
     }
 }
 
@@ -142,18 +182,147 @@ impl std::fmt::Debug for Request {
     }
 }
 
+/// A builder to construct a [Request].
+#[derive(Debug)]
+pub struct RequestBuilder(Request);
+
+impl RequestBuilder {
+    /// The GUID path of the current volume.
+    pub fn volume_guid_path(mut self, path: impl Into<OsString>) -> Self {
+        self.0.volume_guid_path = path.into();
+        self
+    }
+
+    /// The letter of the current volume, in the form `X:`.
+    pub fn volume_letter(mut self, letter: impl Into<OsString>) -> Self {
+        self.0.volume_letter = letter.into();
+        self
+    }
+
+    /// The serial number of the current volume.
+    pub fn volume_serial_number(mut self, serial_number: u32) -> Self {
+        self.0.volume_serial_number = serial_number;
+        self
+    }
+
+    /// Information of the user process that triggered the callback.
+    pub fn process(mut self, process: Process) -> Self {
+        self.0.process = process;
+        self
+    }
+
+    /// The file ID of the sync root folder.
+    pub fn sync_root_file_id(mut self, file_id: i64) -> Self {
+        self.0.sync_root_file_id = file_id;
+        self
+    }
+
+    /// The file ID of the placeholder file/directory.
+    pub fn file_id(mut self, file_id: i64) -> Self {
+        self.0.file_id = file_id;
+        self
+    }
+
+    /// The logical size of the placeholder file.
+    pub fn file_size(mut self, size: u64) -> Self {
+        self.0.file_size = size;
+        self
+    }
+
+    /// The absolute path of the placeholder file/directory.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.0.path = path.into();
+        self
+    }
+
+    /// The priority of the file operation, ranging from 0-15.
+    pub fn priority_hint(mut self, priority_hint: u8) -> Self {
+        self.0.priority_hint = priority_hint;
+        self
+    }
+
+    /// The blob assigned to the placeholder file/directory.
+    pub fn file_blob(mut self, blob: Vec<u8>) -> Self {
+        self.0.file_blob = blob;
+        self
+    }
+
+    /// The blob assigned to the sync root on registration.
+    pub fn register_blob(mut self, blob: Vec<u8>) -> Self {
+        self.0.register_blob = blob;
+        self
+    }
+
+    /// Constructs the [Request].
+    pub fn build(self) -> Request {
+        self.0
+    }
+}
+
+impl Default for RequestBuilder {
+    fn default() -> Self {
+        Self(Request {
+            volume_guid_path: OsString::new(),
+            volume_letter: OsString::new(),
+            volume_serial_number: 0,
+            process: Process::builder().build(),
+            sync_root_file_id: 0,
+            file_id: 0,
+            file_size: 0,
+            path: PathBuf::new(),
+            priority_hint: 0,
+            file_blob: Vec::new(),
+            register_blob: Vec::new(),
+            connection_key: 0,
+            transfer_key: 0,
+        })
+    }
+}
+
 /// Information about the calling process.
-pub struct Process(CF_PROCESS_INFO);
+#[derive(Clone)]
+pub struct Process {
+    pub(crate) name: OsString,
+    pub(crate) id: u32,
+    pub(crate) session_id: u32,
+    pub(crate) application_id: OsString,
+    pub(crate) command_line: Option<OsString>,
+    pub(crate) path: Option<PathBuf>,
+}
 
 impl Process {
+    /// Creates a builder to construct a [Process] without the operating system.
+    pub fn builder() -> ProcessBuilder {
+        ProcessBuilder::default()
+    }
+
+    /// Copies the information out of the raw process info.
+    ///
+    /// # Safety
+    ///
+    /// The pointers in `info` must be valid.
+    #[cfg(windows)]
+    pub(crate) unsafe fn from_raw(info: &CF_PROCESS_INFO) -> Self {
+        let known = |s: &U16CStr| (s != u16cstr!("UNKNOWN")).then(|| s.to_os_string());
+
+        Self {
+            name: U16CStr::from_ptr_str(info.PackageName.0).to_os_string(),
+            id: info.ProcessId,
+            session_id: info.SessionId,
+            application_id: U16CStr::from_ptr_str(info.ApplicationId.0).to_os_string(),
+            command_line: known(U16CStr::from_ptr_str(info.ImagePath.0)),
+            path: known(U16CStr::from_ptr_str(info.ImagePath.0)).map(PathBuf::from),
+        }
+    }
+
     /// The application's package name.
     pub fn name(&self) -> OsString {
-        unsafe { U16CStr::from_ptr_str(self.0.PackageName.0) }.to_os_string()
+        self.name.clone()
     }
 
     /// The ID of the user process.
     pub fn id(&self) -> u32 {
-        self.0.ProcessId
+        self.id
     }
 
     /// The ID of the session where the user process resides.
@@ -162,12 +331,12 @@ impl Process {
     ///
     /// [Process::session_id] is valid in versions 1803 and later.
     pub fn session_id(&self) -> u32 {
-        self.0.SessionId
+        self.session_id
     }
 
     /// The application's ID.
     pub fn application_id(&self) -> OsString {
-        unsafe { U16CStr::from_ptr_str(self.0.ApplicationId.0) }.to_os_string()
+        self.application_id.clone()
     }
 
     /// The exact command used to initialize the user process.
@@ -176,18 +345,15 @@ impl Process {
     ///
     /// [Process::command_line] is valid in versions 1803 and later.
     pub fn command_line(&self) -> Option<OsString> {
-        let cmd = unsafe { U16CStr::from_ptr_str(self.0.ImagePath.0) };
-        (cmd != u16cstr!("UNKNOWN")).then(|| cmd.to_os_string())
+        self.command_line.clone()
     }
 
-    // TODO: Could be optimized
     /// The absolute path to the main executable file of the process in the format of an NT path.
     ///
     /// This function returns [None][std::option::Option::None] when the operating system failed to
     /// retrieve the path.
     pub fn path(&self) -> Option<PathBuf> {
-        let path = unsafe { U16CStr::from_ptr_str(self.0.ImagePath.0) };
-        (path != u16cstr!("UNKNOWN")).then(|| PathBuf::from(path.to_os_string()))
+        self.path.clone()
     }
 }
 
@@ -203,3 +369,72 @@ impl std::fmt::Debug for Process {
             .finish()
     }
 }
+
+/// A builder to construct a [Process].
+#[derive(Debug)]
+pub struct ProcessBuilder(Process);
+
+impl ProcessBuilder {
+    /// The application's package name.
+    pub fn name(mut self, name: impl Into<OsString>) -> Self {
+        self.0.name = name.into();
+        self
+    }
+
+    /// The ID of the user process.
+    pub fn id(mut self, id: u32) -> Self {
+        self.0.id = id;
+        self
+    }
+
+    /// The ID of the session where the user process resides.
+    pub fn session_id(mut self, session_id: u32) -> Self {
+        self.0.session_id = session_id;
+        self
+    }
+
+    /// The application's ID.
+    pub fn application_id(mut self, application_id: impl Into<OsString>) -> Self {
+        self.0.application_id = application_id.into();
+        self
+    }
+
+    /// The exact command used to initialize the user process.
+    pub fn command_line(mut self, command_line: impl Into<OsString>) -> Self {
+        self.0.command_line = Some(command_line.into());
+        self
+    }
+
+    /// The absolute path to the main executable file of the process.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.0.path = Some(path.into());
+        self
+    }
+
+    /// Constructs the [Process].
+    pub fn build(self) -> Process {
+        self.0
+    }
+}
+
+impl Default for ProcessBuilder {
+    fn default() -> Self {
+        Self(Process {
+            name: OsString::new(),
+            id: 0,
+            session_id: 0,
+            application_id: OsString::new(),
+            command_line: None,
+            path: None,
+        })
+    }
+}
+
+/// Copies a raw identity blob into an owned buffer.
+#[cfg(windows)]
+unsafe fn raw_blob(ptr: *const std::ffi::c_void, len: u32) -> Vec<u8> {
+    match ptr.is_null() {
+        true => Vec::new(),
+        false => slice::from_raw_parts(ptr as *const u8, len as usize).to_vec(),
+    }
+}
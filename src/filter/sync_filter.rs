@@ -36,7 +36,7 @@ pub trait SyncFilter: Send + Sync {
         _ticket: ticket::ValidateData,
         _info: info::ValidateData,
     ) -> CResult<()> {
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     /// A directory population has been requested. The behavior of this callback is dependent on
@@ -47,7 +47,7 @@ pub trait SyncFilter: Send + Sync {
         _ticket: ticket::FetchPlaceholders,
         _info: info::FetchPlaceholders,
     ) -> CResult<()> {
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     /// A directory population request has been cancelled.
@@ -72,7 +72,7 @@ pub trait SyncFilter: Send + Sync {
         _ticket: ticket::Dehydrate,
         _info: info::Dehydrate,
     ) -> CResult<()> {
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     /// A placeholder dehydration request has been cancelled.
@@ -88,7 +88,7 @@ pub trait SyncFilter: Send + Sync {
         _ticket: ticket::Delete,
         _info: info::Delete,
     ) -> CResult<()> {
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     /// A placeholder file has been deleted.
@@ -107,7 +107,7 @@ pub trait SyncFilter: Send + Sync {
         _ticket: ticket::Rename,
         _info: info::Rename,
     ) -> CResult<()> {
-        Err(CloudErrorKind::NotSupported)
+        Err(CloudErrorKind::NotSupported.into())
     }
 
     /// A placeholder file has been renamed or moved.
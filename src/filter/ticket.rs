@@ -1,13 +1,14 @@
-use std::ops::Range;
-
-use windows::{
-    core,
-    Win32::Storage::CloudFilters::{CfReportProviderProgress, CF_CONNECTION_KEY},
+use std::{
+    io::{self, Write},
+    ops::Range,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use windows_core as core;
+
 use crate::{
-    command::{self, Command},
-    filter::{RawConnectionKey, RawTransferKey},
+    filter::{Backend, CancellationToken, KeepAlive},
     placeholder_file::PlaceholderFile,
     sealed, utility,
 };
@@ -15,16 +16,45 @@ use crate::{
 /// A ticket for the [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] callback.
 #[derive(Debug)]
 pub struct FetchData {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
+    token: CancellationToken,
+    progress: Arc<Mutex<(u64, u64)>>,
 }
 
 impl FetchData {
-    /// Create a new [FetchData].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
+    /// Create a new [FetchData] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self::with_token(backend, CancellationToken::new())
+    }
+
+    /// Create a new [FetchData] executing its operations through the [Backend], cancelled
+    /// through the [CancellationToken].
+    pub fn with_token(backend: Arc<dyn Backend>, token: CancellationToken) -> Self {
         Self {
-            connection_key,
-            transfer_key,
+            backend,
+            token,
+            progress: Default::default(),
+        }
+    }
+
+    /// The token tripped when the operation is cancelled, see
+    /// [SyncFilter::cancel_fetch_data][crate::filter::SyncFilter::cancel_fetch_data].
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    /// Creates a ticket for the same operation executing its operations through the [Backend],
+    /// sharing the cancellation token and the reported progress.
+    pub(crate) fn reissue(&self, backend: Arc<dyn Backend>) -> Self {
+        Self {
+            backend,
+            token: self.token.clone(),
+            progress: self.progress.clone(),
         }
     }
 
@@ -33,18 +63,55 @@ impl FetchData {
     /// displaying the speed and progress based on the values set. During background hydrations,
     /// an interactive toast will appear notifying the user of an operation with a progress bar.
     pub fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
-        unsafe {
-            CfReportProviderProgress(
-                CF_CONNECTION_KEY(self.connection_key),
-                self.transfer_key,
-                total as i64,
-                completed as i64,
-            )
-        }?;
-
+        let mut progress = self.progress.lock().unwrap();
+        self.backend.report_progress(total, completed)?;
+        *progress = (total, completed);
         Ok(())
     }
 
+    /// Keeps the operation from timing out until the returned guard is dropped, by re-reporting
+    /// the last progress at the interval, see [KeepAlive].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn keep_alive(&self, interval: Duration) -> KeepAlive {
+        KeepAlive::spawn(
+            self.backend.clone(),
+            self.progress.clone(),
+            self.token.clone(),
+            interval,
+        )
+    }
+
+    /// Creates an [io::Write] adapter transferring the range to the placeholder.
+    ///
+    /// The data is buffered and written in chunks aligned to 4KiB, as required by
+    /// `CF_OPERATION_TYPE_TRANSFER_DATA`. The last chunk is written as soon as the end of the
+    /// range is reached, so the range must end on a 4KiB boundary or on the logical file size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is not aligned to 4KiB.
+    pub fn writer(&self, range: Range<u64>) -> FetchDataWriter<'_> {
+        assert!(
+            range.start.is_multiple_of(TRANSFER_ALIGNMENT),
+            "offset {} is not aligned to {TRANSFER_ALIGNMENT} bytes",
+            range.start
+        );
+
+        FetchDataWriter {
+            ticket: self,
+            position: range.start,
+            end: range.end.max(range.start),
+            buf: Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            progress_interval: 0,
+            reported: range.start,
+            failed: false,
+        }
+    }
+
     // TODO: response command::Update
 }
 
@@ -53,11 +120,7 @@ impl utility::ReadAt for FetchData {
     ///
     /// This method is equivalent to calling `CfExecute` with `CF_OPERATION_TYPE_RETRIEVE_DATA`.
     fn read_at(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
-        command::Read {
-            buffer: buf,
-            position: offset,
-        }
-        .execute(self.connection_key, self.transfer_key)
+        self.backend.read(buf, offset)
     }
 }
 
@@ -69,30 +132,165 @@ impl utility::WriteAt for FetchData {
     ///
     /// This method is equivalent to calling `CfExecute` with `CF_OPERATION_TYPE_TRANSFER_DATA`.
     fn write_at(&self, buf: &[u8], offset: u64) -> core::Result<()> {
-        command::Write {
-            buffer: buf,
-            position: offset,
-        }
-        .execute(self.connection_key, self.transfer_key)
+        self.backend.write(buf, offset)
     }
 }
 
 impl sealed::Sealed for FetchData {}
 
+/// The alignment required by `CF_OPERATION_TYPE_TRANSFER_DATA`.
+const TRANSFER_ALIGNMENT: u64 = 4096;
+/// The default size of the chunks written by [FetchDataWriter].
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An [io::Write] adapter transferring a range of data to a placeholder, created with
+/// [FetchData::writer].
+///
+/// Call [FetchDataWriter::finish] once the range is written to handle the errors of the last
+/// chunk. Dropping the writer only writes the buffered data aligned to 4KiB, ignoring any error,
+/// and writes nothing once a write failed or the operation was cancelled.
+#[derive(Debug)]
+pub struct FetchDataWriter<'a> {
+    ticket: &'a FetchData,
+    position: u64,
+    end: u64,
+    buf: Vec<u8>,
+    chunk_size: usize,
+    progress_interval: u64,
+    reported: u64,
+    failed: bool,
+}
+
+impl FetchDataWriter<'_> {
+    /// The size of the chunks written to the placeholder, defaults to 64KiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is not a non-zero multiple of 4KiB.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        assert!(
+            size != 0 && (size as u64).is_multiple_of(TRANSFER_ALIGNMENT),
+            "chunk size must be a non-zero multiple of {TRANSFER_ALIGNMENT} bytes, got {size} bytes"
+        );
+        self.chunk_size = size;
+        self
+    }
+
+    /// The minimum amount of bytes written between two calls to
+    /// [FetchData::report_progress].
+    ///
+    /// Defaults to `0`, progress is reported after each chunk. The progress is always reported
+    /// once the end of the range is reached.
+    pub fn progress_interval(mut self, bytes: u64) -> Self {
+        self.progress_interval = bytes;
+        self
+    }
+
+    /// The offset up to which the data has been written to the placeholder, excluding the
+    /// buffered data.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Writes the buffered data to the placeholder, regardless of its alignment.
+    ///
+    /// Returns an error of kind [io::ErrorKind::Other] if a previous write failed.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::other("a previous write failed"));
+        }
+        self.commit(self.buf.len())
+    }
+
+    /// Writes the first bytes of the buffer to the placeholder and reports the progress.
+    fn commit(&mut self, len: usize) -> io::Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+
+        let result = self.ticket.backend.write(&self.buf[..len], self.position);
+        self.failed |= result.is_err();
+        result.map_err(io::Error::other)?;
+        self.buf.drain(..len);
+        self.position += len as u64;
+
+        if self.position == self.end || self.position - self.reported >= self.progress_interval {
+            self.ticket
+                .report_progress(self.end, self.position)
+                .map_err(io::Error::other)?;
+            self.reported = self.position;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for FetchDataWriter<'_> {
+    /// Buffers the data, writing each full chunk and the end of the range to the placeholder.
+    ///
+    /// Returns an error of kind [io::ErrorKind::InvalidInput] if the range has already been
+    /// written entirely, or an error of kind [io::ErrorKind::Other] if the operation has been
+    /// [cancelled][FetchData::cancellation_token].
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.ticket.token.is_cancelled() {
+            self.failed = true;
+            return Err(io::Error::other("the operation was cancelled"));
+        }
+
+        let remaining = self.end - self.position - self.buf.len() as u64;
+        if remaining == 0 && !buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the range ending at {} is already written", self.end),
+            ));
+        }
+
+        let len = (buf.len() as u64).min(remaining) as usize;
+        self.buf.extend_from_slice(&buf[..len]);
+        while self.buf.len() >= self.chunk_size {
+            self.commit(self.chunk_size)?;
+        }
+        if self.position + self.buf.len() as u64 == self.end {
+            self.commit(self.buf.len())?;
+        }
+
+        Ok(len)
+    }
+
+    /// Writes the buffered data aligned to 4KiB to the placeholder, the unaligned rest is kept
+    /// until more data is written or the writer is [finished][FetchDataWriter::finish].
+    fn flush(&mut self) -> io::Result<()> {
+        let len = self.buf.len() as u64;
+        self.commit((len - len % TRANSFER_ALIGNMENT) as usize)
+    }
+}
+
+impl Drop for FetchDataWriter<'_> {
+    /// Writes the buffered data aligned to 4KiB, the end of the range being written as soon as
+    /// it is reached.
+    fn drop(&mut self) {
+        if !self.failed && !self.ticket.token.is_cancelled() {
+            _ = self.flush();
+        }
+    }
+}
+
 /// A ticket for the [SyncFilter::validate_data][crate::filter::SyncFilter::validate_data] callback.
 #[derive(Debug)]
 pub struct ValidateData {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
 }
 
 impl ValidateData {
-    /// Create a new [ValidateData].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
-        Self {
-            connection_key,
-            transfer_key,
-        }
+    /// Create a new [ValidateData] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self { backend }
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    #[cfg(feature = "record")]
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     /// Validates the data range in the placeholder file is valid.
@@ -102,7 +300,7 @@ impl ValidateData {
     // if the range specified is past the current file length, will it consider that range to be validated?
     // https://docs.microsoft.com/en-us/answers/questions/750302/if-the-ackdata-field-of-cf-operation-parameters-is.html
     pub fn pass(&self, range: Range<u64>) -> core::Result<()> {
-        command::Validate { range }.execute(self.connection_key, self.transfer_key)
+        self.backend.validate(range)
     }
 
     // TODO: response command::Update
@@ -116,11 +314,7 @@ impl utility::ReadAt for ValidateData {
     /// The bytes returned will ALWAYS be the length of the buffer passed in. The operating
     /// system provides these guarantees.
     fn read_at(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
-        command::Read {
-            buffer: buf,
-            position: offset,
-        }
-        .execute(self.connection_key, self.transfer_key)
+        self.backend.read(buf, offset)
     }
 }
 
@@ -129,98 +323,138 @@ impl sealed::Sealed for ValidateData {}
 /// A ticket for the [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] callback.
 #[derive(Debug)]
 pub struct FetchPlaceholders {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
+    token: CancellationToken,
 }
 
 impl FetchPlaceholders {
-    /// Create a new [FetchPlaceholders].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
+    /// Create a new [FetchPlaceholders] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self::with_token(backend, CancellationToken::new())
+    }
+
+    /// Create a new [FetchPlaceholders] executing its operations through the [Backend],
+    /// cancelled through the [CancellationToken].
+    pub fn with_token(backend: Arc<dyn Backend>, token: CancellationToken) -> Self {
+        Self { backend, token }
+    }
+
+    /// The token tripped when the operation is cancelled, see
+    /// [SyncFilter::cancel_fetch_placeholders][crate::filter::SyncFilter::cancel_fetch_placeholders].
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    /// Creates a ticket for the same operation executing its operations through the [Backend],
+    /// sharing the cancellation token.
+    pub(crate) fn reissue(&self, backend: Arc<dyn Backend>) -> Self {
         Self {
-            connection_key,
-            transfer_key,
+            backend,
+            token: self.token.clone(),
         }
     }
 
+    /// Keeps the operation from timing out until the returned guard is dropped, by reporting an
+    /// empty progress at the interval, see [KeepAlive].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn keep_alive(&self, interval: Duration) -> KeepAlive {
+        KeepAlive::spawn(
+            self.backend.clone(),
+            Default::default(),
+            self.token.clone(),
+            interval,
+        )
+    }
+
     /// Creates a list of placeholder files/directorys on the file system.
     ///
     /// The value returned is the final [Usn][crate::usn::Usn] (and if they succeeded) after each placeholder is created.
     pub fn pass_with_placeholder(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
-        command::CreatePlaceholders {
-            total: placeholders.len() as _,
-            placeholders,
-        }
-        .execute(self.connection_key, self.transfer_key)
+        self.backend.create_placeholders(placeholders)
     }
 }
 
 /// A ticket for the [SyncFilter::dehydrate][crate::filter::SyncFilter::dehydrate] callback.
 #[derive(Debug)]
 pub struct Dehydrate {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
 }
 
 impl Dehydrate {
-    /// Create a new [Dehydrate].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
-        Self {
-            connection_key,
-            transfer_key,
-        }
+    /// Create a new [Dehydrate] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self { backend }
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    #[cfg(feature = "record")]
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     /// Confirms dehydration of the file.
     pub fn pass(&self) -> core::Result<()> {
-        command::Dehydrate { blob: &[] }.execute(self.connection_key, self.transfer_key)
+        self.backend.dehydrate(&[])
     }
 
     /// Confirms dehydration of the file and updates its file blob.
     pub fn pass_with_blob(&self, blob: &[u8]) -> core::Result<()> {
-        command::Dehydrate { blob }.execute(self.connection_key, self.transfer_key)
+        self.backend.dehydrate(blob)
     }
 }
 
 /// A ticket for the [SyncFilter::delete][crate::filter::SyncFilter::delete] callback.
 #[derive(Debug)]
 pub struct Delete {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
 }
 
 impl Delete {
-    /// Create a new [Delete].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
-        Self {
-            connection_key,
-            transfer_key,
-        }
+    /// Create a new [Delete] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self { backend }
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    #[cfg(feature = "record")]
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     /// Confirms deletion of the file.
     pub fn pass(&self) -> core::Result<()> {
-        command::Delete.execute(self.connection_key, self.transfer_key)
+        self.backend.delete()
     }
 }
 
 /// A ticket for the [SyncFilter::rename][crate::filter::SyncFilter::rename] callback.
 #[derive(Debug)]
 pub struct Rename {
-    connection_key: RawConnectionKey,
-    transfer_key: RawTransferKey,
+    backend: Arc<dyn Backend>,
 }
 
 impl Rename {
-    /// Create a new [Rename].
-    pub(crate) fn new(connection_key: RawConnectionKey, transfer_key: RawTransferKey) -> Self {
-        Self {
-            connection_key,
-            transfer_key,
-        }
+    /// Create a new [Rename] executing its operations through the [Backend].
+    pub fn new(backend: Arc<dyn Backend>) -> Self {
+        Self { backend }
+    }
+
+    /// The [Backend] executing the operations of the ticket.
+    #[cfg(feature = "record")]
+    pub(crate) fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
     }
 
     /// Confirms the rename/move of a file.
     pub fn pass(&self) -> core::Result<()> {
-        command::Rename.execute(self.connection_key, self.transfer_key)
+        self.backend.rename()
     }
 }
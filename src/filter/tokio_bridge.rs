@@ -0,0 +1,259 @@
+use std::{num::NonZeroUsize, ops::Deref, path::PathBuf, sync::Arc, thread};
+
+use tokio::{
+    runtime::Handle,
+    sync::{
+        mpsc::{self, UnboundedSender},
+        Mutex,
+    },
+    task::{self, LocalSet},
+};
+
+use crate::{
+    command::{self, Fallible},
+    error::{self, CResult},
+    filter::{info, ticket, Filter, Request, SyncFilter},
+    utility::LocalBoxFuture,
+};
+
+/// A callback of the [Filter], sent to the workers driving the futures.
+type Job<F> = Box<dyn FnOnce(Arc<F>) -> LocalBoxFuture<'static, ()> + Send>;
+
+/// Adapts a [Filter] to the [SyncFilter] trait by running its callbacks on a tokio runtime.
+///
+/// Unlike [AsyncBridge][crate::filter::AsyncBridge], the OS callback thread returns as soon as the
+/// callback is queued. The operation is then completed asynchronously through its transfer key:
+/// the ticket responds whenever the future gets to it, and an error returned by the future fails
+/// the operation. This lets the sync engine serve many concurrent hydrations without pinning an
+/// OS callback thread for each of them.
+///
+/// Since the futures of a [Filter] are not required to be [Send], they are spawned onto the
+/// [LocalSet]s of a pool of worker threads, one per available core, driven through the runtime
+/// [Handle] so they can still use the I/O and timers of the runtime. The futures of a worker share
+/// its thread: a blocking call, e.g. a [ticket] method, stalls the other futures of the same
+/// worker until it returns, while the next callbacks are picked up by the other workers. Dropping
+/// the bridge waits for the pending futures.
+pub struct TokioBridge<F> {
+    filter: Arc<F>,
+    jobs: Option<UnboundedSender<Job<F>>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl<F> TokioBridge<F>
+where
+    F: Filter + 'static,
+{
+    /// Creates a new [TokioBridge] spawning the callbacks of the [Filter] onto the runtime.
+    pub fn new(filter: F, handle: Handle) -> Self {
+        let filter = Arc::new(filter);
+        let (jobs, receiver) = mpsc::unbounded_channel::<Job<F>>();
+        // the idle workers take turns waiting for the next callback
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..thread::available_parallelism().map_or(1, NonZeroUsize::get))
+            .map(|_| {
+                let (filter, receiver, handle) = (filter.clone(), receiver.clone(), handle.clone());
+                thread::spawn(move || {
+                    let local = LocalSet::new();
+                    handle.block_on(local.run_until(async {
+                        loop {
+                            let Some(job) = receiver.lock().await.recv().await else {
+                                break;
+                            };
+                            task::spawn_local(job(filter.clone()));
+                        }
+                    }));
+                    // the bridge is dropped, wait for the pending callbacks
+                    handle.block_on(local);
+                })
+            })
+            .collect();
+
+        Self {
+            filter,
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    fn spawn<C>(&self, callback: C)
+    where
+        C: FnOnce(Arc<F>) -> LocalBoxFuture<'static, ()> + Send + 'static,
+    {
+        // the future runs after the callback returned, outside of the span entered for it
+        #[cfg(feature = "tracing")]
+        let callback = {
+            let span = tracing::Span::current();
+            move |filter| -> LocalBoxFuture<'static, ()> {
+                Box::pin(tracing::Instrument::instrument(callback(filter), span))
+            }
+        };
+
+        if let Some(jobs) = &self.jobs {
+            _ = jobs.send(Box::new(callback));
+        }
+    }
+}
+
+impl<F> SyncFilter for TokioBridge<F>
+where
+    F: Filter + 'static,
+{
+    fn fetch_data(
+        &self,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.fetch_data(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::Write::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn cancel_fetch_data(&self, request: Request, info: info::CancelFetchData) {
+        self.spawn(move |filter| {
+            Box::pin(async move { filter.cancel_fetch_data(request, info).await })
+        });
+    }
+
+    fn validate_data(
+        &self,
+        request: Request,
+        ticket: ticket::ValidateData,
+        info: info::ValidateData,
+    ) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.validate_data(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::Validate::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.fetch_placeholders(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::CreatePlaceholders::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn cancel_fetch_placeholders(&self, request: Request, info: info::CancelFetchPlaceholders) {
+        self.spawn(move |filter| {
+            Box::pin(async move { filter.cancel_fetch_placeholders(request, info).await })
+        });
+    }
+
+    fn opened(&self, request: Request, info: info::Opened) {
+        self.spawn(move |filter| Box::pin(async move { filter.opened(request, info).await }));
+    }
+
+    fn closed(&self, request: Request, info: info::Closed) {
+        self.spawn(move |filter| Box::pin(async move { filter.closed(request, info).await }));
+    }
+
+    fn dehydrate(
+        &self,
+        request: Request,
+        ticket: ticket::Dehydrate,
+        info: info::Dehydrate,
+    ) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.dehydrate(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::Dehydrate::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn dehydrated(&self, request: Request, info: info::Dehydrated) {
+        self.spawn(move |filter| Box::pin(async move { filter.dehydrated(request, info).await }));
+    }
+
+    fn delete(&self, request: Request, ticket: ticket::Delete, info: info::Delete) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.delete(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::Delete::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn deleted(&self, request: Request, info: info::Deleted) {
+        self.spawn(move |filter| Box::pin(async move { filter.deleted(request, info).await }));
+    }
+
+    fn rename(&self, request: Request, ticket: ticket::Rename, info: info::Rename) -> CResult<()> {
+        let (connection_key, transfer_key) = (request.connection_key(), request.transfer_key());
+        self.spawn(move |filter| {
+            Box::pin(async move {
+                if let Err(e) = filter.rename(request, ticket, info).await {
+                    error::report(&e);
+                    _ = command::Rename::fail(connection_key, transfer_key, e.kind());
+                }
+            })
+        });
+
+        Ok(())
+    }
+
+    fn renamed(&self, request: Request, info: info::Renamed) {
+        self.spawn(move |filter| Box::pin(async move { filter.renamed(request, info).await }));
+    }
+
+    fn state_changed(&self, changes: Vec<PathBuf>) {
+        self.spawn(move |filter| Box::pin(async move { filter.state_changed(changes).await }));
+    }
+}
+
+impl<F> Deref for TokioBridge<F> {
+    type Target = F;
+
+    fn deref(&self) -> &Self::Target {
+        &self.filter
+    }
+}
+
+impl<F> Drop for TokioBridge<F> {
+    fn drop(&mut self) {
+        // closing the channel stops the workers once the pending callbacks are completed
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            _ = worker.join();
+        }
+    }
+}
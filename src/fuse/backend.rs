@@ -0,0 +1,172 @@
+use std::{
+    fs::{File, OpenOptions},
+    ops::Range,
+    os::unix::fs::FileExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use windows_core::{self as core, HRESULT};
+
+use crate::{
+    error::CloudErrorKind,
+    filter::Backend,
+    fuse::state::{InodeKind, State},
+    placeholder_file::{PlaceholderFile, MAX_BLOB_LENGTH},
+    sim::tree,
+};
+
+/// `E_FAIL`
+const E_FAIL: i32 = 0x80004005_u32 as _;
+/// `ERROR_ALREADY_EXISTS`
+const ERROR_ALREADY_EXISTS: u32 = 183;
+/// The alignment required for `CF_OPERATION_TYPE_TRANSFER_DATA` offsets and lengths.
+const TRANSFER_ALIGNMENT: u64 = 4096;
+
+/// Executes the ticket operations against the inode table and the cache directory.
+#[derive(Debug)]
+pub(crate) struct FuseBackend {
+    state: Arc<Mutex<State>>,
+    ino: u64,
+    acked: AtomicBool,
+}
+
+impl FuseBackend {
+    pub(crate) fn new(state: Arc<Mutex<State>>, ino: u64) -> Self {
+        Self {
+            state,
+            ino,
+            acked: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether or not the operation was acknowledged through the ticket.
+    pub(crate) fn acked(&self) -> bool {
+        self.acked.load(Ordering::Acquire)
+    }
+
+    fn ack(&self) {
+        self.acked.store(true, Ordering::Release);
+    }
+}
+
+fn invalid_request(message: impl AsRef<str>) -> core::Error {
+    core::Error::new(
+        HRESULT::from_nt(CloudErrorKind::InvalidRequest.ntstatus()),
+        message,
+    )
+}
+
+fn io_error(err: std::io::Error) -> core::Error {
+    core::Error::new(HRESULT(E_FAIL), err.to_string())
+}
+
+impl Backend for FuseBackend {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        let path = self.state.lock().unwrap().cache_path(self.ino);
+        let file = File::open(path).map_err(io_error)?;
+
+        file.read_at(buf, offset)
+            .map(|n| n as u64)
+            .map_err(io_error)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let path = state.cache_path(self.ino);
+        let Some(inode) = state.get_mut(self.ino) else {
+            return Err(invalid_request("placeholder does not exist"));
+        };
+        let size = inode.metadata.size;
+        let InodeKind::File { present } = &mut inode.kind else {
+            return Err(invalid_request("placeholder is not a file"));
+        };
+
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or_else(|| invalid_request(format!("offset {offset} overflows")))?;
+        if !offset.is_multiple_of(TRANSFER_ALIGNMENT) {
+            return Err(invalid_request(format!(
+                "offset {offset} is not aligned to {TRANSFER_ALIGNMENT} bytes"
+            )));
+        }
+        if end > size {
+            return Err(invalid_request(format!(
+                "range {offset}..{end} exceeds the file size {size}"
+            )));
+        }
+        if !(buf.len() as u64).is_multiple_of(TRANSFER_ALIGNMENT) && end != size {
+            return Err(invalid_request(format!(
+                "length {} is not aligned to {TRANSFER_ALIGNMENT} bytes and does not end on the file size {size}",
+                buf.len()
+            )));
+        }
+
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.write_all_at(buf, offset))
+            .map_err(io_error)?;
+        tree::insert_range(present, offset..end);
+
+        Ok(())
+    }
+
+    fn report_progress(&self, _total: u64, _completed: u64) -> core::Result<()> {
+        Ok(())
+    }
+
+    fn validate(&self, _range: Range<u64>) -> core::Result<()> {
+        self.ack();
+        Ok(())
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.get_mut(self.ino).map(|inode| &mut inode.kind) {
+            Some(InodeKind::Directory { populated }) => *populated = true,
+            _ => return Err(invalid_request("placeholder is not a directory")),
+        }
+
+        for placeholder in placeholders.iter_mut() {
+            placeholder.result = match state.insert(self.ino, placeholder) {
+                Ok(true) => HRESULT(0),
+                Ok(false) => HRESULT::from_win32(ERROR_ALREADY_EXISTS),
+                Err(err) => io_error(err).code(),
+            };
+        }
+        self.ack();
+
+        Ok(())
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        if blob.len() > MAX_BLOB_LENGTH {
+            return Err(invalid_request(format!(
+                "blob size must not exceed {MAX_BLOB_LENGTH} bytes, got {} bytes",
+                blob.len()
+            )));
+        }
+
+        if let Some(inode) = self.state.lock().unwrap().get_mut(self.ino) {
+            if !blob.is_empty() {
+                inode.blob = blob.to_vec();
+            }
+        }
+        self.ack();
+
+        Ok(())
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.ack();
+        Ok(())
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.ack();
+        Ok(())
+    }
+}
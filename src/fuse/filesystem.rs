@@ -0,0 +1,504 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    ops::Range,
+    os::unix::fs::FileExt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use fuser::{
+    FileType, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, FUSE_ROOT_ID,
+};
+use libc::c_int;
+
+use crate::{
+    error::{self, CloudError, CloudErrorKind},
+    filter::{info, ticket, Process, RawTransferKey, Request, SyncFilter},
+    fuse::{
+        backend::FuseBackend,
+        state::{InodeKind, State},
+    },
+    sim::tree,
+};
+
+/// How long the kernel may cache attributes and entries.
+const TTL: Duration = Duration::from_secs(1);
+/// The alignment of the ranges requested through `fetch_data`.
+const ALIGNMENT: u64 = 4096;
+
+/// Translates the kernel requests into [SyncFilter] callbacks.
+pub(crate) struct Filesystem<F> {
+    filter: Arc<F>,
+    root: PathBuf,
+    state: Arc<Mutex<State>>,
+    uid: u32,
+    gid: u32,
+    next_transfer_key: RawTransferKey,
+}
+
+impl<F: SyncFilter> Filesystem<F> {
+    pub(crate) fn new(filter: Arc<F>, root: PathBuf, cache: PathBuf, uid: u32, gid: u32) -> Self {
+        Self {
+            filter,
+            root,
+            state: Arc::new(Mutex::new(State::new(cache))),
+            uid,
+            gid,
+            next_transfer_key: 1,
+        }
+    }
+
+    fn request(&mut self, req: &fuser::Request<'_>, ino: u64) -> Request {
+        let state = self.state.lock().unwrap();
+        let inode = state.get(ino).unwrap();
+        let size = match inode.kind {
+            InodeKind::Directory { .. } => 0,
+            InodeKind::File { .. } => inode.metadata.size,
+        };
+
+        let mut request = Request::builder()
+            .process(process(req.pid()))
+            .sync_root_file_id(FUSE_ROOT_ID as _)
+            .file_id(ino as _)
+            .file_size(size)
+            .path(match ino {
+                FUSE_ROOT_ID => self.root.clone(),
+                _ => self.root.join(state.path(ino)),
+            })
+            .file_blob(inode.blob.clone())
+            .build();
+        request.transfer_key = self.next_transfer_key;
+        self.next_transfer_key += 1;
+
+        request
+    }
+
+    fn backend(&self, ino: u64) -> Arc<FuseBackend> {
+        Arc::new(FuseBackend::new(self.state.clone(), ino))
+    }
+
+    /// Issues [SyncFilter::fetch_placeholders] if the directory has not been populated yet.
+    fn populate(&mut self, req: &fuser::Request<'_>, ino: u64) -> Result<(), c_int> {
+        match self.state.lock().unwrap().get(ino).map(|inode| &inode.kind) {
+            Some(InodeKind::Directory { populated: false }) => {}
+            Some(InodeKind::Directory { populated: true }) => return Ok(()),
+            Some(InodeKind::File { .. }) => return Err(libc::ENOTDIR),
+            None => return Err(libc::ENOENT),
+        }
+
+        let backend = self.backend(ino);
+        let request = self.request(req, ino);
+        self.filter
+            .fetch_placeholders(
+                request,
+                ticket::FetchPlaceholders::new(backend.clone()),
+                info::FetchPlaceholders::builder().build(),
+            )
+            .map_err(errno)?;
+
+        if !backend.acked() {
+            let request = self.request(req, ino);
+            self.filter.cancel_fetch_placeholders(
+                request,
+                info::CancelFetchPlaceholders::builder()
+                    .timeout(true)
+                    .build(),
+            );
+            return Err(libc::ETIMEDOUT);
+        }
+
+        Ok(())
+    }
+
+    /// Issues [SyncFilter::fetch_data] if the range is not present in the cache.
+    fn hydrate(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        range: Range<u64>,
+    ) -> Result<(), c_int> {
+        let (size, required) = {
+            let state = self.state.lock().unwrap();
+            let inode = state.get(ino).ok_or(libc::ENOENT)?;
+            let InodeKind::File { present } = &inode.kind else {
+                return Err(libc::EISDIR);
+            };
+
+            let size = inode.metadata.size;
+            let start = range.start - range.start % ALIGNMENT;
+            let end = range
+                .end
+                .div_ceil(ALIGNMENT)
+                .saturating_mul(ALIGNMENT)
+                .min(size);
+            if tree::covers(present, &(start..end)) {
+                return Ok(());
+            }
+
+            (size, start..end)
+        };
+
+        let backend = self.backend(ino);
+        let request = self.request(req, ino);
+        self.filter
+            .fetch_data(
+                request,
+                ticket::FetchData::new(backend),
+                info::FetchData::builder()
+                    .required_range(required.clone())
+                    .optional_range(0..size)
+                    .build(),
+            )
+            .map_err(errno)?;
+
+        let hydrated = matches!(
+            self.state.lock().unwrap().get(ino).map(|inode| &inode.kind),
+            Some(InodeKind::File { present }) if tree::covers(present, &required)
+        );
+        if !hydrated {
+            let request = self.request(req, ino);
+            self.filter.cancel_fetch_data(
+                request,
+                info::CancelFetchData::builder()
+                    .timeout(true)
+                    .file_range(required)
+                    .build(),
+            );
+            return Err(libc::ETIMEDOUT);
+        }
+
+        Ok(())
+    }
+
+    fn lookup_child(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+    ) -> Result<u64, c_int> {
+        self.populate(req, parent)?;
+        self.state
+            .lock()
+            .unwrap()
+            .child(parent, name)
+            .ok_or(libc::ENOENT)
+    }
+
+    /// Issues [SyncFilter::delete] followed by [SyncFilter::deleted].
+    fn remove(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        is_directory: bool,
+    ) -> Result<(), c_int> {
+        let ino = self.lookup_child(req, parent, name)?;
+        if self.state.lock().unwrap().get(ino).unwrap().is_directory() != is_directory {
+            return Err(match is_directory {
+                true => libc::ENOTDIR,
+                false => libc::EISDIR,
+            });
+        }
+        // the filter is only asked to delete empty directories, as on Windows
+        if is_directory {
+            self.populate(req, ino)?;
+            if !self.state.lock().unwrap().children(ino).is_empty() {
+                return Err(libc::ENOTEMPTY);
+            }
+        }
+
+        let backend = self.backend(ino);
+        let request = self.request(req, ino);
+        self.filter
+            .delete(
+                request,
+                ticket::Delete::new(backend.clone()),
+                info::Delete::builder().is_directory(is_directory).build(),
+            )
+            .map_err(errno)?;
+        if !backend.acked() {
+            return Err(libc::ETIMEDOUT);
+        }
+
+        let request = self.request(req, ino);
+        self.state
+            .lock()
+            .unwrap()
+            .remove(ino)
+            .map_err(|err| err.raw_os_error().unwrap_or(libc::EIO))?;
+        self.filter.deleted(request, info::Deleted::new());
+
+        Ok(())
+    }
+
+    /// Issues [SyncFilter::rename] followed by [SyncFilter::renamed], replacing the destination
+    /// as `rename(2)` does.
+    fn rename(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+    ) -> Result<(), c_int> {
+        let ino = self.lookup_child(req, parent, name)?;
+        // the destination is replaced, unless it is a directory that is not empty
+        let replaced = match self.lookup_child(req, new_parent, new_name) {
+            Ok(target) if target == ino => return Ok(()),
+            Ok(target) => {
+                let kinds = {
+                    let state = self.state.lock().unwrap();
+                    (
+                        state.get(ino).unwrap().is_directory(),
+                        state.get(target).unwrap().is_directory(),
+                    )
+                };
+                match kinds {
+                    (true, false) => return Err(libc::ENOTDIR),
+                    (false, true) => return Err(libc::EISDIR),
+                    (true, true) => {
+                        self.populate(req, target)?;
+                        if !self.state.lock().unwrap().children(target).is_empty() {
+                            return Err(libc::ENOTEMPTY);
+                        }
+                    }
+                    (false, false) => {}
+                }
+                Some(target)
+            }
+            Err(libc::ENOENT) => None,
+            Err(err) => return Err(err),
+        };
+
+        let (is_directory, source_path, target_path) = {
+            let state = self.state.lock().unwrap();
+            (
+                state.get(ino).unwrap().is_directory(),
+                self.root.join(state.path(ino)),
+                self.root.join(state.path(new_parent)).join(new_name),
+            )
+        };
+
+        let backend = self.backend(ino);
+        let request = self.request(req, ino);
+        self.filter
+            .rename(
+                request,
+                ticket::Rename::new(backend.clone()),
+                info::Rename::builder()
+                    .is_directory(is_directory)
+                    .target_path(target_path)
+                    .build(),
+            )
+            .map_err(errno)?;
+        if !backend.acked() {
+            return Err(libc::ETIMEDOUT);
+        }
+
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(target) = replaced {
+                state
+                    .remove(target)
+                    .map_err(|err| err.raw_os_error().unwrap_or(libc::EIO))?;
+            }
+            if let Some(inode) = state.get_mut(ino) {
+                inode.parent = new_parent;
+                inode.name = new_name.to_owned();
+            }
+        }
+        let request = self.request(req, ino);
+        self.filter.renamed(
+            request,
+            info::Renamed::builder().source_path(source_path).build(),
+        );
+
+        Ok(())
+    }
+}
+
+impl<F: SyncFilter> fuser::Filesystem for Filesystem<F> {
+    fn lookup(&mut self, req: &fuser::Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_child(req, parent, name) {
+            Ok(ino) => {
+                let attr = self.state.lock().unwrap().attr(ino, self.uid, self.gid);
+                reply.entry(&TTL, &attr.unwrap(), 0);
+            }
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn getattr(&mut self, _req: &fuser::Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.state.lock().unwrap().attr(ino, self.uid, self.gid) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, req: &fuser::Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        if self.state.lock().unwrap().get(ino).is_none() {
+            return reply.error(libc::ENOENT);
+        }
+
+        let request = self.request(req, ino);
+        self.filter.opened(request, info::Opened::builder().build());
+        reply.opened(0, 0);
+    }
+
+    fn read(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let file_size = match self.state.lock().unwrap().get(ino) {
+            Some(inode) => inode.metadata.size,
+            None => return reply.error(libc::ENOENT),
+        };
+        let start = (offset as u64).min(file_size);
+        let end = (start + size as u64).min(file_size);
+
+        if let Err(err) = self.hydrate(req, ino, start..end) {
+            return reply.error(err);
+        }
+
+        let path = self.state.lock().unwrap().cache_path(ino);
+        let mut buf = vec![0; (end - start) as usize];
+        match File::open(path).and_then(|file| file.read_exact_at(&mut buf, start)) {
+            Ok(()) => reply.data(&buf),
+            Err(err) => reply.error(err.raw_os_error().unwrap_or(libc::EIO)),
+        }
+    }
+
+    fn release(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        if self.state.lock().unwrap().get(ino).is_some() {
+            let request = self.request(req, ino);
+            self.filter.closed(request, info::Closed::builder().build());
+        }
+        reply.ok();
+    }
+
+    fn readdir(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        if let Err(err) = self.populate(req, ino) {
+            return reply.error(err);
+        }
+
+        let entries = {
+            let state = self.state.lock().unwrap();
+            let parent = state.get(ino).unwrap().parent;
+            let mut entries = vec![
+                (ino, FileType::Directory, OsString::from(".")),
+                (parent, FileType::Directory, OsString::from("..")),
+            ];
+            entries.extend(state.children(ino).into_iter().map(|child| {
+                let inode = state.get(child).unwrap();
+                let kind = match inode.is_directory() {
+                    true => FileType::Directory,
+                    false => FileType::RegularFile,
+                };
+                (child, kind, inode.name.clone())
+            }));
+            entries
+        };
+
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn unlink(&mut self, req: &fuser::Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(req, parent, name, false) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rmdir(&mut self, req: &fuser::Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(req, parent, name, true) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        match Filesystem::rename(self, req, parent, name, newparent, newname) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+}
+
+/// Reads the information of the calling process from `/proc`.
+fn process(pid: u32) -> Process {
+    let mut process = Process::builder().id(pid);
+    if let Ok(path) = fs::read_link(format!("/proc/{pid}/exe")) {
+        if let Some(name) = path.file_name() {
+            process = process.name(name);
+        }
+        process = process.path(path);
+    }
+    if let Ok(command_line) = fs::read(format!("/proc/{pid}/cmdline")) {
+        let command_line = command_line
+            .split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(String::from_utf8_lossy)
+            .collect::<Vec<_>>()
+            .join(" ");
+        process = process.command_line(command_line);
+    }
+
+    process.build()
+}
+
+/// Maps the error of a callback to an `errno` value, passing it to the hook installed with
+/// [error::set_hook][crate::error::set_hook] first.
+fn errno(err: CloudError) -> c_int {
+    error::report(&err);
+    match err.kind() {
+        CloudErrorKind::AccessDenied | CloudErrorKind::AuthenticationFailed => libc::EACCES,
+        CloudErrorKind::InUse | CloudErrorKind::PropertyLockConflict => libc::EBUSY,
+        CloudErrorKind::InsufficientResources => libc::ENOMEM,
+        CloudErrorKind::InvalidRequest => libc::EINVAL,
+        CloudErrorKind::NetworkUnavailable => libc::ENETUNREACH,
+        CloudErrorKind::NotSupported => libc::ENOTSUP,
+        CloudErrorKind::ReadOnlyVolume => libc::EROFS,
+        CloudErrorKind::RequestAborted | CloudErrorKind::RequestCancelled => libc::ECANCELED,
+        CloudErrorKind::RequestTimeout => libc::ETIMEDOUT,
+        _ => libc::EIO,
+    }
+}
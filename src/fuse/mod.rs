@@ -0,0 +1,138 @@
+use std::{
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use fuser::{BackgroundSession, MountOption};
+
+use crate::filter::SyncFilter;
+
+use filesystem::Filesystem;
+
+mod backend;
+mod filesystem;
+mod state;
+
+/// A builder to mount a directory backed by a [SyncFilter][crate::filter::SyncFilter].
+///
+/// The mounted directory behaves like a sync root with
+/// [PopulationType::Full](https://docs.rs/cloud-filter/latest/cloud_filter/root/enum.PopulationType.html)
+/// and [HydrationType::Full](https://docs.rs/cloud-filter/latest/cloud_filter/root/enum.HydrationType.html):
+/// - `lookup` and `readdir` populate the directory through
+///   [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders],
+/// - `read` hydrates the missing data through
+///   [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] and keeps it in the cache
+///   directory,
+/// - `unlink`, `rmdir` and `rename` are forwarded to
+///   [SyncFilter::delete][crate::filter::SyncFilter::delete] and
+///   [SyncFilter::rename][crate::filter::SyncFilter::rename].
+///
+/// The paths passed through [Request::path][crate::filter::Request::path] start with the mount
+/// point, so the same [SyncFilter][crate::filter::SyncFilter] works on Windows and Linux.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    cache_dir: Option<PathBuf>,
+    allow_other: bool,
+}
+
+impl Session {
+    /// Create a new [Session].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The directory hydrated data is persisted in.
+    ///
+    /// By default, a directory named after the process ID is created in the temporary directory.
+    pub fn cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+
+    /// Allows users other than the one mounting the directory to access it.
+    pub fn allow_other(mut self) -> Self {
+        self.allow_other = true;
+        self
+    }
+
+    /// Mounts the directory with the given [SyncFilter][crate::filter::SyncFilter].
+    ///
+    /// The callbacks are invoked from a background thread.
+    pub fn mount<P, F>(self, path: P, filter: F) -> io::Result<Mount<F>>
+    where
+        P: AsRef<Path>,
+        F: SyncFilter + 'static,
+    {
+        let cache_dir = self.cache_dir.unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("cloud-filter-{}", std::process::id()))
+        });
+        fs::create_dir_all(&cache_dir)?;
+
+        let path = path.as_ref();
+        let metadata = fs::metadata(path)?;
+        let filter = Arc::new(filter);
+        let filesystem = Filesystem::new(
+            filter.clone(),
+            path.to_path_buf(),
+            cache_dir,
+            metadata.uid(),
+            metadata.gid(),
+        );
+
+        let mut options = vec![
+            MountOption::FSName("cloud-filter".to_owned()),
+            MountOption::DefaultPermissions,
+        ];
+        if self.allow_other {
+            options.push(MountOption::AllowOther);
+        }
+
+        Ok(Mount {
+            session: Some(fuser::spawn_mount2(filesystem, path, &options)?),
+            filter,
+        })
+    }
+}
+
+/// A handle to a mounted directory.
+///
+/// [Mount] will unmount the directory when dropped.
+pub struct Mount<F> {
+    session: Option<BackgroundSession>,
+    filter: Arc<F>,
+}
+
+impl<F> Mount<F> {
+    /// A reference to the inner [SyncFilter][crate::filter::SyncFilter] struct.
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// Unmounts the directory, waiting for the pending callbacks to complete.
+    pub fn unmount(mut self) {
+        if let Some(session) = self.session.take() {
+            session.join();
+        }
+    }
+}
+
+impl<F> Drop for Mount<F> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.join();
+        }
+    }
+}
+
+impl<F> std::fmt::Debug for Mount<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mount")
+            .field(
+                "mountpoint",
+                &self.session.as_ref().map(|session| &session.mountpoint),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io,
+    ops::Range,
+    path::{Component, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use fuser::{FileAttr, FileType, FUSE_ROOT_ID};
+use nt_time::FileTime;
+
+use crate::{metadata::Metadata, placeholder_file::PlaceholderFile};
+
+/// A placeholder in the mounted directory.
+#[derive(Debug)]
+pub(crate) struct Inode {
+    pub(crate) parent: u64,
+    pub(crate) name: OsString,
+    pub(crate) metadata: Metadata,
+    pub(crate) blob: Vec<u8>,
+    pub(crate) kind: InodeKind,
+}
+
+#[derive(Debug)]
+pub(crate) enum InodeKind {
+    Directory {
+        /// Whether or not the children of the directory have been fetched.
+        populated: bool,
+    },
+    File {
+        /// The sorted, non-overlapping ranges of data present in the cache.
+        present: Vec<Range<u64>>,
+    },
+}
+
+impl Inode {
+    pub(crate) fn is_directory(&self) -> bool {
+        matches!(self.kind, InodeKind::Directory { .. })
+    }
+}
+
+/// The inode table of a mounted directory.
+///
+/// The hydrated data of each file is kept in a sparse file in the cache directory, named after
+/// its inode.
+#[derive(Debug)]
+pub(crate) struct State {
+    inodes: BTreeMap<u64, Inode>,
+    next_ino: u64,
+    cache: PathBuf,
+}
+
+impl State {
+    pub(crate) fn new(cache: PathBuf) -> Self {
+        let mut inodes = BTreeMap::new();
+        inodes.insert(
+            FUSE_ROOT_ID,
+            Inode {
+                parent: FUSE_ROOT_ID,
+                name: OsString::new(),
+                metadata: Metadata::directory(),
+                blob: Vec::new(),
+                kind: InodeKind::Directory { populated: false },
+            },
+        );
+
+        Self {
+            inodes,
+            next_ino: FUSE_ROOT_ID + 1,
+            cache,
+        }
+    }
+
+    pub(crate) fn get(&self, ino: u64) -> Option<&Inode> {
+        self.inodes.get(&ino)
+    }
+
+    pub(crate) fn get_mut(&mut self, ino: u64) -> Option<&mut Inode> {
+        self.inodes.get_mut(&ino)
+    }
+
+    /// The path of the inode relative to the mount point.
+    pub(crate) fn path(&self, mut ino: u64) -> PathBuf {
+        let mut names = Vec::new();
+        while ino != FUSE_ROOT_ID {
+            let inode = &self.inodes[&ino];
+            names.push(inode.name.as_os_str());
+            ino = inode.parent;
+        }
+
+        names.iter().rev().collect()
+    }
+
+    pub(crate) fn child(&self, parent: u64, name: &OsStr) -> Option<u64> {
+        self.inodes
+            .iter()
+            .find(|(ino, inode)| {
+                **ino != FUSE_ROOT_ID && inode.parent == parent && inode.name == name
+            })
+            .map(|(ino, _)| *ino)
+    }
+
+    pub(crate) fn children(&self, parent: u64) -> Vec<u64> {
+        self.inodes
+            .iter()
+            .filter(|(ino, inode)| **ino != FUSE_ROOT_ID && inode.parent == parent)
+            .map(|(ino, _)| *ino)
+            .collect()
+    }
+
+    /// The file in the cache directory holding the data of the inode.
+    pub(crate) fn cache_path(&self, ino: u64) -> PathBuf {
+        self.cache.join(ino.to_string())
+    }
+
+    /// Inserts a placeholder under the directory, mirroring `CF_OPERATION_TYPE_TRANSFER_PLACEHOLDERS`.
+    ///
+    /// Returns `Ok(false)` if a placeholder already exists and overwriting was not requested.
+    pub(crate) fn insert(
+        &mut self,
+        parent: u64,
+        placeholder: &PlaceholderFile,
+    ) -> io::Result<bool> {
+        let mut components = placeholder.relative_path.components();
+        let (Some(Component::Normal(name)), None) = (components.next(), components.next()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "placeholder path must be a single file name",
+            ));
+        };
+
+        if let Some(existing) = self.child(parent, name) {
+            if !placeholder.overwrite {
+                return Ok(false);
+            }
+            self.remove_tree(existing)?;
+        }
+
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let metadata = placeholder.metadata;
+        let kind = match metadata.is_directory() {
+            true => InodeKind::Directory {
+                populated: placeholder.has_no_children,
+            },
+            false => {
+                File::create(self.cache_path(ino))?.set_len(metadata.size)?;
+                InodeKind::File {
+                    present: Vec::new(),
+                }
+            }
+        };
+        self.inodes.insert(
+            ino,
+            Inode {
+                parent,
+                name: name.to_owned(),
+                metadata,
+                blob: placeholder.blob.clone(),
+                kind,
+            },
+        );
+
+        Ok(true)
+    }
+
+    /// Removes the inode and its cached data, failing with `ENOTEMPTY` if it has children.
+    pub(crate) fn remove(&mut self, ino: u64) -> io::Result<()> {
+        if !self.children(ino).is_empty() {
+            return Err(io::Error::from_raw_os_error(libc::ENOTEMPTY));
+        }
+
+        self.remove_tree(ino)
+    }
+
+    /// Removes the inode along with its descendants and cached data, e.g. when a placeholder is
+    /// overwritten.
+    fn remove_tree(&mut self, ino: u64) -> io::Result<()> {
+        for child in self.children(ino) {
+            self.remove_tree(child)?;
+        }
+
+        if let Some(inode) = self.inodes.remove(&ino) {
+            if !inode.is_directory() {
+                match fs::remove_file(self.cache_path(ino)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The attributes reported to the kernel.
+    pub(crate) fn attr(&self, ino: u64, uid: u32, gid: u32) -> Option<FileAttr> {
+        let inode = self.inodes.get(&ino)?;
+        let metadata = &inode.metadata;
+        let (kind, perm, nlink, size) = match inode.kind {
+            InodeKind::Directory { .. } => (FileType::Directory, 0o755, 2, 0),
+            InodeKind::File { .. } => (FileType::RegularFile, 0o644, 1, metadata.size),
+        };
+
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: system_time(metadata.last_access_time),
+            mtime: system_time(metadata.last_write_time),
+            ctime: system_time(metadata.change_time),
+            crtime: system_time(metadata.creation_time),
+            kind,
+            perm,
+            nlink,
+            uid,
+            gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+fn system_time(time: i64) -> SystemTime {
+    match time {
+        0 => UNIX_EPOCH,
+        time => FileTime::new(time as u64).into(),
+    }
+}
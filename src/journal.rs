@@ -0,0 +1,235 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::blob::FileIdentity;
+
+/// The size of the header of a record: its length and its checksum.
+const RECORD_HEADER: usize = 4 + 8;
+
+/// An append-only file of checksummed records, starting with the magic bytes of its format.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    path: PathBuf,
+    magic: &'static [u8; 8],
+    file: Option<File>,
+}
+
+impl Journal {
+    /// Opens the journal at the path, creating it if it does not exist, and reads the payloads
+    /// of its records.
+    ///
+    /// A record that was not completely written, e.g. after a crash, is cut off along with
+    /// everything after it.
+    pub(crate) fn open(path: &Path, magic: &'static [u8; 8]) -> io::Result<(Self, Vec<Vec<u8>>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        if bytes.is_empty() {
+            file.write_all(magic)?;
+            file.sync_all()?;
+        } else if !bytes.starts_with(magic) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{path:?} has an unexpected format"),
+            ));
+        }
+
+        let mut payloads = Vec::new();
+        let mut offset = magic.len();
+        while let Some(payload) = read_record(&bytes[offset.min(bytes.len())..]) {
+            offset += RECORD_HEADER + payload.len();
+            payloads.push(payload.to_vec());
+        }
+        if offset < bytes.len() {
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok((
+            Self {
+                path: path.to_owned(),
+                magic,
+                file: Some(file),
+            },
+            payloads,
+        ))
+    }
+
+    /// Appends a record with the payload, durably.
+    ///
+    /// On error, the journal is cut back to its previous length, so that a partially written
+    /// record cannot hide the records appended after it. The journal refuses further appends if
+    /// it cannot be cut back.
+    pub(crate) fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        let file = self.file.as_mut().ok_or_else(|| {
+            io::Error::other(format!("{:?} could not be reopened or repaired", self.path))
+        })?;
+        let len = file.metadata()?.len();
+        let result = file
+            .write_all(&record(payload))
+            .and_then(|()| file.sync_data());
+        if result.is_err()
+            && file
+                .set_len(len)
+                .and_then(|()| file.seek(SeekFrom::Start(len)))
+                .is_err()
+        {
+            self.file = None;
+        }
+        result
+    }
+
+    /// Replaces the journal with the records of the payloads, through a temporary file renamed
+    /// over the journal.
+    pub(crate) fn rewrite<'a>(
+        &mut self,
+        payloads: impl IntoIterator<Item = &'a [u8]>,
+    ) -> io::Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let mut file = File::create(&temporary)?;
+        file.write_all(self.magic)?;
+        for payload in payloads {
+            file.write_all(&record(payload))?;
+        }
+        file.sync_all()?;
+        drop(file);
+
+        // Windows refuses to replace a file that is still open
+        self.file = None;
+        let renamed = replace(&temporary, &self.path);
+        self.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        renamed
+    }
+}
+
+/// Renames the file over the target, durably.
+#[cfg(windows)]
+fn replace(from: &Path, to: &Path) -> io::Result<()> {
+    use windows::{
+        core::HSTRING,
+        Win32::Storage::FileSystem::{
+            MoveFileExW, MOVEFILE_REPLACE_EXISTING, MOVEFILE_WRITE_THROUGH,
+        },
+    };
+
+    unsafe {
+        MoveFileExW(
+            &HSTRING::from(from),
+            &HSTRING::from(to),
+            MOVEFILE_REPLACE_EXISTING | MOVEFILE_WRITE_THROUGH,
+        )
+    }
+    .map_err(io::Error::from)
+}
+
+/// Renames the file over the target, durably, syncing the directory holding the new entry.
+#[cfg(not(windows))]
+fn replace(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::rename(from, to)?;
+    match to.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Encodes a record: the length of the payload, its checksum and the payload.
+fn record(payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER + payload.len());
+    put_u32(&mut record, payload.len() as u32);
+    put_u64(&mut record, checksum(payload));
+    record.extend_from_slice(payload);
+    record
+}
+
+/// Decodes the record at the start of the bytes, returning its payload.
+fn read_record(bytes: &[u8]) -> Option<&[u8]> {
+    let mut reader = Reader(bytes);
+    let len = reader.u32()? as usize;
+    let sum = reader.u64()?;
+    let payload = reader.take(len)?;
+    (checksum(payload) == sum).then_some(payload)
+}
+
+/// The 64-bit FNV-1a hash of the bytes.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The error of a record whose payload could not be decoded.
+pub(crate) fn invalid(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{path:?} has an invalid record"),
+    )
+}
+
+pub(crate) fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn put_str(buf: &mut Vec<u8>, value: &str) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Encodes the path losslessly in the platform representation, as its
+/// [FileIdentity][crate::blob::FileIdentity].
+pub(crate) fn put_path(buf: &mut Vec<u8>, path: &Path) {
+    let mut bytes = Vec::new();
+    path.to_path_buf().encode(&mut bytes);
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(&bytes);
+}
+
+/// Reads the encoded values from the bytes.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub(crate) fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    pub(crate) fn path(&mut self) -> Option<PathBuf> {
+        let len = self.u32()? as usize;
+        let path = PathBuf::decode(PathBuf::VERSION, self.take(len)?)?;
+        Some(path.components().collect())
+    }
+}
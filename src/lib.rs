@@ -1,20 +1,48 @@
 #![doc = include_str!("../README.md")]
 
+/// Contains the [FileIdentity][crate::blob::FileIdentity] trait for typed placeholder blobs.
+pub mod blob;
 /// Contains callbacks error types.
 pub mod error;
 /// Contains traits extending common structs from the [std].
+#[cfg(windows)]
 pub mod ext;
 /// Contains the [SyncFilter][crate::filter::SyncFilter] and [Filter][crate::filter::Filter] traits
 /// and related structs.
 pub mod filter;
+/// Contains a FUSE backend for hosting a [SyncFilter][crate::filter::SyncFilter] on Linux.
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub mod fuse;
 /// Contains the [Metadata][crate::metadata::Metadata] struct.
 pub mod metadata;
+/// Contains the [Outbox][crate::outbox::Outbox] queuing the local changes to apply to a
+/// [RemoteStore][crate::store::RemoteStore].
+pub mod outbox;
 /// Contains the [Placeholder][crate::placeholder::Placeholder] struct.
+#[cfg(windows)]
 pub mod placeholder;
 /// Contains the [PlaceholderFile][crate::placeholder_file::PlaceholderFile] struct.
 pub mod placeholder_file;
+/// Contains the [Recorder][crate::record::Recorder] writing the callbacks of a filter to a
+/// JSON-lines file and the [Replayer][crate::record::Replayer] feeding them back to a filter.
+#[cfg(feature = "record")]
+pub mod record;
 /// Contains the sync root structs.
+#[cfg(windows)]
 pub mod root;
+/// Contains an in-process simulator of the Cloud Filter API for driving
+/// [SyncFilter][crate::filter::SyncFilter] implementations without the operating system.
+pub mod sim;
+/// Contains the [StateStore][crate::state::StateStore] persisting the sync state of the files
+/// under a sync root.
+pub mod state;
+/// Contains the [RemoteStore][crate::store::RemoteStore] trait and the
+/// [StoreFilter][crate::store::StoreFilter] serving placeholders from it.
+pub mod store;
+/// Contains the [Reconciler][crate::sync::Reconciler] keeping a sync root and a
+/// [RemoteStore][crate::store::RemoteStore] in sync in both directions, and the
+/// [ChangeFeed][crate::sync::ChangeFeed] trait reporting the remote changes.
+pub mod sync;
 pub mod usn;
 pub mod utility;
 
@@ -22,7 +50,11 @@ pub mod utility;
 ///
 /// The [command][crate::command] API is exposed through various higher-level structs, like
 /// [Request][crate::request::Request] and [Placeholder][crate::placeholder::Placeholder].
+#[cfg(windows)]
 mod command;
+/// Contains the append-only [Journal][crate::journal::Journal] persisting the
+/// [StateStore][crate::state::StateStore] and the [Outbox][crate::outbox::Outbox].
+mod journal;
 
 mod sealed {
     pub trait Sealed {}
@@ -1,75 +1,102 @@
 use std::fs;
 
 use nt_time::FileTime;
-use windows::Win32::Storage::{
-    CloudFilters::CF_FS_METADATA,
-    FileSystem::{FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_NORMAL, FILE_BASIC_INFO},
-};
+#[cfg(windows)]
+use windows::Win32::Storage::{CloudFilters::CF_FS_METADATA, FileSystem::FILE_BASIC_INFO};
 
 use crate::sealed;
 
+/// The `FILE_ATTRIBUTE_DIRECTORY` attribute.
+pub(crate) const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+/// The `FILE_ATTRIBUTE_READONLY` attribute.
+#[cfg(not(windows))]
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+/// The `FILE_ATTRIBUTE_NORMAL` attribute.
+pub(crate) const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
 /// The metadata for placeholder.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct Metadata(pub(crate) CF_FS_METADATA);
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub(crate) creation_time: i64,
+    pub(crate) last_access_time: i64,
+    pub(crate) last_write_time: i64,
+    pub(crate) change_time: i64,
+    pub(crate) attributes: u32,
+    pub(crate) size: u64,
+}
 
 impl Metadata {
     /// The default [Metadata] with `FILE_ATTRIBUTE_NORMAL` attribute.
     pub fn file() -> Self {
-        Self(CF_FS_METADATA {
-            BasicInfo: FILE_BASIC_INFO {
-                FileAttributes: FILE_ATTRIBUTE_NORMAL.0,
-                ..Default::default()
-            },
+        Self {
+            attributes: FILE_ATTRIBUTE_NORMAL,
             ..Default::default()
-        })
+        }
     }
 
     /// The default [Metadata] with `FILE_ATTRIBUTE_DIRECTORY` attribute.
     pub fn directory() -> Self {
-        Self(CF_FS_METADATA {
-            BasicInfo: FILE_BASIC_INFO {
-                FileAttributes: FILE_ATTRIBUTE_DIRECTORY.0,
-                ..Default::default()
-            },
+        Self {
+            attributes: FILE_ATTRIBUTE_DIRECTORY,
             ..Default::default()
-        })
+        }
     }
 
     /// The time the file/directory was created.
     pub fn created(mut self, time: FileTime) -> Self {
-        self.0.BasicInfo.CreationTime = time.try_into().unwrap();
+        self.creation_time = time.try_into().unwrap();
         self
     }
 
     /// The time the file/directory was last accessed.
     pub fn accessed(mut self, time: FileTime) -> Self {
-        self.0.BasicInfo.LastAccessTime = time.try_into().unwrap();
+        self.last_access_time = time.try_into().unwrap();
         self
     }
 
     /// The time the file/directory content was last written.
     pub fn written(mut self, time: FileTime) -> Self {
-        self.0.BasicInfo.LastWriteTime = time.try_into().unwrap();
+        self.last_write_time = time.try_into().unwrap();
         self
     }
 
     /// The time the file/directory content or metadata was changed.
     pub fn changed(mut self, time: FileTime) -> Self {
-        self.0.BasicInfo.ChangeTime = time.try_into().unwrap();
+        self.change_time = time.try_into().unwrap();
         self
     }
 
     /// The size of the file's content.
     pub fn size(mut self, size: u64) -> Self {
-        self.0.FileSize = size as i64;
+        self.size = size;
         self
     }
 
     /// File attributes.
     pub fn attributes(mut self, attributes: u32) -> Self {
-        self.0.BasicInfo.FileAttributes |= attributes;
+        self.attributes |= attributes;
         self
     }
+
+    /// Whether or not the `FILE_ATTRIBUTE_DIRECTORY` attribute is set.
+    pub(crate) fn is_directory(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+    }
+
+    /// Converts the [Metadata] to its raw Cloud Filter representation.
+    #[cfg(windows)]
+    pub(crate) fn to_raw(self) -> CF_FS_METADATA {
+        CF_FS_METADATA {
+            BasicInfo: FILE_BASIC_INFO {
+                CreationTime: self.creation_time,
+                LastAccessTime: self.last_access_time,
+                LastWriteTime: self.last_write_time,
+                ChangeTime: self.change_time,
+                FileAttributes: self.attributes,
+            },
+            FileSize: self.size as i64,
+        }
+    }
 }
 
 pub trait MetadataExt: sealed::Sealed {
@@ -92,40 +119,84 @@ pub trait MetadataExt: sealed::Sealed {
 
 impl MetadataExt for Metadata {
     fn change_time(mut self, time: i64) -> Self {
-        self.0.BasicInfo.ChangeTime = time;
+        self.change_time = time;
         self
     }
 
     fn last_access_time(mut self, time: i64) -> Self {
-        self.0.BasicInfo.LastAccessTime = time;
+        self.last_access_time = time;
         self
     }
 
     fn last_write_time(mut self, time: i64) -> Self {
-        self.0.BasicInfo.LastWriteTime = time;
+        self.last_write_time = time;
         self
     }
 
     fn creation_time(mut self, time: i64) -> Self {
-        self.0.BasicInfo.CreationTime = time;
+        self.creation_time = time;
         self
     }
 }
 
 impl sealed::Sealed for Metadata {}
 
+#[cfg(windows)]
 impl From<fs::Metadata> for Metadata {
     fn from(metadata: fs::Metadata) -> Self {
         use std::os::windows::fs::MetadataExt;
-        Self(CF_FS_METADATA {
-            BasicInfo: FILE_BASIC_INFO {
-                CreationTime: metadata.creation_time() as i64,
-                LastAccessTime: metadata.last_access_time() as i64,
-                LastWriteTime: metadata.last_write_time() as i64,
-                ChangeTime: metadata.last_write_time() as i64,
-                FileAttributes: metadata.file_attributes(),
+        Self {
+            creation_time: metadata.creation_time() as i64,
+            last_access_time: metadata.last_access_time() as i64,
+            last_write_time: metadata.last_write_time() as i64,
+            change_time: metadata.last_write_time() as i64,
+            attributes: metadata.file_attributes(),
+            size: metadata.file_size(),
+        }
+    }
+}
+
+/// The timestamps are converted from the Unix epoch, `FILE_ATTRIBUTE_READONLY` is derived from the
+/// permissions and the size of directories is reported as `0`, as on Windows.
+#[cfg(not(windows))]
+impl From<fs::Metadata> for Metadata {
+    fn from(metadata: fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+
+        let time = |time: Option<FileTime>| {
+            time.and_then(|time| i64::try_from(time).ok())
+                .unwrap_or_default()
+        };
+        let system_time = |time: std::io::Result<std::time::SystemTime>| {
+            time.ok().and_then(|time| FileTime::try_from(time).ok())
+        };
+
+        let mut attributes = match metadata.is_dir() {
+            true => FILE_ATTRIBUTE_DIRECTORY,
+            false => 0,
+        };
+        if metadata.permissions().readonly() {
+            attributes |= FILE_ATTRIBUTE_READONLY;
+        }
+
+        Self {
+            creation_time: time(system_time(metadata.created())),
+            last_access_time: time(system_time(metadata.accessed())),
+            last_write_time: time(system_time(metadata.modified())),
+            change_time: time(
+                FileTime::from_unix_time_nanos(
+                    metadata.ctime() as i128 * 1_000_000_000 + metadata.ctime_nsec() as i128,
+                )
+                .ok(),
+            ),
+            attributes: match attributes {
+                0 => FILE_ATTRIBUTE_NORMAL,
+                attributes => attributes,
+            },
+            size: match metadata.is_dir() {
+                true => 0,
+                false => metadata.len(),
             },
-            FileSize: metadata.file_size() as i64,
-        })
+        }
     }
 }
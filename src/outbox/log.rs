@@ -0,0 +1,131 @@
+use std::{io, path::Path};
+
+use nt_time::FileTime;
+
+use crate::{
+    journal::{self, put_path, put_str, put_u32, put_u64, Journal, Reader},
+    outbox::{Entry, Task},
+};
+
+/// The first bytes of an outbox file.
+const MAGIC: &[u8; 8] = b"CFOUTBX1";
+
+/// A change of the queue, as persisted in the log.
+#[derive(Debug, Clone)]
+pub(crate) enum Change {
+    Put(Entry),
+    Remove(u64),
+}
+
+/// The [Journal] of the outbox, each record holding the changes of an operation on the queue.
+#[derive(Debug)]
+pub(crate) struct Log(Journal);
+
+impl Log {
+    /// Opens the log at the path, creating it if it does not exist, and reads its records.
+    pub(crate) fn open(path: &Path) -> io::Result<(Self, Vec<Vec<Change>>)> {
+        let (journal, payloads) = Journal::open(path, MAGIC)?;
+        let records = payloads
+            .iter()
+            .map(|payload| read_record(payload).ok_or_else(|| journal::invalid(path)))
+            .collect::<io::Result<_>>()?;
+
+        Ok((Self(journal), records))
+    }
+
+    /// Appends a record with the changes, durably.
+    pub(crate) fn append(&mut self, changes: &[Change]) -> io::Result<()> {
+        self.0.append(&record(changes))
+    }
+
+    /// Replaces the log with a single record holding the changes.
+    pub(crate) fn rewrite(&mut self, changes: &[Change]) -> io::Result<()> {
+        self.0.rewrite([record(changes).as_slice()])
+    }
+}
+
+/// Encodes the changes of a record.
+fn record(changes: &[Change]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    put_u32(&mut payload, changes.len() as u32);
+    for change in changes {
+        match change {
+            Change::Put(entry) => {
+                payload.push(1);
+                put_entry(&mut payload, entry);
+            }
+            Change::Remove(id) => {
+                payload.push(2);
+                put_u64(&mut payload, *id);
+            }
+        }
+    }
+    payload
+}
+
+/// Decodes the changes of a record.
+fn read_record(payload: &[u8]) -> Option<Vec<Change>> {
+    let mut reader = Reader(payload);
+    let count = reader.u32()?;
+    let mut changes = Vec::new();
+    for _ in 0..count {
+        changes.push(match reader.u8()? {
+            1 => Change::Put(read_entry(&mut reader)?),
+            2 => Change::Remove(reader.u64()?),
+            _ => return None,
+        });
+    }
+    Some(changes)
+}
+
+fn put_entry(buf: &mut Vec<u8>, entry: &Entry) {
+    put_u64(buf, entry.id);
+    match &entry.task {
+        Task::Upload { path } => {
+            buf.push(1);
+            put_path(buf, path);
+        }
+        Task::Delete { path } => {
+            buf.push(2);
+            put_path(buf, path);
+        }
+        Task::Rename { from, to } => {
+            buf.push(3);
+            put_path(buf, from);
+            put_path(buf, to);
+        }
+    }
+    put_u32(buf, entry.attempts);
+    put_u64(buf, entry.next_attempt.to_raw());
+    buf.push(entry.last_error.is_some() as u8);
+    put_str(buf, entry.last_error.as_deref().unwrap_or_default());
+}
+
+fn read_entry(reader: &mut Reader) -> Option<Entry> {
+    let id = reader.u64()?;
+    let task = match reader.u8()? {
+        1 => Task::Upload {
+            path: reader.path()?,
+        },
+        2 => Task::Delete {
+            path: reader.path()?,
+        },
+        3 => Task::Rename {
+            from: reader.path()?,
+            to: reader.path()?,
+        },
+        _ => return None,
+    };
+    let attempts = reader.u32()?;
+    let next_attempt = FileTime::new(reader.u64()?);
+    let failed = reader.u8()? != 0;
+    let last_error = reader.str()?;
+
+    Some(Entry {
+        id,
+        task,
+        attempts,
+        next_attempt,
+        last_error: failed.then_some(last_error),
+    })
+}
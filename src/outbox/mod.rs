@@ -0,0 +1,639 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use nt_time::FileTime;
+
+use crate::{
+    error::{self, CResult, CloudError},
+    filter::{info, layer::SyncLayer, ticket, Request, SyncFilter},
+    store::RemoteStore,
+};
+
+use log::{Change, Log};
+
+mod log;
+
+/// The number of changes written to the log before it is considered for compaction.
+const COMPACTION_THRESHOLD: usize = 1024;
+/// The default delay before retrying a failed task for the first time.
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The default longest delay between two attempts of a failed task.
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// A change of the sync root waiting to be applied to the [RemoteStore].
+///
+/// Paths are relative to the sync root and to the root of the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Task {
+    /// Uploads the file, or creates the directory, with its contents at the time of the upload.
+    Upload { path: PathBuf },
+    /// Deletes the file or directory.
+    Delete { path: PathBuf },
+    /// Renames or moves the file or directory.
+    Rename { from: PathBuf, to: PathBuf },
+}
+
+impl Task {
+    /// The path the task applies to, the source path of the renames.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Upload { path } | Self::Delete { path } => path,
+            Self::Rename { from, .. } => from,
+        }
+    }
+
+    /// Whether or not the task involves the path, one of its ancestors or one of its
+    /// descendants.
+    fn touches(&self, path: &Path) -> bool {
+        let related = |other: &Path| other.starts_with(path) || path.starts_with(other);
+        match self {
+            Self::Upload { path } | Self::Delete { path } => related(path),
+            Self::Rename { from, to } => related(from) || related(to),
+        }
+    }
+}
+
+/// A [Task] queued in an [Outbox].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The identifier of the entry, unique within its outbox.
+    pub id: u64,
+    /// The task.
+    pub task: Task,
+    /// The number of failed attempts.
+    pub attempts: u32,
+    /// The time after which the task is attempted.
+    pub next_attempt: FileTime,
+    /// The error of the last failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// A summary of the queue of an [Outbox], e.g. to be shown in a status bar.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Status {
+    /// The number of queued tasks.
+    pub pending: usize,
+    /// The task being applied by [Outbox::flush].
+    pub in_progress: Option<Task>,
+    /// The time of the next attempt when the first task failed, i.e. while the queue is
+    /// stalled.
+    pub retry_at: Option<FileTime>,
+    /// The error of the last attempt of the first task.
+    pub last_error: Option<String>,
+}
+
+/// A persistent queue of the local changes to upload, delete and rename on a [RemoteStore].
+///
+/// As a [SyncLayer], the outbox acknowledges deletes and renames right away, so they succeed
+/// offline, and queues the changes reported by the `closed`, `deleted` and `renamed` callbacks:
+///
+/// - a placeholder closed with modified data (on Windows) or a new file is uploaded,
+/// - a deleted placeholder is deleted,
+/// - a renamed placeholder is renamed, deleted when moved out of the sync root, or uploaded
+///   along with its entries when moved into it.
+///
+/// The tasks are applied in order by [Outbox::flush]. When a task fails, the queue stalls until
+/// it is retried after an exponential backoff, or sooner through [Outbox::resume], e.g. once the
+/// connection is back.
+///
+/// Redundant tasks are coalesced as they are queued: a file uploaded several times is uploaded
+/// once, a file written then deleted is only deleted, the pending uploads of a renamed file are
+/// moved to its new path, and a file renamed several times is renamed once.
+///
+/// The queue is persisted to an append-only log, flushed to the disk before each change
+/// returns, so that it survives the restarts of the process.
+///
+/// ```no_run
+/// # use std::{thread, time::Duration};
+/// # use cloud_filter::{outbox::Outbox, store::DirectoryStore};
+/// let outbox = Outbox::open("outbox.log", r"C:\Users\me\Cloud")?;
+/// let store = DirectoryStore::new(r"\\server\share");
+///
+/// loop {
+///     if let Err(err) = outbox.flush(&store) {
+///         eprintln!("{err}, {} changes pending", outbox.status().pending);
+///     }
+///     thread::sleep(Duration::from_secs(1));
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Outbox {
+    root: PathBuf,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    queue: Mutex<Queue>,
+}
+
+impl Outbox {
+    /// Opens the outbox persisted at the path, creating it if it does not exist, for the sync
+    /// root.
+    pub fn open(path: impl AsRef<Path>, root: impl Into<PathBuf>) -> io::Result<Self> {
+        let (log, records) = Log::open(path.as_ref())?;
+        let mut queue = Queue {
+            log,
+            entries: Vec::new(),
+            next_id: 0,
+            in_progress: None,
+            written: 0,
+        };
+        for changes in records {
+            queue.written += changes.len();
+            queue.apply(changes);
+        }
+
+        Ok(Self {
+            root: root.into(),
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            queue: Mutex::new(queue),
+        })
+    }
+
+    /// Sets the delay before the first retry of a failed task, doubled after each attempt up to
+    /// the maximum, defaults to 1 second up to 5 minutes.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// The path of the sync root.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Queues the task, coalescing it with the queued tasks it makes redundant.
+    pub fn push(&self, task: Task) -> io::Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        let changes = queue.coalesce(task);
+        queue.commit(changes)
+    }
+
+    /// The queued entries, in the order they are applied.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.queue.lock().unwrap().entries.clone()
+    }
+
+    /// The [Status] of the queue.
+    pub fn status(&self) -> Status {
+        let queue = self.queue.lock().unwrap();
+        let first = queue.entries.first();
+        let failed = first.filter(|entry| entry.attempts > 0);
+        Status {
+            pending: queue.entries.len(),
+            in_progress: queue
+                .in_progress
+                .and_then(|id| Some(queue.get(id)?.task.clone())),
+            retry_at: failed.map(|entry| entry.next_attempt),
+            last_error: failed.and_then(|entry| entry.last_error.clone()),
+        }
+    }
+
+    /// The number of queued tasks.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().entries.len()
+    }
+
+    /// Whether or not the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().entries.is_empty()
+    }
+
+    /// Discards the entry, e.g. a task the user gave up on, returning `false` if it is not
+    /// queued or being applied.
+    pub fn remove(&self, id: u64) -> io::Result<bool> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.get(id).is_none() || queue.in_progress == Some(id) {
+            return Ok(false);
+        }
+
+        queue.commit(vec![Change::Remove(id)])?;
+        Ok(true)
+    }
+
+    /// Makes the failed tasks due immediately, e.g. once the connection is back.
+    pub fn resume(&self) -> io::Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        let now = FileTime::now();
+        let changes = queue
+            .entries
+            .iter()
+            .filter(|entry| entry.next_attempt > now)
+            .map(|entry| {
+                Change::Put(Entry {
+                    next_attempt: now,
+                    ..entry.clone()
+                })
+            })
+            .collect();
+        queue.commit(changes)
+    }
+
+    /// Applies the due tasks in order, returning the number of tasks applied.
+    ///
+    /// Stops at the first task that is not due, or that fails. A failed task is scheduled for
+    /// another attempt after the backoff, and its error is returned.
+    ///
+    /// The queue is not locked while the store is accessed, so that tasks can be queued in the
+    /// meantime.
+    pub fn flush(&self, store: &impl RemoteStore) -> io::Result<usize> {
+        let mut applied = 0;
+        loop {
+            let entry = {
+                let mut queue = self.queue.lock().unwrap();
+                match queue.entries.first() {
+                    Some(entry) if entry.next_attempt <= FileTime::now() => {
+                        let entry = entry.clone();
+                        queue.in_progress = Some(entry.id);
+                        entry
+                    }
+                    _ => return Ok(applied),
+                }
+            };
+
+            let result = self.execute(store, &entry.task);
+            let mut queue = self.queue.lock().unwrap();
+            queue.in_progress = None;
+            match result {
+                Ok(()) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(task = ?entry.task, "applied outbox task");
+                    queue.commit(vec![Change::Remove(entry.id)])?;
+                    applied += 1;
+                }
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(task = ?entry.task, attempts = entry.attempts + 1, %err, "outbox task failed");
+                    let delay = self
+                        .initial_backoff
+                        .saturating_mul(1 << entry.attempts.min(31))
+                        .min(self.max_backoff);
+                    queue.commit(vec![Change::Put(Entry {
+                        attempts: entry.attempts + 1,
+                        next_attempt: FileTime::now().saturating_add(delay),
+                        last_error: Some(err.to_string()),
+                        ..entry
+                    })])?;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Applies the task to the store.
+    ///
+    /// A file that no longer exists locally is not uploaded, the task removing it being queued
+    /// after this one. A path that no longer exists on the remote is considered deleted, and a
+    /// rename whose source does not exist on the remote uploads its target instead, queuing the
+    /// uploads of its entries if it is a directory.
+    fn execute(&self, store: &impl RemoteStore, task: &Task) -> io::Result<()> {
+        match task {
+            Task::Upload { path } => self.upload(store, path),
+            Task::Delete { path } => match store.delete(path) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            },
+            Task::Rename { from, to } => match store.rename(from, to) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    self.upload(store, to)?;
+                    self.push_entries(to)
+                }
+                result => result,
+            },
+        }
+    }
+
+    fn upload(&self, store: &impl RemoteStore, path: &Path) -> io::Result<()> {
+        let absolute = self.root.join(path);
+        let metadata = match fs::metadata(&absolute) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let result = match metadata.is_dir() {
+            true => store.create_dir(path),
+            false => store.write(path, &mut File::open(&absolute)?),
+        };
+        match result {
+            Err(err) if metadata.is_dir() && err.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Queues the uploads of the entries below the path if it is a directory, parents first,
+    /// e.g. of a directory moved into the sync root whose entries are never reported on their
+    /// own.
+    fn push_entries(&self, path: &Path) -> io::Result<()> {
+        let absolute = self.root.join(path);
+        if !fs::symlink_metadata(&absolute).is_ok_and(|metadata| metadata.is_dir()) {
+            return Ok(());
+        }
+
+        let mut names = fs::read_dir(&absolute)?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        names.sort();
+        for name in names {
+            let path = path.join(name);
+            self.push(Task::Upload { path: path.clone() })?;
+            self.push_entries(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Queues the task, reporting the errors since callbacks cannot return them.
+    fn queue(&self, task: Task) {
+        if let Err(err) = self.push(task.clone()) {
+            error::report(&CloudError::from(err).context(format!("failed to queue {task:?}")));
+        }
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.root).ok()
+    }
+
+    /// Whether or not the file has local changes to upload.
+    #[cfg(windows)]
+    fn modified(&self, path: &Path) -> bool {
+        use crate::placeholder::Placeholder;
+
+        let info = Placeholder::open(path).and_then(|placeholder| placeholder.info());
+        match info {
+            Ok(info) => {
+                info.is_none_or(|info| !info.is_in_sync() || info.modified_data_size() != 0)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Whether or not the file has local changes to upload, only known on Windows.
+    #[cfg(not(windows))]
+    fn modified(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+impl<F: SyncFilter> SyncLayer<F> for Outbox {
+    fn closed(&self, next: &F, request: Request, info: info::Closed) {
+        let (path, deleted) = (request.path(), info.deleted());
+        next.closed(request, info);
+
+        if let Some(relative) = self.relative(&path).filter(|_| !deleted) {
+            if self.modified(&path) {
+                self.queue(Task::Upload {
+                    path: relative.to_owned(),
+                });
+            }
+        }
+    }
+
+    fn delete(
+        &self,
+        _next: &F,
+        _request: Request,
+        ticket: ticket::Delete,
+        _info: info::Delete,
+    ) -> CResult<()> {
+        Ok(ticket.pass()?)
+    }
+
+    fn deleted(&self, next: &F, request: Request, info: info::Deleted) {
+        let path = request.path();
+        next.deleted(request, info);
+
+        if let Some(path) = self.relative(&path) {
+            self.queue(Task::Delete {
+                path: path.to_owned(),
+            });
+        }
+    }
+
+    fn rename(
+        &self,
+        _next: &F,
+        _request: Request,
+        ticket: ticket::Rename,
+        _info: info::Rename,
+    ) -> CResult<()> {
+        Ok(ticket.pass()?)
+    }
+
+    fn renamed(&self, next: &F, request: Request, info: info::Renamed) {
+        let (to, from) = (request.path(), info.source_path());
+        next.renamed(request, info);
+
+        match (self.relative(&from), self.relative(&to)) {
+            (Some(from), Some(to)) => self.queue(Task::Rename {
+                from: from.to_owned(),
+                to: to.to_owned(),
+            }),
+            (Some(from), None) => self.queue(Task::Delete {
+                path: from.to_owned(),
+            }),
+            (None, Some(to)) => {
+                self.queue(Task::Upload {
+                    path: to.to_owned(),
+                });
+                if let Err(err) = self.push_entries(to) {
+                    error::report(
+                        &CloudError::from(err)
+                            .context(format!("failed to queue the entries of {to:?}")),
+                    );
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// The entries of an [Outbox] along with their log.
+#[derive(Debug)]
+struct Queue {
+    log: Log,
+    entries: Vec<Entry>,
+    next_id: u64,
+    /// The entry being applied by [Outbox::flush], left out of the coalescing.
+    in_progress: Option<u64>,
+    written: usize,
+}
+
+impl Queue {
+    fn get(&self, id: u64) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// The changes queuing the task and removing or rewriting the entries it makes redundant.
+    fn coalesce(&mut self, task: Task) -> Vec<Change> {
+        let mut entries = self.entries.clone();
+        let mut changes = Vec::new();
+        let mut remove = |entries: &mut Vec<Entry>, index: usize| {
+            changes.push(Change::Remove(entries.remove(index).id));
+        };
+
+        let mut tasks = Vec::new();
+        match task {
+            Task::Upload { ref path } => {
+                // the queued upload reads the latest contents
+                if let Some(entry) = last_touching(&entries, path).map(|index| &entries[index]) {
+                    if matches!(&entry.task, Task::Upload { path: queued } if queued == path)
+                        && Some(entry.id) != self.in_progress
+                    {
+                        return Vec::new();
+                    }
+                }
+                tasks.push(task);
+            }
+            Task::Delete { ref path } => {
+                // the uploads and deletes below the path are superseded, unless a later rename
+                // involves them
+                let mut index = 0;
+                while index < entries.len() {
+                    let entry = &entries[index];
+                    let superseded = matches!(
+                        &entry.task,
+                        Task::Upload { path: queued } | Task::Delete { path: queued }
+                            if queued.starts_with(path)
+                                && Some(entry.id) != self.in_progress
+                                && !entries[index + 1..].iter().any(|later| {
+                                    matches!(later.task, Task::Rename { .. })
+                                        && later.task.touches(queued)
+                                })
+                    );
+                    match superseded {
+                        true => remove(&mut entries, index),
+                        false => index += 1,
+                    }
+                }
+                tasks.push(task);
+            }
+            Task::Rename { ref from, ref to } if from == to => {}
+            Task::Rename { from, to } => {
+                // the pending uploads follow the file to its new path
+                let mut uploads = Vec::new();
+                let mut index = 0;
+                while index < entries.len() {
+                    let entry = &entries[index];
+                    let follows = matches!(
+                        &entry.task,
+                        Task::Upload { path } if path.starts_with(&from)
+                            && Some(entry.id) != self.in_progress
+                            && !entries[index + 1..].iter().any(|later| later.task.touches(path))
+                    );
+                    match follows {
+                        true => {
+                            uploads.push(entries[index].task.path().to_owned());
+                            remove(&mut entries, index);
+                        }
+                        false => index += 1,
+                    }
+                }
+
+                // a file renamed again keeps its first source, unless the source was reused
+                let mut source = from.clone();
+                if let Some(index) = last_touching(&entries, &from) {
+                    let entry = &entries[index];
+                    if let Task::Rename { from: first, to } = &entry.task {
+                        if *to == from
+                            && Some(entry.id) != self.in_progress
+                            && !entries[index + 1..]
+                                .iter()
+                                .any(|later| later.task.touches(first))
+                        {
+                            source = first.clone();
+                            remove(&mut entries, index);
+                        }
+                    }
+                }
+
+                if source != to {
+                    tasks.push(Task::Rename {
+                        from: source,
+                        to: to.clone(),
+                    });
+                }
+                tasks.extend(uploads.into_iter().map(|path| Task::Upload {
+                    path: rebase(&path, &from, &to),
+                }));
+            }
+        }
+
+        let now = FileTime::now();
+        for (id, task) in (self.next_id..).zip(tasks) {
+            changes.push(Change::Put(Entry {
+                id,
+                task,
+                attempts: 0,
+                next_attempt: now,
+                last_error: None,
+            }));
+        }
+        changes
+    }
+
+    /// Applies the changes, durably.
+    fn commit(&mut self, changes: Vec<Change>) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.log.append(&changes)?;
+        self.written += changes.len();
+        self.apply(changes);
+
+        if self.written > COMPACTION_THRESHOLD && self.written > 2 * self.entries.len() {
+            if let Err(err) = self.compact() {
+                error::report(&CloudError::from(err).context("failed to compact the outbox"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log with the current entries only.
+    fn compact(&mut self) -> io::Result<()> {
+        let changes = self
+            .entries
+            .iter()
+            .cloned()
+            .map(Change::Put)
+            .collect::<Vec<_>>();
+        self.log.rewrite(&changes)?;
+        self.written = changes.len();
+        Ok(())
+    }
+
+    fn apply(&mut self, changes: Vec<Change>) {
+        for change in changes {
+            match change {
+                Change::Put(entry) => {
+                    self.next_id = self.next_id.max(entry.id + 1);
+                    match self.entries.iter_mut().find(|queued| queued.id == entry.id) {
+                        Some(queued) => *queued = entry,
+                        None => self.entries.push(entry),
+                    }
+                }
+                Change::Remove(id) => self.entries.retain(|entry| entry.id != id),
+            }
+        }
+    }
+}
+
+/// The index of the last entry involving the path.
+fn last_touching(entries: &[Entry], path: &Path) -> Option<usize> {
+    entries.iter().rposition(|entry| entry.task.touches(path))
+}
+
+/// Replaces the prefix of the path.
+fn rebase(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(rest) if rest.as_os_str().is_empty() => to.to_owned(),
+        Ok(rest) => to.join(rest),
+        Err(_) => path.to_owned(),
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     fmt::Debug,
     fs::File,
     mem::{self, MaybeUninit},
@@ -24,10 +25,20 @@ use windows::{
             CF_PLACEHOLDER_RANGE_INFO_CLASS, CF_PLACEHOLDER_STANDARD_INFO, CF_SET_PIN_FLAGS,
             CF_UPDATE_FLAGS,
         },
+        System::{
+            Ioctl::{FSCTL_READ_FILE_USN_DATA, USN_RECORD_V2},
+            IO::DeviceIoControl,
+        },
     },
 };
 
-use crate::{metadata::Metadata, usn::Usn};
+use crate::{
+    blob::{self, FileIdentity},
+    error::CResult,
+    metadata::Metadata,
+    placeholder_file::MAX_BLOB_LENGTH,
+    usn::Usn,
+};
 
 /// The type of handle that the placeholder file/directory owns.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -335,6 +346,19 @@ impl ConvertOptions {
         self.blob = blob;
         self
     }
+
+    /// Stores the [FileIdentity] as the blob of the placeholder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoded blob exceeds [4KiB][MAX_BLOB_LENGTH], use [blob::encode] along
+    /// with [ConvertOptions::blob] to handle the error instead.
+    pub fn identity<T: FileIdentity>(self, identity: &T) -> Self {
+        match blob::encode(identity) {
+            Ok(blob) => self.blob(blob),
+            Err(_) => panic!("encoded blob size must not exceed {MAX_BLOB_LENGTH} bytes"),
+        }
+    }
 }
 
 impl Default for ConvertOptions {
@@ -388,6 +412,14 @@ impl PlaceholderInfo {
     pub fn blob(&self) -> &[u8] {
         &self.data[mem::size_of::<CF_PLACEHOLDER_STANDARD_INFO>()..]
     }
+
+    /// Decodes the [FileIdentity] stored in the [blob][PlaceholderInfo::blob].
+    ///
+    /// Returns [CloudErrorKind::MetadataCorrupt][crate::error::CloudErrorKind::MetadataCorrupt] if
+    /// the blob is malformed, see [blob::decode].
+    pub fn identity<T: FileIdentity>(&self) -> CResult<T> {
+        blob::decode(self.blob())
+    }
 }
 
 impl std::fmt::Debug for PlaceholderInfo {
@@ -411,7 +443,7 @@ pub struct UpdateOptions<'a> {
     metadata: Option<Metadata>,
     dehydrate_ranges: Vec<CF_FILE_RANGE>,
     flags: CF_UPDATE_FLAGS,
-    blob: &'a [u8],
+    blob: Cow<'a, [u8]>,
 }
 
 impl<'a> UpdateOptions<'a> {
@@ -509,7 +541,21 @@ impl<'a> UpdateOptions<'a> {
             CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH,
             blob.len()
         );
-        self.blob = blob;
+        self.blob = Cow::Borrowed(blob);
+        self
+    }
+
+    /// Replaces the blob of the placeholder with the [FileIdentity].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoded blob exceeds [4KiB][MAX_BLOB_LENGTH], use [blob::encode] along
+    /// with [UpdateOptions::blob] to handle the error instead.
+    pub fn identity<T: FileIdentity>(mut self, identity: &T) -> Self {
+        match blob::encode(identity) {
+            Ok(blob) => self.blob = Cow::Owned(blob),
+            Err(_) => panic!("encoded blob size must not exceed {MAX_BLOB_LENGTH} bytes"),
+        }
         self
     }
 }
@@ -520,7 +566,7 @@ impl Default for UpdateOptions<'_> {
             metadata: None,
             dehydrate_ranges: Vec::new(),
             flags: CloudFilters::CF_UPDATE_FLAG_NONE,
-            blob: &[],
+            blob: Cow::Borrowed(&[]),
         }
     }
 }
@@ -762,10 +808,11 @@ impl Placeholder {
         options: UpdateOptions,
         usn: impl Into<Option<&'a mut Usn>>,
     ) -> core::Result<&mut Self> {
+        let metadata = options.metadata.map(Metadata::to_raw);
         unsafe {
             CfUpdatePlaceholder(
                 self.handle.handle,
-                options.metadata.map(|x| &x.0 as *const _),
+                metadata.as_ref().map(|x| x as *const _),
                 (!options.blob.is_empty()).then_some(options.blob.as_ptr() as *const _),
                 options.blob.len() as _,
                 (options.dehydrate_ranges.is_empty()).then_some(&options.dehydrate_ranges),
@@ -849,6 +896,36 @@ impl Placeholder {
         })
     }
 
+    /// Reads the current [Usn] of the placeholder, e.g. to guard the changes made by
+    /// [Placeholder::update] or [Placeholder::mark_in_sync].
+    ///
+    /// See also [FSCTL_READ_FILE_USN_DATA](https://learn.microsoft.com/en-us/windows/win32/api/winioctl/ni-winioctl-fsctl_read_file_usn_data).
+    pub fn usn(&self) -> core::Result<Usn> {
+        let read = |handle: HANDLE| {
+            // the record is followed by the name of the file, up to 255 UTF-16 characters
+            let mut record = [0u64; (mem::size_of::<USN_RECORD_V2>() + 512).div_ceil(8)];
+            unsafe {
+                DeviceIoControl(
+                    handle,
+                    FSCTL_READ_FILE_USN_DATA,
+                    None,
+                    0,
+                    Some(record.as_mut_ptr() as *mut _),
+                    mem::size_of_val(&record) as _,
+                    None,
+                    None,
+                )
+            }?;
+
+            Ok(unsafe { &*(record.as_ptr() as *const USN_RECORD_V2) }.Usn)
+        };
+
+        match self.handle.handle_type {
+            PlaceholderHandleType::CfApi => read(self.win32_handle()?.handle()),
+            PlaceholderHandleType::Win32 => read(self.handle.handle),
+        }
+    }
+
     /// Returns the owned placeholder handle.
     pub fn inner_handle(&self) -> &OwnedPlaceholderHandle {
         &self.handle
@@ -1,33 +1,58 @@
-use std::{path::Path, ptr, slice};
+use std::path::{Path, PathBuf};
 
+#[cfg(windows)]
 use widestring::U16CString;
+#[cfg(windows)]
 use windows::{
-    core::{self, PCWSTR},
-    Win32::{
-        Foundation,
-        Storage::CloudFilters::{self, CfCreatePlaceholders, CF_PLACEHOLDER_CREATE_INFO},
+    core::PCWSTR,
+    Win32::Storage::CloudFilters::{
+        self, CfCreatePlaceholders, CF_PLACEHOLDER_CREATE_FLAGS, CF_PLACEHOLDER_CREATE_INFO,
     },
 };
+use windows_core::{self as core, HRESULT};
 
-use crate::{metadata::Metadata, sealed, usn::Usn};
+use crate::{
+    blob::{self, FileIdentity},
+    metadata::Metadata,
+    sealed,
+    usn::Usn,
+};
+
+/// The maximum size of a placeholder's file blob in bytes.
+///
+/// This is the value of
+/// [CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Storage/CloudFilters/constant.CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH.html).
+pub const MAX_BLOB_LENGTH: usize = 4096;
 
 /// A builder for creating new placeholder files/directories.
-#[derive(Debug)]
-pub struct PlaceholderFile(CF_PLACEHOLDER_CREATE_INFO);
+#[derive(Debug, Clone)]
+pub struct PlaceholderFile {
+    pub(crate) relative_path: PathBuf,
+    pub(crate) metadata: Metadata,
+    pub(crate) blob: Vec<u8>,
+    pub(crate) has_no_children: bool,
+    pub(crate) mark_in_sync: bool,
+    pub(crate) overwrite: bool,
+    pub(crate) block_dehydration: bool,
+    pub(crate) result: HRESULT,
+    pub(crate) usn: Usn,
+}
 
 impl PlaceholderFile {
     /// Creates a new [PlaceholderFile].
     pub fn new(relative_path: impl AsRef<Path>) -> Self {
-        Self(CF_PLACEHOLDER_CREATE_INFO {
-            RelativeFileName: PCWSTR(
-                U16CString::from_os_str(relative_path.as_ref())
-                    .unwrap()
-                    .into_raw(),
-            ),
-            Flags: CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_NONE,
-            Result: Foundation::S_FALSE,
-            ..Default::default()
-        })
+        Self {
+            relative_path: relative_path.as_ref().to_path_buf(),
+            metadata: Metadata::default(),
+            blob: Vec::new(),
+            has_no_children: false,
+            mark_in_sync: false,
+            overwrite: false,
+            block_dehydration: false,
+            // S_FALSE, the placeholder has not been created yet
+            result: HRESULT(1),
+            usn: 0,
+        }
     }
 
     /// Marks this [PlaceholderFile] as having no child placeholders on
@@ -35,7 +60,7 @@ impl PlaceholderFile {
     ///
     /// Only applicable to placeholder directories.
     pub fn has_no_children(mut self) -> Self {
-        self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_DISABLE_ON_DEMAND_POPULATION;
+        self.has_no_children = true;
         self
     }
 
@@ -45,13 +70,13 @@ impl PlaceholderFile {
     /// [SetInSyncState](https://learn.microsoft.com/en-us/windows/win32/api/cfapi/nf-cfapi-cfsetinsyncstate),
     /// [What does "In-Sync" Mean?](https://www.userfilesystem.com/programming/faq/#nav_whatdoesin-syncmean)
     pub fn mark_in_sync(mut self) -> Self {
-        self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_MARK_IN_SYNC;
+        self.mark_in_sync = true;
         self
     }
 
     /// Whether or not to overwrite an existing placeholder.
     pub fn overwrite(mut self) -> Self {
-        self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE;
+        self.overwrite = true;
         self
     }
 
@@ -59,13 +84,13 @@ impl PlaceholderFile {
     ///
     /// This flag does not work on directories.
     pub fn block_dehydration(mut self) -> Self {
-        self.0.Flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_ALWAYS_FULL;
+        self.block_dehydration = true;
         self
     }
 
     /// The metadata for the [PlaceholderFile].
     pub fn metadata(mut self, metadata: Metadata) -> Self {
-        self.0.FsMetadata = metadata.0;
+        self.metadata = metadata;
         self
     }
 
@@ -73,31 +98,41 @@ impl PlaceholderFile {
     /// [Request::file_blob][crate::filter::Request::file_blob] or
     /// [Placeholder::info][crate::placeholder::Placeholder::info].
     ///
-    /// The buffer must not exceed
-    /// [4KiB](https://microsoft.github.io/windows-docs-rs/doc/windows/Win32/Storage/CloudFilters/constant.CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH.html).
+    /// The buffer must not exceed [4KiB][MAX_BLOB_LENGTH].
     pub fn blob(mut self, blob: Vec<u8>) -> Self {
         assert!(
-            blob.len() <= CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH as usize,
+            blob.len() <= MAX_BLOB_LENGTH,
             "blob size must not exceed {} bytes, got {} bytes",
-            CloudFilters::CF_PLACEHOLDER_MAX_FILE_IDENTITY_LENGTH,
+            MAX_BLOB_LENGTH,
             blob.len()
         );
 
-        if blob.is_empty() {
-            self.0.FileIdentity = ptr::null();
-            self.0.FileIdentityLength = 0;
-            return self;
-        }
+        self.blob = blob;
+        self
+    }
 
-        let leaked_blob = Box::leak(blob.into_boxed_slice());
-        self.0.FileIdentity = leaked_blob.as_ptr() as *const _;
-        self.0.FileIdentityLength = leaked_blob.len() as _;
+    /// Stores the [FileIdentity] as the blob of the placeholder, so that it could be decoded
+    /// through [Request::file_identity][crate::filter::Request::file_identity].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the encoded blob exceeds [4KiB][MAX_BLOB_LENGTH], use [blob::encode] along
+    /// with [PlaceholderFile::blob] to handle the error instead.
+    pub fn identity<T: FileIdentity>(self, identity: &T) -> Self {
+        match blob::encode(identity) {
+            Ok(blob) => self.blob(blob),
+            Err(_) => panic!("encoded blob size must not exceed {MAX_BLOB_LENGTH} bytes"),
+        }
+    }
 
-        self
+    /// The path of the placeholder relative to its parent directory.
+    pub fn relative_path(&self) -> &Path {
+        &self.relative_path
     }
 
+    /// The result of creating the placeholder, along with its final [Usn].
     pub fn result(&self) -> core::Result<Usn> {
-        self.0.Result.ok().map(|_| self.0.CreateUsn as _)
+        self.result.ok().map(|_| self.usn)
     }
 
     /// Creates a placeholder file/directory on the file system.
@@ -112,34 +147,78 @@ impl PlaceholderFile {
     /// [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] callback,
     /// do not use this method. Instead, use
     /// [FetchPlaceholders::pass_with_placeholder][crate::filter::ticket::FetchPlaceholders::pass_with_placeholder].
-    pub fn create<P: AsRef<Path>>(self, parent: impl AsRef<Path>) -> core::Result<Usn> {
-        unsafe {
-            CfCreatePlaceholders(
-                PCWSTR(U16CString::from_os_str(parent.as_ref()).unwrap().as_ptr()),
-                &mut [self.0],
-                CloudFilters::CF_CREATE_FLAG_NONE,
-                None,
-            )?;
+    #[cfg(windows)]
+    pub fn create(mut self, parent: impl AsRef<Path>) -> core::Result<Usn> {
+        std::slice::from_mut(&mut self).create(parent)?;
+        self.result()
+    }
+}
+
+/// The raw Cloud Filter representation of a list of [PlaceholderFile]s.
+///
+/// The relative paths are kept alive for as long as the raw structs are in use.
+#[cfg(windows)]
+pub(crate) struct RawPlaceholders {
+    _names: Vec<U16CString>,
+    pub(crate) infos: Vec<CF_PLACEHOLDER_CREATE_INFO>,
+}
+
+#[cfg(windows)]
+impl RawPlaceholders {
+    pub(crate) fn new(placeholders: &[PlaceholderFile]) -> Self {
+        let names = placeholders
+            .iter()
+            .map(|placeholder| U16CString::from_os_str(&placeholder.relative_path).unwrap())
+            .collect::<Vec<_>>();
+        let infos = placeholders
+            .iter()
+            .zip(&names)
+            .map(|(placeholder, name)| CF_PLACEHOLDER_CREATE_INFO {
+                RelativeFileName: PCWSTR(name.as_ptr()),
+                FsMetadata: placeholder.metadata.to_raw(),
+                FileIdentity: match placeholder.blob.is_empty() {
+                    true => std::ptr::null(),
+                    false => placeholder.blob.as_ptr() as *const _,
+                },
+                FileIdentityLength: placeholder.blob.len() as _,
+                Flags: placeholder.flags(),
+                Result: placeholder.result,
+                CreateUsn: placeholder.usn,
+            })
+            .collect();
+
+        Self {
+            _names: names,
+            infos,
         }
+    }
 
-        self.result()
+    /// Copies the results of the operation back to the [PlaceholderFile]s.
+    pub(crate) fn write_back(&self, placeholders: &mut [PlaceholderFile]) {
+        for (placeholder, info) in placeholders.iter_mut().zip(&self.infos) {
+            placeholder.result = info.Result;
+            placeholder.usn = info.CreateUsn;
+        }
     }
 }
 
-impl Drop for PlaceholderFile {
-    fn drop(&mut self) {
-        // Safety: `self.0.RelativeFileName.0` is a valid pointer to a valid UTF-16 string
-        drop(unsafe { U16CString::from_ptr_str(self.0.RelativeFileName.0) });
-
-        if !self.0.FileIdentity.is_null() {
-            // Safety: `self.0.FileIdentity` is a valid pointer to a valid slice
-            drop(unsafe {
-                Box::from_raw(slice::from_raw_parts_mut(
-                    self.0.FileIdentity as *mut u8,
-                    self.0.FileIdentityLength as _,
-                ))
-            });
+#[cfg(windows)]
+impl PlaceholderFile {
+    fn flags(&self) -> CF_PLACEHOLDER_CREATE_FLAGS {
+        let mut flags = CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_NONE;
+        if self.has_no_children {
+            flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_DISABLE_ON_DEMAND_POPULATION;
+        }
+        if self.mark_in_sync {
+            flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_MARK_IN_SYNC;
+        }
+        if self.overwrite {
+            flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_SUPERSEDE;
         }
+        if self.block_dehydration {
+            flags |= CloudFilters::CF_PLACEHOLDER_CREATE_FLAG_ALWAYS_FULL;
+        }
+        flags
     }
 }
 
@@ -148,16 +227,21 @@ pub trait BatchCreate: sealed::Sealed {
     fn create<P: AsRef<Path>>(&mut self, path: P) -> core::Result<()>;
 }
 
+#[cfg(windows)]
 impl BatchCreate for [PlaceholderFile] {
     fn create<P: AsRef<Path>>(&mut self, path: P) -> core::Result<()> {
-        unsafe {
+        let mut raw = RawPlaceholders::new(self);
+        let result = unsafe {
             CfCreatePlaceholders(
                 PCWSTR(U16CString::from_os_str(path.as_ref()).unwrap().as_ptr()),
-                slice::from_raw_parts_mut(self.as_mut_ptr() as *mut _, self.len()),
+                &mut raw.infos,
                 CloudFilters::CF_CREATE_FLAG_NONE,
                 None,
             )
-        }
+        };
+        raw.write_back(self);
+
+        result
     }
 }
 
@@ -0,0 +1,514 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::CloudErrorKind,
+    filter::{info, info::DehydrationReason, Operation, Process, Request},
+};
+
+/// A callback recorded by the [Recorder][super::Recorder], serialized as one JSON line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// The microseconds elapsed since the start of the recording when the callback was called.
+    pub at: u64,
+    /// The microseconds the callback took.
+    pub duration: u64,
+    /// The request of the callback, [None] for
+    /// [SyncFilter::state_changed][crate::filter::SyncFilter::state_changed].
+    pub request: Option<RequestRecord>,
+    /// The callback and its info.
+    #[serde(flatten)]
+    pub callback: Callback,
+    /// The operations the filter issued through the ticket, in order.
+    pub responses: Vec<Response>,
+    /// The error the callback failed with.
+    pub error: Option<CloudErrorKind>,
+}
+
+impl Event {
+    /// Whether or not the filter responded the same way to both events, ignoring the timings.
+    pub fn same_outcome(&self, other: &Event) -> bool {
+        self.responses == other.responses && self.error == other.error
+    }
+}
+
+/// The fields of a [Request].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestRecord {
+    /// See [Request::volume_guid_path].
+    pub volume_guid_path: String,
+    /// See [Request::volume_letter].
+    pub volume_letter: String,
+    /// See [Request::volume_serial_number].
+    pub volume_serial_number: u32,
+    /// See [Request::process].
+    pub process: ProcessRecord,
+    /// See [Request::sync_root_file_id].
+    pub sync_root_file_id: i64,
+    /// See [Request::file_id].
+    pub file_id: i64,
+    /// See [Request::file_size].
+    pub file_size: u64,
+    /// See [Request::path].
+    pub path: String,
+    /// See [Request::priority_hint].
+    pub priority_hint: u8,
+    /// See [Request::file_blob].
+    pub file_blob: Vec<u8>,
+    /// See [Request::register_blob].
+    pub register_blob: Vec<u8>,
+}
+
+impl RequestRecord {
+    /// Constructs an owned [Request] with the fields, mapping its path.
+    pub(crate) fn to_request(&self, map_path: impl Fn(&str) -> PathBuf) -> Request {
+        let process = &self.process;
+        let mut builder = Process::builder()
+            .name(&process.name)
+            .id(process.id)
+            .session_id(process.session_id)
+            .application_id(&process.application_id);
+        if let Some(command_line) = &process.command_line {
+            builder = builder.command_line(command_line);
+        }
+        if let Some(path) = &process.path {
+            builder = builder.path(path);
+        }
+
+        Request::builder()
+            .volume_guid_path(&self.volume_guid_path)
+            .volume_letter(&self.volume_letter)
+            .volume_serial_number(self.volume_serial_number)
+            .process(builder.build())
+            .sync_root_file_id(self.sync_root_file_id)
+            .file_id(self.file_id)
+            .file_size(self.file_size)
+            .path(map_path(&self.path))
+            .priority_hint(self.priority_hint)
+            .file_blob(self.file_blob.clone())
+            .register_blob(self.register_blob.clone())
+            .build()
+    }
+}
+
+impl From<&Request> for RequestRecord {
+    fn from(request: &Request) -> Self {
+        let process = &request.process;
+        Self {
+            volume_guid_path: request.volume_guid_path.to_string_lossy().into_owned(),
+            volume_letter: request.volume_letter.to_string_lossy().into_owned(),
+            volume_serial_number: request.volume_serial_number,
+            process: ProcessRecord {
+                name: process.name.to_string_lossy().into_owned(),
+                id: process.id,
+                session_id: process.session_id,
+                application_id: process.application_id.to_string_lossy().into_owned(),
+                command_line: process
+                    .command_line
+                    .as_ref()
+                    .map(|command_line| command_line.to_string_lossy().into_owned()),
+                path: process.path.as_deref().map(lossy),
+            },
+            sync_root_file_id: request.sync_root_file_id,
+            file_id: request.file_id,
+            file_size: request.file_size,
+            path: lossy(&request.path),
+            priority_hint: request.priority_hint,
+            file_blob: request.file_blob.clone(),
+            register_blob: request.register_blob.clone(),
+        }
+    }
+}
+
+/// The fields of a [Process].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessRecord {
+    /// See [Process::name].
+    pub name: String,
+    /// See [Process::id].
+    pub id: u32,
+    /// See [Process::session_id].
+    pub session_id: u32,
+    /// See [Process::application_id].
+    pub application_id: String,
+    /// See [Process::command_line].
+    pub command_line: Option<String>,
+    /// See [Process::path].
+    pub path: Option<String>,
+}
+
+/// A callback with the fields of its info, tagged by the name of the callback.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "callback", rename_all = "snake_case")]
+pub enum Callback {
+    /// See [info::FetchData].
+    FetchData {
+        interrupted_hydration: bool,
+        explicit_hydration: bool,
+        required_file_range: Range<u64>,
+        optional_file_range: Range<u64>,
+        /// The raw [FileTime] of the last dehydration.
+        last_dehydration_time: u64,
+        last_dehydration_reason: Option<DehydrationReason>,
+    },
+    /// See [info::CancelFetchData].
+    CancelFetchData {
+        timeout: bool,
+        user_cancelled: bool,
+        file_range: Range<u64>,
+    },
+    /// See [info::ValidateData].
+    ValidateData {
+        explicit_hydration: bool,
+        file_range: Range<u64>,
+    },
+    /// See [info::FetchPlaceholders].
+    FetchPlaceholders { pattern: String },
+    /// See [info::CancelFetchPlaceholders].
+    CancelFetchPlaceholders { timeout: bool, user_cancelled: bool },
+    /// See [info::Opened].
+    Opened {
+        metadata_corrupt: bool,
+        metadata_unsupported: bool,
+    },
+    /// See [info::Closed].
+    Closed { deleted: bool },
+    /// See [info::Dehydrate].
+    Dehydrate {
+        background: bool,
+        reason: Option<DehydrationReason>,
+    },
+    /// See [info::Dehydrated].
+    Dehydrated {
+        background: bool,
+        already_hydrated: bool,
+        reason: Option<DehydrationReason>,
+    },
+    /// See [info::Delete].
+    Delete {
+        is_directory: bool,
+        is_undelete: bool,
+    },
+    /// See [info::Deleted].
+    Deleted,
+    /// See [info::Rename].
+    Rename {
+        is_directory: bool,
+        source_in_scope: bool,
+        target_in_scope: bool,
+        target_path: String,
+    },
+    /// See [info::Renamed].
+    Renamed { source_path: String },
+    /// See [SyncFilter::state_changed][crate::filter::SyncFilter::state_changed].
+    StateChanged { changes: Vec<String> },
+}
+
+impl From<&info::FetchData> for Callback {
+    fn from(info: &info::FetchData) -> Self {
+        Self::FetchData {
+            interrupted_hydration: info.interrupted_hydration,
+            explicit_hydration: info.explicit_hydration,
+            required_file_range: info.required_file_range.clone(),
+            optional_file_range: info.optional_file_range.clone(),
+            last_dehydration_time: info.last_dehydration_time.to_raw(),
+            last_dehydration_reason: info.last_dehydration_reason,
+        }
+    }
+}
+
+impl From<&info::CancelFetchData> for Callback {
+    fn from(info: &info::CancelFetchData) -> Self {
+        Self::CancelFetchData {
+            timeout: info.timeout,
+            user_cancelled: info.user_cancelled,
+            file_range: info.file_range.clone(),
+        }
+    }
+}
+
+impl From<&info::ValidateData> for Callback {
+    fn from(info: &info::ValidateData) -> Self {
+        Self::ValidateData {
+            explicit_hydration: info.explicit_hydration,
+            file_range: info.file_range.clone(),
+        }
+    }
+}
+
+impl From<&info::FetchPlaceholders> for Callback {
+    fn from(info: &info::FetchPlaceholders) -> Self {
+        Self::FetchPlaceholders {
+            pattern: info.pattern.clone(),
+        }
+    }
+}
+
+impl From<&info::CancelFetchPlaceholders> for Callback {
+    fn from(info: &info::CancelFetchPlaceholders) -> Self {
+        Self::CancelFetchPlaceholders {
+            timeout: info.timeout,
+            user_cancelled: info.user_cancelled,
+        }
+    }
+}
+
+impl From<&info::Opened> for Callback {
+    fn from(info: &info::Opened) -> Self {
+        Self::Opened {
+            metadata_corrupt: info.metadata_corrupt,
+            metadata_unsupported: info.metadata_unsupported,
+        }
+    }
+}
+
+impl From<&info::Closed> for Callback {
+    fn from(info: &info::Closed) -> Self {
+        Self::Closed {
+            deleted: info.deleted,
+        }
+    }
+}
+
+impl From<&info::Dehydrate> for Callback {
+    fn from(info: &info::Dehydrate) -> Self {
+        Self::Dehydrate {
+            background: info.background,
+            reason: info.reason,
+        }
+    }
+}
+
+impl From<&info::Dehydrated> for Callback {
+    fn from(info: &info::Dehydrated) -> Self {
+        Self::Dehydrated {
+            background: info.background,
+            already_hydrated: info.already_hydrated,
+            reason: info.reason,
+        }
+    }
+}
+
+impl From<&info::Delete> for Callback {
+    fn from(info: &info::Delete) -> Self {
+        Self::Delete {
+            is_directory: info.is_directory,
+            is_undelete: info.is_undelete,
+        }
+    }
+}
+
+impl From<&info::Deleted> for Callback {
+    fn from(_info: &info::Deleted) -> Self {
+        Self::Deleted
+    }
+}
+
+impl From<&info::Rename> for Callback {
+    fn from(info: &info::Rename) -> Self {
+        Self::Rename {
+            is_directory: info.is_directory,
+            source_in_scope: info.source_in_scope,
+            target_in_scope: info.target_in_scope,
+            target_path: lossy(&info.target_path),
+        }
+    }
+}
+
+impl From<&info::Renamed> for Callback {
+    fn from(info: &info::Renamed) -> Self {
+        Self::Renamed {
+            source_path: lossy(&info.source_path),
+        }
+    }
+}
+
+/// The info of a [Callback], constructed by the [Replayer][super::Replayer].
+pub(crate) enum Info {
+    FetchData(info::FetchData),
+    CancelFetchData(info::CancelFetchData),
+    ValidateData(info::ValidateData),
+    FetchPlaceholders(info::FetchPlaceholders),
+    CancelFetchPlaceholders(info::CancelFetchPlaceholders),
+    Opened(info::Opened),
+    Closed(info::Closed),
+    Dehydrate(info::Dehydrate),
+    Dehydrated(info::Dehydrated),
+    Delete(info::Delete),
+    Deleted(info::Deleted),
+    Rename(info::Rename),
+    Renamed(info::Renamed),
+    StateChanged(Vec<PathBuf>),
+}
+
+impl Callback {
+    /// Constructs the owned info of the callback, mapping its paths.
+    pub(crate) fn to_info(&self, map_path: impl Fn(&str) -> PathBuf) -> Info {
+        match self.clone() {
+            Self::FetchData {
+                interrupted_hydration,
+                explicit_hydration,
+                required_file_range,
+                optional_file_range,
+                last_dehydration_time,
+                last_dehydration_reason,
+            } => Info::FetchData(info::FetchData {
+                interrupted_hydration,
+                explicit_hydration,
+                required_file_range,
+                optional_file_range,
+                last_dehydration_time: FileTime::new(last_dehydration_time),
+                last_dehydration_reason,
+            }),
+            Self::CancelFetchData {
+                timeout,
+                user_cancelled,
+                file_range,
+            } => Info::CancelFetchData(info::CancelFetchData {
+                timeout,
+                user_cancelled,
+                file_range,
+            }),
+            Self::ValidateData {
+                explicit_hydration,
+                file_range,
+            } => Info::ValidateData(info::ValidateData {
+                explicit_hydration,
+                file_range,
+            }),
+            Self::FetchPlaceholders { pattern } => {
+                Info::FetchPlaceholders(info::FetchPlaceholders { pattern })
+            }
+            Self::CancelFetchPlaceholders {
+                timeout,
+                user_cancelled,
+            } => Info::CancelFetchPlaceholders(info::CancelFetchPlaceholders {
+                timeout,
+                user_cancelled,
+            }),
+            Self::Opened {
+                metadata_corrupt,
+                metadata_unsupported,
+            } => Info::Opened(info::Opened {
+                metadata_corrupt,
+                metadata_unsupported,
+            }),
+            Self::Closed { deleted } => Info::Closed(info::Closed { deleted }),
+            Self::Dehydrate { background, reason } => {
+                Info::Dehydrate(info::Dehydrate { background, reason })
+            }
+            Self::Dehydrated {
+                background,
+                already_hydrated,
+                reason,
+            } => Info::Dehydrated(info::Dehydrated {
+                background,
+                already_hydrated,
+                reason,
+            }),
+            Self::Delete {
+                is_directory,
+                is_undelete,
+            } => Info::Delete(info::Delete {
+                is_directory,
+                is_undelete,
+            }),
+            Self::Deleted => Info::Deleted(info::Deleted::new()),
+            Self::Rename {
+                is_directory,
+                source_in_scope,
+                target_in_scope,
+                target_path,
+            } => Info::Rename(info::Rename {
+                is_directory,
+                source_in_scope,
+                target_in_scope,
+                target_path: map_path(&target_path),
+            }),
+            Self::Renamed { source_path } => Info::Renamed(info::Renamed {
+                source_path: map_path(&source_path),
+            }),
+            Self::StateChanged { changes } => {
+                Info::StateChanged(changes.iter().map(|path| map_path(path)).collect())
+            }
+        }
+    }
+}
+
+/// An operation issued through a ticket, the data written being summarized by its checksum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum Response {
+    /// See [Operation::Read].
+    Read { offset: u64, length: u64 },
+    /// See [Operation::Write].
+    Write {
+        offset: u64,
+        length: u64,
+        /// The 64-bit FNV-1a hash of the data.
+        checksum: u64,
+    },
+    /// See [Operation::ReportProgress].
+    ReportProgress { total: u64, completed: u64 },
+    /// See [Operation::Validate].
+    Validate { range: Range<u64> },
+    /// See [Operation::CreatePlaceholders], with the relative path of each placeholder.
+    CreatePlaceholders { paths: Vec<String> },
+    /// See [Operation::Dehydrate].
+    Dehydrate { blob: Vec<u8> },
+    /// See [Operation::Delete].
+    Delete,
+    /// See [Operation::Rename].
+    Rename,
+}
+
+impl Response {
+    /// Summarizes a write of the data at the offset.
+    pub fn write(data: &[u8], offset: u64) -> Self {
+        Self::Write {
+            offset,
+            length: data.len() as u64,
+            checksum: data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            }),
+        }
+    }
+}
+
+impl From<&Operation> for Response {
+    fn from(operation: &Operation) -> Self {
+        match operation {
+            Operation::Read { offset, length } => Self::Read {
+                offset: *offset,
+                length: *length,
+            },
+            Operation::Write { offset, data } => Self::write(data, *offset),
+            Operation::ReportProgress { total, completed } => Self::ReportProgress {
+                total: *total,
+                completed: *completed,
+            },
+            Operation::Validate(range) => Self::Validate {
+                range: range.clone(),
+            },
+            Operation::CreatePlaceholders(placeholders) => Self::CreatePlaceholders {
+                paths: placeholders
+                    .iter()
+                    .map(|placeholder| lossy(placeholder.relative_path()))
+                    .collect(),
+            },
+            Operation::Dehydrate(blob) => Self::Dehydrate { blob: blob.clone() },
+            Operation::Delete => Self::Delete,
+            Operation::Rename => Self::Rename,
+        }
+    }
+}
+
+/// Converts the path to a string, replacing the invalid characters.
+pub(crate) fn lossy(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
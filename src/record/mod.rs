@@ -0,0 +1,7 @@
+pub use event::{Callback, Event, ProcessRecord, RequestRecord, Response};
+pub use recorder::Recorder;
+pub use replayer::{Replay, Replayer};
+
+mod event;
+mod recorder;
+mod replayer;
@@ -0,0 +1,329 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use windows_core as core;
+
+use crate::{
+    error::{self, CResult, CloudError},
+    filter::{info, layer::SyncLayer, ticket, Backend, Request, SyncFilter},
+    placeholder_file::PlaceholderFile,
+    record::{event::lossy, Callback, Event, RequestRecord, Response},
+};
+
+/// A [SyncLayer] writing each callback of the filter as an [Event] to a JSON-lines file, to be
+/// fed back to a filter by the [Replayer][super::Replayer].
+///
+/// Each line holds the [Request], the info and the timings of a callback, along with the
+/// operations the filter issued through the ticket and the error it returned. The data written
+/// to the placeholders is only recorded as a checksum. The lines are flushed as soon as the
+/// callback returns, so a recording survives a crash of the filter; a line that cannot be written
+/// is reported through the [error hook][crate::error::set_hook].
+///
+/// ```no_run
+/// # use cloud_filter::{
+/// #     error::CResult,
+/// #     filter::{info, layer::FilterStack, ticket, Request, SyncFilter},
+/// #     record::Recorder,
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// let filter = FilterStack::new()
+///     .intercept(Recorder::create("session.jsonl")?)
+///     .filter(MyFilter);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Recorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Creates a [Recorder] writing the events to the writer.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            started: Instant::now(),
+        }
+    }
+
+    /// Creates a [Recorder] writing the events to a new file at the path, truncating any
+    /// existing file.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Runs the callback, recording it with the operations issued through the backend.
+    fn record(
+        &self,
+        request: Option<RequestRecord>,
+        callback: Callback,
+        tee: Option<&Tee>,
+        f: impl FnOnce() -> CResult<()>,
+    ) -> CResult<()> {
+        let started = Instant::now();
+        let result = f();
+
+        let event = Event {
+            at: started.duration_since(self.started).as_micros() as u64,
+            duration: started.elapsed().as_micros() as u64,
+            request,
+            callback,
+            responses: tee.map_or_else(Vec::new, |tee| tee.responses.lock().unwrap().clone()),
+            error: result.as_ref().err().map(CloudError::kind),
+        };
+        if let Err(err) = self.write(&event) {
+            error::report(&CloudError::from(err).context("failed to record the callback"));
+        }
+
+        result
+    }
+
+    /// Runs the notification callback, recording it.
+    fn notify(&self, request: Option<RequestRecord>, callback: Callback, f: impl FnOnce()) {
+        _ = self.record(request, callback, None, || {
+            f();
+            Ok(())
+        });
+    }
+
+    fn write(&self, event: &Event) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("started", &self.started)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: SyncFilter> SyncLayer<F> for Recorder {
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket.reissue(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.fetch_data(request, ticket, info)
+        })
+    }
+
+    fn cancel_fetch_data(&self, next: &F, request: Request, info: info::CancelFetchData) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.cancel_fetch_data(request, info)
+        })
+    }
+
+    fn validate_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::ValidateData,
+        info: info::ValidateData,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket::ValidateData::new(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.validate_data(request, ticket, info)
+        })
+    }
+
+    fn fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket.reissue(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.fetch_placeholders(request, ticket, info)
+        })
+    }
+
+    fn cancel_fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        info: info::CancelFetchPlaceholders,
+    ) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.cancel_fetch_placeholders(request, info)
+        })
+    }
+
+    fn opened(&self, next: &F, request: Request, info: info::Opened) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.opened(request, info)
+        })
+    }
+
+    fn closed(&self, next: &F, request: Request, info: info::Closed) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.closed(request, info)
+        })
+    }
+
+    fn dehydrate(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Dehydrate,
+        info: info::Dehydrate,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket::Dehydrate::new(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.dehydrate(request, ticket, info)
+        })
+    }
+
+    fn dehydrated(&self, next: &F, request: Request, info: info::Dehydrated) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.dehydrated(request, info)
+        })
+    }
+
+    fn delete(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Delete,
+        info: info::Delete,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket::Delete::new(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.delete(request, ticket, info)
+        })
+    }
+
+    fn deleted(&self, next: &F, request: Request, info: info::Deleted) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.deleted(request, info)
+        })
+    }
+
+    fn rename(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Rename,
+        info: info::Rename,
+    ) -> CResult<()> {
+        let tee = Arc::new(Tee::new(ticket.backend().clone()));
+        let ticket = ticket::Rename::new(tee.clone());
+        self.record(Some((&request).into()), (&info).into(), Some(&tee), || {
+            next.rename(request, ticket, info)
+        })
+    }
+
+    fn renamed(&self, next: &F, request: Request, info: info::Renamed) {
+        self.notify(Some((&request).into()), (&info).into(), || {
+            next.renamed(request, info)
+        })
+    }
+
+    fn state_changed(&self, next: &F, changes: Vec<PathBuf>) {
+        let callback = Callback::StateChanged {
+            changes: changes.iter().map(|path| lossy(path)).collect(),
+        };
+        self.notify(None, callback, || next.state_changed(changes))
+    }
+}
+
+/// Records the operations executed through the [Backend].
+#[derive(Debug)]
+struct Tee {
+    backend: Arc<dyn Backend>,
+    responses: Mutex<Vec<Response>>,
+}
+
+impl Tee {
+    fn new(backend: Arc<dyn Backend>) -> Self {
+        Self {
+            backend,
+            responses: Mutex::default(),
+        }
+    }
+
+    fn push(&self, response: Response) {
+        self.responses.lock().unwrap().push(response);
+    }
+}
+
+impl Backend for Tee {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        self.push(Response::Read {
+            offset,
+            length: buf.len() as u64,
+        });
+        self.backend.read(buf, offset)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        self.push(Response::write(buf, offset));
+        self.backend.write(buf, offset)
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        self.push(Response::ReportProgress { total, completed });
+        self.backend.report_progress(total, completed)
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        self.push(Response::Validate {
+            range: range.clone(),
+        });
+        self.backend.validate(range)
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        self.push(Response::CreatePlaceholders {
+            paths: placeholders
+                .iter()
+                .map(|placeholder| lossy(placeholder.relative_path()))
+                .collect(),
+        });
+        self.backend.create_placeholders(placeholders)
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        self.push(Response::Dehydrate {
+            blob: blob.to_vec(),
+        });
+        self.backend.dehydrate(blob)
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.push(Response::Delete);
+        self.backend.delete()
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.push(Response::Rename);
+        self.backend.rename()
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{CResult, CloudError},
+    filter::{ticket, RecordingBackend, Request, SyncFilter},
+    record::{event::Info, Event, Response},
+};
+
+/// Feeds the events recorded by the [Recorder][super::Recorder] to a filter, without the
+/// operating system.
+///
+/// Each [Event] is turned back into an owned [Request], info and ticket executing its operations
+/// through a [RecordingBackend], so a session recorded on Windows can be replayed anywhere, e.g.
+/// from a regression test on Linux. The placeholders read through the tickets are empty.
+///
+/// ```no_run
+/// # use cloud_filter::{
+/// #     error::CResult,
+/// #     filter::{info, ticket, Request, SyncFilter},
+/// #     record::Replayer,
+/// # };
+/// # struct MyFilter;
+/// # impl SyncFilter for MyFilter {
+/// #     fn fetch_data(&self, _: Request, _: ticket::FetchData, _: info::FetchData) -> CResult<()> {
+/// #         Ok(())
+/// #     }
+/// # }
+/// let replay = Replayer::open("session.jsonl")?
+///     .rebase(r"C:\Users\me\Cloud", "/tmp/cloud")
+///     .replay(&MyFilter);
+/// assert!(replay.divergences().is_empty());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    events: Vec<Event>,
+    rebase: Option<(String, PathBuf)>,
+    paced: bool,
+}
+
+impl Replayer {
+    /// Creates a [Replayer] feeding the events in order.
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events,
+            rebase: None,
+            paced: false,
+        }
+    }
+
+    /// Reads the events of a JSON-lines recording, skipping the blank lines.
+    pub fn read(reader: impl BufRead) -> io::Result<Self> {
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                events.push(serde_json::from_str(&line)?);
+            }
+        }
+
+        Ok(Self::new(events))
+    }
+
+    /// Reads the events of the JSON-lines file at the path.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Replaces the prefix of the recorded paths starting with `from` by `to`, splitting the rest
+    /// of the paths on both `\` and `/`, e.g. to replay a session recorded under a Windows sync
+    /// root on Linux.
+    ///
+    /// The prefix matches whole components only, ignoring the case of Windows paths.
+    pub fn rebase(mut self, from: impl Into<String>, to: impl Into<PathBuf>) -> Self {
+        self.rebase = Some((from.into(), to.into()));
+        self
+    }
+
+    /// Whether or not to wait between the events as long as during the recording, defaults to
+    /// `false`.
+    pub fn paced(mut self, yes: bool) -> Self {
+        self.paced = yes;
+        self
+    }
+
+    /// The events fed to the filter.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Feeds the events to the filter, recording how it responded to each of them.
+    pub fn replay(&self, filter: &impl SyncFilter) -> Replay {
+        let started = Instant::now();
+        let replayed = self
+            .events
+            .iter()
+            .map(|event| {
+                if self.paced {
+                    let at = started + Duration::from_micros(event.at);
+                    thread::sleep(at.saturating_duration_since(Instant::now()));
+                }
+
+                let at = started.elapsed();
+                let backend = Arc::new(RecordingBackend::new());
+                let result = self.dispatch(filter, event, backend.clone());
+                Event {
+                    at: at.as_micros() as u64,
+                    duration: (started.elapsed() - at).as_micros() as u64,
+                    request: event.request.clone(),
+                    callback: event.callback.clone(),
+                    responses: backend.operations().iter().map(Response::from).collect(),
+                    error: result.err().as_ref().map(CloudError::kind),
+                }
+            })
+            .collect();
+
+        Replay {
+            recorded: self.events.clone(),
+            replayed,
+        }
+    }
+
+    /// Calls the callback of the event with owned arguments.
+    fn dispatch(
+        &self,
+        filter: &impl SyncFilter,
+        event: &Event,
+        backend: Arc<RecordingBackend>,
+    ) -> CResult<()> {
+        let request = || {
+            event
+                .request
+                .as_ref()
+                .map(|request| request.to_request(|path| self.map_path(path)))
+                .unwrap_or_else(|| Request::builder().build())
+        };
+
+        match event.callback.to_info(|path| self.map_path(path)) {
+            Info::FetchData(info) => {
+                filter.fetch_data(request(), ticket::FetchData::new(backend), info)
+            }
+            Info::CancelFetchData(info) => {
+                filter.cancel_fetch_data(request(), info);
+                Ok(())
+            }
+            Info::ValidateData(info) => {
+                filter.validate_data(request(), ticket::ValidateData::new(backend), info)
+            }
+            Info::FetchPlaceholders(info) => {
+                filter.fetch_placeholders(request(), ticket::FetchPlaceholders::new(backend), info)
+            }
+            Info::CancelFetchPlaceholders(info) => {
+                filter.cancel_fetch_placeholders(request(), info);
+                Ok(())
+            }
+            Info::Opened(info) => {
+                filter.opened(request(), info);
+                Ok(())
+            }
+            Info::Closed(info) => {
+                filter.closed(request(), info);
+                Ok(())
+            }
+            Info::Dehydrate(info) => {
+                filter.dehydrate(request(), ticket::Dehydrate::new(backend), info)
+            }
+            Info::Dehydrated(info) => {
+                filter.dehydrated(request(), info);
+                Ok(())
+            }
+            Info::Delete(info) => filter.delete(request(), ticket::Delete::new(backend), info),
+            Info::Deleted(info) => {
+                filter.deleted(request(), info);
+                Ok(())
+            }
+            Info::Rename(info) => filter.rename(request(), ticket::Rename::new(backend), info),
+            Info::Renamed(info) => {
+                filter.renamed(request(), info);
+                Ok(())
+            }
+            Info::StateChanged(changes) => {
+                filter.state_changed(changes);
+                Ok(())
+            }
+        }
+    }
+
+    fn map_path(&self, path: &str) -> PathBuf {
+        let rest = self
+            .rebase
+            .as_ref()
+            .and_then(|(from, to)| Some((strip_prefix(path, from)?, to)));
+        match rest {
+            Some((rest, to)) => {
+                let mut rebased = to.clone();
+                rebased.extend(
+                    rest.split(['\\', '/'])
+                        .filter(|component| !component.is_empty()),
+                );
+                rebased
+            }
+            None => PathBuf::from(path),
+        }
+    }
+}
+
+/// The rest of the recorded path after the prefix, [None] unless the prefix ends on a component
+/// of the path.
+///
+/// Windows paths, i.e. with a drive letter or a `\`, are compared case-insensitively.
+fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches(['\\', '/']);
+    let head = path.get(..prefix.len())?;
+    let rest = &path[prefix.len()..];
+    let windows = prefix.contains('\\') || prefix.as_bytes().get(1) == Some(&b':');
+    let same = match windows {
+        true => head.to_lowercase() == prefix.to_lowercase(),
+        false => head == prefix,
+    };
+
+    (same && (rest.is_empty() || rest.starts_with(['\\', '/']))).then_some(rest)
+}
+
+/// The outcome of [Replayer::replay].
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// The events fed to the filter.
+    pub recorded: Vec<Event>,
+    /// The events recorded while replaying, in the same order.
+    pub replayed: Vec<Event>,
+}
+
+impl Replay {
+    /// The indices of the events the filter responded to differently than during the recording,
+    /// see [Event::same_outcome].
+    pub fn divergences(&self) -> Vec<usize> {
+        self.recorded
+            .iter()
+            .zip(&self.replayed)
+            .enumerate()
+            .filter(|(_, (recorded, replayed))| !recorded.same_outcome(replayed))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
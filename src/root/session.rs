@@ -107,6 +107,22 @@ impl Session {
     {
         self.connect(path, AsyncBridge::new(filter, block_on))
     }
+
+    /// Initiates a connection to the sync root with the given [Filter], spawning its callbacks
+    /// onto the tokio runtime, see [TokioBridge][crate::filter::TokioBridge].
+    #[cfg(feature = "tokio")]
+    pub fn connect_tokio<P, F>(
+        self,
+        path: P,
+        filter: F,
+        handle: tokio::runtime::Handle,
+    ) -> core::Result<Connection<filter::TokioBridge<F>>>
+    where
+        P: AsRef<Path>,
+        F: Filter + 'static,
+    {
+        self.connect(path, filter::TokioBridge::new(filter, handle))
+    }
 }
 
 impl Default for Session {
@@ -0,0 +1,191 @@
+use std::{
+    mem,
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use windows_core::{self as core, HRESULT};
+
+use crate::{
+    error::CloudErrorKind,
+    filter::Backend,
+    placeholder_file::{PlaceholderFile, MAX_BLOB_LENGTH},
+    sim::{
+        tree::{self, NodeKind, Tree},
+        Error,
+    },
+};
+
+/// The alignment required for `CF_OPERATION_TYPE_TRANSFER_DATA` offsets and lengths.
+pub(crate) const TRANSFER_ALIGNMENT: u64 = 4096;
+
+/// `ERROR_ALREADY_EXISTS`
+const ERROR_ALREADY_EXISTS: u32 = 183;
+
+/// The response the filter gave through its ticket.
+#[derive(Debug, Default)]
+pub(crate) struct Response {
+    /// Whether or not the ticket acknowledged the operation.
+    pub(crate) acked: bool,
+    /// The blob passed to [Dehydrate::pass_with_blob][crate::filter::ticket::Dehydrate::pass_with_blob].
+    pub(crate) blob: Option<Vec<u8>>,
+    /// The last progress reported, in the form of `(total, completed)`.
+    pub(crate) progress: Option<(u64, u64)>,
+}
+
+/// Executes the ticket operations against the simulated placeholder tree.
+#[derive(Debug)]
+pub(crate) struct SimBackend {
+    tree: Arc<Mutex<Tree>>,
+    path: PathBuf,
+    response: Mutex<Response>,
+}
+
+impl SimBackend {
+    pub(crate) fn new(tree: Arc<Mutex<Tree>>, path: PathBuf) -> Self {
+        Self {
+            tree,
+            path,
+            response: Mutex::new(Response::default()),
+        }
+    }
+
+    /// Takes the response given through the ticket, failing with
+    /// [CloudErrorKind::RequestTimeout] if the ticket was never used.
+    pub(crate) fn take_response(&self) -> Result<Response, Error> {
+        let response = mem::take(&mut *self.response.lock().unwrap());
+        match response.acked {
+            true => Ok(response),
+            false => Err(Error::Callback(CloudErrorKind::RequestTimeout)),
+        }
+    }
+
+    fn ack(&self) {
+        self.response.lock().unwrap().acked = true;
+    }
+}
+
+fn invalid_request(message: impl AsRef<str>) -> core::Error {
+    core::Error::new(
+        HRESULT::from_nt(CloudErrorKind::InvalidRequest.ntstatus()),
+        message,
+    )
+}
+
+impl Backend for SimBackend {
+    fn read(&self, buf: &mut [u8], offset: u64) -> core::Result<u64> {
+        let tree = self.tree.lock().unwrap();
+        let Some(NodeKind::File { size, data, .. }) = tree.get(&self.path).map(|node| &node.kind)
+        else {
+            return Err(invalid_request("placeholder is not a file"));
+        };
+
+        let start = offset.min(*size);
+        let end = start.saturating_add(buf.len() as u64).min(*size);
+        tree::read_data(data, start, &mut buf[..(end - start) as usize]);
+
+        Ok(end - start)
+    }
+
+    fn write(&self, buf: &[u8], offset: u64) -> core::Result<()> {
+        let mut tree = self.tree.lock().unwrap();
+        let Some(NodeKind::File {
+            size,
+            data,
+            present,
+            ..
+        }) = tree.get_mut(&self.path).map(|node| &mut node.kind)
+        else {
+            return Err(invalid_request("placeholder is not a file"));
+        };
+
+        let size = *size;
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or_else(|| invalid_request(format!("offset {offset} overflows")))?;
+        if !offset.is_multiple_of(TRANSFER_ALIGNMENT) {
+            return Err(invalid_request(format!(
+                "offset {offset} is not aligned to {TRANSFER_ALIGNMENT} bytes"
+            )));
+        }
+        if end > size {
+            return Err(invalid_request(format!(
+                "range {offset}..{end} exceeds the file size {size}"
+            )));
+        }
+        if !(buf.len() as u64).is_multiple_of(TRANSFER_ALIGNMENT) && end != size {
+            return Err(invalid_request(format!(
+                "length {} is not aligned to {TRANSFER_ALIGNMENT} bytes and does not end on the file size {size}",
+                buf.len()
+            )));
+        }
+
+        tree::write_data(data, offset, buf);
+        tree::insert_range(present, offset..end);
+
+        Ok(())
+    }
+
+    fn report_progress(&self, total: u64, completed: u64) -> core::Result<()> {
+        self.response.lock().unwrap().progress = Some((total, completed));
+        Ok(())
+    }
+
+    fn validate(&self, range: Range<u64>) -> core::Result<()> {
+        let mut tree = self.tree.lock().unwrap();
+        let Some(NodeKind::File { validated, .. }) =
+            tree.get_mut(&self.path).map(|node| &mut node.kind)
+        else {
+            return Err(invalid_request("placeholder is not a file"));
+        };
+
+        tree::insert_range(validated, range);
+        self.ack();
+
+        Ok(())
+    }
+
+    fn create_placeholders(&self, placeholders: &mut [PlaceholderFile]) -> core::Result<()> {
+        let mut tree = self.tree.lock().unwrap();
+        match tree.get_mut(&self.path).map(|node| &mut node.kind) {
+            Some(NodeKind::Directory { populated }) => *populated = true,
+            _ => return Err(invalid_request("placeholder is not a directory")),
+        }
+
+        for placeholder in placeholders.iter_mut() {
+            placeholder.result = match tree.insert(&self.path, placeholder) {
+                true => HRESULT(0),
+                false => HRESULT::from_win32(ERROR_ALREADY_EXISTS),
+            };
+        }
+        self.ack();
+
+        Ok(())
+    }
+
+    fn dehydrate(&self, blob: &[u8]) -> core::Result<()> {
+        if blob.len() > MAX_BLOB_LENGTH {
+            return Err(invalid_request(format!(
+                "blob size must not exceed {MAX_BLOB_LENGTH} bytes, got {} bytes",
+                blob.len()
+            )));
+        }
+
+        let mut response = self.response.lock().unwrap();
+        response.acked = true;
+        response.blob = (!blob.is_empty()).then(|| blob.to_vec());
+
+        Ok(())
+    }
+
+    fn delete(&self) -> core::Result<()> {
+        self.ack();
+        Ok(())
+    }
+
+    fn rename(&self) -> core::Result<()> {
+        self.ack();
+        Ok(())
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{
+    error::{self, CloudError, CloudErrorKind},
+    filter::{info, ticket, CancellationToken, Process, RawTransferKey, Request, SyncFilter},
+};
+
+use backend::{SimBackend, TRANSFER_ALIGNMENT};
+use tree::{Node, NodeKind, Tree};
+
+mod backend;
+pub(crate) mod tree;
+
+/// An in-process stand-in for the Cloud Filter API.
+///
+/// The [Simulator] keeps a virtual placeholder tree and issues the same callbacks the operating
+/// system would when a user process lists, reads, dehydrates, deletes or moves a placeholder.
+/// Operations issued through the tickets are applied to the tree, so a
+/// [SyncFilter][crate::filter::SyncFilter] can be exercised on any platform.
+///
+/// Paths passed to the [Simulator] are relative to the sync root. The paths reported through
+/// [Request::path][crate::filter::Request::path] are joined onto the root passed to
+/// [Simulator::new].
+///
+/// The simulator enforces the same rules as the operating system where it matters for sync
+/// engines, e.g. `TRANSFER_DATA` writes must be aligned to 4KiB, and a callback that returns
+/// successfully without responding through its ticket times out with
+/// [CloudErrorKind::RequestTimeout][crate::error::CloudErrorKind::RequestTimeout].
+pub struct Simulator<F> {
+    root: PathBuf,
+    filter: F,
+    tree: Arc<Mutex<Tree>>,
+    register_blob: Vec<u8>,
+    validation_required: bool,
+    next_transfer_key: AtomicI64,
+    in_flight: Mutex<Vec<InFlight>>,
+}
+
+/// A fetch callback that has not returned yet.
+struct InFlight {
+    path: PathBuf,
+    transfer_key: RawTransferKey,
+    token: CancellationToken,
+}
+
+impl<F: SyncFilter> Simulator<F> {
+    /// Creates a new [Simulator] with an empty, unpopulated sync root at `root`.
+    pub fn new(root: impl Into<PathBuf>, filter: F) -> Self {
+        Self {
+            root: root.into(),
+            filter,
+            tree: Arc::new(Mutex::new(Tree::new())),
+            register_blob: Vec::new(),
+            validation_required: false,
+            next_transfer_key: AtomicI64::new(1),
+            in_flight: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The blob passed to [Request::register_blob][crate::filter::Request::register_blob].
+    pub fn register_blob(mut self, blob: Vec<u8>) -> Self {
+        self.register_blob = blob;
+        self
+    }
+
+    /// Requires hydrated data to be validated through
+    /// [SyncFilter::validate_data][crate::filter::SyncFilter::validate_data], mirroring
+    /// [HydrationPolicy::ValidationRequired][crate::root::HydrationPolicy::ValidationRequired].
+    pub fn validation_required(mut self, yes: bool) -> Self {
+        self.validation_required = yes;
+        self
+    }
+
+    /// The [SyncFilter][crate::filter::SyncFilter] receiving the callbacks.
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// Cancels the in-flight hydrations of the file, as if the user aborted the download.
+    ///
+    /// The [CancellationToken] of each pending
+    /// [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] callback is tripped with
+    /// the range before [SyncFilter::cancel_fetch_data][crate::filter::SyncFilter::cancel_fetch_data]
+    /// is issued with the same transfer key. Returns `false` if no hydration was in flight.
+    pub fn cancel_fetch_data(&self, relative: impl AsRef<Path>, range: Range<u64>) -> bool {
+        let relative = relative.as_ref();
+        let cancelled = self.cancel(relative, Some(range.clone()));
+        let any = !cancelled.is_empty();
+        for request in cancelled {
+            self.filter.cancel_fetch_data(
+                request,
+                info::CancelFetchData::builder()
+                    .user_cancelled(true)
+                    .file_range(range.clone())
+                    .build(),
+            );
+        }
+
+        any
+    }
+
+    /// Cancels the in-flight population of the directory, as if the user aborted the listing.
+    ///
+    /// The [CancellationToken] of each pending
+    /// [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] callback
+    /// is tripped before
+    /// [SyncFilter::cancel_fetch_placeholders][crate::filter::SyncFilter::cancel_fetch_placeholders]
+    /// is issued with the same transfer key. Returns `false` if no population was in flight.
+    pub fn cancel_fetch_placeholders(&self, relative: impl AsRef<Path>) -> bool {
+        let relative = relative.as_ref();
+        let cancelled = self.cancel(relative, None);
+        let any = !cancelled.is_empty();
+        for request in cancelled {
+            self.filter.cancel_fetch_placeholders(
+                request,
+                info::CancelFetchPlaceholders::builder()
+                    .user_cancelled(true)
+                    .build(),
+            );
+        }
+
+        any
+    }
+
+    /// Returns the placeholder at the relative path without issuing any callbacks.
+    pub fn entry(&self, relative: impl AsRef<Path>) -> Option<Entry> {
+        let relative = relative.as_ref();
+        let tree = self.tree.lock().unwrap();
+        tree.get(relative).map(|node| Entry::new(relative, node))
+    }
+
+    /// Lists the directory, populating it and its ancestors through
+    /// [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] first if
+    /// needed.
+    pub fn read_dir(&self, relative: impl AsRef<Path>) -> Result<Vec<Entry>, Error> {
+        let relative = relative.as_ref();
+        let node = self.lookup(relative)?;
+        if !node.is_directory() {
+            return Err(Error::NotDirectory(relative.to_path_buf()));
+        }
+        self.populate(relative, &node)?;
+
+        let tree = self.tree.lock().unwrap();
+        Ok(tree
+            .children(relative)
+            .iter()
+            .map(|path| Entry::new(path, tree.get(path).unwrap()))
+            .collect())
+    }
+
+    /// Reads the whole file, see [Simulator::read_range].
+    pub fn read(&self, relative: impl AsRef<Path>) -> Result<Vec<u8>, Error> {
+        self.read_range(relative, 0..u64::MAX)
+    }
+
+    /// Reads the range of the file the way a user process would, hydrating missing data through
+    /// [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data].
+    ///
+    /// The range is clamped to the size of the file, an empty or reversed range reading nothing
+    /// without issuing callbacks. The callbacks are issued in the order `opened`, `fetch_data`,
+    /// `validate_data` (if required) and `closed`.
+    pub fn read_range(
+        &self,
+        relative: impl AsRef<Path>,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>, Error> {
+        let relative = relative.as_ref();
+        let node = self.lookup(relative)?;
+        let size = match node.kind {
+            NodeKind::Directory { .. } => return Err(Error::IsDirectory(relative.to_path_buf())),
+            NodeKind::File { .. } => node.size(),
+        };
+        if range.start >= range.end {
+            return Ok(Vec::new());
+        }
+        let range = range.start.min(size)..range.end.min(size);
+
+        self.filter.opened(
+            self.request(relative, &node),
+            info::Opened::builder().build(),
+        );
+        let required = align_down(range.start)..align_up(range.end).min(size);
+        let result = self.fetch_data(relative, required, false);
+        self.filter.closed(
+            self.request(relative, &node),
+            info::Closed::builder().build(),
+        );
+        result?;
+
+        let tree = self.tree.lock().unwrap();
+        match &tree.get(relative).map(|node| &node.kind) {
+            Some(NodeKind::File { data, .. }) => {
+                let mut buf = vec![0; (range.end - range.start) as usize];
+                tree::read_data(data, range.start, &mut buf);
+                Ok(buf)
+            }
+            _ => Err(Error::NotFound(relative.to_path_buf())),
+        }
+    }
+
+    /// Hydrates the whole file explicitly, the way `CfHydratePlaceholder` would.
+    pub fn hydrate(&self, relative: impl AsRef<Path>) -> Result<(), Error> {
+        let relative = relative.as_ref();
+        let node = self.lookup(relative)?;
+        if node.is_directory() {
+            return Err(Error::IsDirectory(relative.to_path_buf()));
+        }
+
+        self.fetch_data(relative, 0..node.size(), true)
+    }
+
+    /// Dehydrates the file through [SyncFilter::dehydrate][crate::filter::SyncFilter::dehydrate].
+    pub fn dehydrate(&self, relative: impl AsRef<Path>) -> Result<(), Error> {
+        let relative = relative.as_ref();
+        let node = self.lookup(relative)?;
+        let already_hydrated = match &node.kind {
+            NodeKind::Directory { .. } => return Err(Error::IsDirectory(relative.to_path_buf())),
+            NodeKind::File { present, .. } => present.is_empty(),
+        };
+        if node.block_dehydration {
+            return Err(Error::Callback(CloudErrorKind::DehydrationDisallowed));
+        }
+
+        let backend = self.backend(relative);
+        self.filter
+            .dehydrate(
+                self.request(relative, &node),
+                ticket::Dehydrate::new(backend.clone()),
+                info::Dehydrate::builder().build(),
+            )
+            .map_err(Error::failed)?;
+
+        let response = backend.take_response()?;
+        if let Some(node) = self.tree.lock().unwrap().get_mut(relative) {
+            if let Some(blob) = response.blob {
+                node.blob = blob;
+            }
+            if let NodeKind::File { present, .. } = &mut node.kind {
+                present.clear();
+            }
+        }
+
+        self.filter.dehydrated(
+            self.request(relative, &node),
+            info::Dehydrated::builder()
+                .already_hydrated(already_hydrated)
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    /// Deletes the placeholder through [SyncFilter::delete][crate::filter::SyncFilter::delete].
+    pub fn delete(&self, relative: impl AsRef<Path>) -> Result<(), Error> {
+        let relative = relative.as_ref();
+        let node = self.lookup(relative)?;
+
+        let backend = self.backend(relative);
+        self.filter
+            .delete(
+                self.request(relative, &node),
+                ticket::Delete::new(backend.clone()),
+                info::Delete::builder()
+                    .is_directory(node.is_directory())
+                    .build(),
+            )
+            .map_err(Error::failed)?;
+        backend.take_response()?;
+
+        self.tree.lock().unwrap().remove(relative);
+        self.filter
+            .deleted(self.request(relative, &node), info::Deleted::new());
+
+        Ok(())
+    }
+
+    /// Renames or moves the placeholder through
+    /// [SyncFilter::rename][crate::filter::SyncFilter::rename].
+    pub fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Error> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let node = self.lookup(from)?;
+        let parent = to
+            .parent()
+            .ok_or_else(|| Error::NotFound(to.to_path_buf()))?;
+        if !self.lookup(parent)?.is_directory() {
+            return Err(Error::NotDirectory(parent.to_path_buf()));
+        }
+        if self.lookup(to).is_ok() {
+            return Err(Error::AlreadyExists(to.to_path_buf()));
+        }
+
+        let backend = self.backend(from);
+        self.filter
+            .rename(
+                self.request(from, &node),
+                ticket::Rename::new(backend.clone()),
+                info::Rename::builder()
+                    .is_directory(node.is_directory())
+                    .target_path(self.root.join(to))
+                    .build(),
+            )
+            .map_err(Error::failed)?;
+        backend.take_response()?;
+
+        self.tree.lock().unwrap().rename(from, to);
+        self.filter.renamed(
+            self.request(to, &node),
+            info::Renamed::builder()
+                .source_path(self.root.join(from))
+                .build(),
+        );
+
+        Ok(())
+    }
+
+    /// Finds the placeholder, populating its ancestors along the way.
+    fn lookup(&self, relative: &Path) -> Result<Node, Error> {
+        let mut path = PathBuf::new();
+        let mut node = self.tree.lock().unwrap().get(&path).cloned().unwrap();
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                return Err(Error::NotFound(relative.to_path_buf()));
+            };
+            if !node.is_directory() {
+                return Err(Error::NotDirectory(path));
+            }
+            self.populate(&path, &node)?;
+
+            path.push(name);
+            node = self
+                .tree
+                .lock()
+                .unwrap()
+                .get(&path)
+                .cloned()
+                .ok_or_else(|| Error::NotFound(relative.to_path_buf()))?;
+        }
+
+        Ok(node)
+    }
+
+    /// Issues [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] if
+    /// the directory has not been populated yet.
+    fn populate(&self, relative: &Path, node: &Node) -> Result<(), Error> {
+        if !matches!(node.kind, NodeKind::Directory { populated: false }) {
+            return Ok(());
+        }
+
+        let backend = self.backend(relative);
+        let request = self.request(relative, node);
+        let transfer_key = request.transfer_key;
+        let token = self.begin(relative, transfer_key);
+        let result = self.filter.fetch_placeholders(
+            request,
+            ticket::FetchPlaceholders::with_token(backend.clone(), token),
+            info::FetchPlaceholders::builder().build(),
+        );
+        self.end(transfer_key);
+        if let Err(err) = result {
+            return Err(Error::failed(err));
+        }
+
+        if let Err(err) = backend.take_response() {
+            self.filter.cancel_fetch_placeholders(
+                self.request_with_key(relative, node, transfer_key),
+                info::CancelFetchPlaceholders::builder()
+                    .timeout(true)
+                    .build(),
+            );
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Issues [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] if the range is not
+    /// already present.
+    fn fetch_data(
+        &self,
+        relative: &Path,
+        required: Range<u64>,
+        explicit_hydration: bool,
+    ) -> Result<(), Error> {
+        let node = self.lookup(relative)?;
+        let NodeKind::File { present, .. } = &node.kind else {
+            return Err(Error::IsDirectory(relative.to_path_buf()));
+        };
+        if tree::covers(present, &required) {
+            return Ok(());
+        }
+
+        let backend = self.backend(relative);
+        let request = self.request(relative, &node);
+        let transfer_key = request.transfer_key;
+        let token = self.begin(relative, transfer_key);
+        let result = self.filter.fetch_data(
+            request,
+            ticket::FetchData::with_token(backend.clone(), token),
+            info::FetchData::builder()
+                .explicit_hydration(explicit_hydration)
+                .required_range(required.clone())
+                .optional_range(0..node.size())
+                .build(),
+        );
+        self.end(transfer_key);
+        result.map_err(Error::failed)?;
+
+        let hydrated = matches!(
+            self.tree.lock().unwrap().get(relative).map(|node| &node.kind),
+            Some(NodeKind::File { present, .. }) if tree::covers(present, &required)
+        );
+        if !hydrated {
+            self.filter.cancel_fetch_data(
+                self.request_with_key(relative, &node, transfer_key),
+                info::CancelFetchData::builder()
+                    .timeout(true)
+                    .file_range(required)
+                    .build(),
+            );
+            return Err(Error::Callback(CloudErrorKind::RequestTimeout));
+        }
+
+        if self.validation_required {
+            self.validate_data(relative, required, explicit_hydration)?;
+        }
+
+        Ok(())
+    }
+
+    /// Issues [SyncFilter::validate_data][crate::filter::SyncFilter::validate_data] for the
+    /// hydrated range.
+    fn validate_data(
+        &self,
+        relative: &Path,
+        range: Range<u64>,
+        explicit_hydration: bool,
+    ) -> Result<(), Error> {
+        let node = self.lookup(relative)?;
+        let backend = self.backend(relative);
+        self.filter
+            .validate_data(
+                self.request(relative, &node),
+                ticket::ValidateData::new(backend.clone()),
+                info::ValidateData::builder()
+                    .explicit_hydration(explicit_hydration)
+                    .file_range(range.clone())
+                    .build(),
+            )
+            .map_err(Error::failed)?;
+
+        let validated = matches!(
+            self.tree.lock().unwrap().get(relative).map(|node| &node.kind),
+            Some(NodeKind::File { validated, .. }) if tree::covers(validated, &range)
+        );
+        match validated {
+            true => Ok(()),
+            false => Err(Error::Callback(CloudErrorKind::ValidationFailed)),
+        }
+    }
+
+    fn backend(&self, relative: &Path) -> Arc<SimBackend> {
+        Arc::new(SimBackend::new(self.tree.clone(), relative.to_path_buf()))
+    }
+
+    fn transfer_key(&self) -> RawTransferKey {
+        self.next_transfer_key.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Tracks a fetch callback until [Simulator::end] is called, returning its token.
+    fn begin(&self, relative: &Path, transfer_key: RawTransferKey) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.in_flight.lock().unwrap().push(InFlight {
+            path: relative.to_path_buf(),
+            transfer_key,
+            token: token.clone(),
+        });
+        token
+    }
+
+    fn end(&self, transfer_key: RawTransferKey) {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .retain(|in_flight| in_flight.transfer_key != transfer_key);
+    }
+
+    /// Trips the tokens of the fetch callbacks in flight for the path, returning the requests to
+    /// issue the cancel callbacks with.
+    fn cancel(&self, relative: &Path, range: Option<Range<u64>>) -> Vec<Request> {
+        let Some(node) = self.tree.lock().unwrap().get(relative).cloned() else {
+            return Vec::new();
+        };
+
+        let transfer_keys = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|in_flight| in_flight.path == relative)
+            .map(|in_flight| {
+                in_flight.token.trip(range.clone());
+                in_flight.transfer_key
+            })
+            .collect::<Vec<_>>();
+
+        transfer_keys
+            .into_iter()
+            .map(|transfer_key| self.request_with_key(relative, &node, transfer_key))
+            .collect()
+    }
+
+    fn request_with_key(
+        &self,
+        relative: &Path,
+        node: &Node,
+        transfer_key: RawTransferKey,
+    ) -> Request {
+        let mut request = self.request(relative, node);
+        request.transfer_key = transfer_key;
+        request
+    }
+
+    fn request(&self, relative: &Path, node: &Node) -> Request {
+        let volume_letter = match self.root.components().next() {
+            Some(Component::Prefix(prefix)) => prefix.as_os_str().to_owned(),
+            _ => OsString::new(),
+        };
+        let mut process = Process::builder().id(std::process::id());
+        if let Ok(path) = std::env::current_exe() {
+            process = process.path(path);
+        }
+        let sync_root_file_id = self
+            .tree
+            .lock()
+            .unwrap()
+            .get(Path::new(""))
+            .unwrap()
+            .file_id;
+
+        let mut request = Request::builder()
+            .volume_letter(volume_letter)
+            .process(process.build())
+            .sync_root_file_id(sync_root_file_id)
+            .file_id(node.file_id)
+            .file_size(node.size())
+            .path(match relative.as_os_str().is_empty() {
+                true => self.root.clone(),
+                false => self.root.join(relative),
+            })
+            .file_blob(node.blob.clone())
+            .register_blob(self.register_blob.clone())
+            .build();
+        request.transfer_key = self.transfer_key();
+
+        request
+    }
+}
+
+impl<F> fmt::Debug for Simulator<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Simulator")
+            .field("root", &self.root)
+            .field("tree", &self.tree)
+            .finish()
+    }
+}
+
+/// A snapshot of a placeholder in the [Simulator].
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    file_id: i64,
+    is_directory: bool,
+    size: u64,
+    blob: Vec<u8>,
+    in_sync: bool,
+    hydrated: bool,
+}
+
+impl Entry {
+    fn new(path: &Path, node: &Node) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            file_id: node.file_id,
+            is_directory: node.is_directory(),
+            size: node.size(),
+            blob: node.blob.clone(),
+            in_sync: node.in_sync,
+            hydrated: match &node.kind {
+                NodeKind::Directory { populated } => *populated,
+                NodeKind::File { present, .. } => tree::covers(present, &(0..node.size())),
+            },
+        }
+    }
+
+    /// The path of the placeholder relative to the sync root.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file ID of the placeholder.
+    pub fn file_id(&self) -> i64 {
+        self.file_id
+    }
+
+    /// Whether or not the placeholder is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.is_directory
+    }
+
+    /// The logical size of the placeholder file.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The blob assigned to the placeholder.
+    pub fn blob(&self) -> &[u8] {
+        &self.blob
+    }
+
+    /// Whether or not the placeholder is marked as in sync.
+    pub fn in_sync(&self) -> bool {
+        self.in_sync
+    }
+
+    /// Whether or not all of the data of the placeholder file is present, or, for directories,
+    /// whether or not its children were fetched.
+    pub fn is_hydrated(&self) -> bool {
+        self.hydrated
+    }
+}
+
+/// The error type returned by the [Simulator].
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// The placeholder does not exist.
+    NotFound(PathBuf),
+    /// The operation requires a file, but the placeholder is a directory.
+    IsDirectory(PathBuf),
+    /// The operation requires a directory, but the placeholder is a file.
+    NotDirectory(PathBuf),
+    /// The target of the operation already exists.
+    AlreadyExists(PathBuf),
+    /// The callback failed, or did not respond through its ticket in time.
+    Callback(CloudErrorKind),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "placeholder {path:?} does not exist"),
+            Error::IsDirectory(path) => write!(f, "placeholder {path:?} is a directory"),
+            Error::NotDirectory(path) => write!(f, "placeholder {path:?} is not a directory"),
+            Error::AlreadyExists(path) => write!(f, "placeholder {path:?} already exists"),
+            Error::Callback(kind) => write!(f, "callback failed with {kind:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Fails the operation with the error returned by the callback, passing it to the hook
+    /// installed with [error::set_hook][crate::error::set_hook] first.
+    fn failed(err: CloudError) -> Self {
+        error::report(&err);
+        Error::Callback(err.kind())
+    }
+}
+
+fn align_down(offset: u64) -> u64 {
+    offset - offset % TRANSFER_ALIGNMENT
+}
+
+fn align_up(offset: u64) -> u64 {
+    offset.saturating_add(TRANSFER_ALIGNMENT - 1) / TRANSFER_ALIGNMENT * TRANSFER_ALIGNMENT
+}
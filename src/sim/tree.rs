@@ -0,0 +1,230 @@
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{placeholder_file::PlaceholderFile, usn::Usn};
+
+/// The size of the blocks the content of the files is stored in.
+const BLOCK_SIZE: u64 = 4096;
+
+/// A placeholder file/directory in the simulated sync root.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) file_id: i64,
+    pub(crate) blob: Vec<u8>,
+    pub(crate) in_sync: bool,
+    pub(crate) block_dehydration: bool,
+    pub(crate) kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum NodeKind {
+    Directory {
+        /// Whether or not the children of the directory have been fetched.
+        populated: bool,
+    },
+    File {
+        /// The size of the file.
+        size: u64,
+        /// The blocks of content written to the file keyed by their offset, the bytes of the other
+        /// blocks being zeroed.
+        data: BTreeMap<u64, Vec<u8>>,
+        /// The sorted, non-overlapping ranges of data present on "disk".
+        present: Vec<Range<u64>>,
+        /// The sorted, non-overlapping ranges of data that were validated.
+        validated: Vec<Range<u64>>,
+    },
+}
+
+impl Node {
+    pub(crate) fn is_directory(&self) -> bool {
+        matches!(self.kind, NodeKind::Directory { .. })
+    }
+
+    pub(crate) fn size(&self) -> u64 {
+        match &self.kind {
+            NodeKind::Directory { .. } => 0,
+            NodeKind::File { size, .. } => *size,
+        }
+    }
+}
+
+/// The virtual placeholder tree of a simulated sync root.
+///
+/// Nodes are keyed by their path relative to the sync root, the root itself is keyed by an empty
+/// path.
+#[derive(Debug)]
+pub(crate) struct Tree {
+    nodes: BTreeMap<PathBuf, Node>,
+    next_file_id: i64,
+    next_usn: Usn,
+}
+
+impl Tree {
+    pub(crate) fn new() -> Self {
+        let mut tree = Self {
+            nodes: BTreeMap::new(),
+            next_file_id: 1,
+            next_usn: 1,
+        };
+        let file_id = tree.file_id();
+        tree.nodes.insert(
+            PathBuf::new(),
+            Node {
+                file_id,
+                blob: Vec::new(),
+                in_sync: true,
+                block_dehydration: false,
+                kind: NodeKind::Directory { populated: false },
+            },
+        );
+
+        tree
+    }
+
+    fn file_id(&mut self) -> i64 {
+        self.next_file_id += 1;
+        self.next_file_id - 1
+    }
+
+    fn usn(&mut self) -> Usn {
+        self.next_usn += 1;
+        self.next_usn - 1
+    }
+
+    pub(crate) fn get(&self, path: &Path) -> Option<&Node> {
+        self.nodes.get(path)
+    }
+
+    pub(crate) fn get_mut(&mut self, path: &Path) -> Option<&mut Node> {
+        self.nodes.get_mut(path)
+    }
+
+    /// The paths of the direct children of the directory.
+    pub(crate) fn children(&self, path: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .keys()
+            .filter(|child| child.parent() == Some(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Inserts a placeholder under the directory, mirroring `CF_OPERATION_TYPE_TRANSFER_PLACEHOLDERS`.
+    ///
+    /// Returns `false` if a placeholder already exists and overwriting was not requested.
+    pub(crate) fn insert(&mut self, parent: &Path, placeholder: &mut PlaceholderFile) -> bool {
+        let path = parent.join(&placeholder.relative_path);
+        if self.nodes.contains_key(&path) && !placeholder.overwrite {
+            return false;
+        }
+
+        self.remove(&path);
+        let metadata = placeholder.metadata;
+        let node = Node {
+            file_id: self.file_id(),
+            blob: placeholder.blob.clone(),
+            in_sync: placeholder.mark_in_sync,
+            block_dehydration: placeholder.block_dehydration,
+            kind: match metadata.is_directory() {
+                true => NodeKind::Directory {
+                    populated: placeholder.has_no_children,
+                },
+                false => NodeKind::File {
+                    size: metadata.size,
+                    data: BTreeMap::new(),
+                    present: Vec::new(),
+                    validated: Vec::new(),
+                },
+            },
+        };
+        self.nodes.insert(path, node);
+        placeholder.usn = self.usn();
+
+        true
+    }
+
+    /// Removes the node and all of its descendants.
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.nodes
+            .retain(|key, _| !key.starts_with(path) || path.as_os_str().is_empty());
+    }
+
+    /// Moves the node and all of its descendants.
+    pub(crate) fn rename(&mut self, from: &Path, to: &Path) {
+        let moved = self
+            .nodes
+            .keys()
+            .filter(|key| key.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in moved {
+            let node = self.nodes.remove(&key).unwrap();
+            let relative = key.strip_prefix(from).unwrap();
+            let target = match relative.as_os_str().is_empty() {
+                true => to.to_path_buf(),
+                false => to.join(relative),
+            };
+            self.nodes.insert(target, node);
+        }
+    }
+}
+
+/// Writes the buffer at the offset of the blocks of a file, allocating the blocks it covers.
+pub(crate) fn write_data(data: &mut BTreeMap<u64, Vec<u8>>, offset: u64, buf: &[u8]) {
+    let mut written = 0;
+    while written < buf.len() {
+        let position = offset + written as u64;
+        let start = (position % BLOCK_SIZE) as usize;
+        let block = data
+            .entry(position - start as u64)
+            .or_insert_with(|| vec![0; BLOCK_SIZE as usize]);
+        let len = (block.len() - start).min(buf.len() - written);
+        block[start..start + len].copy_from_slice(&buf[written..written + len]);
+        written += len;
+    }
+}
+
+/// Reads the blocks of a file at the offset into the buffer, zeroing the bytes of the blocks that
+/// were never written.
+pub(crate) fn read_data(data: &BTreeMap<u64, Vec<u8>>, offset: u64, buf: &mut [u8]) {
+    let mut read = 0;
+    while read < buf.len() {
+        let position = offset + read as u64;
+        let start = (position % BLOCK_SIZE) as usize;
+        let len = (BLOCK_SIZE as usize - start).min(buf.len() - read);
+        match data.get(&(position - start as u64)) {
+            Some(block) => buf[read..read + len].copy_from_slice(&block[start..start + len]),
+            None => buf[read..read + len].fill(0),
+        }
+        read += len;
+    }
+}
+
+/// Adds the range to a sorted list of non-overlapping ranges.
+pub(crate) fn insert_range(ranges: &mut Vec<Range<u64>>, range: Range<u64>) {
+    if range.is_empty() {
+        return;
+    }
+
+    ranges.push(range);
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+/// Whether or not the range is completely covered by the sorted list of ranges.
+pub(crate) fn covers(ranges: &[Range<u64>], range: &Range<u64>) -> bool {
+    range.is_empty()
+        || ranges
+            .iter()
+            .any(|present| present.start <= range.start && range.end <= present.end)
+}
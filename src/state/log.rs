@@ -0,0 +1,119 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+
+use crate::{
+    journal::{self, put_path, put_str, put_u32, put_u64, Journal, Reader},
+    state::{FileState, PinState},
+};
+
+/// The first bytes of a state file.
+const MAGIC: &[u8; 8] = b"CFSTATE1";
+
+/// A change of the store, as persisted in the log.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Change {
+    Put(PathBuf, FileState),
+    Remove(PathBuf),
+}
+
+/// The [Journal] of the store, each record holding the changes of a transaction.
+#[derive(Debug)]
+pub(crate) struct Log(Journal);
+
+impl Log {
+    /// Opens the log at the path, creating it if it does not exist, and reads its entries.
+    ///
+    /// An entry that was not completely written, e.g. after a crash, is cut off along with
+    /// everything after it.
+    pub(crate) fn open(path: &Path) -> io::Result<(Self, Vec<Vec<Change>>)> {
+        let (journal, payloads) = Journal::open(path, MAGIC)?;
+        let entries = payloads
+            .iter()
+            .map(|payload| read_entry(payload).ok_or_else(|| journal::invalid(path)))
+            .collect::<io::Result<_>>()?;
+
+        Ok((Self(journal), entries))
+    }
+
+    /// Appends an entry with the changes, durably.
+    pub(crate) fn append(&mut self, changes: &[Change]) -> io::Result<()> {
+        self.0.append(&entry(changes))
+    }
+
+    /// Replaces the log with a single entry holding the changes, through a temporary file
+    /// renamed over the log.
+    pub(crate) fn rewrite(&mut self, changes: &[Change]) -> io::Result<()> {
+        self.0.rewrite([entry(changes).as_slice()])
+    }
+}
+
+/// Encodes the changes of an entry.
+fn entry(changes: &[Change]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    put_u32(&mut payload, changes.len() as u32);
+    for change in changes {
+        match change {
+            Change::Put(path, state) => {
+                payload.push(1);
+                put_path(&mut payload, path);
+                put_state(&mut payload, state);
+            }
+            Change::Remove(path) => {
+                payload.push(2);
+                put_path(&mut payload, path);
+            }
+        }
+    }
+    payload
+}
+
+/// Decodes the changes of an entry.
+fn read_entry(payload: &[u8]) -> Option<Vec<Change>> {
+    let mut reader = Reader(payload);
+    let count = reader.u32()?;
+    let mut changes = Vec::new();
+    for _ in 0..count {
+        changes.push(match reader.u8()? {
+            1 => Change::Put(reader.path()?, read_state(&mut reader)?),
+            2 => Change::Remove(reader.path()?),
+            _ => return None,
+        });
+    }
+    Some(changes)
+}
+
+fn put_state(buf: &mut Vec<u8>, state: &FileState) {
+    let flags = state.file_id.is_some() as u8
+        | (state.version.is_some() as u8) << 1
+        | (state.usn.is_some() as u8) << 2
+        | (state.in_sync as u8) << 3
+        | (state.directory as u8) << 4;
+    buf.push(flags);
+    put_u64(buf, state.file_id.unwrap_or_default() as u64);
+    put_str(buf, state.version.as_deref().unwrap_or_default());
+    put_u64(buf, state.usn.unwrap_or_default() as u64);
+    put_u64(buf, state.mtime.to_raw());
+    put_u64(buf, state.size);
+    buf.push(state.pin as u8);
+}
+
+fn read_state(reader: &mut Reader) -> Option<FileState> {
+    let flags = reader.u8()?;
+    let file_id = reader.u64()? as i64;
+    let version = reader.str()?;
+    let usn = reader.u64()? as i64;
+    Some(FileState {
+        file_id: (flags & 1 != 0).then_some(file_id),
+        version: (flags & 1 << 1 != 0).then_some(version),
+        usn: (flags & 1 << 2 != 0).then_some(usn),
+        in_sync: flags & 1 << 3 != 0,
+        directory: flags & 1 << 4 != 0,
+        mtime: FileTime::new(reader.u64()?),
+        size: reader.u64()?,
+        pin: PinState::from_u8(reader.u8()?)?,
+    })
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+
+use crate::{
+    error::{self, CloudError},
+    usn::Usn,
+};
+
+use log::{Change, Log};
+
+mod log;
+
+/// The number of changes written to the log before it is considered for compaction.
+const COMPACTION_THRESHOLD: usize = 1024;
+
+/// The pin state of a placeholder as recorded in a [StateStore], see
+/// [PinState][crate::placeholder::PinState] on Windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PinState {
+    /// The platform could decide freely.
+    #[default]
+    Unspecified = 0,
+    /// The placeholder is kept hydrated.
+    Pinned = 1,
+    /// The placeholder is kept dehydrated.
+    Unpinned = 2,
+    /// The placeholder will never sync to the cloud.
+    Excluded = 3,
+    /// The placeholder inherits the pin state of its parent.
+    Inherit = 4,
+}
+
+impl PinState {
+    fn from_u8(value: u8) -> Option<Self> {
+        [
+            Self::Unspecified,
+            Self::Pinned,
+            Self::Unpinned,
+            Self::Excluded,
+            Self::Inherit,
+        ]
+        .get(value as usize)
+        .copied()
+    }
+}
+
+#[cfg(windows)]
+impl From<crate::placeholder::PinState> for PinState {
+    fn from(state: crate::placeholder::PinState) -> Self {
+        use crate::placeholder::PinState as Placeholder;
+        match state {
+            Placeholder::Unspecified => Self::Unspecified,
+            Placeholder::Pinned => Self::Pinned,
+            Placeholder::Unpinned => Self::Unpinned,
+            Placeholder::Excluded => Self::Excluded,
+            Placeholder::Inherit => Self::Inherit,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<PinState> for crate::placeholder::PinState {
+    fn from(state: PinState) -> Self {
+        match state {
+            PinState::Unspecified => Self::Unspecified,
+            PinState::Pinned => Self::Pinned,
+            PinState::Unpinned => Self::Unpinned,
+            PinState::Excluded => Self::Excluded,
+            PinState::Inherit => Self::Inherit,
+        }
+    }
+}
+
+/// What was last synced of a file or directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileState {
+    /// The id of the placeholder on the volume.
+    pub file_id: Option<i64>,
+    /// The version of the file on the remote, e.g. its etag.
+    pub version: Option<String>,
+    /// The [Usn] of the placeholder when it was last synced.
+    pub usn: Option<Usn>,
+    /// The last write time of the local file when it was last synced.
+    pub mtime: FileTime,
+    /// The size of the file when it was last synced.
+    pub size: u64,
+    /// The pin state of the placeholder.
+    pub pin: PinState,
+    /// Whether or not the placeholder is in sync with the remote.
+    pub in_sync: bool,
+    /// Whether or not the entry is a directory.
+    pub directory: bool,
+}
+
+/// A persistent record of the sync state of the files under a sync root, keyed by their path
+/// relative to the sync root and indexed by their file id.
+///
+/// The store is kept in memory and persisted to an append-only log: each committed
+/// [Transaction] is written as a single checksummed entry and flushed to the disk before
+/// [Transaction::commit] returns. A transaction interrupted by a crash is discarded as a whole
+/// when the store is opened again. The log is compacted once it mostly holds overwritten
+/// changes.
+///
+/// ```no_run
+/// # use cloud_filter::state::{FileState, StateStore};
+/// let mut store = StateStore::open("state.log")?;
+///
+/// let mut transaction = store.transaction();
+/// transaction.put(
+///     "dir/file.txt",
+///     FileState {
+///         version: Some("etag".to_owned()),
+///         in_sync: true,
+///         ..Default::default()
+///     },
+/// );
+/// transaction.rename("dir", "moved");
+/// transaction.commit()?;
+///
+/// assert!(store.get("moved/file.txt").unwrap().in_sync);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct StateStore {
+    log: Log,
+    entries: BTreeMap<PathBuf, FileState>,
+    ids: HashMap<i64, PathBuf>,
+    written: usize,
+}
+
+impl StateStore {
+    /// Opens the store persisted at the path, creating it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let (log, transactions) = Log::open(path.as_ref())?;
+        let mut store = Self {
+            log,
+            entries: BTreeMap::new(),
+            ids: HashMap::new(),
+            written: 0,
+        };
+        for changes in transactions {
+            store.written += changes.len();
+            store.apply(changes);
+        }
+
+        Ok(store)
+    }
+
+    /// The state of the file or directory at the path relative to the sync root.
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&FileState> {
+        self.entries.get(&normalize(path.as_ref()))
+    }
+
+    /// The path and the state of the placeholder with the file id.
+    pub fn find_by_id(&self, file_id: i64) -> Option<(&Path, &FileState)> {
+        let path = self.ids.get(&file_id)?;
+        Some((path, &self.entries[path]))
+    }
+
+    /// The paths and the states in the store, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &FileState)> {
+        self.entries
+            .iter()
+            .map(|(path, state)| (path.as_path(), state))
+    }
+
+    /// The number of entries in the store.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether or not the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Starts a [Transaction], whose changes are applied together by [Transaction::commit].
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            store: self,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Rewrites the log with the current entries only.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let changes = self
+            .entries
+            .iter()
+            .map(|(path, state)| Change::Put(path.clone(), state.clone()))
+            .collect::<Vec<_>>();
+        self.log.rewrite(&changes)?;
+        self.written = changes.len();
+        Ok(())
+    }
+
+    fn apply(&mut self, changes: Vec<Change>) {
+        for change in changes {
+            match change {
+                Change::Put(path, state) => {
+                    if let Some(old) = self.entries.get(&path).and_then(|old| old.file_id) {
+                        self.unindex(old, &path);
+                    }
+                    if let Some(file_id) = state.file_id {
+                        self.ids.insert(file_id, path.clone());
+                    }
+                    self.entries.insert(path, state);
+                }
+                Change::Remove(path) => {
+                    if let Some(file_id) = self.entries.remove(&path).and_then(|old| old.file_id) {
+                        self.unindex(file_id, &path);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Drops the file id from the index, unless it was moved to another path already, e.g. by a
+    /// rename applied before the removal of its source.
+    fn unindex(&mut self, file_id: i64, path: &Path) {
+        if self.ids.get(&file_id).map(PathBuf::as_path) == Some(path) {
+            self.ids.remove(&file_id);
+        }
+    }
+}
+
+/// A set of changes to a [StateStore] applied atomically by [Transaction::commit].
+///
+/// The changes are visible through [Transaction::get] until the transaction is committed, and
+/// discarded if it is dropped without being committed.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    store: &'a mut StateStore,
+    /// The new state of each changed path, [None] if it is removed.
+    pending: BTreeMap<PathBuf, Option<FileState>>,
+}
+
+impl Transaction<'_> {
+    /// The state of the path, including the changes of the transaction.
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&FileState> {
+        let path = normalize(path.as_ref());
+        match self.pending.get(&path) {
+            Some(state) => state.as_ref(),
+            None => self.store.entries.get(&path),
+        }
+    }
+
+    /// Sets the state of the path.
+    pub fn put(&mut self, path: impl AsRef<Path>, state: FileState) -> &mut Self {
+        self.pending.insert(normalize(path.as_ref()), Some(state));
+        self
+    }
+
+    /// Modifies the state of the path, returning `false` if the path has no state.
+    pub fn update(&mut self, path: impl AsRef<Path>, f: impl FnOnce(&mut FileState)) -> bool {
+        let path = normalize(path.as_ref());
+        let Some(mut state) = self.get(&path).cloned() else {
+            return false;
+        };
+
+        f(&mut state);
+        self.pending.insert(path, Some(state));
+        true
+    }
+
+    /// Removes the state of the path and of its descendants.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        for path in self.subtree(&normalize(path.as_ref())) {
+            self.pending.insert(path, None);
+        }
+        self
+    }
+
+    /// Moves the state of the path and of its descendants under the new path, replacing the
+    /// states already there.
+    pub fn rename(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> &mut Self {
+        let (from, to) = (normalize(from.as_ref()), normalize(to.as_ref()));
+        if from == to {
+            return self;
+        }
+
+        let moved = self
+            .subtree(&from)
+            .into_iter()
+            .filter_map(|path| {
+                let state = self.get(&path).cloned()?;
+                let rest = path.strip_prefix(&from).unwrap();
+                Some((normalize(&to.join(rest)), state))
+            })
+            .collect::<Vec<_>>();
+        self.remove(&from);
+        self.remove(&to);
+        for (path, state) in moved {
+            self.pending.insert(path, Some(state));
+        }
+        self
+    }
+
+    /// Applies the changes, durably.
+    ///
+    /// On error, neither the store nor its log are changed.
+    pub fn commit(self) -> io::Result<()> {
+        let changes = self
+            .pending
+            .into_iter()
+            .map(|(path, state)| match state {
+                Some(state) => Change::Put(path, state),
+                None => Change::Remove(path),
+            })
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let store = self.store;
+        store.log.append(&changes)?;
+        store.written += changes.len();
+        store.apply(changes);
+
+        if store.written > COMPACTION_THRESHOLD && store.written > 2 * store.entries.len() {
+            if let Err(err) = store.compact() {
+                error::report(&CloudError::from(err).context("failed to compact the state"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The path and its descendants that have a state, including the changes of the
+    /// transaction.
+    fn subtree(&self, root: &Path) -> Vec<PathBuf> {
+        let mut paths = self
+            .store
+            .entries
+            .range(root.to_path_buf()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(root))
+            .chain(self.pending.keys().filter(|path| path.starts_with(root)))
+            .filter(|path| self.get(path).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// The path with its redundant separators and `.` components removed, the key of the store.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
@@ -0,0 +1,127 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    metadata::Metadata,
+    store::{Entry, RemoteStore},
+};
+
+/// A [RemoteStore] serving a local directory.
+///
+/// Useful to mirror a folder, e.g. a network share or a backup drive, into a sync root, and as a
+/// reference [RemoteStore] in tests.
+#[derive(Debug, Clone)]
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    /// Creates a new [DirectoryStore] serving the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The directory being served.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves the path relative to the root, rejecting paths escaping it.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        match path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            true => Ok(self.root.join(path)),
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is not relative to the root"),
+            )),
+        }
+    }
+}
+
+impl RemoteStore for DirectoryStore {
+    fn stat(&self, path: &Path) -> io::Result<Metadata> {
+        fs::metadata(self.resolve(path)?).map(Into::into)
+    }
+
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<Entry>> {
+        fs::read_dir(self.resolve(path)?)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(Entry::new(entry.file_name(), entry.metadata()?.into()))
+            })
+            .collect()
+    }
+
+    fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(RangeReader {
+            file: File::open(self.resolve(path)?)?,
+            range,
+        }))
+    }
+
+    fn write(&self, path: &Path, data: &mut dyn Read) -> io::Result<Metadata> {
+        let mut file = File::create(self.resolve(path)?)?;
+        io::copy(data, &mut file)?;
+        file.sync_all()?;
+
+        file.metadata().map(Into::into)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<Metadata> {
+        let path = self.resolve(path)?;
+        fs::create_dir(&path)?;
+        fs::metadata(path).map(Into::into)
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let path = self.resolve(path)?;
+        match fs::symlink_metadata(&path)?.is_dir() {
+            true => fs::remove_dir_all(path),
+            false => fs::remove_file(path),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(self.resolve(from)?, self.resolve(to)?)
+    }
+}
+
+/// Reads a range of the file through positioned reads.
+struct RangeReader {
+    file: File,
+    range: Range<u64>,
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (self.range.end.saturating_sub(self.range.start)).min(buf.len() as u64);
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let read = read_at(&self.file, &mut buf[..len as usize], self.range.start)?;
+        self.range.start += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+
+    file.seek_read(buf, offset)
+}
+
+#[cfg(not(windows))]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+
+    file.read_at(buf, offset)
+}
@@ -0,0 +1,256 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io::{self, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{CResult, CloudError, CloudErrorKind},
+    filter::{info, ticket, Request, SyncFilter},
+    metadata::Metadata,
+    placeholder_file::PlaceholderFile,
+};
+
+pub use directory::DirectoryStore;
+
+mod directory;
+
+/// The default size of the chunks transferred by [StoreFilter::fetch_data].
+const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+/// The alignment required by `CF_OPERATION_TYPE_TRANSFER_DATA`.
+const PAGE_SIZE: u64 = 4096;
+
+/// The storage side of a sync engine.
+///
+/// Paths are relative to the root of the store, the root itself being the empty path. They
+/// mirror the paths of the placeholders relative to the sync root.
+pub trait RemoteStore: Send + Sync {
+    /// The [Metadata] of the file or directory.
+    fn stat(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// The entries of the directory.
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<Entry>>;
+
+    /// A reader over the range of the file.
+    ///
+    /// The reader must yield the whole range unless the file is shorter.
+    fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Replaces the content of the file, creating it if it does not exist.
+    ///
+    /// Returns the [Metadata] of the file once written.
+    fn write(&self, path: &Path, data: &mut dyn Read) -> io::Result<Metadata>;
+
+    /// Creates the directory, its parent directory being already created.
+    ///
+    /// Returns the [Metadata] of the directory once created. Unsupported by default.
+    fn create_dir(&self, path: &Path) -> io::Result<Metadata> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("failed to create {path:?}, unsupported by the store"),
+        ))
+    }
+
+    /// Deletes the file or directory along with its descendants.
+    fn delete(&self, path: &Path) -> io::Result<()>;
+
+    /// Moves the file or directory.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// An entry of a directory listed by [RemoteStore::list_dir].
+#[derive(Debug, Clone)]
+pub struct Entry {
+    name: OsString,
+    metadata: Metadata,
+}
+
+impl Entry {
+    /// Creates a new [Entry].
+    pub fn new(name: impl Into<OsString>, metadata: Metadata) -> Self {
+        Self {
+            name: name.into(),
+            metadata,
+        }
+    }
+
+    /// The name of the entry within its directory.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// The [Metadata] of the entry.
+    pub fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+}
+
+/// A [SyncFilter][crate::filter::SyncFilter] serving placeholders from a [RemoteStore].
+///
+/// - [SyncFilter::fetch_placeholders][crate::filter::SyncFilter::fetch_placeholders] lists the
+///   directory and creates in-sync placeholders, mapped back to the store by their path relative
+///   to the sync root,
+/// - [SyncFilter::fetch_data][crate::filter::SyncFilter::fetch_data] streams the required range in
+///   4KiB-aligned chunks, reporting progress after each one and stopping with
+///   [CloudErrorKind::RequestCancelled] once the hydration is cancelled,
+/// - [SyncFilter::delete][crate::filter::SyncFilter::delete] and
+///   [SyncFilter::rename][crate::filter::SyncFilter::rename] are forwarded to the store, a
+///   placeholder moved out of the sync root is deleted from it,
+/// - [SyncFilter::dehydrate][crate::filter::SyncFilter::dehydrate] is always approved.
+///
+/// The store is addressed by the path of the placeholder relative to the sync root, which stays
+/// valid across the renames forwarded to it. [io::Error]s are mapped to the closest
+/// [CloudErrorKind].
+#[derive(Debug)]
+pub struct StoreFilter<S> {
+    store: S,
+    root: PathBuf,
+    chunk_size: usize,
+}
+
+impl<S: RemoteStore> StoreFilter<S> {
+    /// Creates a new [StoreFilter] for the sync root at the given path.
+    pub fn new(store: S, root: impl Into<PathBuf>) -> Self {
+        Self {
+            store,
+            root: root.into(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// The size of the chunks read from the store and written to the placeholder, defaults to
+    /// 256KiB.
+    ///
+    /// The size must be a non-zero multiple of 4KiB.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        assert!(
+            size != 0 && (size as u64).is_multiple_of(PAGE_SIZE),
+            "chunk size must be a non-zero multiple of {PAGE_SIZE} bytes, got {size} bytes"
+        );
+        self.chunk_size = size;
+        self
+    }
+
+    /// A reference to the inner [RemoteStore].
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// The path of the placeholder relative to the sync root.
+    fn remote_path(&self, path: &Path) -> CResult<PathBuf> {
+        path.strip_prefix(&self.root)
+            .map(Path::to_path_buf)
+            .map_err(|_| {
+                CloudError::new(
+                    CloudErrorKind::NotUnderSyncRoot,
+                    format!("{path:?} is not under {:?}", self.root),
+                )
+            })
+    }
+}
+
+impl<S: RemoteStore> SyncFilter for StoreFilter<S> {
+    fn fetch_data(
+        &self,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let path = self.remote_path(&request.path())?;
+        let range = info.required_file_range();
+        // only the last chunk of the file may be unaligned
+        let start = range.start - range.start % PAGE_SIZE;
+        let end = range
+            .end
+            .next_multiple_of(PAGE_SIZE)
+            .min(request.file_size());
+
+        let context = || format!("failed to download {path:?} ({start}..{end})");
+        let reader = self
+            .store
+            .read_range(&path, start..end)
+            .map_err(|err| CloudError::from(err).context(context()))?;
+        let mut writer = ticket.writer(start..end).chunk_size(self.chunk_size);
+        let written = io::copy(&mut reader.take(end - start), &mut writer).map_err(|err| {
+            match ticket.cancellation_token().is_cancelled() {
+                true => CloudError::with_source(CloudErrorKind::RequestCancelled, err),
+                false => CloudError::from(err),
+            }
+            .context(context())
+        })?;
+        if written != end - start {
+            return Err(CloudError::new(
+                CloudErrorKind::NotInSync,
+                format!("{path:?} is shorter on the remote, got {written} bytes"),
+            ));
+        }
+
+        writer
+            .finish()
+            .map_err(|err| CloudError::from(err).context(context()))
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let path = self.remote_path(&request.path())?;
+        let mut placeholders = self
+            .store
+            .list_dir(&path)
+            .map_err(|err| CloudError::from(err).context(format!("failed to list {path:?}")))?
+            .into_iter()
+            .map(|entry| {
+                PlaceholderFile::new(&entry.name)
+                    .metadata(entry.metadata)
+                    .mark_in_sync()
+            })
+            .collect::<Vec<_>>();
+
+        Ok(ticket.pass_with_placeholder(&mut placeholders)?)
+    }
+
+    fn dehydrate(
+        &self,
+        _request: Request,
+        ticket: ticket::Dehydrate,
+        _info: info::Dehydrate,
+    ) -> CResult<()> {
+        Ok(ticket.pass()?)
+    }
+
+    fn delete(&self, request: Request, ticket: ticket::Delete, info: info::Delete) -> CResult<()> {
+        if !info.is_undelete() {
+            let path = self.remote_path(&request.path())?;
+            self.store.delete(&path).map_err(|err| {
+                CloudError::from(err).context(format!("failed to delete {path:?}"))
+            })?;
+        }
+
+        Ok(ticket.pass()?)
+    }
+
+    fn rename(&self, request: Request, ticket: ticket::Rename, info: info::Rename) -> CResult<()> {
+        match (info.source_in_scope(), info.target_in_scope()) {
+            (true, true) => {
+                let from = self.remote_path(&request.path())?;
+                let to = self.remote_path(&info.target_path())?;
+                self.store.rename(&from, &to).map_err(|err| {
+                    CloudError::from(err).context(format!("failed to move {from:?} to {to:?}"))
+                })?;
+            }
+            (true, false) => {
+                let path = self.remote_path(&request.path())?;
+                self.store.delete(&path).map_err(|err| {
+                    CloudError::from(err).context(format!("failed to delete {path:?}"))
+                })?;
+            }
+            _ => {}
+        }
+
+        Ok(ticket.pass()?)
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+
+use crate::sync::{Action, LocalFile, RemoteFile};
+
+/// The hook deciding how to resolve each conflict.
+type Callback = Box<dyn Fn(&Conflict) -> Resolution + Send + Sync>;
+
+/// How a file or directory diverged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The file was changed on both sides.
+    Edited,
+    /// The file was changed locally and deleted on the remote.
+    EditedDeleted,
+    /// The file was deleted locally and changed on the remote.
+    DeletedEdited,
+    /// The file was added on both sides with different contents.
+    Added,
+    /// A file on one side is a directory on the other.
+    TypeChanged,
+    /// The placeholder changed after it was scanned, its [Usn][crate::usn::Usn] no longer
+    /// matching.
+    Stale,
+}
+
+/// A file or directory that diverged on both sides since it was last synced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// The path relative to the sync root and to the root of the remote.
+    pub path: PathBuf,
+    /// How the file diverged.
+    pub kind: ConflictKind,
+    /// The local file, [None] if it was deleted.
+    pub local: Option<LocalFile>,
+    /// The remote file, [None] if it was deleted.
+    pub remote: Option<RemoteFile>,
+}
+
+impl Conflict {
+    /// The actions applying the resolution.
+    ///
+    /// - [Resolution::KeepBoth] moves the local file aside under its [conflict_name], uploads it
+    ///   and creates the placeholder of the remote file in its place, or restores the side that
+    ///   was deleted,
+    /// - [Resolution::RemoteWins] replaces the local file by the remote one, or deletes it,
+    /// - [Resolution::LocalWins] uploads the local file, or deletes the remote one,
+    /// - [Resolution::Skip] leaves both sides as is.
+    ///
+    /// `exists` tells whether or not a path relative to the sync root is taken.
+    pub fn actions(
+        &self,
+        resolution: Resolution,
+        date: Date,
+        exists: impl Fn(&Path) -> bool,
+    ) -> Vec<Action> {
+        let path = self.path.clone();
+        match (resolution, &self.local, &self.remote) {
+            (Resolution::Skip, _, _) | (_, None, None) => Vec::new(),
+            (Resolution::KeepBoth, Some(_), Some(remote)) => {
+                let copy = conflict_name(&path, date, exists);
+                vec![
+                    Action::SetAside {
+                        path: path.clone(),
+                        to: copy.clone(),
+                    },
+                    Action::Upload {
+                        path: copy,
+                        usn: None,
+                    },
+                    Action::CreateLocal {
+                        path,
+                        remote: remote.clone(),
+                    },
+                ]
+            }
+            (Resolution::KeepBoth | Resolution::LocalWins, Some(local), None) => {
+                vec![Action::Upload {
+                    path,
+                    usn: local.usn,
+                }]
+            }
+            (Resolution::KeepBoth | Resolution::RemoteWins, None, Some(remote)) => {
+                vec![Action::CreateLocal {
+                    path,
+                    remote: remote.clone(),
+                }]
+            }
+            (Resolution::RemoteWins, Some(_), remote) => {
+                let mut actions = vec![Action::DeleteLocal {
+                    path: path.clone(),
+                    usn: None,
+                }];
+                if let Some(remote) = remote {
+                    actions.push(Action::CreateLocal {
+                        path,
+                        remote: remote.clone(),
+                    });
+                }
+                actions
+            }
+            (Resolution::LocalWins, Some(local), Some(remote)) => {
+                let mut actions = Vec::new();
+                if local.directory != remote.metadata.is_directory() {
+                    actions.push(Action::DeleteRemote { path: path.clone() });
+                }
+                actions.push(Action::Upload {
+                    path,
+                    usn: local.usn,
+                });
+                actions
+            }
+            (Resolution::LocalWins, None, Some(_)) => vec![Action::DeleteRemote { path }],
+        }
+    }
+}
+
+/// How to resolve a [Conflict].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keeps both files, the local one under its [conflict_name].
+    KeepBoth,
+    /// Keeps the remote file.
+    RemoteWins,
+    /// Keeps the local file.
+    LocalWins,
+    /// Leaves both sides as is, until the next reconciliation.
+    Skip,
+}
+
+/// Decides how to resolve the conflicts found by the [Reconciler][crate::sync::Reconciler],
+/// defaults to [ConflictPolicy::KeepBoth].
+#[derive(Default)]
+pub enum ConflictPolicy {
+    /// Resolves every conflict with [Resolution::KeepBoth].
+    #[default]
+    KeepBoth,
+    /// Resolves every conflict with [Resolution::RemoteWins].
+    RemoteWins,
+    /// Resolves every conflict with [Resolution::LocalWins].
+    LocalWins,
+    /// Lets the application decide, e.g. by asking the user.
+    Callback(Callback),
+}
+
+impl ConflictPolicy {
+    /// Creates a [ConflictPolicy::Callback].
+    pub fn callback(callback: impl Fn(&Conflict) -> Resolution + Send + Sync + 'static) -> Self {
+        Self::Callback(Box::new(callback))
+    }
+
+    /// The resolution of the conflict.
+    pub fn resolve(&self, conflict: &Conflict) -> Resolution {
+        match self {
+            Self::KeepBoth => Resolution::KeepBoth,
+            Self::RemoteWins => Resolution::RemoteWins,
+            Self::LocalWins => Resolution::LocalWins,
+            Self::Callback(callback) => callback(conflict),
+        }
+    }
+}
+
+impl fmt::Debug for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeepBoth => write!(f, "KeepBoth"),
+            Self::RemoteWins => write!(f, "RemoteWins"),
+            Self::LocalWins => write!(f, "LocalWins"),
+            Self::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+/// A calendar date in UTC, as written in the [conflict_name]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    /// The year.
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+}
+
+impl Date {
+    /// The current date.
+    pub fn today() -> Self {
+        FileTime::now().into()
+    }
+}
+
+impl From<FileTime> for Date {
+    fn from(time: FileTime) -> Self {
+        // the civil calendar from the days since the Unix epoch, shifted to start in March
+        let days = time.to_unix_time().div_euclid(86400) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let month = match shifted_month {
+            0..=9 => shifted_month + 3,
+            _ => shifted_month - 9,
+        };
+
+        Self {
+            year: (year_of_era + era * 400 + (month <= 2) as i64) as i32,
+            month: month as u8,
+            day: (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8,
+        }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The path of the copy of a file kept by [Resolution::KeepBoth], `name (conflict YYYY-MM-DD).ext`
+/// in the same directory.
+///
+/// The suffix is inserted before the last extension, e.g. `archive.tar (conflict 2024-06-01).gz`,
+/// and after the name of the files starting with a dot, e.g. `.profile (conflict 2024-06-01)`. A
+/// counter is appended to the date until the path is not taken, e.g.
+/// `name (conflict 2024-06-01 2).ext`.
+pub fn conflict_name(path: &Path, date: Date, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let Some(stem) = path.file_stem() else {
+        return path.to_owned();
+    };
+
+    (1..)
+        .map(|attempt| {
+            let mut name = stem.to_owned();
+            match attempt {
+                1 => name.push(format!(" (conflict {date})")),
+                _ => name.push(format!(" (conflict {date} {attempt})")),
+            }
+            if let Some(extension) = path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+            path.with_file_name(name)
+        })
+        .find(|path| !exists(path))
+        .unwrap()
+}
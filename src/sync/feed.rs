@@ -0,0 +1,218 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    journal::{self, Journal},
+    sync::{Action, LocalFile, RemoteFile, Snapshot},
+};
+
+/// The first bytes of a cursor file.
+const MAGIC: &[u8; 8] = b"CFCURSR1";
+/// The default number of changes returned by each poll of a [MemoryFeed].
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// A change made on the remote, as reported by a [ChangeFeed].
+///
+/// Paths are relative to the root of the remote and to the sync root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A file or directory was added.
+    Added { path: PathBuf, remote: RemoteFile },
+    /// The contents or the metadata of a file or directory changed.
+    Modified { path: PathBuf, remote: RemoteFile },
+    /// A file or directory was deleted, along with its descendants.
+    Deleted { path: PathBuf },
+    /// A file or directory was renamed or moved, along with its descendants, the remote file
+    /// being the one at the destination.
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+        remote: RemoteFile,
+    },
+}
+
+impl Change {
+    /// The path the change applies to, the source path of the moves.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Added { path, .. } | Self::Modified { path, .. } | Self::Deleted { path } => path,
+            Self::Moved { from, .. } => from,
+        }
+    }
+
+    /// Plans the actions applying the change to the sync root.
+    ///
+    /// The [base][Snapshot::base] and [local][Snapshot::local] sides of the snapshot hold the
+    /// path and, when it is deleted or moved, its descendants, along with the destination of a
+    /// move; its remote side is filled from the change. Local changes are thus kept, a file
+    /// changed on both sides being a [Conflict][Action::Conflict].
+    ///
+    /// A move is applied when the source still exists locally and nothing exists at the
+    /// destination, the moved file being updated, or in conflict, when the remote file changed
+    /// as well. Otherwise the source is planned as deleted and the destination as added, e.g.
+    /// creating the placeholder of a file whose source is missing, or a conflict with the file
+    /// already at the destination.
+    pub fn plan(&self, mut snapshot: Snapshot) -> Vec<Action> {
+        match self {
+            Self::Added { path, remote } | Self::Modified { path, remote } => {
+                snapshot.remote.insert(path.clone(), remote.clone());
+            }
+            Self::Deleted { .. } => {}
+            Self::Moved { from, to, remote } => {
+                if from != to && !snapshot.local.contains_key(to) {
+                    if let Some(local) = snapshot.local.remove(from) {
+                        // the moved file is then compared to the remote at its new path, the
+                        // rename changing its Usn
+                        let mut moved = Snapshot::default();
+                        moved
+                            .base
+                            .extend(snapshot.base.remove(from).map(|base| (to.clone(), base)));
+                        moved
+                            .local
+                            .insert(to.clone(), LocalFile { usn: None, ..local });
+                        moved.remote.insert(to.clone(), remote.clone());
+
+                        let mut actions = vec![Action::RenameLocal {
+                            from: from.clone(),
+                            to: to.clone(),
+                        }];
+                        actions.extend(moved.plan());
+                        return actions;
+                    }
+                }
+                snapshot.remote.insert(to.clone(), remote.clone());
+            }
+        }
+
+        snapshot.plan()
+    }
+}
+
+/// A page of [Change]s returned by [ChangeFeed::poll].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delta {
+    /// The changes, in the order they were made.
+    pub changes: Vec<Change>,
+    /// The cursor to poll the next changes from.
+    pub cursor: String,
+    /// Whether or not more changes are available right away.
+    pub has_more: bool,
+}
+
+/// A source of the changes made on the remote, e.g. the delta API of a cloud provider.
+pub trait ChangeFeed: Send + Sync {
+    /// The changes made since the cursor, from the beginning when [None], i.e. every file and
+    /// directory of the remote as [Change::Added].
+    ///
+    /// A cursor that is no longer valid is an error, after which the sync root can be compared
+    /// to the remote as a whole, e.g. with
+    /// [Reconciler::reconcile][crate::sync::Reconciler::reconcile].
+    fn poll(&self, cursor: Option<&str>) -> io::Result<Delta>;
+}
+
+/// A [ChangeFeed] recording the changes pushed to it, e.g. to drive a
+/// [Reconciler][crate::sync::Reconciler] in tests.
+///
+/// Its cursors are the number of changes already returned.
+#[derive(Debug)]
+pub struct MemoryFeed {
+    changes: Mutex<Vec<Change>>,
+    page_size: usize,
+}
+
+impl MemoryFeed {
+    /// Creates an empty [MemoryFeed].
+    pub fn new() -> Self {
+        Self {
+            changes: Mutex::new(Vec::new()),
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    /// The number of changes returned by each poll, defaults to 100.
+    pub fn page_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "the page size must not be 0");
+        self.page_size = size;
+        self
+    }
+
+    /// Records the change.
+    pub fn push(&self, change: Change) {
+        self.changes.lock().unwrap().push(change);
+    }
+}
+
+impl Default for MemoryFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeFeed for MemoryFeed {
+    fn poll(&self, cursor: Option<&str>) -> io::Result<Delta> {
+        let changes = self.changes.lock().unwrap();
+        let start = match cursor {
+            Some(cursor) => cursor
+                .parse::<usize>()
+                .ok()
+                .filter(|&start| start <= changes.len())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid cursor {cursor:?}"),
+                    )
+                })?,
+            None => 0,
+        };
+        let end = changes.len().min(start + self.page_size);
+
+        Ok(Delta {
+            changes: changes[start..end].to_vec(),
+            cursor: end.to_string(),
+            has_more: end < changes.len(),
+        })
+    }
+}
+
+/// The cursor of a [ChangeFeed], persisted to a file so that polling resumes where it stopped
+/// after a restart.
+///
+/// Each cursor replaces the previous one through a temporary file renamed over the file, so that
+/// a crash leaves either of them.
+#[derive(Debug)]
+pub struct FeedCursor {
+    journal: Journal,
+    cursor: Option<String>,
+}
+
+impl FeedCursor {
+    /// Opens the cursor persisted at the path, creating the file if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let (journal, records) = Journal::open(path, MAGIC)?;
+        let cursor = match records.last() {
+            Some(record) => {
+                Some(String::from_utf8(record.clone()).map_err(|_| journal::invalid(path))?)
+            }
+            None => None,
+        };
+
+        Ok(Self { journal, cursor })
+    }
+
+    /// The cursor, [None] until one is set.
+    pub fn get(&self) -> Option<&str> {
+        self.cursor.as_deref()
+    }
+
+    /// Replaces the cursor, durably.
+    pub fn set(&mut self, cursor: impl Into<String>) -> io::Result<()> {
+        let cursor = cursor.into();
+        self.journal.rewrite([cursor.as_bytes()])?;
+        self.cursor = Some(cursor);
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    ffi::OsString,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{
+    sync::{LocalFile, RemoteFile},
+    usn::Usn,
+};
+
+/// The files under a sync root, as scanned and changed by a
+/// [Reconciler][crate::sync::Reconciler].
+///
+/// Paths are relative to the sync root. `Placeholders` implements it on Windows with the
+/// placeholders of a registered sync root, other implementations, e.g. an in-memory tree, driving
+/// the reconciler without the operating system.
+pub trait LocalTree: Send + Sync {
+    /// The absolute path of the sync root, relating the requests of the callbacks to the tree.
+    fn root(&self) -> &Path;
+
+    /// Reads the file or directory at the path, [None] if it does not exist.
+    fn file(&self, path: &Path) -> io::Result<Option<LocalFile>>;
+
+    /// The names of the entries of the directory.
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<OsString>>;
+
+    /// Creates a placeholder in sync with the remote file, replacing the file at the path if any.
+    ///
+    /// A directory is created without children, its descendants being created separately.
+    fn create(&self, path: &Path, remote: &RemoteFile) -> io::Result<()>;
+
+    /// Updates the metadata of the placeholder from the remote file and marks it in sync,
+    /// dehydrating a file.
+    ///
+    /// Fails when the [Usn] of the placeholder is no longer the given one, if any.
+    fn update(&self, path: &Path, remote: &RemoteFile, usn: Option<Usn>) -> io::Result<()>;
+
+    /// Deletes the file, or the directory along with its descendants.
+    fn delete(&self, path: &Path) -> io::Result<()>;
+
+    /// Renames the file or directory, failing with [io::ErrorKind::AlreadyExists] when the
+    /// destination exists.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Opens the file to read its contents, e.g. to upload them.
+    fn read(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Marks the file or directory in sync once uploaded, converting it to a placeholder if it is
+    /// not one.
+    ///
+    /// Fails when the [Usn] of the file is no longer the given one, if any.
+    fn mark_in_sync(&self, path: &Path, usn: Option<Usn>) -> io::Result<()>;
+}
@@ -0,0 +1,15 @@
+pub use conflict::{conflict_name, Conflict, ConflictKind, ConflictPolicy, Date, Resolution};
+pub use feed::{Change, ChangeFeed, Delta, FeedCursor, MemoryFeed};
+pub use local::LocalTree;
+#[cfg(windows)]
+pub use placeholders::Placeholders;
+pub use plan::{Action, LocalFile, RemoteFile, Snapshot};
+pub use reconciler::{Reconciler, Report};
+
+mod conflict;
+mod feed;
+mod local;
+#[cfg(windows)]
+mod placeholders;
+mod plan;
+mod reconciler;
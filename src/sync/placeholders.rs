@@ -0,0 +1,164 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+
+use crate::{
+    blob,
+    metadata::Metadata,
+    placeholder::{ConvertOptions, Placeholder, UpdateOptions},
+    placeholder_file::PlaceholderFile,
+    sync::{LocalFile, LocalTree, RemoteFile},
+    usn::Usn,
+};
+
+/// The [LocalTree] of a sync root registered on Windows, made of placeholders.
+#[derive(Debug, Clone)]
+pub struct Placeholders {
+    root: PathBuf,
+}
+
+impl Placeholders {
+    /// Creates a new [Placeholders] for the sync root at the given path.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl LocalTree for Placeholders {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn file(&self, path: &Path) -> io::Result<Option<LocalFile>> {
+        let absolute = self.root.join(path);
+        let metadata = match fs::metadata(&absolute) {
+            Ok(metadata) => Metadata::from(metadata),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let placeholder = Placeholder::open(&absolute)?;
+        let info = placeholder.info()?;
+        Ok(Some(LocalFile {
+            file_id: info.as_ref().map(|info| info.file_id()),
+            usn: Some(placeholder.usn()?),
+            mtime: FileTime::new(metadata.last_write_time as u64),
+            size: metadata.size,
+            pin: info
+                .as_ref()
+                .map(|info| info.pin_state().into())
+                .unwrap_or_default(),
+            modified: info.is_none_or(|info| !info.is_in_sync() || info.modified_data_size() != 0),
+            directory: metadata.is_directory(),
+        }))
+    }
+
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        fs::read_dir(self.root.join(path))?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect()
+    }
+
+    fn create(&self, path: &Path, remote: &RemoteFile) -> io::Result<()> {
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{path:?} has no name"),
+                ))
+            }
+        };
+
+        let mut placeholder = PlaceholderFile::new(Path::new(name))
+            .metadata(remote.metadata)
+            .mark_in_sync()
+            .overwrite()
+            .blob(identity(path)?);
+        if remote.metadata.is_directory() {
+            placeholder = placeholder.has_no_children();
+        }
+        placeholder.create(self.root.join(parent))?;
+        Ok(())
+    }
+
+    fn update(&self, path: &Path, remote: &RemoteFile, usn: Option<Usn>) -> io::Result<()> {
+        let mut placeholder = Placeholder::options()
+            .write_access()
+            .exclusive()
+            .open(self.root.join(path))?;
+        let mut usn = match usn {
+            Some(usn) => usn,
+            None => placeholder.usn()?,
+        };
+        let mut options = UpdateOptions::default()
+            .metadata(remote.metadata)
+            .mark_in_sync();
+        if !remote.metadata.is_directory() {
+            options = options.dehydrate();
+        }
+        placeholder.update(options, &mut usn)?;
+        Ok(())
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let absolute = self.root.join(path);
+        match fs::symlink_metadata(&absolute)?.is_dir() {
+            true => fs::remove_dir_all(&absolute),
+            false => fs::remove_file(&absolute),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        // a file at the destination would be replaced
+        let to = self.root.join(to);
+        if fs::symlink_metadata(&to).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{to:?} already exists"),
+            ));
+        }
+        fs::rename(self.root.join(from), to)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(self.root.join(path))?))
+    }
+
+    fn mark_in_sync(&self, path: &Path, usn: Option<Usn>) -> io::Result<()> {
+        let mut placeholder = Placeholder::options()
+            .write_access()
+            .open(self.root.join(path))?;
+        let mut usn = match usn {
+            Some(usn) => usn,
+            None => placeholder.usn()?,
+        };
+        match placeholder.info()? {
+            Some(_) => placeholder.mark_in_sync(true, &mut usn)?,
+            None => placeholder.convert_to_placeholder(
+                ConvertOptions::default()
+                    .mark_in_sync()
+                    .has_children()
+                    .blob(identity(path)?),
+                &mut usn,
+            )?,
+        };
+        Ok(())
+    }
+}
+
+/// Encodes the path as the [FileIdentity][crate::blob::FileIdentity] blob of the placeholder,
+/// failing instead of panicking when it does not fit.
+fn identity(path: &Path) -> io::Result<Vec<u8>> {
+    blob::encode(&path.to_path_buf()).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            err.context(format!("{path:?} is too long to be stored in a blob")),
+        )
+    })
+}
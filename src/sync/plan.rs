@@ -0,0 +1,409 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use nt_time::FileTime;
+
+use crate::{
+    metadata::Metadata,
+    state::{FileState, PinState},
+    sync::{Conflict, ConflictKind},
+    usn::Usn,
+};
+
+/// A file or directory on the remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteFile {
+    /// The [Metadata] of the file or directory.
+    pub metadata: Metadata,
+    /// The version of the file, compared to [FileState::version] to detect remote changes.
+    pub version: String,
+}
+
+impl RemoteFile {
+    /// Creates a new [RemoteFile].
+    pub fn new(metadata: Metadata, version: impl Into<String>) -> Self {
+        Self {
+            metadata,
+            version: version.into(),
+        }
+    }
+
+    /// The [LocalFile] of a placeholder in sync with the remote file, without its id and [Usn].
+    pub fn to_local(&self) -> LocalFile {
+        LocalFile {
+            mtime: FileTime::new(self.metadata.last_write_time as u64),
+            size: self.metadata.size,
+            directory: self.metadata.is_directory(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The version is derived from the last write time and the size of the file.
+impl From<Metadata> for RemoteFile {
+    fn from(metadata: Metadata) -> Self {
+        Self::new(
+            metadata,
+            format!("{:x}-{:x}", metadata.last_write_time, metadata.size),
+        )
+    }
+}
+
+/// A file or directory under the sync root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalFile {
+    /// The id of the file on the volume, [None] if it is not a placeholder.
+    pub file_id: Option<i64>,
+    /// The [Usn] of the file when it was scanned, guarding the changes applied to it.
+    pub usn: Option<Usn>,
+    /// The last write time of the file.
+    pub mtime: FileTime,
+    /// The size of the file.
+    pub size: u64,
+    /// The pin state of the placeholder.
+    pub pin: PinState,
+    /// Whether or not the file has local changes, i.e. it is not a placeholder, it is not in sync
+    /// or it has modified data.
+    pub modified: bool,
+    /// Whether or not the entry is a directory.
+    pub directory: bool,
+}
+
+impl LocalFile {
+    /// The [FileState] of the file once in sync with the version of the remote.
+    pub fn to_state(&self, version: impl Into<Option<String>>) -> FileState {
+        FileState {
+            file_id: self.file_id,
+            version: version.into(),
+            usn: self.usn,
+            mtime: self.mtime,
+            size: self.size,
+            pin: self.pin,
+            in_sync: true,
+            directory: self.directory,
+        }
+    }
+
+    /// Whether or not the file differs from the state it was last synced with.
+    fn changed(&self, base: &FileState) -> bool {
+        !self.directory && (self.modified || self.size != base.size || self.mtime != base.mtime)
+    }
+
+    /// Whether or not the file has the same last write time and size as on the remote.
+    fn same(&self, remote: &RemoteFile) -> bool {
+        self.size == remote.metadata.size
+            && self.mtime.to_raw() as i64 == remote.metadata.last_write_time
+    }
+}
+
+/// A change planned by [Snapshot::plan].
+///
+/// Paths are relative to the sync root and to the root of the remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Creates the placeholder of a file or directory added on the remote.
+    CreateLocal { path: PathBuf, remote: RemoteFile },
+    /// Updates the placeholder of a file changed on the remote, dehydrating it.
+    UpdateLocal {
+        path: PathBuf,
+        remote: RemoteFile,
+        usn: Option<Usn>,
+    },
+    /// Deletes the file or directory deleted on the remote.
+    DeleteLocal { path: PathBuf, usn: Option<Usn> },
+    /// Moves the file moved on the remote.
+    RenameLocal { from: PathBuf, to: PathBuf },
+    /// Uploads the file added or changed locally, or creates the directory added locally, then
+    /// marks it in sync.
+    Upload { path: PathBuf, usn: Option<Usn> },
+    /// Deletes the file or directory deleted locally from the remote.
+    DeleteRemote { path: PathBuf },
+    /// Moves the file or directory moved locally on the remote.
+    RenameRemote { from: PathBuf, to: PathBuf },
+    /// Records the state of a file or directory found identical on both sides.
+    Track {
+        path: PathBuf,
+        local: LocalFile,
+        remote: RemoteFile,
+    },
+    /// Removes the state of a file or directory deleted on both sides.
+    Forget { path: PathBuf },
+    /// Moves the local file aside, forgetting its state, e.g. to keep a copy of a [Conflict].
+    SetAside { path: PathBuf, to: PathBuf },
+    /// Resolves a file or directory changed on both sides.
+    Conflict(Conflict),
+}
+
+impl Action {
+    /// The path the action applies to, the source path of the renames.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::CreateLocal { path, .. }
+            | Self::UpdateLocal { path, .. }
+            | Self::DeleteLocal { path, .. }
+            | Self::Upload { path, .. }
+            | Self::DeleteRemote { path }
+            | Self::Track { path, .. }
+            | Self::Forget { path }
+            | Self::SetAside { path, .. }
+            | Self::Conflict(Conflict { path, .. }) => path,
+            Self::RenameLocal { from, .. } | Self::RenameRemote { from, .. } => from,
+        }
+    }
+
+    /// Whether or not the action removes the path along with its descendants.
+    fn removes(&self) -> bool {
+        matches!(
+            self,
+            Self::DeleteLocal { .. } | Self::DeleteRemote { .. } | Self::Forget { .. }
+        )
+    }
+}
+
+/// The trees compared by [Snapshot::plan], keyed by their paths relative to the sync root.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// The state of the files when they were last synced, e.g. from a
+    /// [StateStore][crate::state::StateStore].
+    pub base: BTreeMap<PathBuf, FileState>,
+    /// The files under the sync root.
+    pub local: BTreeMap<PathBuf, LocalFile>,
+    /// The files on the remote.
+    pub remote: BTreeMap<PathBuf, RemoteFile>,
+}
+
+impl Snapshot {
+    /// Plans the actions bringing both sides in sync, without touching either of them.
+    ///
+    /// Each path is compared on both sides against its last synced state: a side changed the file
+    /// when its [FileState::version] differs on the remote, or when it is
+    /// [modified][LocalFile::modified] or its size or last write time differs locally.
+    ///
+    /// - A change on a single side is applied to the other one,
+    /// - a file added on both sides is tracked when identical, updated when unmodified locally,
+    ///   and a conflict otherwise,
+    /// - a change on both sides is a [Conflict][Action::Conflict],
+    /// - a local move is found by the file id of the placeholder and a remote move by the version
+    ///   of the file, then the paths are compared at their new location,
+    /// - a directory is deleted along with its descendants only when none of them is in conflict
+    ///   or changed on the other side,
+    /// - directories added locally are created on the remote along with their files.
+    ///
+    /// The actions are ordered by path, after the moves, so that a directory is created before its
+    /// descendants.
+    pub fn plan(&self) -> Vec<Action> {
+        let mut base = self.base.clone();
+        let mut local = self.local.clone();
+        let mut remote = self.remote.clone();
+        let mut actions = Vec::new();
+
+        // files moved locally keep their file id
+        let ids = self
+            .base
+            .iter()
+            .filter_map(|(path, state)| Some((state.file_id?, path)))
+            .collect::<HashMap<_, _>>();
+        let mut moved = Vec::<(PathBuf, PathBuf)>::new();
+        for (to, file) in &self.local {
+            let Some(&from) = file.file_id.and_then(|id| ids.get(&id)) else {
+                continue;
+            };
+            let implied = moved.iter().any(|(source, target)| {
+                matches!(
+                    (from.strip_prefix(source), to.strip_prefix(target)),
+                    (Ok(a), Ok(b)) if a == b
+                )
+            });
+            if from == to
+                || implied
+                || self.local.get(from).map(|file| file.file_id) == Some(file.file_id)
+                || !remote.contains_key(from)
+                || remote.contains_key(to)
+            {
+                continue;
+            }
+
+            rename(&mut base, from, to);
+            rename(&mut remote, from, to);
+            moved.push((from.clone(), to.clone()));
+            actions.push(Action::RenameRemote {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+
+        // files moved on the remote keep their version, unless it is shared by several files
+        let mut gone = HashMap::<String, Option<PathBuf>>::new();
+        for (path, state) in &base {
+            if let Some(version) = state.version.as_ref().filter(|_| !state.directory) {
+                if !remote.contains_key(path) {
+                    gone.entry(version.clone())
+                        .and_modify(|path| *path = None)
+                        .or_insert_with(|| Some(path.clone()));
+                }
+            }
+        }
+        let added = remote
+            .iter()
+            .filter(|(path, file)| !file.metadata.is_directory() && !base.contains_key(*path))
+            .map(|(path, file)| (path.clone(), file.version.clone()))
+            .collect::<Vec<_>>();
+        for (to, version) in added {
+            let Some(Some(from)) = gone.remove(&version) else {
+                continue;
+            };
+            if !local.contains_key(&from) || local.contains_key(&to) {
+                continue;
+            }
+
+            rename(&mut base, &from, &to);
+            rename(&mut local, &from, &to);
+            actions.push(Action::RenameLocal { from, to });
+        }
+
+        let paths = base
+            .keys()
+            .chain(local.keys())
+            .chain(remote.keys())
+            .collect::<BTreeSet<_>>();
+        let decided = paths
+            .into_iter()
+            .filter_map(|path| decide(path, base.get(path), local.get(path), remote.get(path)))
+            .collect::<Vec<_>>();
+
+        // a removal covers the descendants removed the same way
+        let mut decided = decided.into_iter().peekable();
+        while let Some(action) = decided.next() {
+            if action.removes() {
+                let root = action.path().to_owned();
+                let descendants = decided
+                    .clone()
+                    .take_while(|descendant| descendant.path().starts_with(&root))
+                    .collect::<Vec<_>>();
+                let covered = descendants.iter().all(|descendant| {
+                    matches!(descendant, Action::Forget { .. })
+                        || std::mem::discriminant(descendant) == std::mem::discriminant(&action)
+                });
+                if !covered {
+                    continue;
+                }
+                for _ in &descendants {
+                    decided.next();
+                }
+            }
+
+            actions.push(action);
+        }
+
+        actions
+    }
+}
+
+/// The action for a path, given its last synced state and its current state on both sides.
+fn decide(
+    path: &Path,
+    base: Option<&FileState>,
+    local: Option<&LocalFile>,
+    remote: Option<&RemoteFile>,
+) -> Option<Action> {
+    let path = path.to_owned();
+    let conflict = |path, kind| {
+        Action::Conflict(Conflict {
+            path,
+            kind,
+            local: local.cloned(),
+            remote: remote.cloned(),
+        })
+    };
+
+    Some(match (base, local, remote) {
+        (None, None, None) => return None,
+        (Some(_), None, None) => Action::Forget { path },
+        (None, None, Some(remote)) => Action::CreateLocal {
+            path,
+            remote: remote.clone(),
+        },
+        (None, Some(local), None) => Action::Upload {
+            path,
+            usn: local.usn,
+        },
+        (None, Some(local), Some(remote)) => {
+            if local.directory != remote.metadata.is_directory() {
+                conflict(path, ConflictKind::TypeChanged)
+            } else if local.directory || (!local.modified && local.same(remote)) {
+                Action::Track {
+                    path,
+                    local: local.clone(),
+                    remote: remote.clone(),
+                }
+            } else if !local.modified {
+                Action::UpdateLocal {
+                    path,
+                    remote: remote.clone(),
+                    usn: local.usn,
+                }
+            } else {
+                conflict(path, ConflictKind::Added)
+            }
+        }
+        (Some(base), Some(local), None) => match local.changed(base) {
+            true => conflict(path, ConflictKind::EditedDeleted),
+            false => Action::DeleteLocal {
+                path,
+                usn: local.usn,
+            },
+        },
+        (Some(base), None, Some(remote)) => match remote_changed(base, remote) {
+            true => conflict(path, ConflictKind::DeletedEdited),
+            false => Action::DeleteRemote { path },
+        },
+        (Some(base), Some(local), Some(remote)) => {
+            if local.directory != remote.metadata.is_directory() {
+                return Some(conflict(path, ConflictKind::TypeChanged));
+            }
+
+            match (local.changed(base), remote_changed(base, remote)) {
+                (false, false) => return None,
+                (true, false) => Action::Upload {
+                    path,
+                    usn: local.usn,
+                },
+                (false, true) => Action::UpdateLocal {
+                    path,
+                    remote: remote.clone(),
+                    usn: local.usn,
+                },
+                (true, true) => conflict(path, ConflictKind::Edited),
+            }
+        }
+    })
+}
+
+/// Whether or not the file has a different version than the state it was last synced with.
+fn remote_changed(base: &FileState, remote: &RemoteFile) -> bool {
+    !remote.metadata.is_directory() && base.version.as_deref() != Some(remote.version.as_str())
+}
+
+/// Moves the entries of the path and of its descendants under the new path.
+fn rename<T>(tree: &mut BTreeMap<PathBuf, T>, from: &Path, to: &Path) {
+    let moved = tree
+        .keys()
+        .filter(|path| path.starts_with(from))
+        .cloned()
+        .collect::<Vec<_>>();
+    for path in moved {
+        let entry = tree.remove(&path).unwrap();
+        tree.insert(rebase(&path, from, to), entry);
+    }
+}
+
+/// Replaces the prefix of the path.
+fn rebase(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(rest) if rest.as_os_str().is_empty() => to.to_owned(),
+        Ok(rest) => to.join(rest),
+        Err(_) => path.to_owned(),
+    }
+}
@@ -0,0 +1,504 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io, mem,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    error::{self, CResult, CloudError},
+    filter::{info, layer::SyncLayer, ticket, Request, SyncFilter},
+    state::StateStore,
+    store::RemoteStore,
+    sync::{
+        Action, Change, ChangeFeed, Conflict, ConflictKind, ConflictPolicy, Date, FeedCursor,
+        LocalFile, LocalTree, RemoteFile, Resolution, Snapshot,
+    },
+};
+
+/// Keeps a sync root, seen through a [LocalTree], and a [RemoteStore] in sync in both directions,
+/// recording what was last synced in a [StateStore].
+///
+/// [Reconciler::reconcile] scans both sides, plans the actions with [Snapshot::plan] and applies
+/// them:
+/// - remote changes are applied through [LocalTree::create] and [LocalTree::update], guarded by
+///   the [Usn][crate::usn::Usn] of the placeholder read while scanning so that a file changed in
+///   the meantime is left as is,
+/// - local changes are uploaded through [RemoteStore::write] and [RemoteStore::create_dir], then
+///   the files are marked in sync with the same guard,
+/// - conflicts are resolved by the [ConflictPolicy], a placeholder changed after it was scanned
+///   being a [ConflictKind::Stale] conflict. Each resolution is reported, and logged as a
+///   `tracing` event with the `tracing` feature.
+///
+/// [Reconciler::ingest] applies the changes reported by a [ChangeFeed] instead of scanning the
+/// remote, persisting its cursor in a [FeedCursor].
+///
+/// The [Reconciler] is also a [SyncLayer] recording the files modified locally as they are
+/// closed, to be uploaded by [Reconciler::reconcile_closed] off the callback, and passing the
+/// deletes and renames issued by the current process, i.e. by the [Reconciler] itself, without
+/// forwarding them to the inner filter.
+///
+/// ```no_run
+/// # #[cfg(windows)]
+/// # {
+/// # use std::{sync::Arc, thread, time::Duration};
+/// # use cloud_filter::{
+/// #     filter::layer::FilterStack,
+/// #     state::StateStore,
+/// #     store::{DirectoryStore, StoreFilter},
+/// #     sync::{Placeholders, Reconciler},
+/// # };
+/// let root = r"C:\Users\me\Cloud";
+/// let reconciler = Arc::new(Reconciler::new(
+///     DirectoryStore::new(r"\\server\share"),
+///     Placeholders::new(root),
+///     StateStore::open(r"C:\Users\me\AppData\Local\Cloud\state.log")?,
+/// ));
+/// let filter = FilterStack::new()
+///     .intercept(reconciler.clone())
+///     .filter(StoreFilter::new(DirectoryStore::new(r"\\server\share"), root));
+///
+/// let report = reconciler.reconcile()?;
+/// assert!(report.failed.is_empty());
+///
+/// // upload the files closed in the meantime
+/// loop {
+///     thread::sleep(Duration::from_secs(5));
+///     reconciler.reconcile_closed()?;
+/// }
+/// # }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Reconciler<S, T> {
+    store: S,
+    tree: T,
+    state: Mutex<StateStore>,
+    policy: ConflictPolicy,
+    closed: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl<S: RemoteStore, T: LocalTree> Reconciler<S, T> {
+    /// Creates a new [Reconciler] for the sync root.
+    pub fn new(store: S, tree: T, state: StateStore) -> Self {
+        Self {
+            store,
+            tree,
+            state: Mutex::new(state),
+            policy: ConflictPolicy::default(),
+            closed: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// How to resolve the conflicts, defaults to [ConflictPolicy::KeepBoth].
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// A reference to the inner [RemoteStore].
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// A reference to the [LocalTree].
+    pub fn tree(&self) -> &T {
+        &self.tree
+    }
+
+    /// Locks the [StateStore].
+    pub fn state(&self) -> MutexGuard<'_, StateStore> {
+        self.state.lock().unwrap()
+    }
+
+    /// Scans the sync root and the remote.
+    pub fn snapshot(&self) -> io::Result<Snapshot> {
+        let mut snapshot = Snapshot {
+            base: self
+                .state()
+                .iter()
+                .map(|(path, state)| (path.to_owned(), state.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        self.scan_local(Path::new(""), &mut snapshot.local)?;
+        self.scan_remote(Path::new(""), &mut snapshot.remote)?;
+
+        Ok(snapshot)
+    }
+
+    /// Scans both sides and applies the planned actions.
+    pub fn reconcile(&self) -> io::Result<Report> {
+        Ok(self.apply(self.snapshot()?.plan()))
+    }
+
+    /// Compares a single path on both sides and applies the planned actions, without looking for
+    /// moves.
+    pub fn reconcile_path(&self, path: impl AsRef<Path>) -> io::Result<Report> {
+        let path = path.as_ref();
+        let mut snapshot = Snapshot::default();
+        if let Some(state) = self.state().get(path) {
+            snapshot.base.insert(path.to_owned(), state.clone());
+        }
+        if let Some(file) = self.local_file(path)? {
+            snapshot.local.insert(path.to_owned(), file);
+        }
+        match self.store.stat(path) {
+            Ok(metadata) => {
+                snapshot
+                    .remote
+                    .insert(path.to_owned(), RemoteFile::from(metadata));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        Ok(self.apply(snapshot.plan()))
+    }
+
+    /// Reconciles the paths of the files modified locally and closed since the last call, e.g.
+    /// periodically from a background thread.
+    ///
+    /// A path that could not be compared is kept for the next call.
+    pub fn reconcile_closed(&self) -> io::Result<Report> {
+        let paths = mem::take(&mut *self.closed.lock().unwrap());
+        let mut report = Report::default();
+        let mut paths = paths.into_iter();
+        while let Some(path) = paths.next() {
+            match self.reconcile_path(&path) {
+                Ok(page) => {
+                    report.applied.extend(page.applied);
+                    report.resolved.extend(page.resolved);
+                    report.failed.extend(page.failed);
+                }
+                Err(err) => {
+                    self.closed
+                        .lock()
+                        .unwrap()
+                        .extend(std::iter::once(path).chain(paths));
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Polls the feed from the cursor and applies the changes, until no more changes are
+    /// available.
+    ///
+    /// Each change is planned by [Change::plan] against the last synced state and the local
+    /// files, so that local changes are kept or resolved as conflicts. The cursor is persisted
+    /// after each page of changes applied without failures; polling stops at the first page with
+    /// failures, to be polled again from the same cursor.
+    pub fn ingest(&self, feed: &impl ChangeFeed, cursor: &mut FeedCursor) -> io::Result<Report> {
+        let mut report = Report::default();
+        loop {
+            let delta = feed.poll(cursor.get())?;
+            let mut failed = false;
+            for change in &delta.changes {
+                let page = self.apply(change.plan(self.change_snapshot(change)?));
+                failed |= !page.failed.is_empty();
+                report.applied.extend(page.applied);
+                report.resolved.extend(page.resolved);
+                report.failed.extend(page.failed);
+            }
+
+            if failed {
+                return Ok(report);
+            }
+            cursor.set(delta.cursor)?;
+            if !delta.has_more {
+                return Ok(report);
+            }
+        }
+    }
+
+    /// Applies the actions in order, recording the new state of each path once applied.
+    pub fn apply(&self, actions: impl IntoIterator<Item = Action>) -> Report {
+        let mut report = Report::default();
+        for action in actions {
+            let conflict = match action {
+                Action::Conflict(conflict) => conflict,
+                action => match self.stale(&action) {
+                    Ok(Some(conflict)) => conflict,
+                    Ok(None) => {
+                        match self.execute(&action) {
+                            Ok(()) => report.applied.push(action),
+                            Err(err) => report.failed.push((action, err)),
+                        }
+                        continue;
+                    }
+                    Err(err) => {
+                        report.failed.push((action, err));
+                        continue;
+                    }
+                },
+            };
+
+            let resolution = self.policy.resolve(&conflict);
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                path = %conflict.path.display(),
+                kind = ?conflict.kind,
+                ?resolution,
+                "conflict resolved"
+            );
+
+            let actions = conflict.actions(resolution, Date::today(), |path| {
+                !matches!(self.tree.file(path), Ok(None)) || self.store.stat(path).is_ok()
+            });
+            for action in actions {
+                match self.execute(&action) {
+                    Ok(()) => report.applied.push(action),
+                    Err(err) => report.failed.push((action, err)),
+                }
+            }
+            report.resolved.push((conflict, resolution));
+        }
+
+        report
+    }
+
+    /// The conflict found when the placeholder changed since the action was planned.
+    fn stale(&self, action: &Action) -> io::Result<Option<Conflict>> {
+        let (path, remote, usn) = match action {
+            Action::UpdateLocal { path, remote, usn } => (path, Some(remote), usn),
+            Action::DeleteLocal { path, usn } => (path, None, usn),
+            _ => return Ok(None),
+        };
+        let Some(usn) = usn else {
+            return Ok(None);
+        };
+
+        let local = self.local_file(path)?;
+        Ok(local
+            .as_ref()
+            .is_some_and(|local| local.usn != Some(*usn))
+            .then(|| Conflict {
+                path: path.clone(),
+                kind: ConflictKind::Stale,
+                local,
+                remote: remote.cloned(),
+            }))
+    }
+
+    fn execute(&self, action: &Action) -> io::Result<()> {
+        match action {
+            Action::CreateLocal { path, remote } => {
+                self.tree.create(path, remote)?;
+                self.record(path, remote)
+            }
+            Action::UpdateLocal { path, remote, usn } => {
+                self.tree.update(path, remote, *usn)?;
+                self.record(path, remote)
+            }
+            Action::DeleteLocal { path, .. } => {
+                self.tree.delete(path)?;
+                self.forget(path)
+            }
+            Action::RenameLocal { from, to } => {
+                self.tree.rename(from, to)?;
+                self.moved(from, to)
+            }
+            Action::Upload { path, usn } => {
+                let directory = self.local_file(path)?.is_some_and(|file| file.directory);
+                let metadata = match directory {
+                    true => self.store.create_dir(path)?,
+                    false => self.store.write(path, &mut self.tree.read(path)?)?,
+                };
+                self.tree.mark_in_sync(path, *usn)?;
+                self.record(path, &RemoteFile::from(metadata))
+            }
+            Action::DeleteRemote { path } => {
+                self.store.delete(path)?;
+                self.forget(path)
+            }
+            Action::RenameRemote { from, to } => {
+                self.store.rename(from, to)?;
+                self.moved(from, to)
+            }
+            Action::Track {
+                path,
+                local,
+                remote,
+            } => {
+                let mut state = self.state();
+                let mut transaction = state.transaction();
+                transaction.put(path, local.to_state(remote.version.clone()));
+                transaction.commit()
+            }
+            Action::Forget { path } => self.forget(path),
+            Action::SetAside { path, to } => {
+                self.tree.rename(path, to)?;
+                self.forget(path)
+            }
+            // resolved by the policy instead
+            Action::Conflict(conflict) => Err(io::Error::other(format!(
+                "{:?} changed on both sides",
+                conflict.path
+            ))),
+        }
+    }
+
+    /// Records the path as in sync with the remote file.
+    fn record(&self, path: &Path, remote: &RemoteFile) -> io::Result<()> {
+        let local = self.local_file(path)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{path:?} disappeared"))
+        })?;
+
+        let mut state = self.state();
+        let mut transaction = state.transaction();
+        transaction.put(path, local.to_state(remote.version.clone()));
+        transaction.commit()
+    }
+
+    fn forget(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.state();
+        let mut transaction = state.transaction();
+        transaction.remove(path);
+        transaction.commit()
+    }
+
+    fn moved(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.state();
+        let mut transaction = state.transaction();
+        transaction.rename(from, to);
+        transaction.commit()
+    }
+
+    /// The last synced state and the local files the change is planned against.
+    fn change_snapshot(&self, change: &Change) -> io::Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+        // a deleted or moved directory is compared along with its descendants
+        match change {
+            Change::Added { path, .. } | Change::Modified { path, .. } => {
+                self.compare(path, false, &mut snapshot)?;
+            }
+            Change::Deleted { path } => self.compare(path, true, &mut snapshot)?,
+            Change::Moved { from, to, .. } => {
+                self.compare(from, true, &mut snapshot)?;
+                self.compare(to, false, &mut snapshot)?;
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Adds the last synced state and the local file at the path to the snapshot, along with
+    /// those of its descendants if `subtree`.
+    fn compare(&self, path: &Path, subtree: bool, snapshot: &mut Snapshot) -> io::Result<()> {
+        snapshot.base.extend(
+            self.state()
+                .iter()
+                .filter(|(other, _)| match subtree {
+                    true => other.starts_with(path),
+                    false => *other == path,
+                })
+                .map(|(other, state)| (other.to_owned(), state.clone())),
+        );
+        if let Some(file) = self.local_file(path)? {
+            if subtree && file.directory {
+                self.scan_local(path, &mut snapshot.local)?;
+            }
+            snapshot.local.insert(path.to_owned(), file);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the file at the path relative to the sync root, [None] if it does not exist.
+    fn local_file(&self, path: &Path) -> io::Result<Option<LocalFile>> {
+        self.tree.file(path)
+    }
+
+    fn scan_local(&self, dir: &Path, files: &mut BTreeMap<PathBuf, LocalFile>) -> io::Result<()> {
+        for name in self.tree.list_dir(dir)? {
+            let path = dir.join(name);
+            let Some(file) = self.local_file(&path)? else {
+                continue;
+            };
+            if file.directory {
+                self.scan_local(&path, files)?;
+            }
+            files.insert(path, file);
+        }
+
+        Ok(())
+    }
+
+    fn scan_remote(&self, dir: &Path, files: &mut BTreeMap<PathBuf, RemoteFile>) -> io::Result<()> {
+        for entry in self.store.list_dir(dir)? {
+            let path = dir.join(entry.name());
+            if entry.metadata().is_directory() {
+                self.scan_remote(&path, files)?;
+            }
+            files.insert(path, RemoteFile::from(entry.metadata()));
+        }
+
+        Ok(())
+    }
+
+    /// The path relative to the sync root, [None] if it is outside of it.
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(self.tree.root()).ok()
+    }
+}
+
+impl<S: RemoteStore, T: LocalTree, F: SyncFilter> SyncLayer<F> for Reconciler<S, T> {
+    fn closed(&self, next: &F, request: Request, info: info::Closed) {
+        let (path, deleted) = (request.path(), info.deleted());
+        let own = request.process().id() == std::process::id();
+        next.closed(request, info);
+
+        // files opened without being changed are left alone
+        if let Some(path) = self.relative(&path).filter(|_| !deleted && !own) {
+            match self.local_file(path) {
+                Ok(Some(file)) if file.modified => {
+                    self.closed.lock().unwrap().insert(path.to_owned());
+                }
+                Ok(_) => {}
+                Err(err) => error::report(
+                    &CloudError::from(err).context(format!("failed to read {path:?}")),
+                ),
+            }
+        }
+    }
+
+    fn delete(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Delete,
+        info: info::Delete,
+    ) -> CResult<()> {
+        match request.process().id() == std::process::id() {
+            true => Ok(ticket.pass()?),
+            false => next.delete(request, ticket, info),
+        }
+    }
+
+    fn rename(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::Rename,
+        info: info::Rename,
+    ) -> CResult<()> {
+        match request.process().id() == std::process::id() {
+            true => Ok(ticket.pass()?),
+            false => next.rename(request, ticket, info),
+        }
+    }
+}
+
+/// The outcome of [Reconciler::apply].
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The actions applied.
+    pub applied: Vec<Action>,
+    /// The conflicts, along with their resolution.
+    pub resolved: Vec<(Conflict, Resolution)>,
+    /// The actions that failed, along with their error.
+    pub failed: Vec<(Action, io::Error)>,
+}
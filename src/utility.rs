@@ -1,6 +1,8 @@
 use std::{future::Future, pin::Pin};
 
-use windows::core::{self, HSTRING};
+#[cfg(windows)]
+use windows::core::HSTRING;
+use windows_core as core;
 
 use crate::sealed;
 
@@ -8,6 +10,7 @@ pub use nt_time::FileTime;
 
 // TODO: add something to convert an Option<T> to a *const T and *mut T
 
+#[cfg(windows)]
 pub(crate) trait ToHString
 where
     Self: AsRef<[u16]>,
@@ -22,6 +25,7 @@ where
     }
 }
 
+#[cfg(windows)]
 impl<T: AsRef<[u16]>> ToHString for T {}
 
 /// A trait for types that can read data from a file-like object at a specific offset.
@@ -1,4 +1,3 @@
-use core::str;
 use std::{fs, future::Future, path::Path, pin::Pin};
 
 use anyhow::Context;
@@ -27,10 +26,11 @@ impl Filter for MemFilter {
         ticket: ticket::FetchData,
         info: info::FetchData,
     ) -> CResult<()> {
-        let path = unsafe { str::from_utf8_unchecked(request.file_blob()) };
+        let path = request.file_identity::<String>()?;
+        let path = path.as_str();
         println!("fetch_data: path: {path:?}");
 
-        let content = match path.as_ref() {
+        let content = match path {
             "test1.txt" | "dir1\\test2.txt" => path,
             _ => Err(CloudErrorKind::InvalidRequest)?,
         };
@@ -60,7 +60,7 @@ impl Filter for MemFilter {
                 PlaceholderFile::new("dir1")
                     .mark_in_sync()
                     .metadata(Metadata::directory().created(now).written(now).size(0))
-                    .blob("dir1".into()),
+                    .identity(&"dir1".to_owned()),
                 PlaceholderFile::new("test1.txt")
                     .has_no_children()
                     .mark_in_sync()
@@ -70,7 +70,7 @@ impl Filter for MemFilter {
                             .written(now)
                             .size("test1.txt".len() as _),
                     )
-                    .blob("test1.txt".into()),
+                    .identity(&"test1.txt".to_owned()),
             ],
             "dir1" => vec![PlaceholderFile::new("test2.txt")
                 .has_no_children()
@@ -81,7 +81,7 @@ impl Filter for MemFilter {
                         .written(now)
                         .size("dir1\\test2.txt".len() as _),
                 )
-                .blob("dir1\\test2.txt".into())],
+                .identity(&"dir1\\test2.txt".to_owned())],
             _ => Err(CloudErrorKind::InvalidRequest)?,
         };
 
@@ -90,10 +90,7 @@ impl Filter for MemFilter {
     }
 }
 
-fn init() -> anyhow::Result<(
-    SyncRootId,
-    Connection<AsyncBridge<MemFilter, impl Fn(Pin<Box<dyn Future<Output = ()>>>)>>,
-)> {
+fn register() -> anyhow::Result<SyncRootId> {
     let sync_root_id = SyncRootIdBuilder::new("sync_filter_test_provider")
         .user_security_id(SecurityId::current_user().context("current_user")?)
         .build();
@@ -115,6 +112,15 @@ fn init() -> anyhow::Result<(
             .context("register")?
     }
 
+    Ok(sync_root_id)
+}
+
+#[allow(clippy::type_complexity)]
+fn init() -> anyhow::Result<(
+    SyncRootId,
+    Connection<AsyncBridge<MemFilter, impl Fn(Pin<Box<dyn Future<Output = ()>>>)>>,
+)> {
+    let sync_root_id = register()?;
     let connection = Session::new()
         .connect_async(ROOT_PATH, MemFilter, move |f| {
             futures::executor::block_on(f)
@@ -141,3 +147,26 @@ pub fn test() -> Result<(), Failed> {
 
     Ok(())
 }
+
+#[cfg(feature = "tokio")]
+pub fn test_tokio() -> Result<(), Failed> {
+    if !Path::new(ROOT_PATH).try_exists().context("exists")? {
+        fs::create_dir(ROOT_PATH).context("create root dir")?;
+    }
+
+    let runtime = tokio::runtime::Runtime::new().context("runtime")?;
+    let sync_root_id = register().context("init")?;
+    let connection = Session::new()
+        .connect_tokio(ROOT_PATH, MemFilter, runtime.handle().clone())
+        .context("connect")?;
+
+    crate::test_list_folders(ROOT_PATH);
+    crate::test_read_file(ROOT_PATH);
+
+    drop(connection);
+    sync_root_id.unregister().context("unregister")?;
+
+    fs::remove_dir_all(ROOT_PATH).context("remove root dir")?;
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use cloud_filter::{
+    blob::{self, FileIdentity, MAX_PAYLOAD_LENGTH},
+    error::CloudErrorKind,
+    filter::Request,
+    placeholder_file::MAX_BLOB_LENGTH,
+};
+use libtest_mimic::Failed;
+
+#[derive(Debug, PartialEq)]
+struct RemoteId {
+    id: u64,
+    etag: String,
+}
+
+impl FileIdentity for RemoteId {
+    const VERSION: u8 = 2;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.id.to_le_bytes());
+        buf.extend_from_slice(self.etag.as_bytes());
+    }
+
+    fn decode(version: u8, payload: &[u8]) -> Option<Self> {
+        match version {
+            // version 1 did not store the etag
+            1 => Some(Self {
+                id: u64::from_le_bytes(payload.try_into().ok()?),
+                etag: String::new(),
+            }),
+            2 => Some(Self {
+                id: u64::from_le_bytes(payload.get(..8)?.try_into().ok()?),
+                etag: String::from_utf8(payload[8..].to_vec()).ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let identity = RemoteId {
+        id: 42,
+        etag: "\"0x8DC\"".to_owned(),
+    };
+    let encoded = blob::encode(&identity).unwrap();
+    assert_eq!(encoded[0], RemoteId::VERSION);
+    assert_eq!(encoded.len(), 1 + 8 + identity.etag.len() + 4);
+
+    let request = Request::builder().file_blob(encoded.clone()).build();
+    assert_eq!(request.file_identity::<RemoteId>().unwrap(), identity);
+
+    for path in ["", "dir1/test2.txt", "ünïcødé/🦀.rs"] {
+        let path = PathBuf::from(path);
+        assert_eq!(
+            blob::decode::<PathBuf>(&blob::encode(&path).unwrap()).unwrap(),
+            path
+        );
+    }
+    assert_eq!(
+        blob::decode::<String>(&blob::encode(&"test1.txt".to_owned()).unwrap()).unwrap(),
+        "test1.txt"
+    );
+
+    // flipping any bit must be detected
+    for i in 0..encoded.len() {
+        let mut corrupt = encoded.clone();
+        corrupt[i] ^= 0x10;
+        assert!(matches!(
+            blob::decode::<RemoteId>(&corrupt).map_err(|e| e.kind()),
+            Err(CloudErrorKind::MetadataCorrupt)
+        ));
+    }
+
+    // truncated, empty, raw and oversized blobs
+    for malformed in [
+        &encoded[..encoded.len() - 1],
+        &[][..],
+        &b"test1.txt"[..],
+        &vec![0; MAX_BLOB_LENGTH + 1][..],
+    ] {
+        assert!(matches!(
+            blob::decode::<RemoteId>(malformed).map_err(|e| e.kind()),
+            Err(CloudErrorKind::MetadataCorrupt)
+        ));
+    }
+
+    // a well-formed blob the type does not understand
+    assert!(matches!(
+        blob::decode::<RemoteId>(&blob::encode(&"test1.txt".to_owned()).unwrap())
+            .map_err(|e| e.kind()),
+        Err(CloudErrorKind::MetadataCorrupt)
+    ));
+
+    let max = "a".repeat(MAX_PAYLOAD_LENGTH);
+    assert_eq!(blob::encode(&max).unwrap().len(), MAX_BLOB_LENGTH);
+    assert!(matches!(
+        blob::encode(&format!("{max}a")).map_err(|e| e.kind()),
+        Err(CloudErrorKind::MetadataTooLarge)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info, ticket, CancellationToken, ParallelHydration, RecordingBackend, Request, SyncFilter,
+    },
+    metadata::Metadata,
+    placeholder_file::PlaceholderFile,
+    sim::{Error, Simulator},
+};
+use libtest_mimic::Failed;
+
+const ROOT_PATH: &str = "cancellation_test";
+const FILE_SIZE: u64 = 100_000;
+
+/// The cancellations observed, either through the token or the cancel callbacks.
+type Cancellations = Vec<(&'static str, Option<Range<u64>>)>;
+
+/// Hydrates slowly until cancelled, blocks on the async cancellation when listing `dir`.
+struct SlowFilter {
+    started: Mutex<mpsc::Sender<()>>,
+    cancelled: Mutex<Cancellations>,
+}
+
+impl SyncFilter for SlowFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        let mut writer = ticket.writer(0..FILE_SIZE).chunk_size(4096);
+        writer.write_all(&[1; 4096]).unwrap();
+        self.started.lock().unwrap().send(()).unwrap();
+
+        for _ in 0..500 {
+            match writer.write_all(&[1; 4096]) {
+                Ok(_) => thread::sleep(Duration::from_millis(10)),
+                Err(_) => {
+                    let token = ticket.cancellation_token();
+                    assert!(token.is_cancelled());
+                    self.cancelled
+                        .lock()
+                        .unwrap()
+                        .push(("token", token.cancelled_range()));
+                    return Err(CloudErrorKind::RequestCancelled.into());
+                }
+            }
+        }
+
+        Err(CloudErrorKind::RequestTimeout.into())
+    }
+
+    fn cancel_fetch_data(&self, _request: Request, info: info::CancelFetchData) {
+        assert!(info.user_cancelled());
+        self.cancelled
+            .lock()
+            .unwrap()
+            .push(("cancel_fetch_data", Some(info.file_range())));
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        if request.path() == Path::new(ROOT_PATH) {
+            return Ok(ticket.pass_with_placeholder(&mut [
+                PlaceholderFile::new("large.bin")
+                    .metadata(Metadata::file().size(FILE_SIZE))
+                    .mark_in_sync(),
+                PlaceholderFile::new("dir")
+                    .metadata(Metadata::directory())
+                    .mark_in_sync(),
+            ])?);
+        }
+
+        self.started.lock().unwrap().send(()).unwrap();
+        futures::executor::block_on(ticket.cancellation_token().cancelled());
+        self.cancelled.lock().unwrap().push(("token", None));
+        Err(CloudErrorKind::RequestCancelled.into())
+    }
+
+    fn cancel_fetch_placeholders(&self, _request: Request, info: info::CancelFetchPlaceholders) {
+        assert!(info.user_cancelled());
+        self.cancelled
+            .lock()
+            .unwrap()
+            .push(("cancel_fetch_placeholders", None));
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    // the future completes once the token is cancelled from another thread
+    let token = CancellationToken::new();
+    assert!(!token.is_cancelled());
+    thread::scope(|scope| {
+        let waiter = scope.spawn(|| futures::executor::block_on(token.clone().cancelled()));
+        thread::sleep(Duration::from_millis(20));
+        token.cancel();
+        waiter.join().unwrap();
+    });
+    assert!(token.is_cancelled());
+    assert_eq!(token.cancelled_range(), None);
+
+    let (sender, receiver) = mpsc::channel();
+    let sim = Simulator::new(
+        ROOT_PATH,
+        SlowFilter {
+            started: Mutex::new(sender),
+            cancelled: Mutex::new(Vec::new()),
+        },
+    );
+    sim.read_dir("")?;
+    assert!(!sim.cancel_fetch_data("large.bin", 0..FILE_SIZE));
+
+    // the in-flight hydration observes the cancelled range through its token
+    thread::scope(|scope| {
+        let reader = scope.spawn(|| sim.read("large.bin"));
+        receiver.recv().unwrap();
+        assert!(sim.cancel_fetch_data("large.bin", 4096..FILE_SIZE));
+        assert!(matches!(
+            reader.join().unwrap(),
+            Err(Error::Callback(CloudErrorKind::RequestCancelled))
+        ));
+    });
+    let mut cancelled = sim
+        .filter()
+        .cancelled
+        .lock()
+        .unwrap()
+        .drain(..)
+        .collect::<Vec<_>>();
+    cancelled.sort_by_key(|(name, _)| *name);
+    assert_eq!(
+        cancelled,
+        [
+            ("cancel_fetch_data", Some(4096..FILE_SIZE)),
+            ("token", Some(4096..FILE_SIZE))
+        ]
+    );
+
+    // the async waker wakes up the in-flight population
+    thread::scope(|scope| {
+        let lister = scope.spawn(|| sim.read_dir("dir"));
+        receiver.recv().unwrap();
+        assert!(sim.cancel_fetch_placeholders("dir"));
+        assert!(matches!(
+            lister.join().unwrap(),
+            Err(Error::Callback(CloudErrorKind::RequestCancelled))
+        ));
+    });
+    let mut cancelled = sim.filter().cancelled.lock().unwrap().clone();
+    cancelled.sort_by_key(|(name, _)| *name);
+    assert_eq!(
+        cancelled,
+        [("cancel_fetch_placeholders", None), ("token", None)]
+    );
+
+    // cancelled hydrations stop before the next segment
+    let backend = Arc::new(RecordingBackend::new());
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = ParallelHydration::new().run(
+        &ticket::FetchData::with_token(backend.clone(), token),
+        &info::FetchData::builder()
+            .required_range(0..FILE_SIZE)
+            .build(),
+        FILE_SIZE,
+        |_, _| Ok(()),
+    );
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Other);
+    assert!(backend.operations().is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use cloud_filter::{
+    metadata::Metadata,
+    sync::{
+        conflict_name, Action, Conflict, ConflictKind, ConflictPolicy, Date, LocalFile, RemoteFile,
+        Resolution,
+    },
+};
+use libtest_mimic::Failed;
+use nt_time::FileTime;
+
+const DATE: Date = Date {
+    year: 2024,
+    month: 6,
+    day: 1,
+};
+
+fn name(path: &str) -> PathBuf {
+    conflict_name(Path::new(path), DATE, |_| false)
+}
+
+fn local() -> LocalFile {
+    LocalFile {
+        file_id: Some(1),
+        usn: Some(7),
+        modified: true,
+        ..Default::default()
+    }
+}
+
+fn remote() -> RemoteFile {
+    RemoteFile::new(Metadata::file().size(4), "v2")
+}
+
+fn conflict(local: Option<LocalFile>, remote: Option<RemoteFile>) -> Conflict {
+    Conflict {
+        path: PathBuf::from("dir/a.txt"),
+        kind: ConflictKind::Edited,
+        local,
+        remote,
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    // renaming rules
+    assert_eq!(
+        name("report.docx"),
+        Path::new("report (conflict 2024-06-01).docx")
+    );
+    assert_eq!(
+        name("dir/sub/report.docx"),
+        Path::new("dir/sub/report (conflict 2024-06-01).docx")
+    );
+    assert_eq!(
+        name("Makefile"),
+        Path::new("Makefile (conflict 2024-06-01)")
+    );
+    assert_eq!(
+        name(".profile"),
+        Path::new(".profile (conflict 2024-06-01)")
+    );
+    assert_eq!(
+        name("archive.tar.gz"),
+        Path::new("archive.tar (conflict 2024-06-01).gz")
+    );
+    assert_eq!(
+        name("notes (conflict 2024-05-01).txt"),
+        Path::new("notes (conflict 2024-05-01) (conflict 2024-06-01).txt")
+    );
+    assert_eq!(
+        conflict_name(Path::new("a.txt"), DATE, |path| {
+            path != Path::new("a (conflict 2024-06-01 3).txt")
+        }),
+        Path::new("a (conflict 2024-06-01 3).txt")
+    );
+
+    // dates
+    assert_eq!(DATE.to_string(), "2024-06-01");
+    let date = |secs| Date::from(FileTime::from_unix_time(secs).unwrap());
+    assert_eq!(date(0).to_string(), "1970-01-01");
+    assert_eq!(date(951_782_400).to_string(), "2000-02-29");
+    assert_eq!(date(1_709_251_199).to_string(), "2024-02-29");
+    assert_eq!(date(1_735_689_599).to_string(), "2024-12-31");
+    assert_eq!(date(-86_400).to_string(), "1969-12-31");
+
+    // resolutions
+    let both = conflict(Some(local()), Some(remote()));
+    assert_eq!(
+        both.actions(Resolution::KeepBoth, DATE, |_| false),
+        [
+            Action::SetAside {
+                path: PathBuf::from("dir/a.txt"),
+                to: PathBuf::from("dir/a (conflict 2024-06-01).txt")
+            },
+            Action::Upload {
+                path: PathBuf::from("dir/a (conflict 2024-06-01).txt"),
+                usn: None
+            },
+            Action::CreateLocal {
+                path: PathBuf::from("dir/a.txt"),
+                remote: remote()
+            },
+        ]
+    );
+    assert_eq!(
+        both.actions(Resolution::RemoteWins, DATE, |_| false),
+        [
+            Action::DeleteLocal {
+                path: PathBuf::from("dir/a.txt"),
+                usn: None
+            },
+            Action::CreateLocal {
+                path: PathBuf::from("dir/a.txt"),
+                remote: remote()
+            },
+        ]
+    );
+    assert_eq!(
+        both.actions(Resolution::LocalWins, DATE, |_| false),
+        [Action::Upload {
+            path: PathBuf::from("dir/a.txt"),
+            usn: Some(7)
+        }]
+    );
+    assert_eq!(both.actions(Resolution::Skip, DATE, |_| false), []);
+
+    // the deleted side is restored when keeping both
+    assert_eq!(
+        conflict(Some(local()), None).actions(Resolution::KeepBoth, DATE, |_| false),
+        [Action::Upload {
+            path: PathBuf::from("dir/a.txt"),
+            usn: Some(7)
+        }]
+    );
+    assert_eq!(
+        conflict(None, Some(remote())).actions(Resolution::KeepBoth, DATE, |_| false),
+        [Action::CreateLocal {
+            path: PathBuf::from("dir/a.txt"),
+            remote: remote()
+        }]
+    );
+    assert_eq!(
+        conflict(None, Some(remote())).actions(Resolution::LocalWins, DATE, |_| false),
+        [Action::DeleteRemote {
+            path: PathBuf::from("dir/a.txt")
+        }]
+    );
+
+    // policies
+    assert_eq!(
+        ConflictPolicy::default().resolve(&both),
+        Resolution::KeepBoth
+    );
+    assert_eq!(
+        ConflictPolicy::RemoteWins.resolve(&both),
+        Resolution::RemoteWins
+    );
+    let policy = ConflictPolicy::callback(|conflict| match conflict.local {
+        Some(_) => Resolution::LocalWins,
+        None => Resolution::Skip,
+    });
+    assert_eq!(policy.resolve(&both), Resolution::LocalWins);
+    assert_eq!(
+        policy.resolve(&conflict(None, Some(remote()))),
+        Resolution::Skip
+    );
+
+    Ok(())
+}
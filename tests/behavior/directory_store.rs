@@ -0,0 +1,73 @@
+use std::{env, fs, path::Path};
+
+use anyhow::Context;
+use cloud_filter::{
+    sim::Simulator,
+    store::{DirectoryStore, RemoteStore, StoreFilter},
+};
+use libtest_mimic::Failed;
+
+const ROOT_PATH: &str = "directory_store_test";
+
+pub fn test() -> Result<(), Failed> {
+    let server = env::temp_dir().join(format!("directory_store_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&server);
+    fs::create_dir_all(server.join("dir1")).context("create server dir")?;
+    let large = (0..10000).map(|i| i as u8).collect::<Vec<_>>();
+    fs::write(server.join("test1.txt"), "test1.txt").context("write")?;
+    fs::write(server.join("dir1/large.bin"), &large).context("write")?;
+
+    let store = DirectoryStore::new(&server);
+    assert!(store.stat(Path::new("../test1.txt")).is_err());
+    assert!(store.stat(&server.join("test1.txt")).is_err());
+
+    let sim = Simulator::new(
+        ROOT_PATH,
+        StoreFilter::new(store, ROOT_PATH).chunk_size(4096),
+    );
+
+    let mut names = sim
+        .read_dir("")?
+        .iter()
+        .map(|entry| (entry.path().to_owned(), entry.is_directory(), entry.size()))
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            (Path::new("dir1").to_owned(), true, 0),
+            (Path::new("test1.txt").to_owned(), false, 9)
+        ]
+    );
+
+    assert_eq!(sim.read("test1.txt")?, b"test1.txt");
+    assert_eq!(
+        sim.read_range("dir1/large.bin", 5000..9000)?,
+        &large[5000..9000]
+    );
+    assert_eq!(sim.read("dir1/large.bin")?, large);
+
+    sim.rename("test1.txt", "dir1/moved.txt")?;
+    assert!(!server.join("test1.txt").exists());
+    assert_eq!(
+        fs::read(server.join("dir1/moved.txt")).context("read")?,
+        b"test1.txt"
+    );
+
+    let store = sim.filter().store();
+    store.write(Path::new("dir1/moved.txt"), &mut &b"updated"[..])?;
+    assert_eq!(
+        fs::read(server.join("dir1/moved.txt")).context("read")?,
+        b"updated"
+    );
+
+    store.create_dir(Path::new("dir1/sub"))?;
+    assert!(server.join("dir1/sub").is_dir());
+    assert!(store.create_dir(Path::new("missing/sub")).is_err());
+
+    sim.delete("dir1")?;
+    assert!(!server.join("dir1").exists());
+
+    fs::remove_dir_all(&server).context("remove server dir")?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::{
+    collections::HashSet,
+    error::Error as _,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use cloud_filter::{
+    error::{self, CResult, CloudError, CloudErrorKind},
+    filter::{info, ticket, Request, SyncFilter},
+    sim::{Error, Simulator},
+};
+use libtest_mimic::Failed;
+use windows_core::HRESULT;
+
+const ROOT_PATH: &str = "error_test";
+
+/// The `STATUS_CLOUD_FILE_*` code of each kind, matched exhaustively so that a new kind fails to
+/// compile until it is covered.
+fn expected_status(kind: CloudErrorKind) -> u32 {
+    match kind {
+        CloudErrorKind::AccessDenied => 0xC000CF18,
+        CloudErrorKind::AlreadyConnected => 0xC000CF09,
+        CloudErrorKind::AuthenticationFailed => 0xC000CF0F,
+        CloudErrorKind::ConnectedProviderOnly => 0xC000CF0D,
+        CloudErrorKind::DehydrationDisallowed => 0xC000CF20,
+        CloudErrorKind::IncompatibleHardlinks => 0xC000CF19,
+        CloudErrorKind::InsufficientResources => 0xC000CF10,
+        CloudErrorKind::InvalidRequest => 0xC000CF0B,
+        CloudErrorKind::InUse => 0xC000CF14,
+        CloudErrorKind::MetadataCorrupt => 0xC000CF02,
+        CloudErrorKind::MetadataTooLarge => 0xC000CF03,
+        CloudErrorKind::NetworkUnavailable => 0xC000CF11,
+        CloudErrorKind::NotInSync => 0xC000CF08,
+        CloudErrorKind::NotSupported => 0xC000CF0A,
+        CloudErrorKind::NotUnderSyncRoot => 0xC000CF13,
+        CloudErrorKind::Pinned => 0xC000CF15,
+        CloudErrorKind::PropertyBlobChecksumMismatch => 0x8000CF00,
+        CloudErrorKind::PropertyBlobTooLarge => 0x8000CF04,
+        CloudErrorKind::PropertyCorrupt => 0xC000CF17,
+        CloudErrorKind::PropertyLockConflict => 0xC000CF1A,
+        CloudErrorKind::PropertyVersionNotSupported => 0xC000CF06,
+        CloudErrorKind::ProviderNotRunning => 0xC000CF01,
+        CloudErrorKind::ProviderTerminated => 0xC000CF1D,
+        CloudErrorKind::ReadOnlyVolume => 0xC000CF0C,
+        CloudErrorKind::RequestAborted => 0xC000CF16,
+        CloudErrorKind::RequestCancelled => 0xC000CF1B,
+        CloudErrorKind::RequestTimeout => 0xC000CF1F,
+        CloudErrorKind::SyncRootMetadataCorrupt => 0xC000CF00,
+        CloudErrorKind::TooManyPropertyBlobs => 0x8000CF05,
+        CloudErrorKind::Unsuccessful => 0xC000CF12,
+        CloudErrorKind::ValidationFailed => 0xC000CF0E,
+    }
+}
+
+struct UnreachableFilter;
+
+impl SyncFilter for UnreachableFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        _ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        unreachable!()
+    }
+
+    fn fetch_placeholders(
+        &self,
+        _request: Request,
+        _ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let err = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        Err(CloudError::from(err).context("failed to list error_test"))
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    // io errors are classified by their kind
+    for (kind, expected) in [
+        (
+            io::ErrorKind::ConnectionRefused,
+            CloudErrorKind::NetworkUnavailable,
+        ),
+        (io::ErrorKind::TimedOut, CloudErrorKind::NetworkUnavailable),
+        (
+            io::ErrorKind::PermissionDenied,
+            CloudErrorKind::AccessDenied,
+        ),
+        (io::ErrorKind::NotFound, CloudErrorKind::NotInSync),
+        (io::ErrorKind::Other, CloudErrorKind::Unsuccessful),
+    ] {
+        let err = CloudError::from(io::Error::from(kind));
+        assert_eq!(err.kind(), expected);
+        assert!(err.source().is_some());
+    }
+
+    // a wrapped CloudError is unwrapped rather than classified again
+    let inner = CloudError::new(CloudErrorKind::Pinned, "the file is pinned");
+    let err = CloudError::from(io::Error::other(inner));
+    assert_eq!(err.kind(), CloudErrorKind::Pinned);
+    assert_eq!(err.message(), Some("the file is pinned"));
+
+    // windows errors are classified by their HRESULT
+    for (code, expected) in [
+        (0x80070005u32, CloudErrorKind::AccessDenied),
+        (0x800704C9, CloudErrorKind::NetworkUnavailable),
+        (0x800704C7, CloudErrorKind::RequestCancelled),
+        (0x80004001, CloudErrorKind::NotSupported),
+        (0x80004005, CloudErrorKind::Unsuccessful),
+    ] {
+        let err = CloudError::from(windows_core::Error::from(HRESULT(code as i32)));
+        assert_eq!(err.kind(), expected);
+    }
+
+    // every kind round-trips through its NTSTATUS and its HRESULTs
+    let kinds = CloudErrorKind::all().collect::<Vec<_>>();
+    assert_eq!(kinds.len(), 31);
+    assert_eq!(kinds.iter().collect::<HashSet<_>>().len(), kinds.len());
+    let mut codes = HashSet::new();
+    for kind in kinds {
+        assert_eq!(kind.ntstatus() as u32, expected_status(kind));
+        assert!(codes.insert(kind.ntstatus()));
+        assert!(codes.insert(kind.hresult().0));
+
+        let status = HRESULT::from_nt(kind.ntstatus());
+        assert_eq!(CloudErrorKind::try_from(status), Ok(kind));
+        assert_eq!(CloudErrorKind::try_from(kind.hresult()), Ok(kind));
+        assert_eq!(
+            CloudError::from(windows_core::Error::from(kind.hresult())).kind(),
+            kind
+        );
+    }
+
+    // other codes are handed back
+    for code in [
+        0,
+        0x80004005u32 as i32,
+        0x80070005u32 as i32,
+        0xC000CF21u32 as i32,
+    ] {
+        assert_eq!(CloudErrorKind::try_from(HRESULT(code)), Err(HRESULT(code)));
+    }
+
+    assert!(CloudErrorKind::NetworkUnavailable.is_transient());
+    assert!(CloudErrorKind::RequestTimeout.is_transient());
+    assert!(!CloudErrorKind::RequestCancelled.is_transient());
+    assert!(!CloudErrorKind::AccessDenied.is_transient());
+
+    // the alternate format displays the whole chain
+    let err = CloudError::with_source(
+        CloudErrorKind::NetworkUnavailable,
+        io::Error::other("connection reset"),
+    )
+    .context("failed to download test1.txt");
+    assert_eq!(err.to_string(), "failed to download test1.txt");
+    assert_eq!(
+        format!("{err:#}"),
+        "failed to download test1.txt: connection reset"
+    );
+    assert!(!CloudError::from(CloudErrorKind::InUse)
+        .to_string()
+        .is_empty());
+
+    // the hook sees the error before the operation is failed with its kind
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    error::set_hook({
+        let reported = reported.clone();
+        move |err| {
+            if err.message() == Some("failed to list error_test") {
+                reported
+                    .lock()
+                    .unwrap()
+                    .push((err.kind(), format!("{err:#}")));
+            }
+        }
+    });
+
+    let sim = Simulator::new(ROOT_PATH, UnreachableFilter);
+    assert!(matches!(
+        sim.read_dir(""),
+        Err(Error::Callback(CloudErrorKind::NetworkUnavailable))
+    ));
+    assert_eq!(
+        *reported.lock().unwrap(),
+        [(
+            CloudErrorKind::NetworkUnavailable,
+            "failed to list error_test: connection refused".to_owned()
+        )]
+    );
+
+    // a hook may replace itself
+    let replaced = Arc::new(Mutex::new(false));
+    error::set_hook({
+        let replaced = replaced.clone();
+        move |err| {
+            if err.message() == Some("failed to list error_test") {
+                error::set_hook(|_| {});
+                *replaced.lock().unwrap() = true;
+            }
+        }
+    });
+    assert!(sim.read_dir("").is_err());
+    assert!(*replaced.lock().unwrap());
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use std::{env, fs};
+
+use anyhow::Context;
+use cloud_filter::sync::{
+    Action, Change, ChangeFeed, Conflict, ConflictKind, FeedCursor, LocalFile, MemoryFeed, Snapshot,
+};
+use libtest_mimic::Failed;
+
+use crate::fixtures::{base, base_dir, local, local_dir, modified, path, remote};
+
+/// A snapshot of the synced files at the paths.
+fn synced(files: &[(&str, LocalFile)]) -> Snapshot {
+    let mut snapshot = Snapshot::default();
+    for (file_path, file) in files {
+        let file_id = file.file_id.unwrap();
+        let state = match file.directory {
+            true => base_dir(file_id),
+            false => base(file_id, "v1"),
+        };
+        snapshot.base.insert(path(file_path), state);
+        snapshot.local.insert(path(file_path), file.clone());
+    }
+    snapshot
+}
+
+pub fn test() -> Result<(), Failed> {
+    // the in-memory feed pages its changes
+    let feed = MemoryFeed::new().page_size(2);
+    for name in ["a", "b", "c"] {
+        feed.push(Change::Added {
+            path: path(name),
+            remote: remote("v1"),
+        });
+    }
+    let first = feed.poll(None)?;
+    assert_eq!(first.changes.len(), 2);
+    assert!(first.has_more);
+    let second = feed.poll(Some(&first.cursor))?;
+    assert_eq!(
+        second.changes,
+        [Change::Added {
+            path: path("c"),
+            remote: remote("v1")
+        }]
+    );
+    assert!(!second.has_more);
+    assert!(feed.poll(Some(&second.cursor))?.changes.is_empty());
+    feed.push(Change::Deleted { path: path("a") });
+    assert_eq!(
+        feed.poll(Some(&second.cursor))?.changes,
+        [Change::Deleted { path: path("a") }]
+    );
+    assert!(feed.poll(Some("42")).is_err());
+    assert!(feed.poll(Some("token")).is_err());
+
+    // the cursor survives restarts
+    let dir = env::temp_dir().join(format!("feed_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).context("create dir")?;
+    let cursor_path = dir.join("cursor");
+    let mut cursor = FeedCursor::open(&cursor_path)?;
+    assert_eq!(cursor.get(), None);
+    cursor.set(first.cursor)?;
+    cursor.set(second.cursor.clone())?;
+    drop(cursor);
+    let cursor = FeedCursor::open(&cursor_path)?;
+    assert_eq!(cursor.get(), Some(second.cursor.as_str()));
+    drop(cursor);
+    fs::write(&cursor_path, b"not a cursor").context("write")?;
+    assert!(FeedCursor::open(&cursor_path).is_err());
+    fs::remove_dir_all(&dir).context("remove dir")?;
+
+    // changes become placeholder operations
+    assert_eq!(
+        Change::Added {
+            path: path("a"),
+            remote: remote("v1")
+        }
+        .plan(Snapshot::default()),
+        [Action::CreateLocal {
+            path: path("a"),
+            remote: remote("v1")
+        }]
+    );
+    assert_eq!(
+        Change::Modified {
+            path: path("a"),
+            remote: remote("v2")
+        }
+        .plan(synced(&[("a", local(1))])),
+        [Action::UpdateLocal {
+            path: path("a"),
+            remote: remote("v2"),
+            usn: Some(1)
+        }]
+    );
+    assert_eq!(
+        Change::Modified {
+            path: path("a"),
+            remote: remote("v1")
+        }
+        .plan(synced(&[("a", local(1))])),
+        []
+    );
+    assert_eq!(
+        Change::Deleted { path: path("a") }.plan(synced(&[("a", local(1))])),
+        [Action::DeleteLocal {
+            path: path("a"),
+            usn: Some(1)
+        }]
+    );
+    let moved = Change::Moved {
+        from: path("a"),
+        to: path("b"),
+        remote: remote("v1"),
+    };
+    assert_eq!(
+        moved.plan(synced(&[("a", local(1))])),
+        [Action::RenameLocal {
+            from: path("a"),
+            to: path("b")
+        }]
+    );
+
+    // a file changed while moved is updated at its destination, unless changed locally too
+    let moved_changed = Change::Moved {
+        from: path("a"),
+        to: path("b"),
+        remote: remote("v2"),
+    };
+    assert_eq!(
+        moved_changed.plan(synced(&[("a", local(1))])),
+        [
+            Action::RenameLocal {
+                from: path("a"),
+                to: path("b")
+            },
+            Action::UpdateLocal {
+                path: path("b"),
+                remote: remote("v2"),
+                usn: None
+            }
+        ]
+    );
+    assert_eq!(
+        moved_changed.plan(synced(&[("a", modified(1))])),
+        [
+            Action::RenameLocal {
+                from: path("a"),
+                to: path("b")
+            },
+            Action::Conflict(Conflict {
+                path: path("b"),
+                kind: ConflictKind::Edited,
+                local: Some(LocalFile {
+                    usn: None,
+                    ..modified(1)
+                }),
+                remote: Some(remote("v2"))
+            })
+        ]
+    );
+
+    // a move whose source is missing locally creates the destination
+    assert_eq!(
+        moved.plan(Snapshot::default()),
+        [Action::CreateLocal {
+            path: path("b"),
+            remote: remote("v1")
+        }]
+    );
+    let mut snapshot = synced(&[("a", local(1))]);
+    snapshot.local.clear();
+    assert_eq!(
+        moved.plan(snapshot),
+        [
+            Action::Forget { path: path("a") },
+            Action::CreateLocal {
+                path: path("b"),
+                remote: remote("v1")
+            }
+        ]
+    );
+
+    // a move never replaces the file at its destination
+    let mut snapshot = synced(&[("a", local(1))]);
+    snapshot.local.insert(path("b"), modified(2));
+    assert_eq!(
+        moved.plan(snapshot),
+        [
+            Action::DeleteLocal {
+                path: path("a"),
+                usn: Some(1)
+            },
+            Action::Conflict(Conflict {
+                path: path("b"),
+                kind: ConflictKind::Added,
+                local: Some(modified(2)),
+                remote: Some(remote("v1"))
+            })
+        ]
+    );
+
+    // local changes are kept
+    assert_eq!(
+        Change::Modified {
+            path: path("a"),
+            remote: remote("v2")
+        }
+        .plan(synced(&[("a", modified(1))])),
+        [Action::Conflict(Conflict {
+            path: path("a"),
+            kind: ConflictKind::Edited,
+            local: Some(modified(1)),
+            remote: Some(remote("v2"))
+        })]
+    );
+    let dir = local_dir(1);
+    assert_eq!(
+        Change::Deleted { path: path("dir") }.plan(synced(&[
+            ("dir", dir.clone()),
+            ("dir/a", local(2)),
+            ("dir/b", modified(3)),
+        ])),
+        [
+            Action::DeleteLocal {
+                path: path("dir/a"),
+                usn: Some(2)
+            },
+            Action::Conflict(Conflict {
+                path: path("dir/b"),
+                kind: ConflictKind::EditedDeleted,
+                local: Some(modified(3)),
+                remote: None
+            }),
+        ]
+    );
+    assert_eq!(
+        Change::Deleted { path: path("dir") }.plan(synced(&[("dir", dir), ("dir/a", local(2))])),
+        [Action::DeleteLocal {
+            path: path("dir"),
+            usn: Some(1)
+        }]
+    );
+
+    Ok(())
+}
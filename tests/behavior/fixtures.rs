@@ -0,0 +1,249 @@
+//! Factories and an in-memory [LocalTree] shared by the tests of the sync module.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+};
+
+use cloud_filter::{
+    metadata::Metadata,
+    state::FileState,
+    sync::{LocalFile, LocalTree, RemoteFile},
+    usn::Usn,
+};
+use nt_time::FileTime;
+
+pub const MTIME: FileTime = FileTime::new(133_000_000_000_000_000);
+
+pub fn base(file_id: i64, version: &str) -> FileState {
+    FileState {
+        file_id: Some(file_id),
+        version: Some(version.to_owned()),
+        usn: Some(file_id),
+        mtime: MTIME,
+        size: 4,
+        in_sync: true,
+        ..Default::default()
+    }
+}
+
+pub fn base_dir(file_id: i64) -> FileState {
+    FileState {
+        file_id: Some(file_id),
+        directory: true,
+        in_sync: true,
+        ..Default::default()
+    }
+}
+
+pub fn local(file_id: i64) -> LocalFile {
+    LocalFile {
+        file_id: Some(file_id),
+        usn: Some(file_id),
+        mtime: MTIME,
+        size: 4,
+        ..Default::default()
+    }
+}
+
+pub fn local_dir(file_id: i64) -> LocalFile {
+    LocalFile {
+        file_id: Some(file_id),
+        usn: Some(file_id),
+        directory: true,
+        ..Default::default()
+    }
+}
+
+pub fn modified(file_id: i64) -> LocalFile {
+    LocalFile {
+        modified: true,
+        size: 5,
+        ..local(file_id)
+    }
+}
+
+pub fn remote(version: &str) -> RemoteFile {
+    RemoteFile::new(Metadata::file().size(4).written(MTIME), version)
+}
+
+pub fn remote_dir() -> RemoteFile {
+    RemoteFile::from(Metadata::directory())
+}
+
+pub fn path(path: &str) -> PathBuf {
+    PathBuf::from(path)
+}
+
+/// A [LocalTree] held in memory, each change of a file bumping its [Usn].
+#[derive(Debug)]
+pub struct MemTree {
+    root: PathBuf,
+    files: Mutex<BTreeMap<PathBuf, (LocalFile, Vec<u8>)>>,
+    usn: AtomicI64,
+}
+
+impl MemTree {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            files: Mutex::new(BTreeMap::new()),
+            usn: AtomicI64::new(0),
+        }
+    }
+
+    /// Writes the file the way a user process would, a new file not being a placeholder.
+    pub fn write(&self, file_path: &str, data: &[u8]) {
+        let usn = self.next_usn();
+        let mut files = self.files.lock().unwrap();
+        let (file, contents) = files.entry(path(file_path)).or_default();
+        file.usn = Some(usn);
+        file.size = data.len() as u64;
+        file.modified = true;
+        *contents = data.to_vec();
+    }
+
+    /// The contents of the file, [None] if it does not exist.
+    pub fn data(&self, file_path: &str) -> Option<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files.get(&path(file_path)).map(|(_, data)| data.clone())
+    }
+
+    /// The paths of the files and directories, in order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn next_usn(&self) -> Usn {
+        self.usn.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{path:?} not found"))
+}
+
+/// Fails when the [Usn] of the file is not the expected one.
+fn check_usn(path: &Path, file: &LocalFile, usn: Option<Usn>) -> io::Result<()> {
+    match usn.is_some_and(|usn| file.usn != Some(usn)) {
+        true => Err(io::Error::other(format!("{path:?} changed"))),
+        false => Ok(()),
+    }
+}
+
+impl LocalTree for MemTree {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn file(&self, path: &Path) -> io::Result<Option<LocalFile>> {
+        let files = self.files.lock().unwrap();
+        Ok(files.get(path).map(|(file, _)| file.clone()))
+    }
+
+    fn list_dir(&self, dir: &Path) -> io::Result<Vec<OsString>> {
+        let files = self.files.lock().unwrap();
+        Ok(files
+            .keys()
+            .filter(|path| path.parent() == Some(dir))
+            .filter_map(|path| path.file_name().map(ToOwned::to_owned))
+            .collect())
+    }
+
+    fn create(&self, path: &Path, remote: &RemoteFile) -> io::Result<()> {
+        let usn = self.next_usn();
+        let mut files = self.files.lock().unwrap();
+        let parent = path.parent().ok_or_else(|| not_found(path))?;
+        if parent != Path::new("") && !files.contains_key(parent) {
+            return Err(not_found(parent));
+        }
+
+        files.retain(|other, _| !other.starts_with(path));
+        let file = LocalFile {
+            file_id: Some(usn),
+            usn: Some(usn),
+            ..remote.to_local()
+        };
+        files.insert(path.to_owned(), (file, Vec::new()));
+        Ok(())
+    }
+
+    fn update(&self, path: &Path, remote: &RemoteFile, usn: Option<Usn>) -> io::Result<()> {
+        let next = self.next_usn();
+        let mut files = self.files.lock().unwrap();
+        let (file, data) = files.get_mut(path).ok_or_else(|| not_found(path))?;
+        check_usn(path, file, usn)?;
+
+        *file = LocalFile {
+            file_id: file.file_id,
+            usn: Some(next),
+            pin: file.pin,
+            ..remote.to_local()
+        };
+        data.clear();
+        Ok(())
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        if !files.contains_key(path) {
+            return Err(not_found(path));
+        }
+        files.retain(|other, _| !other.starts_with(path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let usn = self.next_usn();
+        let mut files = self.files.lock().unwrap();
+        if !files.contains_key(from) {
+            return Err(not_found(from));
+        }
+        if files.contains_key(to) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{to:?} already exists"),
+            ));
+        }
+
+        let moved = files
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            let entry = files.remove(&path).unwrap();
+            let path = match path.strip_prefix(from).unwrap() {
+                relative if relative.as_os_str().is_empty() => to.to_owned(),
+                relative => to.join(relative),
+            };
+            files.insert(path, entry);
+        }
+        files.get_mut(to).unwrap().0.usn = Some(usn);
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let files = self.files.lock().unwrap();
+        let (_, data) = files.get(path).ok_or_else(|| not_found(path))?;
+        Ok(Box::new(Cursor::new(data.clone())))
+    }
+
+    fn mark_in_sync(&self, path: &Path, usn: Option<Usn>) -> io::Result<()> {
+        let next = self.next_usn();
+        let mut files = self.files.lock().unwrap();
+        let (file, _) = files.get_mut(path).ok_or_else(|| not_found(path))?;
+        check_usn(path, file, usn)?;
+
+        file.file_id.get_or_insert(next);
+        file.usn = Some(next);
+        file.modified = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{info, ticket, Request, SyncFilter},
+    fuse::Session,
+    metadata::Metadata,
+    placeholder_file::PlaceholderFile,
+    utility::WriteAt,
+};
+use libtest_mimic::Failed;
+use nt_time::FileTime;
+
+struct MemFilter {
+    root: PathBuf,
+}
+
+impl SyncFilter for MemFilter {
+    fn fetch_data(
+        &self,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let path = request.file_identity::<String>()?;
+        let path = path.as_str();
+        println!("fetch_data: path: {path:?}");
+
+        let content = match path {
+            "test1.txt" | "dir1/test2.txt" => path,
+            _ => Err(CloudErrorKind::InvalidRequest)?,
+        };
+
+        if info.required_file_range() != (0..content.len() as u64) {
+            Err(CloudErrorKind::InvalidRequest)?;
+        }
+
+        // the transfers are aligned like on Windows and cannot overflow
+        assert!(ticket.write_at(&content.as_bytes()[1..], 1).is_err());
+        assert!(ticket.write_at(b"x", u64::MAX - 4095).is_err());
+        ticket.write_at(content.as_bytes(), 0).unwrap();
+
+        Ok(())
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let path = request.path();
+        let relative_path = path.strip_prefix(&self.root).unwrap();
+        println!("fetch_placeholders: path: {path:?}, relative path: {relative_path:?}");
+
+        let now = FileTime::now();
+        let mut placeholders = match relative_path.to_string_lossy().as_ref() {
+            "" => vec![
+                PlaceholderFile::new("dir1")
+                    .mark_in_sync()
+                    .metadata(Metadata::directory().created(now).written(now).size(0))
+                    .identity(&"dir1".to_owned()),
+                PlaceholderFile::new("dir2")
+                    .mark_in_sync()
+                    .metadata(Metadata::directory().created(now).written(now).size(0))
+                    .identity(&"dir2".to_owned()),
+                PlaceholderFile::new("test1.txt")
+                    .has_no_children()
+                    .mark_in_sync()
+                    .metadata(
+                        Metadata::file()
+                            .created(now)
+                            .written(now)
+                            .size("test1.txt".len() as _),
+                    )
+                    .identity(&"test1.txt".to_owned()),
+            ],
+            "dir1" => vec![PlaceholderFile::new("test2.txt")
+                .has_no_children()
+                .mark_in_sync()
+                .metadata(
+                    Metadata::file()
+                        .created(now)
+                        .written(now)
+                        .size("dir1/test2.txt".len() as _),
+                )
+                .identity(&"dir1/test2.txt".to_owned())],
+            "dir2" => Vec::new(),
+            _ => Err(CloudErrorKind::InvalidRequest)?,
+        };
+
+        ticket.pass_with_placeholder(&mut placeholders).unwrap();
+        Ok(())
+    }
+
+    fn delete(
+        &self,
+        _request: Request,
+        ticket: ticket::Delete,
+        _info: info::Delete,
+    ) -> CResult<()> {
+        ticket.pass().unwrap();
+        Ok(())
+    }
+
+    fn rename(
+        &self,
+        _request: Request,
+        ticket: ticket::Rename,
+        _info: info::Rename,
+    ) -> CResult<()> {
+        ticket.pass().unwrap();
+        Ok(())
+    }
+}
+
+/// Whether or not the kernel FUSE module is available.
+pub fn available() -> bool {
+    Path::new("/dev/fuse").exists()
+}
+
+pub fn test() -> Result<(), Failed> {
+    let base = env::temp_dir().join(format!("fuse_test_{}", std::process::id()));
+    let (root, cache) = (base.join("root"), base.join("cache"));
+    fs::create_dir_all(&root).context("create root dir")?;
+
+    let mount = Session::new()
+        .cache_dir(&cache)
+        .mount(&root, MemFilter { root: root.clone() })
+        .context("mount")?;
+
+    let mut names = fs::read_dir(&root)
+        .context("read_dir")?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("read_dir")?;
+    names.sort();
+    assert_eq!(names, ["dir1", "dir2", "test1.txt"]);
+
+    for relative in ["test1.txt", "dir1/test2.txt"] {
+        let content = fs::read_to_string(root.join(relative)).context("read")?;
+        assert_eq!(content, relative);
+    }
+
+    fs::rename(root.join("test1.txt"), root.join("dir1/moved.txt")).context("rename")?;
+    assert_eq!(
+        fs::read_to_string(root.join("dir1/moved.txt")).context("read")?,
+        "test1.txt"
+    );
+
+    // the destination of a rename is replaced
+    fs::rename(root.join("dir1/moved.txt"), root.join("dir1/test2.txt")).context("rename")?;
+    assert!(!root.join("dir1/moved.txt").exists());
+    assert_eq!(
+        fs::read_to_string(root.join("dir1/test2.txt")).context("read")?,
+        "test1.txt"
+    );
+
+    // only empty directories are removed or replaced
+    let err = fs::remove_dir(root.join("dir1")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::DirectoryNotEmpty);
+    let err = fs::rename(root.join("dir2"), root.join("dir1")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::DirectoryNotEmpty);
+    assert!(root.join("dir1/test2.txt").exists());
+    fs::remove_file(root.join("dir1/test2.txt")).context("remove")?;
+    assert!(!root.join("dir1/test2.txt").exists());
+    assert_eq!(
+        fs::read_dir(root.join("dir2")).context("read_dir")?.count(),
+        0
+    );
+    fs::rename(root.join("dir2"), root.join("dir1")).context("rename dir")?;
+    assert!(!root.join("dir2").exists());
+    fs::remove_dir(root.join("dir1")).context("remove dir")?;
+    assert!(!root.join("dir1").exists());
+
+    mount.unmount();
+    fs::remove_dir_all(&base).context("remove base dir")?;
+
+    Ok(())
+}
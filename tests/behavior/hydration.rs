@@ -0,0 +1,110 @@
+use std::{
+    io,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use cloud_filter::filter::{info, ticket, Operation, ParallelHydration, RecordingBackend};
+use libtest_mimic::Failed;
+
+const FILE_SIZE: u64 = 100_000;
+
+/// The written ranges, sorted by offset, and the progress reports in order.
+fn summarize(backend: &RecordingBackend) -> (Vec<Range<u64>>, Vec<(u64, u64)>) {
+    let (mut writes, mut progress) = (Vec::new(), Vec::new());
+    for operation in backend.operations() {
+        match operation {
+            Operation::Write { offset, data } => writes.push(offset..offset + data.len() as u64),
+            Operation::ReportProgress { total, completed } => progress.push((total, completed)),
+            operation => panic!("unexpected operation {operation:?}"),
+        }
+    }
+
+    writes.sort_by_key(|range| range.start);
+    (writes, progress)
+}
+
+pub fn test() -> Result<(), Failed> {
+    let data = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    // the optional range is merged with the required range and both are aligned
+    let backend = Arc::new(RecordingBackend::new());
+    let (running, concurrency) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    ParallelHydration::new()
+        .segment_size(16384)
+        .workers(3)
+        .optional_range(true)
+        .run(
+            &ticket::FetchData::new(backend.clone()),
+            &info::FetchData::builder()
+                .required_range(5000..30000)
+                .optional_range(30000..70000)
+                .build(),
+            FILE_SIZE,
+            |range, buf| {
+                concurrency.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                buf.copy_from_slice(&data[range.start as usize..range.end as usize]);
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            },
+        )?;
+
+    let (writes, progress) = summarize(&backend);
+    assert_eq!(
+        writes,
+        [
+            4096..20480,
+            20480..36864,
+            36864..53248,
+            53248..69632,
+            69632..73728
+        ]
+    );
+    assert_eq!(&backend.data()[4096..73728], &data[4096..73728]);
+    assert!((2..=3).contains(&concurrency.load(Ordering::SeqCst)));
+    assert_eq!(progress.len(), writes.len());
+    assert!(progress.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    assert_eq!(progress.last(), Some(&(69632, 69632)));
+
+    // disjoint ranges are hydrated separately, the last segment ends on the file size
+    let backend = Arc::new(RecordingBackend::new());
+    ParallelHydration::new()
+        .segment_size(8192)
+        .optional_range(true)
+        .run(
+            &ticket::FetchData::new(backend.clone()),
+            &info::FetchData::builder()
+                .required_range(90000..FILE_SIZE)
+                .optional_range(0..100)
+                .build(),
+            FILE_SIZE,
+            |range, buf| {
+                buf.copy_from_slice(&data[range.start as usize..range.end as usize]);
+                Ok(())
+            },
+        )?;
+    let (writes, _) = summarize(&backend);
+    assert_eq!(writes, [0..4096, 86016..94208, 94208..FILE_SIZE]);
+
+    // the first error is returned and the remaining segments are skipped
+    let backend = Arc::new(RecordingBackend::new());
+    let result = ParallelHydration::new().segment_size(4096).workers(1).run(
+        &ticket::FetchData::new(backend.clone()),
+        &info::FetchData::builder().required_range(0..40960).build(),
+        FILE_SIZE,
+        |range, _| match range.start {
+            8192 => Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+            _ => Ok(()),
+        },
+    );
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    assert_eq!(summarize(&backend).0, [0..4096, 4096..8192]);
+
+    Ok(())
+}
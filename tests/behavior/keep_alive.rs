@@ -0,0 +1,61 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use cloud_filter::filter::{ticket, CancellationToken, Operation, RecordingBackend};
+use libtest_mimic::Failed;
+
+/// The reported progress, as `(total, completed)`.
+fn progress(backend: &RecordingBackend) -> Vec<(u64, u64)> {
+    backend
+        .operations()
+        .into_iter()
+        .map(|operation| match operation {
+            Operation::ReportProgress { total, completed } => (total, completed),
+            operation => panic!("unexpected operation {operation:?}"),
+        })
+        .collect()
+}
+
+pub fn test() -> Result<(), Failed> {
+    // the last progress is re-reported until the guard is dropped
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let keep_alive = ticket.keep_alive(Duration::from_millis(10));
+    thread::sleep(Duration::from_millis(55));
+    ticket.report_progress(100, 40)?;
+    thread::sleep(Duration::from_millis(55));
+    drop(keep_alive);
+
+    let reported = progress(&backend);
+    let split = reported.iter().position(|&p| p == (100, 40)).unwrap();
+    assert!(split >= 2);
+    assert!(reported[..split].iter().all(|&p| p == (0, 0)));
+    assert!(reported[split..].len() >= 3);
+    assert!(reported[split..].iter().all(|&p| p == (100, 40)));
+
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(progress(&backend).len(), reported.len());
+
+    // the timer stops once the operation is cancelled
+    let backend = Arc::new(RecordingBackend::new());
+    let token = CancellationToken::new();
+    let ticket = ticket::FetchPlaceholders::with_token(backend.clone(), token.clone());
+    let _keep_alive = ticket.keep_alive(Duration::from_millis(10));
+    thread::sleep(Duration::from_millis(35));
+    token.cancel();
+    thread::sleep(Duration::from_millis(20));
+    let reported = progress(&backend);
+    assert!(!reported.is_empty());
+    assert!(reported.iter().all(|&p| p == (0, 0)));
+    thread::sleep(Duration::from_millis(30));
+    assert_eq!(progress(&backend).len(), reported.len());
+
+    // a zero interval is rejected up front
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| ticket.keep_alive(Duration::ZERO))).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info,
+        layer::{AsyncLayer, FilterStack, Retry, SyncLayer},
+        ticket, Filter, RecordingBackend, Request, SyncFilter,
+    },
+    sim::{Error, Simulator},
+};
+use libtest_mimic::Failed;
+
+use crate::sim::{MemFilter, ROOT_PATH};
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Records the callbacks it sees before forwarding them.
+struct Trace {
+    name: &'static str,
+    log: Log,
+}
+
+impl Trace {
+    fn record(&self, callback: &str, path: &Path) {
+        let path = path
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} {callback} {path}", self.name));
+    }
+}
+
+impl<F: SyncFilter> SyncLayer<F> for Trace {
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        self.record("fetch_data", &request.path());
+        next.fetch_data(request, ticket, info)
+    }
+
+    fn fetch_placeholders(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        self.record("fetch_placeholders", &request.path());
+        next.fetch_placeholders(request, ticket, info)
+    }
+}
+
+impl<F: Filter> AsyncLayer<F> for Trace {
+    async fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        self.record("fetch_data", &request.path());
+        next.fetch_data(request, ticket, info).await
+    }
+}
+
+/// Refuses to hydrate the files of a directory.
+struct Deny(&'static str);
+
+impl<F: SyncFilter> SyncLayer<F> for Deny {
+    fn fetch_data(
+        &self,
+        next: &F,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        if request
+            .path()
+            .starts_with(Path::new(ROOT_PATH).join(self.0))
+        {
+            return Err(CloudErrorKind::AccessDenied.into());
+        }
+        next.fetch_data(request, ticket, info)
+    }
+}
+
+struct EmptyFilter;
+
+impl Filter for EmptyFilter {
+    async fn fetch_data(
+        &self,
+        _request: Request,
+        _ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        Ok(())
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let log = Log::default();
+    let trace = |name| Trace {
+        name,
+        log: log.clone(),
+    };
+
+    // the first layer added sees the callbacks first, a layer can short-circuit the others
+    let filter = FilterStack::new()
+        .intercept(Retry::new().max_attempts(2))
+        .intercept(trace("outer"))
+        .intercept(Deny("dir1"))
+        .intercept(trace("inner"))
+        .filter(MemFilter);
+    let sim = Simulator::new(ROOT_PATH, filter);
+
+    assert_eq!(sim.read("test1.txt")?, b"test1.txt");
+    assert!(matches!(
+        sim.read("dir1/test2.txt"),
+        Err(Error::Callback(CloudErrorKind::AccessDenied))
+    ));
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "outer fetch_placeholders sim_test",
+            "inner fetch_placeholders sim_test",
+            "outer fetch_data sim_test/test1.txt",
+            "inner fetch_data sim_test/test1.txt",
+            "outer fetch_placeholders sim_test/dir1",
+            "inner fetch_placeholders sim_test/dir1",
+            "outer fetch_data sim_test/dir1/test2.txt",
+        ]
+    );
+
+    // the callbacks a layer does not override are forwarded
+    sim.dehydrate("test1.txt")?;
+    sim.rename("test1.txt", "dir1/moved.txt")?;
+    sim.delete("dir1")?;
+    assert!(sim.entry("dir1").is_none());
+
+    // async layers
+    log.lock().unwrap().clear();
+    let filter = FilterStack::new()
+        .intercept(trace("async"))
+        .filter(EmptyFilter);
+    futures::executor::block_on(filter.fetch_data(
+        Request::builder().path("file.txt").build(),
+        ticket::FetchData::new(Arc::new(RecordingBackend::new())),
+        info::FetchData::builder().build(),
+    ))?;
+    assert_eq!(*log.lock().unwrap(), ["async fetch_data file.txt"]);
+
+    Ok(())
+}
@@ -2,24 +2,106 @@ use std::process::ExitCode;
 
 use libtest_mimic::{run, Arguments, Trial};
 
+#[cfg(windows)]
 mod async_filter;
+mod blob;
+mod cancellation;
+mod conflict;
+mod directory_store;
+mod error;
+mod feed;
+mod fixtures;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+mod fuse;
+mod hydration;
+mod keep_alive;
+mod layer;
+mod metrics;
+mod outbox;
+mod policy;
+mod reconcile;
+mod reconciler;
+#[cfg(feature = "record")]
+mod record;
+mod recording;
+mod retry;
+mod sim;
+mod state;
+mod store;
+#[cfg(windows)]
 mod sync_filter;
+mod writer;
 
 fn main() -> ExitCode {
     let args = Arguments::from_args();
-    let tests = vec![Trial::test("sync_filter", sync_filter::test)];
+    #[allow(unused_mut)]
+    let mut tests = vec![
+        Trial::test("blob", blob::test),
+        Trial::test("sim", sim::test),
+        Trial::test("recording", recording::test),
+        Trial::test("store", store::test),
+        Trial::test("directory_store", directory_store::test),
+        Trial::test("writer", writer::test),
+        Trial::test("hydration", hydration::test),
+        Trial::test("cancellation", cancellation::test),
+        Trial::test("keep_alive", keep_alive::test),
+        Trial::test("error", error::test),
+        Trial::test("retry", retry::test),
+        Trial::test("layer", layer::test),
+        Trial::test("metrics", metrics::test),
+        Trial::test("policy", policy::test),
+        Trial::test("state", state::test),
+        Trial::test("reconcile", reconcile::test),
+        Trial::test("reconciler", reconciler::test),
+        Trial::test("conflict", conflict::test),
+        Trial::test("outbox", outbox::test),
+        Trial::test("feed", feed::test),
+    ];
+    #[cfg(feature = "record")]
+    tests.push(Trial::test("record", record::test));
 
     let conclusion = run(&args, tests);
     if conclusion.has_failed() {
         return conclusion.exit_code();
     }
 
-    let tests = vec![Trial::test("async_filter", async_filter::test)];
-    let conclusion = run(&args, tests);
+    #[cfg(all(feature = "fuse", target_os = "linux"))]
+    {
+        let tests = vec![Trial::test("fuse", fuse::test).with_ignored_flag(!fuse::available())];
+        let conclusion = run(&args, tests);
+        if conclusion.has_failed() {
+            return conclusion.exit_code();
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        let tests = vec![Trial::test("sync_filter", sync_filter::test)];
+        let conclusion = run(&args, tests);
+        if conclusion.has_failed() {
+            return conclusion.exit_code();
+        }
+
+        let tests = vec![Trial::test("async_filter", async_filter::test)];
+        let conclusion = run(&args, tests);
+        if conclusion.has_failed() {
+            return conclusion.exit_code();
+        }
+
+        #[cfg(feature = "tokio")]
+        {
+            let tests = vec![Trial::test("tokio_filter", async_filter::test_tokio)];
+            let conclusion = run(&args, tests);
+            if conclusion.has_failed() {
+                return conclusion.exit_code();
+            }
+        }
+    }
 
-    conclusion.exit_code()
+    ExitCode::SUCCESS
 }
 
+#[cfg(windows)]
 fn test_list_folders(root: &str) {
     let output = powershell_script::run(&format!("Get-ChildItem {root} -Recurse -Name"))
         .expect("run script");
@@ -31,6 +113,7 @@ fn test_list_folders(root: &str) {
     );
 }
 
+#[cfg(windows)]
 fn test_read_file(root: &str) {
     for relative in ["test1.txt", "dir1\\test2.txt"] {
         let path = format!("{root}\\{relative}");
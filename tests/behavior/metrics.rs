@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info,
+        layer::{metrics::InMemorySink, FilterStack, Metrics},
+        ticket, RecordingBackend, Request, SyncFilter,
+    },
+    sim::Simulator,
+    utility::WriteAt,
+};
+use libtest_mimic::Failed;
+
+use crate::sim::{MemFilter, ROOT_PATH};
+
+/// Writes two chunks before failing.
+struct FailingFilter;
+
+impl SyncFilter for FailingFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        ticket.write_at(&[1; 4096], 0)?;
+        ticket.write_at(&[1; 100], 4096)?;
+        Err(CloudErrorKind::NetworkUnavailable.into())
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let sink = Arc::new(InMemorySink::new());
+    let filter = FilterStack::new()
+        .intercept(Metrics::new(sink.clone()))
+        .filter(MemFilter);
+    let sim = Simulator::new(ROOT_PATH, filter);
+
+    assert_eq!(sim.read("test1.txt")?, b"test1.txt");
+    let snapshot = sink.snapshot();
+    assert_eq!(
+        snapshot.counter("cloud_filter_transferred_bytes_total", &[]),
+        9
+    );
+    let latency = |callback| {
+        snapshot
+            .histogram(
+                "cloud_filter_callback_duration_seconds",
+                &[("callback", callback)],
+            )
+            .map(|histogram| histogram.count)
+    };
+    assert_eq!(latency("fetch_placeholders"), Some(1));
+    assert_eq!(latency("fetch_data"), Some(1));
+    assert_eq!(latency("delete"), None);
+
+    // failures, write sizes and cancellations
+    let sink = Arc::new(InMemorySink::new());
+    let filter = FilterStack::new()
+        .intercept(Metrics::new(sink.clone()))
+        .filter(FailingFilter);
+    let result = filter.fetch_data(
+        Request::builder().path("file.txt").build(),
+        ticket::FetchData::new(Arc::new(RecordingBackend::new())),
+        info::FetchData::builder().build(),
+    );
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::NetworkUnavailable)
+    );
+    for info in [
+        info::CancelFetchData::builder().timeout(true).build(),
+        info::CancelFetchData::builder()
+            .user_cancelled(true)
+            .build(),
+        info::CancelFetchData::builder().timeout(true).build(),
+    ] {
+        filter.cancel_fetch_data(Request::builder().path("file.txt").build(), info);
+    }
+
+    let snapshot = sink.snapshot();
+    assert_eq!(
+        snapshot.counter(
+            "cloud_filter_callback_failures_total",
+            &[("callback", "fetch_data"), ("kind", "NetworkUnavailable")]
+        ),
+        1
+    );
+    assert_eq!(
+        snapshot.counter(
+            "cloud_filter_fetch_data_cancellations_total",
+            &[("reason", "timeout")]
+        ),
+        2
+    );
+    assert_eq!(
+        snapshot.counter(
+            "cloud_filter_fetch_data_cancellations_total",
+            &[("reason", "user")]
+        ),
+        1
+    );
+    let writes = snapshot
+        .histogram("cloud_filter_write_size_bytes", &[])
+        .ok_or("no write sizes")?;
+    assert_eq!(writes.count, 2);
+    assert_eq!(writes.sum, 4196.0);
+    assert_eq!(writes.buckets[..2], [(4096.0, 2), (16384.0, 2)]);
+
+    // Prometheus text exposition
+    let text = snapshot.to_prometheus();
+    for line in [
+        "# TYPE cloud_filter_callback_failures_total counter",
+        "cloud_filter_callback_failures_total{callback=\"fetch_data\",kind=\"NetworkUnavailable\"} 1",
+        "# TYPE cloud_filter_write_size_bytes histogram",
+        "cloud_filter_write_size_bytes_bucket{le=\"4096\"} 2",
+        "cloud_filter_write_size_bytes_bucket{le=\"+Inf\"} 2",
+        "cloud_filter_write_size_bytes_sum 4196",
+        "cloud_filter_write_size_bytes_count 2",
+        "cloud_filter_transferred_bytes_total 4196",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing {line:?} in\n{text}");
+    }
+    assert_eq!(
+        text.matches("# HELP cloud_filter_callback_duration_seconds ")
+            .count(),
+        1
+    );
+
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use std::{
+    env, fs,
+    io::{self, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use cloud_filter::{
+    filter::{info, layer::FilterStack, Request, SyncFilter},
+    metadata::Metadata,
+    outbox::{Outbox, Task},
+    sim::Simulator,
+    store::{DirectoryStore, Entry, RemoteStore, StoreFilter},
+};
+use libtest_mimic::Failed;
+use nt_time::FileTime;
+
+const ROOT_PATH: &str = "outbox_test";
+
+/// A [DirectoryStore] that can be disconnected.
+struct Flaky {
+    store: DirectoryStore,
+    online: AtomicBool,
+}
+
+impl Flaky {
+    fn check(&self) -> io::Result<()> {
+        match self.online.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => Err(io::Error::new(io::ErrorKind::NotConnected, "offline")),
+        }
+    }
+}
+
+impl RemoteStore for Flaky {
+    fn stat(&self, path: &Path) -> io::Result<Metadata> {
+        self.check()?;
+        self.store.stat(path)
+    }
+
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<Entry>> {
+        self.check()?;
+        self.store.list_dir(path)
+    }
+
+    fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<Box<dyn Read + '_>> {
+        self.check()?;
+        self.store.read_range(path, range)
+    }
+
+    fn write(&self, path: &Path, data: &mut dyn Read) -> io::Result<Metadata> {
+        self.check()?;
+        self.store.write(path, data)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<Metadata> {
+        self.check()?;
+        self.store.create_dir(path)
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        self.check()?;
+        self.store.delete(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check()?;
+        self.store.rename(from, to)
+    }
+}
+
+fn upload(path: &str) -> Task {
+    Task::Upload {
+        path: PathBuf::from(path),
+    }
+}
+
+fn delete(path: &str) -> Task {
+    Task::Delete {
+        path: PathBuf::from(path),
+    }
+}
+
+fn rename(from: &str, to: &str) -> Task {
+    Task::Rename {
+        from: PathBuf::from(from),
+        to: PathBuf::from(to),
+    }
+}
+
+fn tasks(outbox: &Outbox) -> Vec<Task> {
+    outbox
+        .entries()
+        .into_iter()
+        .map(|entry| entry.task)
+        .collect()
+}
+
+pub fn test() -> Result<(), Failed> {
+    let dir = env::temp_dir().join(format!("outbox_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (root, server) = (dir.join("root"), dir.join("server"));
+    fs::create_dir_all(root.join("moved")).context("create root")?;
+    fs::create_dir_all(server.join("dir")).context("create server")?;
+    fs::write(root.join("a.txt"), "hello").context("write")?;
+    fs::write(root.join("moved/x.txt"), "x").context("write")?;
+    fs::write(server.join("b.txt"), "old").context("write")?;
+    fs::write(server.join("c.txt"), "c").context("write")?;
+    let path = dir.join("outbox.log");
+
+    // coalescing
+    let outbox = Outbox::open(&path, &root)?.backoff(Duration::from_secs(60), Duration::MAX);
+    assert!(outbox.is_empty());
+    outbox.push(upload("a.txt"))?;
+    outbox.push(upload("a.txt"))?;
+    outbox.push(upload("b.txt"))?;
+    outbox.push(delete("b.txt"))?;
+    outbox.push(rename("c.txt", "d.txt"))?;
+    outbox.push(rename("d.txt", "e.txt"))?;
+    outbox.push(upload("dir/x.txt"))?;
+    outbox.push(rename("dir", "moved"))?;
+    outbox.push(rename("same", "same"))?;
+    let expected = [
+        upload("a.txt"),
+        delete("b.txt"),
+        rename("c.txt", "e.txt"),
+        rename("dir", "moved"),
+        upload("moved/x.txt"),
+    ];
+    assert_eq!(tasks(&outbox), expected);
+
+    // a file renamed back is not renamed, a source reused in between is not merged
+    outbox.push(rename("p.txt", "q.txt"))?;
+    outbox.push(rename("q.txt", "p.txt"))?;
+    outbox.push(upload("f.txt"))?;
+    outbox.push(rename("f.txt", "g.txt"))?;
+    outbox.push(upload("f.txt"))?;
+    outbox.push(rename("g.txt", "h.txt"))?;
+    assert_eq!(
+        tasks(&outbox)[expected.len()..],
+        [
+            rename("f.txt", "g.txt"),
+            upload("f.txt"),
+            rename("g.txt", "h.txt"),
+            upload("h.txt"),
+        ]
+    );
+    for entry in &outbox.entries()[expected.len()..] {
+        assert!(outbox.remove(entry.id)?);
+    }
+    assert!(!outbox.remove(u64::MAX)?);
+    assert_eq!(tasks(&outbox), expected);
+
+    // the queue survives restarts
+    let entries = outbox.entries();
+    drop(outbox);
+    let outbox = Outbox::open(&path, &root)?.backoff(Duration::from_secs(60), Duration::MAX);
+    assert_eq!(outbox.entries(), entries);
+
+    // a failed task stalls the queue until retried
+    let store = Flaky {
+        store: DirectoryStore::new(&server),
+        online: AtomicBool::new(false),
+    };
+    assert!(outbox.flush(&store).is_err());
+    let status = outbox.status();
+    assert_eq!(status.pending, expected.len());
+    assert_eq!(status.in_progress, None);
+    assert_eq!(status.last_error.as_deref(), Some("offline"));
+    assert!(status.retry_at.is_some_and(|at| at > FileTime::now()));
+    assert_eq!(outbox.entries()[0].attempts, 1);
+
+    store.online.store(true, Ordering::SeqCst);
+    assert_eq!(outbox.flush(&store)?, 0);
+    outbox.resume()?;
+    assert_eq!(outbox.flush(&store)?, expected.len());
+    assert_eq!(outbox.status(), Default::default());
+
+    assert_eq!(fs::read(server.join("a.txt")).context("read")?, b"hello");
+    assert_eq!(fs::read(server.join("e.txt")).context("read")?, b"c");
+    assert_eq!(fs::read(server.join("moved/x.txt")).context("read")?, b"x");
+    assert!(!server.join("b.txt").exists());
+    assert!(!server.join("c.txt").exists());
+    assert!(!server.join("dir").exists());
+
+    drop(outbox);
+    assert!(Outbox::open(&path, &root)?.is_empty());
+
+    // deletes and renames are acknowledged locally and queued
+    let outbox = Arc::new(Outbox::open(dir.join("layer.log"), ROOT_PATH)?);
+    let sim = Simulator::new(
+        ROOT_PATH,
+        FilterStack::new()
+            .intercept(outbox.clone())
+            .filter(StoreFilter::new(DirectoryStore::new(&server), ROOT_PATH)),
+    );
+    sim.delete("a.txt")?;
+    sim.rename("moved", "dir")?;
+    assert!(sim.entry("a.txt").is_none());
+    assert!(sim.entry("dir").is_some());
+    assert_eq!(tasks(&outbox), [delete("a.txt"), rename("moved", "dir")]);
+    assert!(server.join("a.txt").exists());
+
+    assert_eq!(outbox.flush(&DirectoryStore::new(&server))?, 2);
+    assert!(!server.join("a.txt").exists());
+    assert!(server.join("dir/x.txt").exists());
+
+    drop(sim);
+    drop(outbox);
+
+    // a populated directory moved into the sync root is uploaded along with its entries
+    fs::create_dir_all(root.join("in/sub")).context("create dir")?;
+    fs::write(root.join("in/sub/y.txt"), "y").context("write")?;
+    let outbox = Arc::new(Outbox::open(dir.join("moved.log"), &root)?);
+    let filter = FilterStack::new()
+        .intercept(outbox.clone())
+        .filter(StoreFilter::new(DirectoryStore::new(&server), &root));
+    filter.renamed(
+        Request::builder().path(root.join("in")).build(),
+        info::Renamed::builder().source_path(dir.join("in")).build(),
+    );
+    assert_eq!(
+        tasks(&outbox),
+        [upload("in"), upload("in/sub"), upload("in/sub/y.txt")]
+    );
+    assert_eq!(outbox.flush(&DirectoryStore::new(&server))?, 3);
+    assert_eq!(fs::read(server.join("in/sub/y.txt")).context("read")?, b"y");
+
+    // so is a renamed directory whose source is missing on the remote
+    fs::create_dir_all(root.join("new")).context("create dir")?;
+    fs::write(root.join("new/z.txt"), "z").context("write")?;
+    outbox.push(rename("missing", "new"))?;
+    assert_eq!(outbox.flush(&DirectoryStore::new(&server))?, 2);
+    assert_eq!(fs::read(server.join("new/z.txt")).context("read")?, b"z");
+
+    drop(filter);
+    drop(outbox);
+    fs::write(&path, b"not an outbox").context("write")?;
+    assert!(Outbox::open(&path, &root).is_err());
+
+    fs::remove_dir_all(&dir).context("remove dir")?;
+    Ok(())
+}
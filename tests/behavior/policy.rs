@@ -0,0 +1,175 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info,
+        layer::{Decision, FilterStack, Matcher, Policy},
+        ticket, Operation, Process, RecordingBackend, Request, SyncFilter,
+    },
+    utility::WriteAt,
+};
+use libtest_mimic::Failed;
+
+/// Fills the required range with ones, recording the ranges it was asked for.
+#[derive(Default)]
+struct OnesFilter {
+    ranges: Mutex<Vec<Range<u64>>>,
+}
+
+impl SyncFilter for OnesFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let range = info.required_file_range();
+        self.ranges.lock().unwrap().push(range.clone());
+        ticket.write_at(&vec![1; (range.end - range.start) as usize], range.start)?;
+        Ok(())
+    }
+}
+
+/// Hydrates the range on behalf of the process, returning the result and the bytes written.
+fn hydrate(
+    filter: &impl SyncFilter,
+    process: Process,
+    range: Range<u64>,
+) -> (CResult<()>, Vec<(u64, Vec<u8>)>) {
+    let backend = Arc::new(RecordingBackend::new());
+    let result = filter.fetch_data(
+        Request::builder().path("file.jpg").process(process).build(),
+        ticket::FetchData::new(backend.clone()),
+        info::FetchData::builder()
+            .required_range(range.clone())
+            .optional_range(range)
+            .build(),
+    );
+    let writes = backend
+        .operations()
+        .into_iter()
+        .filter_map(|operation| match operation {
+            Operation::Write { offset, data } => Some((offset, data)),
+            _ => None,
+        })
+        .collect();
+    (result, writes)
+}
+
+pub fn test() -> Result<(), Failed> {
+    let indexer = || Process::builder().name("Contoso.Indexer").build();
+    let explorer = || {
+        Process::builder()
+            .application_id("Microsoft.Windows.Explorer")
+            .build()
+    };
+    let editor = || Process::builder().name("Contoso.Editor").build();
+
+    let decisions = Arc::new(Mutex::new(Vec::new()));
+    let policy = Policy::new()
+        .rule(Matcher::package_name("contoso.indexer"), Decision::Zeros)
+        .rule(
+            Matcher::application_id("Microsoft.Windows.Explorer"),
+            Decision::Thumbnail(4096),
+        )
+        .on_decision({
+            let decisions = decisions.clone();
+            move |request, decision| {
+                decisions
+                    .lock()
+                    .unwrap()
+                    .push((request.process().name(), decision))
+            }
+        });
+    #[cfg(feature = "globs")]
+    let policy = policy.deny(Matcher::image_path(r"*\antivirus\*.EXE")?);
+    let filter = FilterStack::new()
+        .intercept(policy)
+        .filter(OnesFilter::default());
+
+    // the first matching rule decides, ignoring the case
+    let (result, writes) = hydrate(&filter, indexer(), 0..10_000);
+    result?;
+    assert_eq!(writes, [(0, vec![0; 10_000])]);
+
+    // only the start of the file is served to the thumbnailer
+    let (result, writes) = hydrate(&filter, explorer(), 0..10_000);
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::AccessDenied)
+    );
+    assert_eq!(writes, [(0, vec![1; 4096])]);
+    let (result, writes) = hydrate(&filter, explorer(), 8192..10_000);
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::AccessDenied)
+    );
+    assert!(writes.is_empty());
+    let (result, _) = hydrate(&filter, explorer(), 0..100);
+    result?;
+
+    // the thumbnail length is rounded up to the transfer alignment
+    let thumbnails = FilterStack::new()
+        .intercept(Policy::new().rule(
+            Matcher::application_id("Microsoft.Windows.Explorer"),
+            Decision::Thumbnail(5000),
+        ))
+        .filter(OnesFilter::default());
+    let (result, writes) = hydrate(&thumbnails, explorer(), 0..10_000);
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::AccessDenied)
+    );
+    assert_eq!(writes, [(0, vec![1; 8192])]);
+    let (result, writes) = hydrate(&thumbnails, explorer(), 4096..8000);
+    result?;
+    assert_eq!(writes, [(4096, vec![1; 3904])]);
+
+    // other processes are allowed
+    let (result, writes) = hydrate(&filter, editor(), 0..10);
+    result?;
+    assert_eq!(writes, [(0, vec![1; 10])]);
+    assert_eq!(
+        *filter.inner().ranges.lock().unwrap(),
+        [0..4096, 0..100, 0..10]
+    );
+
+    #[cfg(feature = "globs")]
+    {
+        let antivirus = Process::builder()
+            .name("Contoso.Antivirus")
+            .path(r"\??\C:\Program Files\Antivirus\scan.exe")
+            .build();
+        let (result, writes) = hydrate(&filter, antivirus, 0..10);
+        assert_eq!(
+            result.map_err(|e| e.kind()),
+            Err(CloudErrorKind::AccessDenied)
+        );
+        assert!(writes.is_empty());
+    }
+
+    let decisions = decisions.lock().unwrap();
+    assert_eq!(decisions[0], ("Contoso.Indexer".into(), Decision::Zeros));
+    assert_eq!(decisions[1].1, Decision::Thumbnail(4096));
+    assert_eq!(decisions[4], ("Contoso.Editor".into(), Decision::Allow));
+
+    // the processes matching no rule fall back to the default
+    let filter = FilterStack::new()
+        .intercept(
+            Policy::new()
+                .allow(Matcher::package_name("Contoso.Editor"))
+                .otherwise(Decision::Deny),
+        )
+        .filter(OnesFilter::default());
+    hydrate(&filter, editor(), 0..10).0?;
+    assert_eq!(
+        hydrate(&filter, indexer(), 0..10).0.map_err(|e| e.kind()),
+        Err(CloudErrorKind::AccessDenied)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+use cloud_filter::{
+    state::FileState,
+    sync::{Action, Conflict, ConflictKind, LocalFile, RemoteFile, Snapshot},
+};
+use libtest_mimic::Failed;
+
+use crate::fixtures::{base, base_dir, local, local_dir, modified, path, remote, remote_dir};
+
+#[derive(Default)]
+struct Builder(Snapshot);
+
+impl Builder {
+    fn base(mut self, path: &str, state: FileState) -> Self {
+        self.0.base.insert(PathBuf::from(path), state);
+        self
+    }
+
+    fn local(mut self, path: &str, file: LocalFile) -> Self {
+        self.0.local.insert(PathBuf::from(path), file);
+        self
+    }
+
+    fn remote(mut self, path: &str, file: RemoteFile) -> Self {
+        self.0.remote.insert(PathBuf::from(path), file);
+        self
+    }
+
+    /// Tracks the file as unchanged on both sides.
+    fn synced(self, path: &str, file_id: i64, version: &str) -> Self {
+        self.base(path, base(file_id, version))
+            .local(path, local(file_id))
+            .remote(path, remote(version))
+    }
+
+    fn synced_dir(self, path: &str, file_id: i64) -> Self {
+        self.base(path, base_dir(file_id))
+            .local(path, local_dir(file_id))
+            .remote(path, remote_dir())
+    }
+
+    fn plan(self) -> Vec<Action> {
+        self.0.plan()
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    // unchanged files are left as is
+    assert_eq!(
+        Builder::default()
+            .synced_dir("dir", 1)
+            .synced("dir/a.txt", 2, "v1")
+            .plan(),
+        []
+    );
+
+    // additions
+    assert_eq!(
+        Builder::default()
+            .remote("dir", remote_dir())
+            .remote("dir/a.txt", remote("v1"))
+            .local("b.txt", modified(3))
+            .local("new", local_dir(4))
+            .plan(),
+        [
+            Action::Upload {
+                path: path("b.txt"),
+                usn: Some(3)
+            },
+            Action::CreateLocal {
+                path: path("dir"),
+                remote: remote_dir()
+            },
+            Action::CreateLocal {
+                path: path("dir/a.txt"),
+                remote: remote("v1")
+            },
+            Action::Upload {
+                path: path("new"),
+                usn: Some(4)
+            },
+        ]
+    );
+
+    // files added on both sides
+    assert_eq!(
+        Builder::default()
+            .local("same.txt", local(1))
+            .remote("same.txt", remote("v1"))
+            .local(
+                "stale.txt",
+                LocalFile {
+                    size: 3,
+                    ..local(2)
+                }
+            )
+            .remote("stale.txt", remote("v1"))
+            .local("edited.txt", modified(3))
+            .remote("edited.txt", remote("v1"))
+            .plan(),
+        [
+            Action::Conflict(Conflict {
+                path: path("edited.txt"),
+                kind: ConflictKind::Added,
+                local: Some(modified(3)),
+                remote: Some(remote("v1"))
+            }),
+            Action::Track {
+                path: path("same.txt"),
+                local: local(1),
+                remote: remote("v1")
+            },
+            Action::UpdateLocal {
+                path: path("stale.txt"),
+                remote: remote("v1"),
+                usn: Some(2)
+            },
+        ]
+    );
+
+    // modifications
+    assert_eq!(
+        Builder::default()
+            .base("both.txt", base(1, "v1"))
+            .local("both.txt", modified(1))
+            .remote("both.txt", remote("v2"))
+            .base("local.txt", base(2, "v1"))
+            .local("local.txt", modified(2))
+            .remote("local.txt", remote("v1"))
+            .base("remote.txt", base(3, "v1"))
+            .local("remote.txt", local(3))
+            .remote("remote.txt", remote("v2"))
+            .plan(),
+        [
+            Action::Conflict(Conflict {
+                path: path("both.txt"),
+                kind: ConflictKind::Edited,
+                local: Some(modified(1)),
+                remote: Some(remote("v2"))
+            }),
+            Action::Upload {
+                path: path("local.txt"),
+                usn: Some(2)
+            },
+            Action::UpdateLocal {
+                path: path("remote.txt"),
+                remote: remote("v2"),
+                usn: Some(3)
+            },
+        ]
+    );
+
+    // deletions
+    assert_eq!(
+        Builder::default()
+            .base("both.txt", base(1, "v1"))
+            .base("edited.txt", base(2, "v1"))
+            .local("edited.txt", modified(2))
+            .base("local.txt", base(3, "v1"))
+            .remote("local.txt", remote("v1"))
+            .base("remote.txt", base(4, "v1"))
+            .local("remote.txt", local(4))
+            .plan(),
+        [
+            Action::Forget {
+                path: path("both.txt")
+            },
+            Action::Conflict(Conflict {
+                path: path("edited.txt"),
+                kind: ConflictKind::EditedDeleted,
+                local: Some(modified(2)),
+                remote: None
+            }),
+            Action::DeleteRemote {
+                path: path("local.txt")
+            },
+            Action::DeleteLocal {
+                path: path("remote.txt"),
+                usn: Some(4)
+            },
+        ]
+    );
+
+    // a directory is deleted along with its descendants
+    let deleted = Builder::default()
+        .base("dir", base_dir(1))
+        .local("dir", local_dir(1))
+        .base("dir/a.txt", base(2, "v1"))
+        .local("dir/a.txt", local(2))
+        .base("dir/b.txt", base(3, "v1"));
+    assert_eq!(
+        deleted.plan(),
+        [Action::DeleteLocal {
+            path: path("dir"),
+            usn: Some(1)
+        }]
+    );
+
+    // unless one of them was modified
+    let deleted = Builder::default()
+        .base("dir", base_dir(1))
+        .local("dir", local_dir(1))
+        .base("dir/a.txt", base(2, "v1"))
+        .local("dir/a.txt", local(2))
+        .base("dir/b.txt", base(3, "v1"))
+        .local("dir/b.txt", modified(3));
+    assert_eq!(
+        deleted.plan(),
+        [
+            Action::DeleteLocal {
+                path: path("dir/a.txt"),
+                usn: Some(2)
+            },
+            Action::Conflict(Conflict {
+                path: path("dir/b.txt"),
+                kind: ConflictKind::EditedDeleted,
+                local: Some(modified(3)),
+                remote: None
+            }),
+        ]
+    );
+
+    // a directory moved locally is moved on the remote, its descendants compared at their new
+    // location
+    assert_eq!(
+        Builder::default()
+            .base("dir", base_dir(1))
+            .local("moved", local_dir(1))
+            .remote("dir", remote_dir())
+            .base("dir/a.txt", base(2, "v1"))
+            .local("moved/a.txt", modified(2))
+            .remote("dir/a.txt", remote("v1"))
+            .base("dir/b.txt", base(3, "v1"))
+            .local("moved/b.txt", local(3))
+            .remote("dir/b.txt", remote("v1"))
+            .plan(),
+        [
+            Action::RenameRemote {
+                from: path("dir"),
+                to: path("moved")
+            },
+            Action::Upload {
+                path: path("moved/a.txt"),
+                usn: Some(2)
+            },
+        ]
+    );
+
+    // a file moved on the remote keeps its version
+    assert_eq!(
+        Builder::default()
+            .synced_dir("dir", 1)
+            .base("a.txt", base(2, "v1"))
+            .local("a.txt", local(2))
+            .remote("dir/a.txt", remote("v1"))
+            .base("b.txt", base(3, "v2"))
+            .local("b.txt", local(3))
+            .remote("dir/b.txt", remote("v3"))
+            .plan(),
+        [
+            Action::RenameLocal {
+                from: path("a.txt"),
+                to: path("dir/a.txt")
+            },
+            Action::DeleteLocal {
+                path: path("b.txt"),
+                usn: Some(3)
+            },
+            Action::CreateLocal {
+                path: path("dir/b.txt"),
+                remote: remote("v3")
+            },
+        ]
+    );
+
+    // a type change is a conflict
+    assert!(matches!(
+        &Builder::default()
+            .synced("a", 1, "v1")
+            .remote("a", remote_dir())
+            .plan()[..],
+        [Action::Conflict(Conflict {
+            kind: ConflictKind::TypeChanged,
+            ..
+        })]
+    ));
+
+    Ok(())
+}
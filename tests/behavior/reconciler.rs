@@ -0,0 +1,159 @@
+use std::{env, fs, path::Path, sync::Arc};
+
+use anyhow::Context;
+use cloud_filter::{
+    filter::{info, layer::FilterStack, Process, Request, SyncFilter},
+    state::StateStore,
+    store::{DirectoryStore, StoreFilter},
+    sync::{
+        Action, Change, ConflictKind, FeedCursor, LocalTree, MemoryFeed, Reconciler, Resolution,
+    },
+};
+use libtest_mimic::Failed;
+
+use crate::fixtures::{path, remote, remote_dir, MemTree};
+
+const ROOT_PATH: &str = "reconciler_test";
+
+pub fn test() -> Result<(), Failed> {
+    let dir = env::temp_dir().join(format!("reconciler_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let server = dir.join("server");
+    fs::create_dir_all(server.join("dir")).context("create server")?;
+    fs::write(server.join("a.txt"), "old").context("write")?;
+    fs::write(server.join("dir/b.txt"), "b").context("write")?;
+
+    let reconciler = Arc::new(Reconciler::new(
+        DirectoryStore::new(&server),
+        MemTree::new(ROOT_PATH),
+        StateStore::open(dir.join("state.log"))?,
+    ));
+    let tree = reconciler.tree();
+
+    // the remote files become placeholders
+    let report = reconciler.reconcile()?;
+    assert!(report.failed.is_empty());
+    assert_eq!(
+        tree.paths(),
+        [path("a.txt"), path("dir"), path("dir/b.txt")]
+    );
+    assert!(reconciler.state().get("dir/b.txt").is_some());
+    assert!(reconciler.reconcile()?.applied.is_empty());
+
+    // a placeholder changed after it was scanned is a stale conflict, both sides being kept
+    fs::write(server.join("a.txt"), "new!").context("write")?;
+    let actions = reconciler.snapshot()?.plan();
+    assert!(matches!(actions[..], [Action::UpdateLocal { .. }]));
+    tree.write("a.txt", b"mine");
+    let report = reconciler.apply(actions);
+    assert!(report.failed.is_empty());
+    let [(conflict, resolution)] = &report.resolved[..] else {
+        panic!("expected a single conflict, got {:?}", report.resolved);
+    };
+    assert_eq!(conflict.kind, ConflictKind::Stale);
+    assert_eq!(*resolution, Resolution::KeepBoth);
+    let copy = report
+        .applied
+        .iter()
+        .find_map(|action| match action {
+            Action::SetAside { to, .. } => Some(to.clone()),
+            _ => None,
+        })
+        .context("set aside")?;
+    assert_eq!(
+        tree.data(copy.to_str().unwrap()).as_deref(),
+        Some(&b"mine"[..])
+    );
+    assert_eq!(fs::read(server.join(&copy)).context("read")?, b"mine");
+    let file = tree.file(Path::new("a.txt"))?.context("a.txt")?;
+    assert_eq!(file.size, 4);
+    assert!(!file.modified);
+
+    // a file added locally is uploaded and converted to a placeholder
+    tree.write("new.txt", b"hello");
+    assert!(tree.file(Path::new("new.txt"))?.unwrap().file_id.is_none());
+    let report = reconciler.reconcile()?;
+    assert!(report.failed.is_empty());
+    assert!(matches!(
+        &report.applied[..],
+        [Action::Upload { path, .. }] if path == Path::new("new.txt")
+    ));
+    assert_eq!(fs::read(server.join("new.txt")).context("read")?, b"hello");
+    let file = tree.file(Path::new("new.txt"))?.context("new.txt")?;
+    assert!(file.file_id.is_some());
+    assert!(!file.modified);
+
+    // the files modified by other processes are uploaded off the close callback
+    let filter = FilterStack::new()
+        .intercept(reconciler.clone())
+        .filter(StoreFilter::new(DirectoryStore::new(&server), ROOT_PATH));
+    let close = |relative: &str, process: u32| {
+        filter.closed(
+            Request::builder()
+                .path(Path::new(ROOT_PATH).join(relative))
+                .process(Process::builder().id(process).build())
+                .build(),
+            info::Closed::builder().build(),
+        )
+    };
+    let other = std::process::id() + 1;
+    close("dir/b.txt", other);
+    tree.write("new.txt", b"edited");
+    close("new.txt", std::process::id());
+    assert!(reconciler.reconcile_closed()?.applied.is_empty());
+
+    close("new.txt", other);
+    assert_eq!(fs::read(server.join("new.txt")).context("read")?, b"hello");
+    let report = reconciler.reconcile_closed()?;
+    assert!(matches!(&report.applied[..], [Action::Upload { .. }]));
+    assert_eq!(fs::read(server.join("new.txt")).context("read")?, b"edited");
+    assert!(reconciler.reconcile_closed()?.applied.is_empty());
+
+    // the remote changes are ingested from a feed, a page with failures being polled again
+    let feed = MemoryFeed::new().page_size(2);
+    feed.push(Change::Modified {
+        path: path("dir/b.txt"),
+        remote: remote("v2"),
+    });
+    feed.push(Change::Moved {
+        from: path("dir/b.txt"),
+        to: path("c.txt"),
+        remote: remote("v2"),
+    });
+    feed.push(Change::Added {
+        path: path("later/d.txt"),
+        remote: remote("v1"),
+    });
+    feed.push(Change::Deleted {
+        path: path("a.txt"),
+    });
+    let mut cursor = FeedCursor::open(dir.join("cursor"))?;
+    let report = reconciler.ingest(&feed, &mut cursor)?;
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(cursor.get(), Some("2"));
+    assert!(tree.file(Path::new("dir/b.txt"))?.is_none());
+    assert_eq!(tree.file(Path::new("c.txt"))?.context("c.txt")?.size, 4);
+    assert_eq!(
+        reconciler
+            .state()
+            .get("c.txt")
+            .and_then(|state| state.version.clone()),
+        Some("v2".to_owned())
+    );
+    assert!(tree.file(Path::new("a.txt"))?.is_none());
+
+    reconciler.apply([Action::CreateLocal {
+        path: path("later"),
+        remote: remote_dir(),
+    }]);
+    let report = reconciler.ingest(&feed, &mut cursor)?;
+    assert!(report.failed.is_empty());
+    assert_eq!(cursor.get(), Some("4"));
+    assert!(tree.file(Path::new("later/d.txt"))?.is_some());
+    assert!(reconciler.ingest(&feed, &mut cursor)?.applied.is_empty());
+
+    drop(filter);
+    drop(reconciler);
+    fs::remove_dir_all(&dir).context("remove dir")?;
+    Ok(())
+}
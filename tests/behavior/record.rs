@@ -0,0 +1,164 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{info, layer::FilterStack, ticket, Request, SyncFilter},
+    record::{Callback, Recorder, Replayer, Response},
+    sim::Simulator,
+};
+use libtest_mimic::Failed;
+
+use crate::sim::{MemFilter, ROOT_PATH};
+
+/// A writer whose output is kept after the [Recorder] is dropped.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fails every hydration.
+struct OfflineFilter;
+
+impl SyncFilter for OfflineFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        _ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        Err(CloudErrorKind::NetworkUnavailable.into())
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        MemFilter.fetch_placeholders(request, ticket, info)
+    }
+}
+
+/// Records the paths it is called with.
+#[derive(Default)]
+struct Paths(Mutex<Vec<PathBuf>>);
+
+impl SyncFilter for Paths {
+    fn fetch_data(
+        &self,
+        request: Request,
+        _ticket: ticket::FetchData,
+        _info: info::FetchData,
+    ) -> CResult<()> {
+        self.0.lock().unwrap().push(request.path());
+        Ok(())
+    }
+
+    fn renamed(&self, _request: Request, info: info::Renamed) {
+        self.0.lock().unwrap().push(info.source_path());
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    // record a session driven by the simulator
+    let output = Output::default();
+    let filter = FilterStack::new()
+        .intercept(Recorder::new(output.clone()))
+        .filter(MemFilter);
+    let sim = Simulator::new(ROOT_PATH, filter);
+    sim.read("test1.txt")?;
+    sim.read("dir1/test2.txt")?;
+    sim.dehydrate("test1.txt")?;
+    sim.delete("dir1/test2.txt")?;
+    drop(sim);
+
+    let output = output.0.lock().unwrap().clone();
+    let lines = String::from_utf8(output.clone())?;
+    assert!(lines.lines().all(|line| line.starts_with('{')));
+    assert!(lines.contains(r#""callback":"fetch_data""#));
+
+    let replayer = Replayer::read(&output[..])?;
+    let events = replayer.events();
+    assert_eq!(events.len(), lines.lines().count());
+    let fetch = events
+        .iter()
+        .find(|event| matches!(event.callback, Callback::FetchData { .. }))
+        .ok_or("no fetch_data")?;
+    assert_eq!(fetch.responses, [Response::write(b"test1.txt", 0)]);
+    assert_eq!(fetch.error, None);
+    let request = fetch.request.as_ref().ok_or("no request")?;
+    assert_eq!(
+        Path::new(&request.path),
+        Path::new(ROOT_PATH).join("test1.txt")
+    );
+    assert!(events
+        .iter()
+        .any(|event| matches!(event.callback, Callback::Delete { .. })
+            && event.responses == [Response::Delete]));
+
+    // the same filter responds the same way
+    let replay = replayer.replay(&MemFilter);
+    assert_eq!(replay.replayed.len(), events.len());
+    assert_eq!(replay.divergences(), Vec::<usize>::new());
+
+    // a regression is pointed out
+    let replay = replayer.replay(&OfflineFilter);
+    let divergences = replay
+        .divergences()
+        .into_iter()
+        .map(|index| {
+            let callback = match replay.recorded[index].callback {
+                Callback::FetchData { .. } => "fetch_data",
+                Callback::Dehydrate { .. } => "dehydrate",
+                Callback::Delete { .. } => "delete",
+                _ => "other",
+            };
+            (callback, replay.replayed[index].error)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        divergences,
+        [
+            ("fetch_data", Some(CloudErrorKind::NetworkUnavailable)),
+            ("fetch_data", Some(CloudErrorKind::NetworkUnavailable)),
+            ("dehydrate", Some(CloudErrorKind::NotSupported)),
+            ("delete", Some(CloudErrorKind::NotSupported)),
+        ]
+    );
+
+    // a session recorded on Windows is replayed under another root, matching its components
+    // regardless of their case
+    let session = r#"
+{"at":0,"duration":10,"request":{"volume_guid_path":"","volume_letter":"C:","volume_serial_number":0,"process":{"name":"","id":4,"session_id":1,"application_id":"","command_line":null,"path":null},"sync_root_file_id":0,"file_id":1,"file_size":5,"path":"C:\\Cloud\\dir\\a.txt","priority_hint":0,"file_blob":[],"register_blob":[]},"callback":"fetch_data","interrupted_hydration":false,"explicit_hydration":false,"required_file_range":{"start":0,"end":5},"optional_file_range":{"start":0,"end":5},"last_dehydration_time":0,"last_dehydration_reason":null,"responses":[],"error":null}
+
+{"at":20,"duration":1,"request":null,"callback":"renamed","source_path":"c:\\cloud\\b.txt","responses":[],"error":"AccessDenied"}
+{"at":30,"duration":1,"request":null,"callback":"renamed","source_path":"C:\\CloudOther\\c.txt","responses":[],"error":null}
+"#;
+    let filter = Paths::default();
+    let replay = Replayer::read(session.as_bytes())?
+        .rebase(r"C:\Cloud", "root")
+        .replay(&filter);
+    assert_eq!(
+        *filter.0.lock().unwrap(),
+        [
+            Path::new("root").join("dir").join("a.txt"),
+            Path::new("root").join("b.txt"),
+            PathBuf::from(r"C:\CloudOther\c.txt")
+        ]
+    );
+    assert_eq!(replay.divergences(), [1]);
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::{path::Path, sync::Arc};
+
+use cloud_filter::{
+    blob,
+    error::CloudErrorKind,
+    filter::{info, ticket, Operation, RecordingBackend, Request, SyncFilter},
+    placeholder_file::PlaceholderFile,
+};
+use libtest_mimic::Failed;
+
+use crate::sim::{MemFilter, ROOT_PATH};
+
+pub fn test() -> Result<(), Failed> {
+    let backend = Arc::new(RecordingBackend::new());
+    MemFilter
+        .fetch_data(
+            Request::builder()
+                .path(Path::new(ROOT_PATH).join("test1.txt"))
+                .file_blob(blob::encode(&"test1.txt".to_owned()).unwrap())
+                .build(),
+            ticket::FetchData::new(backend.clone()),
+            info::FetchData::builder().required_range(0..9).build(),
+        )
+        .unwrap();
+    assert!(matches!(backend.operations().as_slice(),
+        [Operation::Write { offset: 0, data }] if data == b"test1.txt"
+    ));
+    assert_eq!(backend.data(), b"test1.txt");
+
+    // blobs not written through `FileIdentity` are rejected before any operation
+    let backend = Arc::new(RecordingBackend::new());
+    let result = MemFilter.fetch_data(
+        Request::builder()
+            .path(Path::new(ROOT_PATH).join("test1.txt"))
+            .file_blob("test1.txt".into())
+            .build(),
+        ticket::FetchData::new(backend.clone()),
+        info::FetchData::builder().required_range(0..9).build(),
+    );
+    assert!(matches!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::MetadataCorrupt)
+    ));
+    assert!(backend.operations().is_empty());
+
+    let backend = Arc::new(RecordingBackend::new());
+    MemFilter
+        .fetch_placeholders(
+            Request::builder().path(ROOT_PATH).build(),
+            ticket::FetchPlaceholders::new(backend.clone()),
+            info::FetchPlaceholders::builder().build(),
+        )
+        .unwrap();
+    let operations = backend.operations();
+    let [Operation::CreatePlaceholders(placeholders)] = operations.as_slice() else {
+        return Err("expected a single CreatePlaceholders operation".into());
+    };
+    assert_eq!(
+        placeholders
+            .iter()
+            .map(PlaceholderFile::relative_path)
+            .collect::<Vec<_>>(),
+        ["dir1", "test1.txt", "large.bin"].map(Path::new)
+    );
+    assert!(placeholders
+        .iter()
+        .all(|placeholder| placeholder.result().is_ok()));
+
+    let backend = Arc::new(RecordingBackend::new());
+    MemFilter
+        .delete(
+            Request::builder().build(),
+            ticket::Delete::new(backend.clone()),
+            info::Delete::builder().build(),
+        )
+        .unwrap();
+    assert!(matches!(
+        backend.operations().as_slice(),
+        [Operation::Delete]
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use std::{
+    future::Future,
+    ops::Range,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{
+        info,
+        layer::{Layered, Retry},
+        ticket, Filter, Operation, RecordingBackend, Request, SyncFilter,
+    },
+    utility::WriteAt,
+};
+use libtest_mimic::Failed;
+
+/// Writes one 4KiB chunk of the required range per attempt, failing until the range is written.
+#[derive(Default)]
+struct FlakyFilter {
+    failure: Option<CloudErrorKind>,
+    ranges: Mutex<Vec<Range<u64>>>,
+}
+
+impl FlakyFilter {
+    fn failing(kind: CloudErrorKind) -> Self {
+        Self {
+            failure: Some(kind),
+            ..Default::default()
+        }
+    }
+
+    fn attempt(&self, ticket: &ticket::FetchData, info: &info::FetchData) -> CResult<()> {
+        let range = info.required_file_range();
+        self.ranges.lock().unwrap().push(range.clone());
+
+        let end = (range.start + 4096).min(range.end);
+        ticket.write_at(&vec![1; (end - range.start) as usize], range.start)?;
+        match self.failure {
+            Some(kind) if end < range.end => Err(kind.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SyncFilter for FlakyFilter {
+    fn fetch_data(
+        &self,
+        _request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        self.attempt(&ticket, &info)
+    }
+
+    fn fetch_placeholders(
+        &self,
+        _request: Request,
+        _ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let mut ranges = self.ranges.lock().unwrap();
+        ranges.push(0..0);
+        match ranges.len() {
+            1 => Err(CloudErrorKind::InsufficientResources.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Filter for FlakyFilter {
+    async fn fetch_data(
+        &self,
+        _request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        self.attempt(&ticket, &info)
+    }
+}
+
+/// Sleeps on a thread of its own, standing in for the timer of a runtime.
+fn sleep(duration: Duration) -> impl Future<Output = ()> {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    thread::spawn(move || {
+        thread::sleep(duration);
+        _ = sender.send(());
+    });
+    async move {
+        _ = receiver.await;
+    }
+}
+
+fn fetch(
+    filter: &Layered<Retry, FlakyFilter>,
+    ticket: ticket::FetchData,
+    range: Range<u64>,
+) -> CResult<()> {
+    SyncFilter::fetch_data(
+        filter,
+        Request::builder().build(),
+        ticket,
+        info::FetchData::builder().required_range(range).build(),
+    )
+}
+
+fn writes(backend: &RecordingBackend) -> Vec<(u64, usize)> {
+    backend
+        .operations()
+        .into_iter()
+        .map(|operation| match operation {
+            Operation::Write { offset, data } => (offset, data.len()),
+            operation => panic!("unexpected operation {operation:?}"),
+        })
+        .collect()
+}
+
+pub fn test() -> Result<(), Failed> {
+    // each retry resumes from the end of the written data
+    let retry = Layered::new(
+        Retry::new().initial_backoff(Duration::from_millis(1)),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let backend = Arc::new(RecordingBackend::new());
+    fetch(&retry, ticket::FetchData::new(backend.clone()), 0..10000)?;
+    assert_eq!(writes(&backend), [(0, 4096), (4096, 4096), (8192, 1808)]);
+    assert_eq!(
+        *retry.inner().ranges.lock().unwrap(),
+        [0..10000, 4096..10000, 8192..10000]
+    );
+
+    // the attempts are bounded
+    let retry = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_millis(1))
+            .max_attempts(2),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let backend = Arc::new(RecordingBackend::new());
+    let result = fetch(&retry, ticket::FetchData::new(backend.clone()), 0..10000);
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::NetworkUnavailable)
+    );
+    assert_eq!(writes(&backend), [(0, 4096), (4096, 4096)]);
+
+    // permanent errors are not retried
+    let retry = Layered::new(
+        Retry::new(),
+        FlakyFilter::failing(CloudErrorKind::AccessDenied),
+    );
+    let backend = Arc::new(RecordingBackend::new());
+    let result = fetch(&retry, ticket::FetchData::new(backend.clone()), 0..10000);
+    assert_eq!(
+        result.map_err(|e| e.kind()),
+        Err(CloudErrorKind::AccessDenied)
+    );
+    assert_eq!(retry.inner().ranges.lock().unwrap().len(), 1);
+
+    // unless asked to
+    let retry = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_millis(1))
+            .retry_if(|kind| kind == CloudErrorKind::AccessDenied),
+        FlakyFilter::failing(CloudErrorKind::AccessDenied),
+    );
+    fetch(
+        &retry,
+        ticket::FetchData::new(Arc::new(RecordingBackend::new())),
+        0..8192,
+    )?;
+    assert_eq!(retry.inner().ranges.lock().unwrap().len(), 2);
+
+    // a backoff reaching past the deadline gives up right away
+    let retry = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_secs(10))
+            .deadline(Duration::from_secs(1)),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let started = Instant::now();
+    let result = fetch(
+        &retry,
+        ticket::FetchData::new(Arc::new(RecordingBackend::new())),
+        0..10000,
+    );
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    // cancelling the operation interrupts the backoff
+    let retry = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_secs(10))
+            .max_backoff(Duration::from_secs(10))
+            .deadline(Duration::from_secs(60)),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let ticket = ticket::FetchData::new(Arc::new(RecordingBackend::new()));
+    let token = ticket.cancellation_token().clone();
+    let started = Instant::now();
+    let result = thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        fetch(&retry, ticket, 0..10000)
+    });
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(retry.inner().ranges.lock().unwrap().len(), 1);
+
+    // directory populations are retried as well
+    let retry = Layered::new(
+        Retry::new().initial_backoff(Duration::from_millis(1)),
+        FlakyFilter::default(),
+    );
+    SyncFilter::fetch_placeholders(
+        &retry,
+        Request::builder().build(),
+        ticket::FetchPlaceholders::new(Arc::new(RecordingBackend::new())),
+        info::FetchPlaceholders::builder().build(),
+    )?;
+    assert_eq!(retry.inner().ranges.lock().unwrap().len(), 2);
+
+    // the async filter is retried the same way
+    let retry = Layered::new(
+        Retry::new().initial_backoff(Duration::from_millis(1)),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let backend = Arc::new(RecordingBackend::new());
+    futures::executor::block_on(Filter::fetch_data(
+        &retry,
+        Request::builder().build(),
+        ticket::FetchData::new(backend.clone()),
+        info::FetchData::builder().required_range(0..10000).build(),
+    ))?;
+    assert_eq!(writes(&backend), [(0, 4096), (4096, 4096), (8192, 1808)]);
+
+    // async backoffs waiting on the sleep hook do not block each other, and are interrupted by
+    // cancellation
+    let retry = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_secs(10))
+            .max_backoff(Duration::from_secs(10))
+            .deadline(Duration::from_secs(60))
+            .sleep(sleep),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let quick = Layered::new(
+        Retry::new()
+            .initial_backoff(Duration::from_millis(1))
+            .sleep(sleep),
+        FlakyFilter::failing(CloudErrorKind::NetworkUnavailable),
+    );
+    let ticket = ticket::FetchData::new(Arc::new(RecordingBackend::new()));
+    let token = ticket.cancellation_token().clone();
+    let started = Instant::now();
+    let (slow, fast) = thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(500));
+            token.cancel();
+        });
+        futures::executor::block_on(futures::future::join(
+            Filter::fetch_data(
+                &retry,
+                Request::builder().build(),
+                ticket,
+                info::FetchData::builder().required_range(0..10000).build(),
+            ),
+            async {
+                let result = Filter::fetch_data(
+                    &quick,
+                    Request::builder().build(),
+                    ticket::FetchData::new(Arc::new(RecordingBackend::new())),
+                    info::FetchData::builder().required_range(0..10000).build(),
+                )
+                .await;
+                result.map(|()| started.elapsed())
+            },
+        ))
+    });
+    assert!(slow.is_err());
+    assert!(fast? < Duration::from_millis(500));
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    Ok(())
+}
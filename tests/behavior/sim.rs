@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use cloud_filter::{
+    error::{CResult, CloudErrorKind},
+    filter::{info, ticket, Request, SyncFilter},
+    metadata::Metadata,
+    placeholder_file::PlaceholderFile,
+    sim::{Error, Simulator},
+    utility::WriteAt,
+};
+use libtest_mimic::Failed;
+use nt_time::FileTime;
+
+pub const ROOT_PATH: &str = "sim_test";
+
+pub struct MemFilter;
+
+impl SyncFilter for MemFilter {
+    fn fetch_data(
+        &self,
+        request: Request,
+        ticket: ticket::FetchData,
+        info: info::FetchData,
+    ) -> CResult<()> {
+        let path = request.file_identity::<String>()?;
+        let path = path.as_str();
+        println!("fetch_data: path: {path:?}");
+
+        match path {
+            "test1.txt" | "dir1/test2.txt" => {
+                if info.required_file_range() != (0..path.len() as u64) {
+                    Err(CloudErrorKind::InvalidRequest)?;
+                }
+                ticket.write_at(path.as_bytes(), 0).unwrap();
+            }
+            "large.bin" => {
+                // misaligned writes must be rejected
+                assert!(ticket.write_at(&[1; 10], 1).is_err());
+                assert!(ticket.write_at(&[1; 10], 0).is_err());
+                // as well as writes past the largest offset
+                assert!(ticket.write_at(&[1; 4096], u64::MAX - 4095).is_err());
+            }
+            _ => Err(CloudErrorKind::InvalidRequest)?,
+        }
+
+        Ok(())
+    }
+
+    fn fetch_placeholders(
+        &self,
+        request: Request,
+        ticket: ticket::FetchPlaceholders,
+        _info: info::FetchPlaceholders,
+    ) -> CResult<()> {
+        let path = request.path();
+        let relative_path = path.strip_prefix(ROOT_PATH).unwrap();
+        println!("fetch_placeholders: path: {path:?}, relative path: {relative_path:?}");
+
+        let now = FileTime::now();
+        let file = |name: &str, blob: &str, size: u64| {
+            PlaceholderFile::new(name)
+                .has_no_children()
+                .mark_in_sync()
+                .metadata(Metadata::file().created(now).written(now).size(size))
+                .identity(&blob.to_owned())
+        };
+        let mut placeholders = match relative_path.to_string_lossy().as_ref() {
+            "" => vec![
+                PlaceholderFile::new("dir1")
+                    .mark_in_sync()
+                    .metadata(Metadata::directory().created(now).written(now).size(0))
+                    .identity(&"dir1".to_owned()),
+                file("test1.txt", "test1.txt", "test1.txt".len() as _),
+                // the content of the placeholders is not allocated up front
+                file("large.bin", "large.bin", 1 << 40),
+            ],
+            "dir1" => vec![file(
+                "test2.txt",
+                "dir1/test2.txt",
+                "dir1/test2.txt".len() as _,
+            )],
+            _ => Err(CloudErrorKind::InvalidRequest)?,
+        };
+
+        ticket.pass_with_placeholder(&mut placeholders).unwrap();
+        Ok(())
+    }
+
+    fn delete(
+        &self,
+        _request: Request,
+        ticket: ticket::Delete,
+        _info: info::Delete,
+    ) -> CResult<()> {
+        ticket.pass().unwrap();
+        Ok(())
+    }
+
+    fn rename(&self, _request: Request, ticket: ticket::Rename, info: info::Rename) -> CResult<()> {
+        assert_eq!(
+            info.target_path(),
+            Path::new(ROOT_PATH).join("dir1/moved.txt")
+        );
+        ticket.pass().unwrap();
+        Ok(())
+    }
+
+    fn dehydrate(
+        &self,
+        _request: Request,
+        ticket: ticket::Dehydrate,
+        _info: info::Dehydrate,
+    ) -> CResult<()> {
+        ticket.pass().unwrap();
+        Ok(())
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let sim = Simulator::new(ROOT_PATH, MemFilter);
+
+    let mut names = sim
+        .read_dir("")?
+        .iter()
+        .map(|entry| entry.path().to_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["dir1", "large.bin", "test1.txt"].map(Path::new));
+
+    for relative in ["test1.txt", "dir1/test2.txt"] {
+        assert_eq!(sim.read(relative)?, relative.as_bytes());
+        assert!(sim.entry(relative).unwrap().is_hydrated());
+    }
+
+    assert!(matches!(
+        sim.read("large.bin"),
+        Err(Error::Callback(CloudErrorKind::RequestTimeout))
+    ));
+    assert!(matches!(sim.read("missing.txt"), Err(Error::NotFound(_))));
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 5..2;
+    assert!(sim.read_range("test1.txt", reversed)?.is_empty());
+
+    sim.dehydrate("test1.txt")?;
+    assert!(!sim.entry("test1.txt").unwrap().is_hydrated());
+
+    sim.rename("test1.txt", "dir1/moved.txt")?;
+    assert!(sim.entry("test1.txt").is_none());
+    assert_eq!(sim.read("dir1/moved.txt")?, b"test1.txt");
+
+    sim.delete("dir1")?;
+    assert!(sim.entry("dir1/test2.txt").is_none());
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Context;
+use cloud_filter::state::{FileState, PinState, StateStore};
+use libtest_mimic::Failed;
+
+fn file(file_id: i64, version: &str) -> FileState {
+    FileState {
+        file_id: Some(file_id),
+        version: Some(version.to_owned()),
+        usn: Some(file_id * 10),
+        size: 42,
+        pin: PinState::Pinned,
+        in_sync: true,
+        ..Default::default()
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let dir = env::temp_dir().join(format!("state_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).context("create dir")?;
+    let path = dir.join("state.log");
+
+    let mut store = StateStore::open(&path)?;
+    assert!(store.is_empty());
+
+    let mut transaction = store.transaction();
+    transaction
+        .put(
+            "dir",
+            FileState {
+                directory: true,
+                ..Default::default()
+            },
+        )
+        .put("dir/a.txt", file(1, "v1"))
+        .put("dir/sub/b.txt", file(2, "v1"))
+        .put("c.txt", file(3, "v1"));
+    assert_eq!(transaction.get("dir/a.txt"), Some(&file(1, "v1")));
+    transaction.commit()?;
+    assert_eq!(store.len(), 4);
+
+    // dropped transactions are rolled back
+    let mut transaction = store.transaction();
+    transaction.remove("c.txt");
+    assert!(transaction.get("c.txt").is_none());
+    drop(transaction);
+    assert_eq!(store.get("c.txt"), Some(&file(3, "v1")));
+
+    // renames move the descendants and keep the file ids indexed
+    let mut transaction = store.transaction();
+    transaction.rename("dir", "moved");
+    assert!(transaction.update("moved/a.txt", |state| state.in_sync = false));
+    assert!(!transaction.update("dir/a.txt", |state| state.in_sync = false));
+    transaction.commit()?;
+    assert!(store.get("dir/a.txt").is_none());
+    assert!(!store.get("moved/a.txt").unwrap().in_sync);
+    assert_eq!(
+        store.find_by_id(2).map(|(path, _)| path.to_owned()),
+        Some(Path::new("moved/sub/b.txt").to_owned())
+    );
+
+    // a rename to a path sorting before the source keeps the file id indexed
+    let mut transaction = store.transaction();
+    transaction.rename("c.txt", "b.txt");
+    transaction.commit()?;
+    assert_eq!(
+        store.find_by_id(3).map(|(path, _)| path.to_owned()),
+        Some(Path::new("b.txt").to_owned())
+    );
+    let mut transaction = store.transaction();
+    transaction.rename("b.txt", "c.txt");
+    transaction.commit()?;
+    assert_eq!(
+        store.find_by_id(3).map(|(path, _)| path.to_owned()),
+        Some(Path::new("c.txt").to_owned())
+    );
+
+    // removals remove the descendants
+    let mut transaction = store.transaction();
+    transaction.remove("moved/sub");
+    transaction.commit()?;
+    assert!(store.find_by_id(2).is_none());
+
+    let entries = store
+        .iter()
+        .map(|(path, state)| (path.to_owned(), state.clone()))
+        .collect::<Vec<_>>();
+    drop(store);
+
+    // the entries persist
+    let mut store = StateStore::open(&path)?;
+    assert_eq!(
+        store
+            .iter()
+            .map(|(path, state)| (path.to_owned(), state.clone()))
+            .collect::<Vec<_>>(),
+        entries
+    );
+
+    // compaction keeps the entries
+    store.compact()?;
+    drop(store);
+    let store = StateStore::open(&path)?;
+    assert_eq!(store.len(), entries.len());
+    assert_eq!(store.get("c.txt"), Some(&file(3, "v1")));
+    drop(store);
+
+    // a torn entry is discarded along with its transaction
+    let len = fs::metadata(&path).context("metadata")?.len();
+    let mut store = StateStore::open(&path)?;
+    let mut transaction = store.transaction();
+    transaction.put("c.txt", file(3, "v2")).remove("moved");
+    transaction.commit()?;
+    drop(store);
+    let torn = fs::metadata(&path).context("metadata")?.len() - 3;
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .context("open")?
+        .set_len(torn)
+        .context("truncate")?;
+
+    let mut store = StateStore::open(&path)?;
+    assert_eq!(store.get("c.txt"), Some(&file(3, "v1")));
+    assert!(store.get("moved/a.txt").is_some());
+    assert_eq!(fs::metadata(&path).context("metadata")?.len(), len);
+
+    // the store is writable after the tail is cut off
+    let mut transaction = store.transaction();
+    transaction.put("c.txt", file(3, "v2"));
+    transaction.commit()?;
+    drop(store);
+
+    // a corrupt tail is cut off as well
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .context("open")?
+        .write_all(&[0xff; 32])
+        .context("append")?;
+    let store = StateStore::open(&path)?;
+    assert_eq!(store.get("c.txt"), Some(&file(3, "v2")));
+    assert_eq!(store.len(), entries.len());
+    drop(store);
+
+    // paths that are not valid Unicode are kept as is
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let name = Path::new(OsStr::from_bytes(b"caf\xe9.txt"));
+        let mut store = StateStore::open(&path)?;
+        let mut transaction = store.transaction();
+        transaction.put(name, file(4, "v1"));
+        transaction.commit()?;
+        drop(store);
+        let store = StateStore::open(&path)?;
+        assert_eq!(store.get(name), Some(&file(4, "v1")));
+        assert!(store.get("caf\u{fffd}.txt").is_none());
+    }
+
+    fs::write(&path, b"not a state file").context("write")?;
+    assert!(StateStore::open(&path).is_err());
+
+    fs::remove_dir_all(&dir).context("remove dir")?;
+    Ok(())
+}
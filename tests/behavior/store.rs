@@ -0,0 +1,173 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use cloud_filter::{
+    error::CloudErrorKind,
+    filter::{info, ticket, Operation, RecordingBackend, Request, SyncFilter},
+    metadata::Metadata,
+    sim::{Error, Simulator},
+    store::{Entry, RemoteStore, StoreFilter},
+};
+use libtest_mimic::Failed;
+
+const ROOT_PATH: &str = "store_test";
+
+/// A store keeping files in memory, `None` marking directories.
+#[derive(Debug, Default)]
+struct MemStore(Mutex<BTreeMap<PathBuf, Option<Vec<u8>>>>);
+
+impl MemStore {
+    fn metadata(data: &Option<Vec<u8>>) -> Metadata {
+        match data {
+            Some(data) => Metadata::file().size(data.len() as _),
+            None => Metadata::directory(),
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("{path:?} does not exist"))
+    }
+}
+
+impl RemoteStore for MemStore {
+    fn stat(&self, path: &Path) -> io::Result<Metadata> {
+        let files = self.0.lock().unwrap();
+        files
+            .get(path)
+            .map(Self::metadata)
+            .ok_or_else(|| Self::not_found(path))
+    }
+
+    fn list_dir(&self, path: &Path) -> io::Result<Vec<Entry>> {
+        let files = self.0.lock().unwrap();
+        Ok(files
+            .iter()
+            .filter(|(child, _)| child.parent() == Some(path))
+            .map(|(child, data)| Entry::new(child.file_name().unwrap(), Self::metadata(data)))
+            .collect())
+    }
+
+    fn read_range(&self, path: &Path, range: Range<u64>) -> io::Result<Box<dyn Read + '_>> {
+        let files = self.0.lock().unwrap();
+        let Some(Some(data)) = files.get(path) else {
+            return Err(Self::not_found(path));
+        };
+        let end = (range.end as usize).min(data.len());
+        Ok(Box::new(Cursor::new(
+            data[range.start as usize..end].to_vec(),
+        )))
+    }
+
+    fn write(&self, path: &Path, data: &mut dyn Read) -> io::Result<Metadata> {
+        let mut buf = Vec::new();
+        data.read_to_end(&mut buf)?;
+        let data = Some(buf);
+        let metadata = Self::metadata(&data);
+        self.0.lock().unwrap().insert(path.to_path_buf(), data);
+        Ok(metadata)
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let mut files = self.0.lock().unwrap();
+        files.remove(path).ok_or_else(|| Self::not_found(path))?;
+        files.retain(|child, _| !child.starts_with(path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.0.lock().unwrap();
+        let moved = files
+            .keys()
+            .filter(|child| child.starts_with(from))
+            .cloned()
+            .collect::<Vec<_>>();
+        if moved.is_empty() {
+            return Err(Self::not_found(from));
+        }
+
+        for child in moved {
+            let data = files.remove(&child).unwrap();
+            files.insert(to.join(child.strip_prefix(from).unwrap()), data);
+        }
+        Ok(())
+    }
+}
+
+pub fn test() -> Result<(), Failed> {
+    let large = (0..10000).map(|i| i as u8).collect::<Vec<_>>();
+    let store = MemStore::default();
+    store.write(Path::new("test1.txt"), &mut &b"test1.txt"[..])?;
+    store.0.lock().unwrap().insert("dir1".into(), None);
+    store.write(Path::new("dir1/large.bin"), &mut large.as_slice())?;
+
+    let sim = Simulator::new(
+        ROOT_PATH,
+        StoreFilter::new(store, ROOT_PATH).chunk_size(4096),
+    );
+
+    let mut names = sim
+        .read_dir("")?
+        .iter()
+        .map(|entry| entry.path().to_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["dir1", "test1.txt"].map(Path::new));
+
+    let entry = sim.entry("test1.txt").unwrap();
+    assert!(entry.in_sync());
+    // the placeholders are resolved by their path, a blob would go stale once renamed
+    assert!(entry.blob().is_empty());
+
+    assert_eq!(sim.read("test1.txt")?, b"test1.txt");
+    // a full chunk followed by the unaligned tail at the end of the file
+    assert_eq!(
+        sim.read_range("dir1/large.bin", 4100..9000)?,
+        &large[4100..9000]
+    );
+    assert_eq!(sim.read("dir1/large.bin")?, large);
+
+    // an unaligned required range is transferred from the page it starts in
+    let backend = Arc::new(RecordingBackend::new());
+    sim.filter().fetch_data(
+        Request::builder()
+            .path(Path::new(ROOT_PATH).join("dir1/large.bin"))
+            .file_size(large.len() as u64)
+            .build(),
+        ticket::FetchData::new(backend.clone()),
+        info::FetchData::builder()
+            .required_range(5000..6000)
+            .build(),
+    )?;
+    let writes = backend
+        .operations()
+        .into_iter()
+        .filter_map(|operation| match operation {
+            Operation::Write { offset, data } => Some((offset, data)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(writes, [(4096, large[4096..8192].to_vec())]);
+
+    sim.rename("test1.txt", "dir1/moved.txt")?;
+    let store = sim.filter().store();
+    assert!(store.stat(Path::new("test1.txt")).is_err());
+    assert!(store.stat(Path::new("dir1/moved.txt")).is_ok());
+
+    // the placeholder no longer matches the remote
+    sim.dehydrate("dir1/moved.txt")?;
+    store.delete(Path::new("dir1/moved.txt"))?;
+    assert!(matches!(
+        sim.read("dir1/moved.txt"),
+        Err(Error::Callback(CloudErrorKind::NotInSync))
+    ));
+
+    sim.delete("dir1")?;
+    assert!(store.list_dir(Path::new(""))?.is_empty());
+
+    Ok(())
+}
@@ -1,4 +1,3 @@
-use core::str;
 use std::{fs, path::Path};
 
 use anyhow::Context;
@@ -27,10 +26,11 @@ impl SyncFilter for MemFilter {
         ticket: ticket::FetchData,
         info: info::FetchData,
     ) -> CResult<()> {
-        let path = unsafe { str::from_utf8_unchecked(request.file_blob()) };
+        let path = request.file_identity::<String>()?;
+        let path = path.as_str();
         println!("fetch_data: path: {path:?}");
 
-        let content = match path.as_ref() {
+        let content = match path {
             "test1.txt" | "dir1\\test2.txt" => path,
             _ => Err(CloudErrorKind::InvalidRequest)?,
         };
@@ -60,7 +60,7 @@ impl SyncFilter for MemFilter {
                 PlaceholderFile::new("dir1")
                     .mark_in_sync()
                     .metadata(Metadata::directory().created(now).written(now).size(0))
-                    .blob("dir1".into()),
+                    .identity(&"dir1".to_owned()),
                 PlaceholderFile::new("test1.txt")
                     .has_no_children()
                     .mark_in_sync()
@@ -70,7 +70,7 @@ impl SyncFilter for MemFilter {
                             .written(now)
                             .size("test1.txt".len() as _),
                     )
-                    .blob("test1.txt".into()),
+                    .identity(&"test1.txt".to_owned()),
             ],
             "dir1" => vec![PlaceholderFile::new("test2.txt")
                 .has_no_children()
@@ -81,7 +81,7 @@ impl SyncFilter for MemFilter {
                         .written(now)
                         .size("dir1\\test2.txt".len() as _),
                 )
-                .blob("dir1\\test2.txt".into())],
+                .identity(&"dir1\\test2.txt".to_owned())],
             _ => Err(CloudErrorKind::InvalidRequest)?,
         };
 
@@ -0,0 +1,118 @@
+use std::{
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use cloud_filter::filter::{ticket, CancellationToken, Operation, RecordingBackend};
+use libtest_mimic::Failed;
+
+/// Summarizes the operations as `(offset, length)` for writes and `(total, completed)` for
+/// progress reports.
+fn summarize(backend: &RecordingBackend) -> Vec<(char, u64, u64)> {
+    backend
+        .operations()
+        .into_iter()
+        .map(|operation| match operation {
+            Operation::Write { offset, data } => ('w', offset, data.len() as u64),
+            Operation::ReportProgress { total, completed } => ('p', total, completed),
+            operation => panic!("unexpected operation {operation:?}"),
+        })
+        .collect()
+}
+
+pub fn test() -> Result<(), Failed> {
+    let data = (0..10000).map(|i| i as u8).collect::<Vec<_>>();
+
+    // odd-sized writes are regrouped into aligned chunks, the tail is written at the end
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let mut writer = ticket.writer(0..10000).chunk_size(4096);
+    for piece in data.chunks(999) {
+        writer.write_all(piece)?;
+    }
+    assert_eq!(writer.position(), 10000);
+    writer.finish()?;
+    assert_eq!(
+        summarize(&backend),
+        [
+            ('w', 0, 4096),
+            ('p', 10000, 4096),
+            ('w', 4096, 4096),
+            ('p', 10000, 8192),
+            ('w', 8192, 1808),
+            ('p', 10000, 10000),
+        ]
+    );
+    assert_eq!(backend.data(), data);
+
+    // flushing only writes the aligned part of the buffer
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let mut writer = ticket.writer(4096..10000).progress_interval(u64::MAX);
+    writer.write_all(&data[4096..9000])?;
+    writer.flush()?;
+    assert_eq!(writer.position(), 8192);
+    writer.write_all(&data[9000..])?;
+    writer.finish()?;
+    assert_eq!(
+        summarize(&backend),
+        [('w', 4096, 4096), ('w', 8192, 1808), ('p', 10000, 10000)]
+    );
+
+    // progress is reported at the configured cadence
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let mut writer = ticket
+        .writer(0..16384)
+        .chunk_size(4096)
+        .progress_interval(8192);
+    writer.write_all(&[0; 16384])?;
+    writer.finish()?;
+    assert_eq!(
+        summarize(&backend)
+            .into_iter()
+            .filter(|(kind, ..)| *kind == 'p')
+            .collect::<Vec<_>>(),
+        [('p', 16384, 8192), ('p', 16384, 16384)]
+    );
+
+    // writing past the range is rejected
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let mut writer = ticket.writer(0..100);
+    assert_eq!(writer.write(&[0; 200])?, 100);
+    assert_eq!(
+        writer.write(&[0]).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    drop(writer);
+    assert_eq!(summarize(&backend), [('w', 0, 100), ('p', 100, 100)]);
+
+    // dropping the writer only writes the aligned part of the buffer
+    let backend = Arc::new(RecordingBackend::new());
+    let ticket = ticket::FetchData::new(backend.clone());
+    let mut writer = ticket.writer(0..10000).progress_interval(u64::MAX);
+    writer.write_all(&data[..5000])?;
+    drop(writer);
+    assert_eq!(summarize(&backend), [('w', 0, 4096)]);
+
+    // nothing is written once cancelled
+    let backend = Arc::new(RecordingBackend::new());
+    let token = CancellationToken::new();
+    let ticket = ticket::FetchData::with_token(backend.clone(), token.clone());
+    let mut writer = ticket.writer(0..10000).chunk_size(4096);
+    writer.write_all(&data[..5000])?;
+    token.cancel();
+    assert!(writer.write_all(&data[5000..]).is_err());
+    drop(writer);
+    assert_eq!(summarize(&backend), [('w', 0, 4096), ('p', 10000, 4096)]);
+
+    // misaligned ranges and chunk sizes are rejected up front
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| ticket.writer(1..100))).is_err());
+    assert!(
+        panic::catch_unwind(AssertUnwindSafe(|| ticket.writer(0..100).chunk_size(100))).is_err()
+    );
+
+    Ok(())
+}